@@ -18,10 +18,11 @@
 //! # async fn test() {
 //! use futures::TryStreamExt;
 //! use mountpoint_s3_client::{S3CrtClient, ObjectClient};
+//! use mountpoint_s3_client::types::GetObjectParams;
 //!
 //! let client = S3CrtClient::new(Default::default()).expect("client construction failed");
 //!
-//! let response = client.get_object("my-bucket", "my-key", None, None).await.expect("get_object failed");
+//! let response = client.get_object("my-bucket", "my-key", &GetObjectParams::new()).await.expect("get_object failed");
 //! let body = response.map_ok(|(offset, body)| body.to_vec()).try_concat().await.expect("body streaming failed");
 //! # }
 //! ```
@@ -59,9 +60,12 @@ pub mod user_agent;
 
 pub mod error_metadata;
 
-pub use object_client::{ObjectClient, PutObjectRequest};
+pub use object_client::{MultipartUpload, ObjectClient, PutObjectRequest};
 
-pub use s3_crt_client::{get_object::S3GetObjectRequest, put_object::S3PutObjectRequest, S3CrtClient, S3RequestError};
+pub use s3_crt_client::{
+    get_object::S3GetObjectRequest, multipart_upload::S3MultipartUpload, put_object::S3PutObjectRequest, S3CrtClient,
+    S3RequestError,
+};
 
 /// Configuration for the S3 client
 pub mod config {
@@ -72,10 +76,11 @@ pub mod config {
 /// Types used by all object clients
 pub mod types {
     pub use super::object_client::{
-        Checksum, ChecksumAlgorithm, DeleteObjectResult, ETag, GetBodyPart, GetObjectAttributesParts,
-        GetObjectAttributesResult, GetObjectRequest, HeadObjectResult, ListObjectsResult, ObjectAttribute,
-        ObjectClientResult, ObjectInfo, ObjectPart, PutObjectParams, PutObjectResult, PutObjectTrailingChecksums,
-        RestoreStatus, UploadReview, UploadReviewPart,
+        Checksum, ChecksumAlgorithm, CopyObjectParams, CopyObjectResult, DeleteMarkerInfo, DeleteObjectResult, ETag,
+        GetBodyPart, GetObjectAttributesParts, GetObjectAttributesResult, GetObjectParams, GetObjectRequest,
+        HeadObjectResult, ListObjectVersionsResult, ListObjectsResult, ObjectAttribute, ObjectClientResult, ObjectInfo,
+        ObjectPart, ObjectVersionEntry, ObjectVersionInfo, PutObjectParams, PutObjectResult,
+        PutObjectTrailingChecksums, RestoreStatus, UploadReview, UploadReviewPart, MIN_MULTIPART_PART_SIZE,
     };
 }
 
@@ -86,8 +91,8 @@ pub mod types {
 /// client errors. See its documentation for more details.
 pub mod error {
     pub use super::object_client::{
-        DeleteObjectError, GetObjectAttributesError, GetObjectError, HeadObjectError, ListObjectsError,
-        ObjectClientError, PutObjectError,
+        CopyObjectError, DeleteObjectError, GetObjectAttributesError, GetObjectError, HeadObjectError,
+        ListObjectsError, ObjectClientError, PutObjectError,
     };
     #[doc(hidden)]
     pub use super::s3_crt_client::HeadBucketError;
@@ -24,11 +24,13 @@ use tracing::trace;
 use crate::checksums::crc32c_to_base64;
 use crate::error_metadata::{ClientErrorMetadata, ProvideErrorMetadata};
 use crate::object_client::{
-    Checksum, ChecksumAlgorithm, DeleteObjectError, DeleteObjectResult, ETag, GetBodyPart, GetObjectAttributesError,
-    GetObjectAttributesParts, GetObjectAttributesResult, GetObjectError, GetObjectRequest, HeadObjectError,
-    HeadObjectResult, ListObjectsError, ListObjectsResult, ObjectAttribute, ObjectClient, ObjectClientError,
-    ObjectClientResult, ObjectInfo, ObjectPart, PutObjectError, PutObjectParams, PutObjectRequest, PutObjectResult,
-    PutObjectTrailingChecksums, RestoreStatus, UploadReview, UploadReviewPart,
+    Checksum, ChecksumAlgorithm, CopyObjectError, CopyObjectParams, CopyObjectResult, DeleteMarkerInfo,
+    DeleteObjectError, DeleteObjectResult, ETag, GetBodyPart, GetObjectAttributesError, GetObjectAttributesParts,
+    GetObjectAttributesResult, GetObjectError, GetObjectParams, GetObjectRequest, HeadObjectError, HeadObjectResult,
+    ListObjectVersionsResult, ListObjectsError, ListObjectsResult, MultipartUpload, ObjectAttribute, ObjectClient,
+    ObjectClientError, ObjectClientResult, ObjectInfo, ObjectPart, ObjectVersionEntry, ObjectVersionInfo,
+    PutObjectError, PutObjectParams, PutObjectRequest, PutObjectResult, PutObjectTrailingChecksums, RestoreStatus,
+    UploadReview, UploadReviewPart, MIN_MULTIPART_PART_SIZE,
 };
 
 mod leaky_bucket;
@@ -75,20 +77,41 @@ pub struct MockClientConfig {
 pub struct MockClient {
     config: MockClientConfig,
     objects: Arc<RwLock<BTreeMap<String, MockObject>>>,
+    /// Noncurrent versions and delete markers of each key, oldest first
+    versions: Arc<RwLock<BTreeMap<String, Vec<MockVersion>>>>,
     in_progress_uploads: Arc<RwLock<BTreeSet<String>>>,
     operation_counts: Arc<RwLock<HashMap<Operation, u64>>>,
 }
 
+/// A noncurrent version of an object, or a delete marker, in a [MockClient]'s bucket
+#[derive(Debug)]
+enum MockVersion {
+    Object {
+        version_id: String,
+        object: MockObject,
+    },
+    DeleteMarker {
+        version_id: String,
+        last_modified: OffsetDateTime,
+    },
+}
+
 fn add_object(objects: &Arc<RwLock<BTreeMap<String, MockObject>>>, key: &str, value: MockObject) {
     objects.write().unwrap().insert(key.to_owned(), value);
 }
 
+fn inc_op_count(operation_counts: &Arc<RwLock<HashMap<Operation, u64>>>, operation: Operation) {
+    let mut op_counts = operation_counts.write().unwrap();
+    op_counts.entry(operation).and_modify(|count| *count += 1).or_insert(1);
+}
+
 impl MockClient {
     /// Create a new [MockClient] with the given config
     pub fn new(config: MockClientConfig) -> Self {
         Self {
             config,
             objects: Default::default(),
+            versions: Default::default(),
             in_progress_uploads: Default::default(),
             operation_counts: Default::default(),
         }
@@ -99,6 +122,31 @@ impl MockClient {
         add_object(&self.objects, key, value);
     }
 
+    /// Add a noncurrent version of an object to this mock client's bucket. Versions and delete
+    /// markers added later are newer. The current version of an object is the one added by
+    /// [MockClient::add_object], and has the version ID `null`.
+    pub fn add_object_version(&self, key: &str, version_id: &str, value: MockObject) {
+        let mut versions = self.versions.write().unwrap();
+        versions.entry(key.to_owned()).or_default().push(MockVersion::Object {
+            version_id: version_id.to_owned(),
+            object: value,
+        });
+    }
+
+    /// Add a delete marker for an object to this mock client's bucket, created at the given time.
+    /// The marker is the latest version of the object if it was added last and the object has no
+    /// current version.
+    pub fn add_delete_marker(&self, key: &str, version_id: &str, last_modified: OffsetDateTime) {
+        let mut versions = self.versions.write().unwrap();
+        versions
+            .entry(key.to_owned())
+            .or_default()
+            .push(MockVersion::DeleteMarker {
+                version_id: version_id.to_owned(),
+                last_modified,
+            });
+    }
+
     /// Remove object for the mock client's bucket
     pub fn remove_object(&self, key: &str) {
         self.objects.write().unwrap().remove(key);
@@ -129,6 +177,15 @@ impl MockClient {
         }
     }
 
+    /// Returns the objects user-defined metadata
+    pub fn get_object_user_metadata(&self, key: &str) -> Result<HashMap<String, String>, MockClientError> {
+        if let Some(mock_object) = self.objects.read().unwrap().get(key) {
+            Ok(mock_object.user_metadata.clone())
+        } else {
+            Err(MockClientError("object not found".into()))
+        }
+    }
+
     /// Returns error if object does not exist
     pub fn restore_object(&self, key: &str) -> Result<(), MockClientError> {
         match self.objects.write().unwrap().get_mut(key) {
@@ -167,8 +224,7 @@ impl MockClient {
 
     /// Track number of operations for verifying API calls made by the client in testing.
     fn inc_op_count(&self, operation: Operation) {
-        let mut op_counts = self.operation_counts.write().unwrap();
-        op_counts.entry(operation).and_modify(|count| *count += 1).or_insert(1);
+        inc_op_count(&self.operation_counts, operation);
     }
 
     /// Ordered list implementation
@@ -329,17 +385,119 @@ impl MockClient {
             next_continuation_token,
         }
     }
+
+    /// List object versions implementation. Only paginates between keys, so all versions of a key
+    /// are always returned in the same page.
+    fn list_versions(
+        &self,
+        key_marker: Option<&str>,
+        delimiter: &str,
+        max_keys: usize,
+        prefix: &str,
+    ) -> ListObjectVersionsResult {
+        let delimiter = (!delimiter.is_empty()).then_some(delimiter);
+
+        let objects = self.objects.read().unwrap();
+        let versions = self.versions.read().unwrap();
+
+        // A key marker that is a common prefix skips every key under that prefix
+        let after_marker = |key: &str| match key_marker {
+            Some(marker) => {
+                let skipped_prefix = delimiter.is_some_and(|d| marker.ends_with(d)) && key.starts_with(marker);
+                key > marker && !skipped_prefix
+            }
+            None => true,
+        };
+        let keys: BTreeSet<&String> = objects
+            .keys()
+            .chain(versions.keys())
+            .filter(|key| key.starts_with(prefix) && after_marker(key))
+            .collect();
+
+        let mut result = ListObjectVersionsResult {
+            versions: Vec::new(),
+            common_prefixes: Vec::new(),
+            next_key_marker: None,
+            next_version_id_marker: None,
+        };
+        let mut count = 0;
+        let mut last_marker: Option<String> = None;
+        for key in keys {
+            let remaining_key = key.chars().skip(prefix.chars().count()).collect::<String>();
+            let common_prefix =
+                delimiter.and_then(|d| remaining_key.split_once(d).map(|(pre, _)| format!("{prefix}{pre}{d}")));
+            if common_prefix.is_some() && result.common_prefixes.last() == common_prefix.as_ref() {
+                continue;
+            }
+
+            if count >= max_keys {
+                result.next_key_marker = last_marker;
+                break;
+            }
+
+            if let Some(common_prefix) = common_prefix {
+                last_marker = Some(common_prefix.clone());
+                result.common_prefixes.push(common_prefix);
+                count += 1;
+                continue;
+            }
+
+            let version_info = |version_id: &str, object: &MockObject, is_latest| {
+                ObjectVersionEntry::Version(ObjectVersionInfo {
+                    key: key.to_string(),
+                    version_id: version_id.to_owned(),
+                    is_latest,
+                    size: object.len() as u64,
+                    last_modified: object.last_modified,
+                    storage_class: object.storage_class.clone(),
+                    etag: object.etag.as_str().to_string(),
+                })
+            };
+            let current = objects.get(key);
+            if let Some(object) = current {
+                result.versions.push(version_info("null", object, true));
+                count += 1;
+            }
+            let noncurrent = versions.get(key).map(Vec::as_slice).unwrap_or_default();
+            for (i, version) in noncurrent.iter().enumerate().rev() {
+                let entry = match version {
+                    MockVersion::Object { version_id, object } => version_info(version_id, object, false),
+                    MockVersion::DeleteMarker {
+                        version_id,
+                        last_modified,
+                    } => ObjectVersionEntry::DeleteMarker(DeleteMarkerInfo {
+                        key: key.to_string(),
+                        version_id: version_id.clone(),
+                        is_latest: current.is_none() && i == noncurrent.len() - 1,
+                        last_modified: *last_modified,
+                    }),
+                };
+                result.versions.push(entry);
+                count += 1;
+            }
+            last_marker = Some(key.to_string());
+        }
+
+        result
+    }
 }
 
 /// Operations for use in operation counters.
 #[derive(Debug, Eq, Hash, PartialEq)]
 pub enum Operation {
+    AbortMultipartUpload,
+    AppendObject,
+    CopyObject,
+    CreateMultipartUpload,
     DeleteObject,
     HeadObject,
     GetObject,
     GetObjectAttributes,
     ListObjectsV2,
+    ListObjectVersions,
     PutObject,
+    UploadPart,
+    UploadPartCopy,
 }
 
 /// Counter for a specific client [Operation].
@@ -371,6 +529,8 @@ pub struct MockObject {
     last_modified: OffsetDateTime,
     etag: ETag,
     parts: Option<MockObjectParts>,
+    content_type: Option<String>,
+    user_metadata: HashMap<String, String>,
 }
 
 impl MockObject {
@@ -389,6 +549,8 @@ impl MockObject {
             last_modified: OffsetDateTime::now_utc(),
             etag,
             parts: None,
+            content_type: None,
+            user_metadata: HashMap::new(),
         }
     }
 
@@ -401,6 +563,8 @@ impl MockObject {
             last_modified: OffsetDateTime::now_utc(),
             etag,
             parts: None,
+            content_type: None,
+            user_metadata: HashMap::new(),
         }
     }
 
@@ -423,6 +587,8 @@ impl MockObject {
             last_modified: OffsetDateTime::now_utc(),
             etag,
             parts: None,
+            content_type: None,
+            user_metadata: HashMap::new(),
         }
     }
 
@@ -438,6 +604,14 @@ impl MockObject {
         self.restore_status = restore_status;
     }
 
+    pub fn set_content_type(&mut self, content_type: Option<String>) {
+        self.content_type = content_type;
+    }
+
+    pub fn set_user_metadata(&mut self, user_metadata: HashMap<String, String>) {
+        self.user_metadata = user_metadata;
+    }
+
     pub fn len(&self) -> usize {
         self.size
     }
@@ -465,6 +639,8 @@ impl std::fmt::Debug for MockObject {
             .field("last_modified", &self.last_modified)
             .field("etag", &self.etag)
             .field("restored", &self.restore_status)
+            .field("content_type", &self.content_type)
+            .field("user_metadata", &self.user_metadata)
             .finish()
     }
 }
@@ -552,12 +728,52 @@ fn mock_client_error<T, E>(s: impl Into<Cow<'static, str>>) -> ObjectClientResul
 impl ObjectClient for MockClient {
     type GetObjectRequest = MockGetObjectRequest;
     type PutObjectRequest = MockPutObjectRequest;
+    type MultipartUpload = MockMultipartUpload;
     type ClientError = MockClientError;
 
     fn part_size(&self) -> Option<usize> {
         Some(self.config.part_size)
     }
 
+    async fn copy_object(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        destination_bucket: &str,
+        destination_key: &str,
+        params: &CopyObjectParams,
+    ) -> ObjectClientResult<CopyObjectResult, CopyObjectError, Self::ClientError> {
+        trace!(
+            source_bucket,
+            source_key,
+            destination_bucket,
+            destination_key,
+            "CopyObject"
+        );
+        self.inc_op_count(Operation::CopyObject);
+
+        if source_bucket != self.config.bucket || destination_bucket != self.config.bucket {
+            return Err(ObjectClientError::ServiceError(CopyObjectError::NotFound));
+        }
+
+        let mut objects = self.objects.write().unwrap();
+        let Some(object) = objects.get(source_key) else {
+            return Err(ObjectClientError::ServiceError(CopyObjectError::NotFound));
+        };
+        if params.source_if_match.as_ref().is_some_and(|etag| etag != &object.etag) {
+            return Err(ObjectClientError::ServiceError(CopyObjectError::PreconditionFailed));
+        }
+
+        let mut copy = object.clone();
+        copy.set_last_modified(OffsetDateTime::now_utc());
+        if let Some(user_metadata) = &params.replace_user_metadata {
+            copy.set_user_metadata(user_metadata.clone());
+        }
+        objects.insert(destination_key.to_owned(), copy);
+
+        Ok(CopyObjectResult {})
+    }
+
     async fn delete_object(
         &self,
         bucket: &str,
@@ -579,10 +795,9 @@ impl ObjectClient for MockClient {
         &self,
         bucket: &str,
         key: &str,
-        range: Option<Range<u64>>,
-        if_match: Option<ETag>,
+        params: &GetObjectParams,
     ) -> ObjectClientResult<Self::GetObjectRequest, GetObjectError, Self::ClientError> {
-        trace!(bucket, key, ?range, ?if_match, "GetObject");
+        trace!(bucket, key, ?params.range, ?params.if_match, ?params.version_id, "GetObject");
         self.inc_op_count(Operation::GetObject);
 
         if bucket != self.config.bucket {
@@ -590,15 +805,26 @@ impl ObjectClient for MockClient {
         }
 
         let objects = self.objects.read().unwrap();
+        let versions = self.versions.read().unwrap();
+
+        let object = match params.version_id.as_deref() {
+            None | Some("null") => objects.get(key),
+            Some(version_id) => versions.get(key).and_then(|versions| {
+                versions.iter().find_map(|version| match version {
+                    MockVersion::Object { version_id: id, object } if id == version_id => Some(object),
+                    _ => None,
+                })
+            }),
+        };
 
-        if let Some(object) = objects.get(key) {
-            if let Some(etag_match) = if_match {
-                if etag_match != object.etag {
+        if let Some(object) = object {
+            if let Some(etag_match) = &params.if_match {
+                if *etag_match != object.etag {
                     return Err(ObjectClientError::ServiceError(GetObjectError::PreconditionFailed));
                 }
             }
 
-            let (next_offset, length) = if let Some(range) = range {
+            let (next_offset, length) = if let Some(range) = params.range.clone() {
                 if range.start >= object.len() as u64 || range.end > object.len() as u64 {
                     return mock_client_error(format!("invalid range, length={}", object.len()));
                 }
@@ -644,6 +870,9 @@ impl ObjectClient for MockClient {
                     storage_class: object.storage_class.clone(),
                     restore_status: object.restore_status,
                 },
+                version_id: None,
+                content_type: object.content_type.clone(),
+                user_metadata: object.user_metadata.clone(),
             })
         } else {
             Err(ObjectClientError::ServiceError(HeadObjectError::NotFound))
@@ -672,6 +901,33 @@ impl ObjectClient for MockClient {
         }
     }
 
+    async fn list_object_versions(
+        &self,
+        bucket: &str,
+        key_marker: Option<&str>,
+        version_id_marker: Option<&str>,
+        delimiter: &str,
+        max_keys: usize,
+        prefix: &str,
+    ) -> ObjectClientResult<ListObjectVersionsResult, ListObjectsError, Self::ClientError> {
+        trace!(
+            bucket,
+            ?key_marker,
+            ?version_id_marker,
+            delimiter,
+            max_keys,
+            prefix,
+            "ListObjectVersions"
+        );
+        self.inc_op_count(Operation::ListObjectVersions);
+
+        if bucket != self.config.bucket {
+            return Err(ObjectClientError::ServiceError(ListObjectsError::NoSuchBucket));
+        }
+
+        Ok(self.list_versions(key_marker, delimiter, max_keys, prefix))
+    }
+
     async fn put_object(
         &self,
         bucket: &str,
@@ -695,6 +951,68 @@ impl ObjectClient for MockClient {
         Ok(put_request)
     }
 
+    async fn append_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> ObjectClientResult<PutObjectResult, PutObjectError, Self::ClientError> {
+        trace!(bucket, key, offset, len = data.len(), "AppendObject");
+        self.inc_op_count(Operation::AppendObject);
+
+        if bucket != self.config.bucket {
+            return Err(ObjectClientError::ServiceError(PutObjectError::NoSuchBucket));
+        }
+
+        let mut objects = self.objects.write().unwrap();
+        let Some(existing) = objects.get(key) else {
+            return mock_client_error(format!("object {key} does not exist"));
+        };
+        if existing.size as u64 != offset {
+            return Err(ObjectClientError::ServiceError(PutObjectError::InvalidWriteOffset));
+        }
+
+        let mut body = existing.read(0, existing.size).into_vec();
+        body.extend_from_slice(data);
+        let mut object: MockObject = body.into();
+        object.storage_class = existing.storage_class.clone();
+        object.content_type = existing.content_type.clone();
+        object.user_metadata = existing.user_metadata.clone();
+        let etag = object.etag.clone();
+        objects.insert(key.to_owned(), object);
+        Ok(PutObjectResult {
+            etag: Some(etag),
+            sse_type: None,
+            sse_kms_key_id: None,
+        })
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        params: &PutObjectParams,
+    ) -> ObjectClientResult<Self::MultipartUpload, PutObjectError, Self::ClientError> {
+        trace!(bucket, key, "CreateMultipartUpload");
+        self.inc_op_count(Operation::CreateMultipartUpload);
+
+        if bucket != self.config.bucket {
+            return Err(ObjectClientError::ServiceError(PutObjectError::NoSuchBucket));
+        }
+
+        self.in_progress_uploads.write().unwrap().insert(key.to_owned());
+        Ok(MockMultipartUpload {
+            key: key.to_owned(),
+            parts: Vec::new(),
+            params: params.clone(),
+            objects: self.objects.clone(),
+            in_progress_uploads: self.in_progress_uploads.clone(),
+            operation_counts: self.operation_counts.clone(),
+            finished: false,
+        })
+    }
+
     async fn get_object_attributes(
         &self,
         bucket: &str,
@@ -822,17 +1140,32 @@ impl MockPutObjectRequest {
         mut self,
         parts: Vec<MockObjectPartAttributes>,
     ) -> ObjectClientResult<PutObjectResult, PutObjectError, MockClientError> {
+        if let Some(etag) = &self.params.if_match {
+            if self.objects.read().unwrap().get(&self.key).map(|object| &object.etag) != Some(etag) {
+                return Err(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed));
+            }
+        }
         let buffer = std::mem::take(&mut self.buffer);
         let mut object: MockObject = buffer.into();
         object.set_storage_class(self.params.storage_class.clone());
+        // S3 stores user-defined metadata names in lowercase
+        let user_metadata = self
+            .params
+            .user_metadata
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+            .collect();
+        object.set_user_metadata(user_metadata);
         // For S3 Standard, part attributes are only available when additional checksums are used
         if self.params.trailing_checksums == PutObjectTrailingChecksums::Enabled {
             object.parts = Some(MockObjectParts::Parts(parts));
         } else {
             object.parts = Some(MockObjectParts::Count(parts.len()));
         }
+        let etag = object.etag.clone();
         add_object(&self.objects, &self.key, object);
         Ok(PutObjectResult {
+            etag: Some(etag),
             sse_type: None,
             sse_kms_key_id: None,
         })
@@ -887,6 +1220,112 @@ impl PutObjectRequest for MockPutObjectRequest {
     }
 }
 
+#[derive(Debug)]
+pub struct MockMultipartUpload {
+    key: String,
+    parts: Vec<Box<[u8]>>,
+    params: PutObjectParams,
+    objects: Arc<RwLock<BTreeMap<String, MockObject>>>,
+    in_progress_uploads: Arc<RwLock<BTreeSet<String>>>,
+    operation_counts: Arc<RwLock<HashMap<Operation, u64>>>,
+    /// Whether the upload was completed or aborted
+    finished: bool,
+}
+
+impl Drop for MockMultipartUpload {
+    fn drop(&mut self) {
+        // Like the S3 client, abort uploads that are dropped before they complete
+        if !self.finished {
+            inc_op_count(&self.operation_counts, Operation::AbortMultipartUpload);
+        }
+        self.in_progress_uploads.write().unwrap().remove(&self.key);
+    }
+}
+
+#[cfg_attr(not(docs_rs), async_trait)]
+impl MultipartUpload for MockMultipartUpload {
+    type ClientError = MockClientError;
+
+    async fn upload_part(&mut self, data: &[u8]) -> ObjectClientResult<(), PutObjectError, Self::ClientError> {
+        inc_op_count(&self.operation_counts, Operation::UploadPart);
+        self.parts.push(data.into());
+        Ok(())
+    }
+
+    async fn upload_part_copy(
+        &mut self,
+        source_key: &str,
+        range: Range<u64>,
+        if_match: Option<ETag>,
+    ) -> ObjectClientResult<(), PutObjectError, Self::ClientError> {
+        trace!(key = self.key, source_key, ?range, ?if_match, "UploadPartCopy");
+        inc_op_count(&self.operation_counts, Operation::UploadPartCopy);
+
+        let objects = self.objects.read().unwrap();
+        let Some(object) = objects.get(source_key) else {
+            return mock_client_error("copy source not found");
+        };
+        if if_match.is_some_and(|etag| etag != object.etag) {
+            return Err(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed));
+        }
+        if range.is_empty() || range.end > object.len() as u64 {
+            return mock_client_error(format!("invalid copy source range, length={}", object.len()));
+        }
+        let part = object.read(range.start, (range.end - range.start) as usize);
+        drop(objects);
+
+        self.parts.push(part);
+        Ok(())
+    }
+
+    async fn complete(
+        mut self,
+        if_match: Option<ETag>,
+    ) -> ObjectClientResult<PutObjectResult, PutObjectError, Self::ClientError> {
+        if let Some(etag) = if_match {
+            if self.objects.read().unwrap().get(&self.key).map(|object| &object.etag) != Some(&etag) {
+                return Err(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed));
+            }
+        }
+        let Some((_last, other_parts)) = self.parts.split_last() else {
+            return mock_client_error("multipart upload has no parts");
+        };
+        if other_parts
+            .iter()
+            .any(|part| (part.len() as u64) < MIN_MULTIPART_PART_SIZE)
+        {
+            return mock_client_error("part smaller than the minimum part size");
+        }
+
+        let body = self.parts.concat();
+        let mut object: MockObject = body.into();
+        object.set_storage_class(self.params.storage_class.clone());
+        // S3 stores user-defined metadata names in lowercase
+        let user_metadata = self
+            .params
+            .user_metadata
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+            .collect();
+        object.set_user_metadata(user_metadata);
+        object.parts = Some(MockObjectParts::Count(self.parts.len()));
+        let etag = object.etag.clone();
+        add_object(&self.objects, &self.key, object);
+        self.finished = true;
+        Ok(PutObjectResult {
+            etag: Some(etag),
+            sse_type: None,
+            sse_kms_key_id: None,
+        })
+    }
+
+    async fn abort(mut self) -> ObjectClientResult<(), PutObjectError, Self::ClientError> {
+        inc_op_count(&self.operation_counts, Operation::AbortMultipartUpload);
+        self.finished = true;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 struct MockObjectPartAttributes {
     size: usize,
@@ -931,7 +1370,7 @@ mod tests {
         client.add_object(key, MockObject::from_bytes(&body, ETag::for_tests()));
 
         let mut get_request = client
-            .get_object("test_bucket", key, range.clone(), None)
+            .get_object("test_bucket", key, &GetObjectParams::new().range(range.clone()))
             .await
             .expect("should not fail");
 
@@ -976,7 +1415,7 @@ mod tests {
         client.add_object(key, MockObject::from_bytes(&body, ETag::for_tests()));
 
         let get_request = client
-            .get_object("test_bucket", key, range.clone(), None)
+            .get_object("test_bucket", key, &GetObjectParams::new().range(range.clone()))
             .await
             .expect("should not fail");
         pin_mut!(get_request);
@@ -1033,33 +1472,45 @@ mod tests {
         }
 
         assert!(matches!(
-            client.get_object("wrong_bucket", "key1", None, None).await,
+            client.get_object("wrong_bucket", "key1", &GetObjectParams::new()).await,
             Err(ObjectClientError::ServiceError(GetObjectError::NoSuchBucket))
         ));
 
         assert!(matches!(
-            client.get_object("test_bucket", "wrong_key", None, None).await,
+            client
+                .get_object("test_bucket", "wrong_key", &GetObjectParams::new())
+                .await,
             Err(ObjectClientError::ServiceError(GetObjectError::NoSuchKey))
         ));
 
         assert_client_error!(
-            client.get_object("test_bucket", "key1", Some(0..2001), None).await,
+            client
+                .get_object("test_bucket", "key1", &GetObjectParams::new().range(Some(0..2001)))
+                .await,
             "invalid range, length=2000"
         );
         assert_client_error!(
-            client.get_object("test_bucket", "key1", Some(2000..2000), None).await,
+            client
+                .get_object("test_bucket", "key1", &GetObjectParams::new().range(Some(2000..2000)))
+                .await,
             "invalid range, length=2000"
         );
         assert_client_error!(
-            client.get_object("test_bucket", "key1", Some(500..2001), None).await,
+            client
+                .get_object("test_bucket", "key1", &GetObjectParams::new().range(Some(500..2001)))
+                .await,
             "invalid range, length=2000"
         );
         assert_client_error!(
-            client.get_object("test_bucket", "key1", Some(5000..2001), None).await,
+            client
+                .get_object("test_bucket", "key1", &GetObjectParams::new().range(Some(5000..2001)))
+                .await,
             "invalid range, length=2000"
         );
         assert_client_error!(
-            client.get_object("test_bucket", "key1", Some(5000..1), None).await,
+            client
+                .get_object("test_bucket", "key1", &GetObjectParams::new().range(Some(5000..1)))
+                .await,
             "invalid range, length=2000"
         );
     }
@@ -1085,7 +1536,7 @@ mod tests {
         client.add_object(key, MockObject::from_bytes(&body, ETag::for_tests()));
 
         let mut get_request = client
-            .get_object("test_bucket", key, Some(range.clone()), None)
+            .get_object("test_bucket", key, &GetObjectParams::new().range(Some(range.clone())))
             .await
             .expect("should not fail");
 
@@ -1202,6 +1653,85 @@ mod tests {
         check_continuation!("/", 2, "dirs/dir2/", &keys[7..9], &[]);
     }
 
+    #[tokio::test]
+    async fn list_object_versions() {
+        let client = MockClient::new(MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024,
+            unordered_list_seed: None,
+            ..Default::default()
+        });
+
+        client.add_object("a", MockObject::constant(0u8, 5, ETag::for_tests()));
+        client.add_object_version("a", "v1", MockObject::constant(1u8, 10, ETag::for_tests()));
+        client.add_object_version("a", "v2", MockObject::constant(2u8, 15, ETag::for_tests()));
+        client.add_object_version("b", "v3", MockObject::constant(3u8, 20, ETag::for_tests()));
+        client.add_delete_marker("a", "m1", OffsetDateTime::now_utc());
+        client.add_object("dir/c", MockObject::constant(4u8, 25, ETag::for_tests()));
+
+        let result = client
+            .list_object_versions("test_bucket", None, None, "/", 1000, "")
+            .await
+            .expect("should not fail");
+        let versions = result
+            .versions
+            .iter()
+            .map(|entry| match entry {
+                ObjectVersionEntry::Version(v) => (v.key.as_str(), v.version_id.as_str(), v.is_latest, Some(v.size)),
+                ObjectVersionEntry::DeleteMarker(m) => (m.key.as_str(), m.version_id.as_str(), m.is_latest, None),
+            })
+            .collect::<Vec<_>>();
+        // The delete marker was added after the noncurrent versions of `a`, so it comes before them
+        assert_eq!(
+            versions,
+            vec![
+                ("a", "null", true, Some(5)),
+                ("a", "m1", false, None),
+                ("a", "v2", false, Some(15)),
+                ("a", "v1", false, Some(10)),
+                ("b", "v3", false, Some(20))
+            ]
+        );
+        assert_eq!(result.common_prefixes, vec!["dir/".to_string()]);
+        assert!(result.next_key_marker.is_none());
+
+        // Pages only break between keys
+        let result = client
+            .list_object_versions("test_bucket", None, None, "/", 2, "")
+            .await
+            .expect("should not fail");
+        assert_eq!(result.versions.len(), 4);
+        assert_eq!(result.next_key_marker.as_deref(), Some("a"));
+        let result = client
+            .list_object_versions("test_bucket", Some("a"), None, "/", 2, "")
+            .await
+            .expect("should not fail");
+        assert_eq!(result.versions.len(), 1);
+        assert_eq!(result.common_prefixes, vec!["dir/".to_string()]);
+        assert!(result.next_key_marker.is_none());
+
+        let get = client
+            .get_object(
+                "test_bucket",
+                "a",
+                &GetObjectParams::new().version_id(Some("v1".to_owned())),
+            )
+            .await
+            .expect("should not fail");
+        assert_eq!(&get.collect().await.unwrap()[..], &[1u8; 10][..]);
+
+        assert!(matches!(
+            client
+                .get_object(
+                    "test_bucket",
+                    "a",
+                    &GetObjectParams::new().version_id(Some("v3".to_owned()))
+                )
+                .await,
+            Err(ObjectClientError::ServiceError(GetObjectError::NoSuchKey))
+        ));
+    }
+
     #[tokio::test]
     async fn list_objects_unicode() {
         let client = MockClient::new(MockClientConfig {
@@ -1485,7 +2015,7 @@ mod tests {
         put_request.complete().await.expect("put_object failed");
 
         let mut get_request = client
-            .get_object("test_bucket", "key1", None, None)
+            .get_object("test_bucket", "key1", &GetObjectParams::new())
             .await
             .expect("get_object failed");
 
@@ -1497,6 +2027,225 @@ mod tests {
             next_offset += body.len() as u64;
             assert_eq!(body, obj.read(offset, body.len()));
         }
+
+        // Completion fails if the object being replaced doesn't have the expected ETag
+        let etag = client.head_object("test_bucket", "key1").await.unwrap().object.etag;
+        let params = PutObjectParams::new().if_match(Some("\"other\"".parse().unwrap()));
+        let mut put_request = client
+            .put_object("test_bucket", "key1", &params)
+            .await
+            .expect("put_object failed");
+        put_request.write(b"hello").await.unwrap();
+        let result = put_request.complete().await;
+        assert!(matches!(
+            result,
+            Err(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed))
+        ));
+        assert_eq!(
+            client.head_object("test_bucket", "key1").await.unwrap().object.etag,
+            etag
+        );
+
+        let params = PutObjectParams::new().if_match(Some(etag));
+        let mut put_request = client
+            .put_object("test_bucket", "key1", &params)
+            .await
+            .expect("put_object failed");
+        put_request.write(b"hello").await.unwrap();
+        put_request.complete().await.expect("put_object failed");
+        assert_eq!(client.head_object("test_bucket", "key1").await.unwrap().object.size, 5);
+    }
+
+    #[tokio::test]
+    async fn test_copy_object() {
+        let bucket = "test_bucket";
+        let client = MockClient::new(MockClientConfig {
+            bucket: bucket.to_owned(),
+            part_size: 1024,
+            unordered_list_seed: None,
+            ..Default::default()
+        });
+
+        let obj = MockObject::ramp(0xaa, 2 * RAMP_BUFFER_SIZE, ETag::for_tests());
+        client.add_object("src", obj.clone());
+
+        client
+            .copy_object(bucket, "src", bucket, "dst", &Default::default())
+            .await
+            .expect("copy_object failed");
+        assert!(client.contains_key("src"));
+        assert!(client.contains_key("dst"));
+
+        let get_request = client
+            .get_object(bucket, "dst", &GetObjectParams::new())
+            .await
+            .expect("get_object failed");
+        let body = get_request
+            .map(|r| r.expect("get_object body part failed").1)
+            .collect::<Vec<_>>()
+            .await
+            .concat();
+        assert_eq!(&body[..], &obj.read(0, obj.len())[..]);
+
+        let result = client
+            .copy_object(bucket, "missing", bucket, "dst2", &Default::default())
+            .await;
+        assert!(matches!(
+            result,
+            Err(ObjectClientError::ServiceError(CopyObjectError::NotFound))
+        ));
+        assert!(!client.contains_key("dst2"));
+
+        let params = CopyObjectParams::new().source_if_match(Some("\"other\"".parse().unwrap()));
+        let result = client.copy_object(bucket, "src", bucket, "dst3", &params).await;
+        assert!(matches!(
+            result,
+            Err(ObjectClientError::ServiceError(CopyObjectError::PreconditionFailed))
+        ));
+        assert!(!client.contains_key("dst3"));
+
+        let user_metadata = HashMap::from([("key".to_owned(), "value".to_owned())]);
+        let params = CopyObjectParams::new().replace_user_metadata(Some(user_metadata.clone()));
+        client
+            .copy_object(bucket, "src", bucket, "dst4", &params)
+            .await
+            .expect("copy_object failed");
+        assert_eq!(client.get_object_user_metadata("dst4").unwrap(), user_metadata);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload() {
+        let bucket = "test_bucket";
+        let client = MockClient::new(MockClientConfig {
+            bucket: bucket.to_owned(),
+            part_size: 1024,
+            unordered_list_seed: None,
+            ..Default::default()
+        });
+
+        let part_size = MIN_MULTIPART_PART_SIZE as usize;
+        let src = MockObject::ramp(0xaa, 2 * part_size, ETag::for_tests());
+        client.add_object("src", src.clone());
+
+        let params = PutObjectParams::new().user_metadata(HashMap::from([("Key".to_owned(), "value".to_owned())]));
+        let mut upload = client
+            .create_multipart_upload(bucket, "dst", &params)
+            .await
+            .expect("create_multipart_upload failed");
+        assert!(client.is_upload_in_progress("dst"));
+
+        let range = 1..part_size as u64 + 1;
+        upload
+            .upload_part_copy("src", range.clone(), Some(ETag::for_tests()))
+            .await
+            .expect("upload_part_copy failed");
+        upload.upload_part(b"hello").await.expect("upload_part failed");
+        assert!(!client.contains_key("dst"));
+        upload.complete(None).await.expect("complete failed");
+        assert!(!client.is_upload_in_progress("dst"));
+
+        let body = client
+            .get_object(bucket, "dst", &GetObjectParams::new())
+            .await
+            .expect("get_object failed")
+            .collect()
+            .await
+            .expect("get_object failed");
+        let mut expected = src.read(range.start, part_size).into_vec();
+        expected.extend_from_slice(b"hello");
+        assert_eq!(&body[..], &expected[..]);
+        assert_eq!(
+            client.get_object_user_metadata("dst").unwrap(),
+            HashMap::from([("key".to_owned(), "value".to_owned())])
+        );
+
+        // Copies fail if the source doesn't match the precondition
+        let mut upload = client
+            .create_multipart_upload(bucket, "dst2", &Default::default())
+            .await
+            .expect("create_multipart_upload failed");
+        let result = upload
+            .upload_part_copy("src", 0..10, Some("other_etag".parse().unwrap()))
+            .await;
+        assert!(matches!(
+            result,
+            Err(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed))
+        ));
+
+        // Only the last part can be smaller than the minimum part size
+        upload.upload_part(b"hello").await.expect("upload_part failed");
+        upload.upload_part(b"world").await.expect("upload_part failed");
+        upload.complete(None).await.expect_err("small parts should fail");
+        assert!(!client.contains_key("dst2"));
+        assert!(!client.is_upload_in_progress("dst2"));
+
+        // Completion fails if the object being replaced doesn't have the expected ETag, and the
+        // failed upload is aborted
+        let abort_counter = client.new_counter(Operation::AbortMultipartUpload);
+        let mut upload = client
+            .create_multipart_upload(bucket, "src", &Default::default())
+            .await
+            .expect("create_multipart_upload failed");
+        upload.upload_part(b"hello").await.expect("upload_part failed");
+        let result = upload.complete(Some("other_etag".parse().unwrap())).await;
+        assert!(matches!(
+            result,
+            Err(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed))
+        ));
+        assert_eq!(abort_counter.count(), 1);
+        let head_result = client.head_object(bucket, "src").await.unwrap();
+        assert_eq!(head_result.object.size, 2 * part_size as u64);
+        let mut upload = client
+            .create_multipart_upload(bucket, "src", &Default::default())
+            .await
+            .expect("create_multipart_upload failed");
+        upload.upload_part(b"hello").await.expect("upload_part failed");
+        upload.complete(Some(ETag::for_tests())).await.expect("complete failed");
+        assert_eq!(abort_counter.count(), 1, "completed uploads are not aborted");
+        assert_eq!(client.head_object(bucket, "src").await.unwrap().object.size, 5);
+    }
+
+    #[tokio::test]
+    async fn test_append_object() {
+        let bucket = "test_bucket";
+        let client = MockClient::new(MockClientConfig {
+            bucket: bucket.to_owned(),
+            part_size: 1024,
+            unordered_list_seed: None,
+            ..Default::default()
+        });
+
+        let mut object = MockObject::from(b"hello");
+        object.set_user_metadata(HashMap::from([("key".to_owned(), "value".to_owned())]));
+        client.add_object("key", object);
+
+        client
+            .append_object(bucket, "key", 5, b" world")
+            .await
+            .expect("append_object failed");
+        let body = client
+            .get_object(bucket, "key", &GetObjectParams::new())
+            .await
+            .expect("get_object failed")
+            .collect()
+            .await
+            .expect("get_object failed");
+        assert_eq!(&body[..], b"hello world");
+        assert_eq!(
+            client.get_object_user_metadata("key").unwrap(),
+            HashMap::from([("key".to_owned(), "value".to_owned())])
+        );
+
+        // Appends must start at the end of the object
+        let result = client.append_object(bucket, "key", 5, b"!").await;
+        assert!(matches!(
+            result,
+            Err(ObjectClientError::ServiceError(PutObjectError::InvalidWriteOffset))
+        ));
+        client
+            .append_object(bucket, "missing", 0, b"!")
+            .await
+            .expect_err("append to a missing object should fail");
     }
 
     proptest::proptest! {
@@ -2,6 +2,7 @@ use crate::error_metadata::{ClientErrorMetadata, ProvideErrorMetadata};
 use async_trait::async_trait;
 use auto_impl::auto_impl;
 use futures::Stream;
+use std::collections::HashMap;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::time::SystemTime;
@@ -75,12 +76,24 @@ impl FromStr for ETag {
 pub trait ObjectClient {
     type GetObjectRequest: GetObjectRequest<ClientError = Self::ClientError>;
     type PutObjectRequest: PutObjectRequest<ClientError = Self::ClientError>;
+    type MultipartUpload: MultipartUpload<ClientError = Self::ClientError>;
     type ClientError: std::error::Error + ProvideErrorMetadata + Send + Sync + 'static;
 
     /// Query the part size this client uses for PUT and GET operations to the object store. This
     /// can be `None` if the client does not do multi-part operations.
     fn part_size(&self) -> Option<usize>;
 
+    /// Create a copy of an existing object. The copy happens entirely within the object store, so
+    /// no object data is transferred through the client.
+    async fn copy_object(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        destination_bucket: &str,
+        destination_key: &str,
+        params: &CopyObjectParams,
+    ) -> ObjectClientResult<CopyObjectResult, CopyObjectError, Self::ClientError>;
+
     /// Delete a single object from the object store.
     ///
     /// DeleteObject will succeed even if the object within the bucket does not exist.
@@ -96,8 +109,7 @@ pub trait ObjectClient {
         &self,
         bucket: &str,
         key: &str,
-        range: Option<Range<u64>>,
-        if_match: Option<ETag>,
+        params: &GetObjectParams,
     ) -> ObjectClientResult<Self::GetObjectRequest, GetObjectError, Self::ClientError>;
 
     /// List the objects in a bucket under a given prefix
@@ -110,6 +122,19 @@ pub trait ObjectClient {
         prefix: &str,
     ) -> ObjectClientResult<ListObjectsResult, ListObjectsError, Self::ClientError>;
 
+    /// List the versions of the objects in a bucket under a given prefix, including delete
+    /// markers. Versions and delete markers of the same key are returned together from newest to
+    /// oldest.
+    async fn list_object_versions(
+        &self,
+        bucket: &str,
+        key_marker: Option<&str>,
+        version_id_marker: Option<&str>,
+        delimiter: &str,
+        max_keys: usize,
+        prefix: &str,
+    ) -> ObjectClientResult<ListObjectVersionsResult, ListObjectsError, Self::ClientError>;
+
     /// Retrieve object metadata without retrieving the object contents
     async fn head_object(
         &self,
@@ -126,6 +151,31 @@ pub trait ObjectClient {
         params: &PutObjectParams,
     ) -> ObjectClientResult<Self::PutObjectRequest, PutObjectError, Self::ClientError>;
 
+    /// Append data to the end of an existing object. `offset` must be the current size of the
+    /// object, otherwise the request fails with [PutObjectError::InvalidWriteOffset], which allows
+    /// callers to detect concurrent writers.
+    ///
+    /// Only directory buckets (S3 Express One Zone) support appends.
+    async fn append_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> ObjectClientResult<PutObjectResult, PutObjectError, Self::ClientError>;
+
+    /// Start a multipart upload of a new object. Returns a [MultipartUpload] for callers to add the
+    /// parts of the object to. Unlike [`put_object`](ObjectClient::put_object), parts can be copied
+    /// from existing objects, and the caller decides where each part starts and ends.
+    ///
+    /// The `trailing_checksums` field of `params` is ignored.
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        params: &PutObjectParams,
+    ) -> ObjectClientResult<Self::MultipartUpload, PutObjectError, Self::ClientError>;
+
     /// Retrieves all the metadata from an object without returning the object contents.
     async fn get_object_attributes(
         &self,
@@ -178,6 +228,46 @@ where
 /// Shorthand type for the result of an object client request
 pub type ObjectClientResult<T, S, C> = Result<T, ObjectClientError<S, C>>;
 
+/// Parameters to a [`get_object`](ObjectClient::get_object) request
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct GetObjectParams {
+    /// If set, only this byte range of the object is returned.
+    pub range: Option<Range<u64>>,
+
+    /// If set, the request fails with [`GetObjectError::PreconditionFailed`] unless the object
+    /// has this ETag.
+    pub if_match: Option<ETag>,
+
+    /// If set, this version of the object is returned instead of the current one.
+    pub version_id: Option<String>,
+}
+
+impl GetObjectParams {
+    /// Create a default [GetObjectParams].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the byte range to return.
+    pub fn range(mut self, value: Option<Range<u64>>) -> Self {
+        self.range = value;
+        self
+    }
+
+    /// Set the ETag the object must have.
+    pub fn if_match(mut self, value: Option<ETag>) -> Self {
+        self.if_match = value;
+        self
+    }
+
+    /// Set the version of the object to return.
+    pub fn version_id(mut self, value: Option<String>) -> Self {
+        self.version_id = value;
+        self
+    }
+}
+
 /// Errors returned by a [`get_object`](ObjectClient::get_object) request
 #[derive(Debug, Error, PartialEq, Eq)]
 #[non_exhaustive]
@@ -190,6 +280,9 @@ pub enum GetObjectError {
 
     #[error("At least one of the preconditions specified did not hold")]
     PreconditionFailed,
+
+    #[error("The write offset does not match the size of the object")]
+    InvalidWriteOffset,
 }
 
 /// Result of a [`list_objects`](ObjectClient::list_objects) request
@@ -207,7 +300,27 @@ pub struct ListObjectsResult {
     pub next_continuation_token: Option<String>,
 }
 
-/// Errors returned by a [`list_objects`](ObjectClient::list_objects) request
+/// Result of a [`list_object_versions`](ObjectClient::list_object_versions) request
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ListObjectVersionsResult {
+    /// The list of object versions and delete markers, in the order they were listed: by key, then
+    /// newest first for each key.
+    pub versions: Vec<ObjectVersionEntry>,
+
+    /// The list of common prefixes. This rolls up all of the objects with a common prefix up to
+    /// the next instance of the delimiter.
+    pub common_prefixes: Vec<String>,
+
+    /// If present, the key marker to use to query more results.
+    pub next_key_marker: Option<String>,
+
+    /// If present, the version ID marker to use to query more results.
+    pub next_version_id_marker: Option<String>,
+}
+
+/// Errors returned by a [`list_objects`](ObjectClient::list_objects) or
+/// [`list_object_versions`](ObjectClient::list_object_versions) request
 #[derive(Debug, Error, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum ListObjectsError {
@@ -224,6 +337,16 @@ pub struct HeadObjectResult {
 
     /// Object metadata
     pub object: ObjectInfo,
+
+    /// Version ID of the object, if versioning has been enabled on the bucket
+    pub version_id: Option<String>,
+
+    /// A standard MIME type describing the format of the object data
+    pub content_type: Option<String>,
+
+    /// User-defined metadata of the object, keyed by the name that follows the `x-amz-meta-` prefix
+    /// of the corresponding header. Names are always lowercase.
+    pub user_metadata: HashMap<String, String>,
 }
 
 /// Errors returned by a [`head_object`](ObjectClient::head_object) request
@@ -251,6 +374,57 @@ pub enum DeleteObjectError {
     NoSuchBucket,
 }
 
+/// Parameters to a [`copy_object`](ObjectClient::copy_object) request
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+pub struct CopyObjectParams {
+    /// If set, the copy fails with [`CopyObjectError::PreconditionFailed`] unless the source
+    /// object has this ETag.
+    pub source_if_match: Option<ETag>,
+    /// If set, the new object has this user-defined metadata instead of the metadata of the source
+    /// object (the `REPLACE` metadata directive). Keys are the metadata names, without the
+    /// `x-amz-meta-` prefix.
+    pub replace_user_metadata: Option<HashMap<String, String>>,
+}
+
+impl CopyObjectParams {
+    /// Create a default [CopyObjectParams].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the ETag the source object must have.
+    pub fn source_if_match(mut self, value: Option<ETag>) -> Self {
+        self.source_if_match = value;
+        self
+    }
+
+    /// Set the user-defined metadata of the new object, rather than copying it from the source.
+    pub fn replace_user_metadata(mut self, value: Option<HashMap<String, String>>) -> Self {
+        self.replace_user_metadata = value;
+        self
+    }
+}
+
+/// Result of a [`copy_object`](ObjectClient::copy_object) request
+// TODO: Populate this struct with return fields from the S3 API, e.g., etag.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct CopyObjectResult {}
+
+/// Errors returned by a [`copy_object`](ObjectClient::copy_object) request
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CopyObjectError {
+    /// Note that CopyObject cannot distinguish between NoSuchBucket and NoSuchKey errors for the
+    /// source object
+    #[error("The object was not found")]
+    NotFound,
+
+    #[error("At least one of the preconditions specified did not hold")]
+    PreconditionFailed,
+}
+
 /// Result of a [`get_object_attributes`](ObjectClient::get_object_attributes) request
 #[derive(Debug, Default)]
 pub struct GetObjectAttributesResult {
@@ -294,6 +468,12 @@ pub struct PutObjectParams {
     /// If `server_side_encryption` has a valid value of aws:kms or aws:kms:dsse, this value may be used to specify AWS KMS key ID to be used
     /// when creating new S3 object
     pub ssekms_key_id: Option<String>,
+    /// User-defined metadata to store with the new S3 object, sent as `x-amz-meta-*` headers. Keys
+    /// are the metadata names, without the `x-amz-meta-` prefix.
+    pub user_metadata: HashMap<String, String>,
+    /// Only create the object if it replaces an existing object with this ETag. Ignored by
+    /// [ObjectClient::create_multipart_upload], whose uploads take the ETag when they complete.
+    pub if_match: Option<ETag>,
 }
 
 impl PutObjectParams {
@@ -325,6 +505,18 @@ impl PutObjectParams {
         self.ssekms_key_id = value;
         self
     }
+
+    /// Set user-defined metadata for the new object.
+    pub fn user_metadata(mut self, value: HashMap<String, String>) -> Self {
+        self.user_metadata = value;
+        self
+    }
+
+    /// Set the ETag that the object being replaced must have.
+    pub fn if_match(mut self, value: Option<ETag>) -> Self {
+        self.if_match = value;
+        self
+    }
 }
 
 /// How CRC32c checksums are used for parts of a multi-part PutObject request
@@ -403,11 +595,53 @@ pub trait PutObjectRequest: Send {
     ) -> ObjectClientResult<PutObjectResult, PutObjectError, Self::ClientError>;
 }
 
+/// A multipart upload started by [`create_multipart_upload`](ObjectClient::create_multipart_upload).
+///
+/// Parts are numbered in the order they are added. Every part except the last must be at least
+/// [`MIN_MULTIPART_PART_SIZE`] bytes, which is only checked when the upload is completed. The new
+/// object is not visible until the upload is completed, and an upload that will not be completed
+/// should be aborted so the object store can discard its parts. Dropping an upload that was not
+/// completed, including one that failed to complete, aborts it in the background on a best-effort
+/// basis.
+#[cfg_attr(not(docs_rs), async_trait)]
+pub trait MultipartUpload: Send {
+    type ClientError: std::error::Error + Send + Sync + 'static;
+
+    /// Upload the given data as the next part of the object.
+    async fn upload_part(&mut self, data: &[u8]) -> ObjectClientResult<(), PutObjectError, Self::ClientError>;
+
+    /// Copy a range of an existing object in the same bucket as the next part of the object. If
+    /// `if_match` is set, the copy fails with [`PutObjectError::PreconditionFailed`] unless the
+    /// source object has that ETag.
+    async fn upload_part_copy(
+        &mut self,
+        source_key: &str,
+        range: Range<u64>,
+        if_match: Option<ETag>,
+    ) -> ObjectClientResult<(), PutObjectError, Self::ClientError>;
+
+    /// Complete the upload from the parts added so far and return a [`PutObjectResult`]. If
+    /// `if_match` is set, the upload fails with [`PutObjectError::PreconditionFailed`] unless the
+    /// object it replaces still has that ETag.
+    async fn complete(
+        self,
+        if_match: Option<ETag>,
+    ) -> ObjectClientResult<PutObjectResult, PutObjectError, Self::ClientError>;
+
+    /// Abort the upload, discarding any parts added so far.
+    async fn abort(self) -> ObjectClientResult<(), PutObjectError, Self::ClientError>;
+}
+
+/// The minimum size of every part but the last in a [`MultipartUpload`]
+pub const MIN_MULTIPART_PART_SIZE: u64 = 5 * 1024 * 1024;
+
 /// Result of a [ObjectClient::put_object] request
 // TODO: Populate this struct with return fields from the S3 API, e.g., etag.
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct PutObjectResult {
+    /// ETag of the new object, if reported by S3
+    pub etag: Option<ETag>,
     /// Server-side encryption type that was used to store new object (reported by S3)
     pub sse_type: Option<String>,
     /// Server-side encryption KMS key ID that was used to store new object (reported by S3)
@@ -420,6 +654,9 @@ pub struct PutObjectResult {
 pub enum PutObjectError {
     #[error("The bucket does not exist")]
     NoSuchBucket,
+
+    #[error("At least one of the preconditions specified did not hold")]
+    PreconditionFailed,
 }
 
 /// Restoration status for S3 objects in flexible retrieval storage classes.
@@ -466,6 +703,90 @@ pub struct ObjectInfo {
     pub etag: String,
 }
 
+/// Metadata about a single version of an S3 object.
+///
+/// See [ObjectVersion](https://docs.aws.amazon.com/AmazonS3/latest/API/API_ObjectVersion.html) in
+/// the *Amazon S3 API Reference* for more details.
+#[derive(Debug, Clone)]
+pub struct ObjectVersionInfo {
+    /// Key for this object.
+    pub key: String,
+
+    /// Version ID of this version. Objects written while versioning was not enabled on the bucket
+    /// have the version ID `null`.
+    pub version_id: String,
+
+    /// Whether this is the current version of the object.
+    pub is_latest: bool,
+
+    /// Size of this version in bytes.
+    pub size: u64,
+
+    /// The time this version was created.
+    pub last_modified: OffsetDateTime,
+
+    /// Storage class for this version.
+    pub storage_class: Option<String>,
+
+    /// Entity tag of this version.
+    pub etag: String,
+}
+
+/// A delete marker for an S3 object, which hides the versions of that object created before it.
+///
+/// See [DeleteMarkerEntry](https://docs.aws.amazon.com/AmazonS3/latest/API/API_DeleteMarkerEntry.html)
+/// in the *Amazon S3 API Reference* for more details.
+#[derive(Debug, Clone)]
+pub struct DeleteMarkerInfo {
+    /// Key for this delete marker.
+    pub key: String,
+
+    /// Version ID of this delete marker.
+    pub version_id: String,
+
+    /// Whether this delete marker is the current version of the object.
+    pub is_latest: bool,
+
+    /// The time this delete marker was created.
+    pub last_modified: OffsetDateTime,
+}
+
+/// An entry in a [`list_object_versions`](ObjectClient::list_object_versions) result, which is
+/// either a version of an object or a delete marker.
+#[derive(Debug, Clone)]
+pub enum ObjectVersionEntry {
+    /// A version of an object
+    Version(ObjectVersionInfo),
+    /// A delete marker
+    DeleteMarker(DeleteMarkerInfo),
+}
+
+impl ObjectVersionEntry {
+    /// Key of the object this entry is a version of.
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Version(version) => &version.key,
+            Self::DeleteMarker(marker) => &marker.key,
+        }
+    }
+
+    /// Whether this entry is the current version of the object.
+    pub fn is_latest(&self) -> bool {
+        match self {
+            Self::Version(version) => version.is_latest,
+            Self::DeleteMarker(marker) => marker.is_latest,
+        }
+    }
+
+    /// The time this entry was created.
+    pub fn last_modified(&self) -> OffsetDateTime {
+        match self {
+            Self::Version(version) => version.last_modified,
+            Self::DeleteMarker(marker) => marker.last_modified,
+        }
+    }
+}
+
 /// All possible object attributes that can be retrived from [ObjectClient::get_object_attributes].
 /// Fields that you do not specify are not returned.
 #[derive(Debug)]
@@ -37,6 +37,7 @@ use thiserror::Error;
 use tracing::{debug, error, trace, Span};
 
 use self::get_object::S3GetObjectRequest;
+use self::multipart_upload::S3MultipartUpload;
 use self::put_object::S3PutObjectRequest;
 use crate::endpoint_config::EndpointError;
 use crate::endpoint_config::{self, EndpointConfig};
@@ -56,11 +57,14 @@ macro_rules! request_span {
     ($self:expr, $method:expr) => { request_span!($self, $method,) };
 }
 
+pub(crate) mod copy_object;
 pub(crate) mod delete_object;
 pub(crate) mod get_object;
 pub(crate) mod get_object_attributes;
 pub(crate) mod head_object;
+pub(crate) mod list_object_versions;
 pub(crate) mod list_objects;
+pub(crate) mod multipart_upload;
 pub(crate) mod put_object;
 
 pub(crate) mod head_bucket;
@@ -822,6 +826,18 @@ impl S3Message {
         self.set_request_path_and_query::<&str>(path, &[])
     }
 
+    /// Set the body of this message, along with the matching Content-Length header.
+    fn set_body(
+        &mut self,
+        allocator: &Allocator,
+        body: impl Into<Box<[u8]>>,
+    ) -> Result<(), mountpoint_s3_crt::common::error::Error> {
+        let body = body.into();
+        self.inner
+            .set_header(&Header::new("Content-Length", body.len().to_string()))?;
+        self.inner.set_body(allocator, body)
+    }
+
     /// Sets the checksum configuration for this message.
     fn set_checksum_config(&mut self, checksum_config: Option<ChecksumConfig>) {
         self.checksum_config = checksum_config;
@@ -1104,6 +1120,7 @@ fn emit_throughput_metric(bytes: u64, duration: Duration, op: &'static str) {
 impl ObjectClient for S3CrtClient {
     type GetObjectRequest = S3GetObjectRequest;
     type PutObjectRequest = S3PutObjectRequest;
+    type MultipartUpload = S3MultipartUpload;
     type ClientError = S3RequestError;
 
     fn part_size(&self) -> Option<usize> {
@@ -1113,6 +1130,18 @@ impl ObjectClient for S3CrtClient {
         Some(self.inner.part_size)
     }
 
+    async fn copy_object(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        destination_bucket: &str,
+        destination_key: &str,
+        params: &CopyObjectParams,
+    ) -> ObjectClientResult<CopyObjectResult, CopyObjectError, Self::ClientError> {
+        self.copy_object(source_bucket, source_key, destination_bucket, destination_key, params)
+            .await
+    }
+
     async fn delete_object(
         &self,
         bucket: &str,
@@ -1125,12 +1154,9 @@ impl ObjectClient for S3CrtClient {
         &self,
         bucket: &str,
         key: &str,
-        range: Option<Range<u64>>,
-        if_match: Option<ETag>,
-        // TODO: If more arguments are added to get object, make a request struct having those arguments
-        // along with bucket and key.
+        params: &GetObjectParams,
     ) -> ObjectClientResult<Self::GetObjectRequest, GetObjectError, Self::ClientError> {
-        self.get_object(bucket, key, range, if_match)
+        self.get_object(bucket, key, params)
     }
 
     async fn list_objects(
@@ -1145,6 +1171,19 @@ impl ObjectClient for S3CrtClient {
             .await
     }
 
+    async fn list_object_versions(
+        &self,
+        bucket: &str,
+        key_marker: Option<&str>,
+        version_id_marker: Option<&str>,
+        delimiter: &str,
+        max_keys: usize,
+        prefix: &str,
+    ) -> ObjectClientResult<ListObjectVersionsResult, ListObjectsError, Self::ClientError> {
+        self.list_object_versions(bucket, key_marker, version_id_marker, delimiter, max_keys, prefix)
+            .await
+    }
+
     async fn head_object(
         &self,
         bucket: &str,
@@ -1162,6 +1201,25 @@ impl ObjectClient for S3CrtClient {
         self.put_object(bucket, key, params).await
     }
 
+    async fn append_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> ObjectClientResult<PutObjectResult, PutObjectError, Self::ClientError> {
+        self.append_object(bucket, key, offset, data).await
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        params: &PutObjectParams,
+    ) -> ObjectClientResult<Self::MultipartUpload, PutObjectError, Self::ClientError> {
+        self.create_multipart_upload(bucket, key, params).await
+    }
+
     async fn get_object_attributes(
         &self,
         bucket: &str,
@@ -0,0 +1,151 @@
+use std::ops::Deref;
+use std::os::unix::prelude::OsStrExt;
+
+use mountpoint_s3_crt::http::request_response::Header;
+use mountpoint_s3_crt::s3::client::{MetaRequestResult, MetaRequestType};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+use crate::object_client::{CopyObjectError, CopyObjectParams, CopyObjectResult, ObjectClientResult};
+use crate::s3_crt_client::{S3CrtClient, S3RequestError};
+
+use super::head_object::USER_METADATA_HEADER_PREFIX;
+
+/// The copy source is sent as a header rather than a path, but is URL-encoded the same way, with
+/// '/' considered a safe character.
+pub(super) const URLENCODE_COPY_SOURCE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'/');
+
+impl S3CrtClient {
+    /// Create and begin a new CopyObject request.
+    ///
+    /// The CRT issues a single CopyObject request for small objects, and switches to a multipart
+    /// copy (using UploadPartCopy) for objects larger than the multipart threshold.
+    pub(super) async fn copy_object(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        destination_bucket: &str,
+        destination_key: &str,
+        params: &CopyObjectParams,
+    ) -> ObjectClientResult<CopyObjectResult, CopyObjectError, S3RequestError> {
+        let span = request_span!(
+            self.inner,
+            "copy_object",
+            source_bucket,
+            source_key,
+            destination_bucket,
+            destination_key
+        );
+
+        // Scope the endpoint, message, etc. since otherwise rustc thinks we use Message across the await.
+        let request = {
+            let mut message = self
+                .inner
+                .new_request_template("PUT", destination_bucket)
+                .map_err(S3RequestError::construction_failure)?;
+
+            let copy_source = format!("{source_bucket}/{source_key}");
+            let copy_source = utf8_percent_encode(&copy_source, URLENCODE_COPY_SOURCE).to_string();
+            message
+                .set_header(&Header::new("x-amz-copy-source", copy_source))
+                .map_err(S3RequestError::construction_failure)?;
+            if let Some(etag) = &params.source_if_match {
+                message
+                    .set_header(&Header::new("x-amz-copy-source-if-match", etag.as_str()))
+                    .map_err(S3RequestError::construction_failure)?;
+            }
+            if let Some(user_metadata) = &params.replace_user_metadata {
+                message
+                    .set_header(&Header::new("x-amz-metadata-directive", "REPLACE"))
+                    .map_err(S3RequestError::construction_failure)?;
+                for (name, value) in user_metadata {
+                    message
+                        .set_header(&Header::new(format!("{USER_METADATA_HEADER_PREFIX}{name}"), value))
+                        .map_err(S3RequestError::construction_failure)?;
+                }
+            }
+            message
+                .set_request_path(format!("/{destination_key}"))
+                .map_err(S3RequestError::construction_failure)?;
+
+            self.inner
+                .make_simple_http_request(message, MetaRequestType::CopyObject, span, parse_copy_object_error)?
+        };
+
+        let _body = request.await?;
+
+        Ok(CopyObjectResult {})
+    }
+}
+
+fn parse_copy_object_error(result: &MetaRequestResult) -> Option<CopyObjectError> {
+    match result.response_status {
+        404 => {
+            // The CRT issues a HeadObject request against the source before copying, which fails
+            // with an empty body, so we can't tell whether the source bucket or key is missing.
+            let Some(body) = result.error_response_body.as_ref() else {
+                return Some(CopyObjectError::NotFound);
+            };
+            let root = xmltree::Element::parse(body.as_bytes()).ok()?;
+            let error_code = root.get_child("Code")?;
+            let error_str = error_code.get_text()?;
+            match error_str.deref() {
+                "NoSuchKey" | "NoSuchBucket" => Some(CopyObjectError::NotFound),
+                _ => None,
+            }
+        }
+        412 => Some(CopyObjectError::PreconditionFailed),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{OsStr, OsString};
+
+    use super::*;
+
+    fn make_result(response_status: i32, body: Option<impl Into<OsString>>) -> MetaRequestResult {
+        MetaRequestResult {
+            response_status,
+            crt_error: 1i32.into(),
+            error_response_headers: None,
+            error_response_body: body.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn parse_404_no_such_key() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>NoSuchKey</Code><Message>The specified key does not exist.</Message><Key>not-a-key</Key><RequestId>NN7MEXA3TX4QNSFN</RequestId><HostId>VnV5Xz2EQf8dyjH8IdTA3BMzBNEzQIiaPZwjVvhHOTkk4q5mWTAPmLdRKxRG2GmpSHCHtsY2Cus=</HostId></Error>"#;
+        let result = make_result(404, Some(OsStr::from_bytes(&body[..])));
+        let result = parse_copy_object_error(&result);
+        assert_eq!(result, Some(CopyObjectError::NotFound));
+    }
+
+    #[test]
+    fn parse_404_no_such_bucket() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>NoSuchBucket</Code><Message>The specified bucket does not exist</Message><BucketName>amzn-s3-demo-bucket</BucketName><RequestId>BHCQ0FTYY0HKMV43</RequestId><HostId>ntCK1jQfPxY7sSNL/GB13RttgJLjSETfIuOiuRnwImO0dQP2ttj2Qqpn5S/jSLt3Ql0TgHWuYF0=</HostId></Error>"#;
+        let result = make_result(404, Some(OsStr::from_bytes(&body[..])));
+        let result = parse_copy_object_error(&result);
+        assert_eq!(result, Some(CopyObjectError::NotFound));
+    }
+
+    #[test]
+    fn parse_404_empty_body() {
+        let result = make_result(404, None::<OsString>);
+        let result = parse_copy_object_error(&result);
+        assert_eq!(result, Some(CopyObjectError::NotFound));
+    }
+
+    #[test]
+    fn parse_412_precondition_failed() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>PreconditionFailed</Code><Message>At least one of the pre-conditions you specified did not hold</Message><Condition>x-amz-copy-source-If-Match</Condition><RequestId>TX5YD2G1W0G3RJWY</RequestId><HostId>1ZR3hN0wG5ZHhbNQ0PTj5b1xPSHgZ6mbGRHiKZBeD8QwFC9dfuGmUQWb0cSJm8V9sXMVNRa2xXo=</HostId></Error>"#;
+        let result = make_result(412, Some(OsStr::from_bytes(&body[..])));
+        let result = parse_copy_object_error(&result);
+        assert_eq!(result, Some(CopyObjectError::PreconditionFailed));
+    }
+}
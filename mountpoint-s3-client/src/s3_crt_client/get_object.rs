@@ -1,6 +1,5 @@
 use std::future::Future;
 use std::ops::Deref;
-use std::ops::Range;
 use std::os::unix::prelude::OsStrExt;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -12,7 +11,7 @@ use mountpoint_s3_crt::http::request_response::Header;
 use mountpoint_s3_crt::s3::client::{MetaRequestResult, MetaRequestType};
 use pin_project::pin_project;
 
-use crate::object_client::{ETag, GetBodyPart, GetObjectError, ObjectClientError, ObjectClientResult};
+use crate::object_client::{GetBodyPart, GetObjectError, GetObjectParams, ObjectClientError, ObjectClientResult};
 use crate::s3_crt_client::{S3CrtClient, S3HttpRequest, S3RequestError};
 
 use super::GetObjectRequest;
@@ -24,10 +23,17 @@ impl S3CrtClient {
         &self,
         bucket: &str,
         key: &str,
-        range: Option<Range<u64>>,
-        if_match: Option<ETag>,
+        params: &GetObjectParams,
     ) -> Result<S3GetObjectRequest, ObjectClientError<GetObjectError, S3RequestError>> {
-        let span = request_span!(self.inner, "get_object", bucket, key, ?range, ?if_match);
+        let span = request_span!(
+            self.inner,
+            "get_object",
+            bucket,
+            key,
+            range=?params.range,
+            if_match=?params.if_match,
+            version_id=?params.version_id
+        );
 
         let mut message = self
             .inner
@@ -39,14 +45,14 @@ impl S3CrtClient {
             .set_header(&Header::new("accept", "*/*"))
             .map_err(S3RequestError::construction_failure)?;
 
-        if let Some(etag) = if_match {
+        if let Some(etag) = &params.if_match {
             // Return the object only if its entity tag (ETag) is matched
             message
                 .set_header(&Header::new("If-Match", etag.as_str()))
                 .map_err(S3RequestError::construction_failure)?;
         }
 
-        if let Some(range) = range {
+        if let Some(range) = &params.range {
             // Range HTTP header is bounded below *inclusive*
             let range_value = format!("bytes={}-{}", range.start, range.end.saturating_sub(1));
             message
@@ -55,8 +61,9 @@ impl S3CrtClient {
         }
 
         let key = format!("/{key}");
+        let query = params.version_id.as_deref().map(|version_id| ("versionId", version_id));
         message
-            .set_request_path(key)
+            .set_request_path_and_query(key, query.as_slice())
             .map_err(S3RequestError::construction_failure)?;
 
         let (sender, receiver) = futures::channel::mpsc::unbounded();
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
@@ -53,6 +54,9 @@ fn get_optional_field(headers: &Headers, name: &str) -> Result<Option<String>, P
     })
 }
 
+/// Prefix of the headers that carry user-defined object metadata
+pub(super) const USER_METADATA_HEADER_PREFIX: &str = "x-amz-meta-";
+
 lazy_static! {
     // Example: ongoing-request="true"
     static ref RESTORE_IN_PROGRESS_RE: Regex = Regex::new(r#"^ongoing-request="(?<ongoing>[^"]*)"$"#).unwrap();
@@ -93,6 +97,9 @@ impl HeadObjectResult {
         let etag = get_field(headers, "Etag")?;
         let storage_class = get_optional_field(headers, "x-amz-storage-class")?;
         let restore_status = Self::parse_restore_status(headers)?;
+        let version_id = get_optional_field(headers, "x-amz-version-id")?;
+        let content_type = get_optional_field(headers, "Content-Type")?;
+        let user_metadata = Self::parse_user_metadata(headers)?;
         let object = ObjectInfo {
             key,
             size,
@@ -101,7 +108,28 @@ impl HeadObjectResult {
             restore_status,
             etag,
         };
-        Ok(HeadObjectResult { bucket, object })
+        Ok(HeadObjectResult {
+            bucket,
+            object,
+            version_id,
+            content_type,
+            user_metadata,
+        })
+    }
+
+    fn parse_user_metadata(headers: &Headers) -> Result<HashMap<String, String>, ParseError> {
+        let mut user_metadata = HashMap::new();
+        for (name, value) in headers.iter() {
+            let name = name.to_str().ok_or_else(|| ParseError::Invalid(name.clone()))?;
+            // Header names are case-insensitive, and S3 always returns user metadata names in lowercase
+            let name = name.to_ascii_lowercase();
+            let Some(name) = name.strip_prefix(USER_METADATA_HEADER_PREFIX) else {
+                continue;
+            };
+            let value = value.to_str().ok_or_else(|| ParseError::Invalid(value.clone()))?;
+            user_metadata.insert(name.to_owned(), value.to_owned());
+        }
+        Ok(user_metadata)
     }
 }
 
@@ -236,4 +264,22 @@ mod tests {
         let restore_status = HeadObjectResult::parse_restore_status(&headers).expect("failed to parse headers");
         assert!(restore_status.is_none());
     }
+
+    #[test]
+    fn test_parse_user_metadata() {
+        let mut headers = Headers::new(&Allocator::default()).unwrap();
+        headers.add_header(&Header::new("Content-Type", "text/plain")).unwrap();
+        headers
+            .add_header(&Header::new("x-amz-meta-owner", "data-team"))
+            .unwrap();
+        headers
+            .add_header(&Header::new("X-Amz-Meta-Source", "pipeline"))
+            .unwrap();
+        let user_metadata = HeadObjectResult::parse_user_metadata(&headers).expect("failed to parse headers");
+        let expected = HashMap::from([
+            ("owner".to_owned(), "data-team".to_owned()),
+            ("source".to_owned(), "pipeline".to_owned()),
+        ]);
+        assert_eq!(user_metadata, expected);
+    }
 }
@@ -0,0 +1,225 @@
+use std::str::FromStr;
+
+use mountpoint_s3_crt::s3::client::MetaRequestType;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::object_client::{
+    DeleteMarkerInfo, ListObjectVersionsResult, ListObjectsError, ObjectClientError, ObjectClientResult,
+    ObjectVersionEntry, ObjectVersionInfo,
+};
+use crate::s3_crt_client::list_objects::{get_field, get_text, parse_list_objects_error, ParseError};
+use crate::s3_crt_client::{S3CrtClient, S3RequestError};
+
+fn parse_result_from_bytes(bytes: &[u8]) -> Result<ListObjectVersionsResult, ParseError> {
+    parse_result_from_xml(&mut xmltree::Element::parse(bytes)?)
+}
+
+fn parse_result_from_xml(element: &mut xmltree::Element) -> Result<ListObjectVersionsResult, ParseError> {
+    // Versions and delete markers are interleaved, and their order matters when they have the same
+    // LastModified time, so don't take them out by name
+    let mut versions = Vec::new();
+    for child in element.children.iter().filter_map(|node| node.as_element()) {
+        match child.name.as_str() {
+            "Version" => versions.push(ObjectVersionEntry::Version(parse_version_from_xml(child)?)),
+            "DeleteMarker" => versions.push(ObjectVersionEntry::DeleteMarker(parse_delete_marker_from_xml(child)?)),
+            _ => {}
+        }
+    }
+
+    let mut common_prefixes = Vec::new();
+    while let Some(common_prefix) = element.take_child("CommonPrefixes") {
+        let prefix = get_field(&common_prefix, "Prefix")?;
+        common_prefixes.push(prefix);
+    }
+
+    let mut next_key_marker = None;
+    if let Some(elem) = element.get_child("NextKeyMarker") {
+        next_key_marker = Some(get_text(elem)?);
+    }
+
+    let mut next_version_id_marker = None;
+    if let Some(elem) = element.get_child("NextVersionIdMarker") {
+        next_version_id_marker = Some(get_text(elem)?);
+    }
+
+    let is_truncated = parse_bool(element, "IsTruncated")?;
+    if is_truncated != next_key_marker.is_some() {
+        return Err(ParseError::InvalidResponse(
+            element.clone(),
+            "IsTruncated doesn't match NextKeyMarker".to_string(),
+        ));
+    }
+
+    Ok(ListObjectVersionsResult {
+        versions,
+        common_prefixes,
+        next_key_marker,
+        next_version_id_marker,
+    })
+}
+
+fn parse_bool(element: &xmltree::Element, name: &str) -> Result<bool, ParseError> {
+    bool::from_str(&get_field(element, name)?).map_err(|e| ParseError::Bool(e, name.to_string()))
+}
+
+fn parse_last_modified(element: &xmltree::Element) -> Result<OffsetDateTime, ParseError> {
+    OffsetDateTime::parse(&get_field(element, "LastModified")?, &Rfc3339)
+        .map_err(|e| ParseError::OffsetDateTime(e, "LastModified".to_string()))
+}
+
+fn parse_version_from_xml(element: &xmltree::Element) -> Result<ObjectVersionInfo, ParseError> {
+    let size = get_field(element, "Size")?;
+    let size = u64::from_str(&size).map_err(|e| ParseError::Int(e, "Size".to_string()))?;
+
+    Ok(ObjectVersionInfo {
+        key: get_field(element, "Key")?,
+        version_id: get_field(element, "VersionId")?,
+        is_latest: parse_bool(element, "IsLatest")?,
+        size,
+        last_modified: parse_last_modified(element)?,
+        storage_class: get_field(element, "StorageClass").ok(),
+        etag: get_field(element, "ETag")?,
+    })
+}
+
+fn parse_delete_marker_from_xml(element: &xmltree::Element) -> Result<DeleteMarkerInfo, ParseError> {
+    Ok(DeleteMarkerInfo {
+        key: get_field(element, "Key")?,
+        version_id: get_field(element, "VersionId")?,
+        is_latest: parse_bool(element, "IsLatest")?,
+        last_modified: parse_last_modified(element)?,
+    })
+}
+
+impl S3CrtClient {
+    pub(super) async fn list_object_versions(
+        &self,
+        bucket: &str,
+        key_marker: Option<&str>,
+        version_id_marker: Option<&str>,
+        delimiter: &str,
+        max_keys: usize,
+        prefix: &str,
+    ) -> ObjectClientResult<ListObjectVersionsResult, ListObjectsError, S3RequestError> {
+        // Scope the endpoint, message, etc. since otherwise rustc thinks we use Message across the await.
+        let body = {
+            let mut message = self
+                .inner
+                .new_request_template("GET", bucket)
+                .map_err(S3RequestError::construction_failure)?;
+            let max_keys = format!("{max_keys}");
+            let mut query = vec![
+                ("versions", ""),
+                ("delimiter", delimiter),
+                ("max-keys", &max_keys),
+                ("prefix", prefix),
+            ];
+            if let Some(key_marker) = key_marker {
+                query.push(("key-marker", key_marker));
+            }
+            if let Some(version_id_marker) = version_id_marker {
+                query.push(("version-id-marker", version_id_marker));
+            }
+
+            message
+                .set_request_path_and_query("/", query)
+                .map_err(S3RequestError::construction_failure)?;
+
+            let span = request_span!(
+                self.inner,
+                "list_object_versions",
+                bucket,
+                continued = key_marker.is_some(),
+                delimiter,
+                max_keys,
+                prefix
+            );
+
+            self.inner
+                .make_simple_http_request(message, MetaRequestType::Default, span, parse_list_objects_error)?
+        };
+
+        let body = body.await?;
+
+        parse_result_from_bytes(&body)
+            .map_err(|e| ObjectClientError::ClientError(S3RequestError::InternalError(e.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions_and_delete_markers() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?>
+<ListVersionsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+    <Name>DOC-EXAMPLE-BUCKET</Name>
+    <Prefix>dir/</Prefix>
+    <KeyMarker></KeyMarker>
+    <VersionIdMarker></VersionIdMarker>
+    <NextKeyMarker>dir/b</NextKeyMarker>
+    <NextVersionIdMarker>3HL4kqtJlcpXroDTDmJ.rmSpXd3dIbrHY</NextVersionIdMarker>
+    <MaxKeys>3</MaxKeys>
+    <Delimiter>/</Delimiter>
+    <IsTruncated>true</IsTruncated>
+    <Version>
+        <Key>dir/a</Key>
+        <VersionId>null</VersionId>
+        <IsLatest>false</IsLatest>
+        <LastModified>2009-10-12T17:50:30.000Z</LastModified>
+        <ETag>"fba9dede5f27731c9771645a39863328"</ETag>
+        <Size>434234</Size>
+        <StorageClass>STANDARD</StorageClass>
+    </Version>
+    <DeleteMarker>
+        <Key>dir/a</Key>
+        <VersionId>03jpff543dhffds434rfdsFDN943fdsFkdmqnh892</VersionId>
+        <IsLatest>true</IsLatest>
+        <LastModified>2009-11-12T17:50:30.000Z</LastModified>
+    </DeleteMarker>
+    <Version>
+        <Key>dir/b</Key>
+        <VersionId>3HL4kqtJlcpXroDTDmJ.rmSpXd3dIbrHY</VersionId>
+        <IsLatest>true</IsLatest>
+        <LastModified>2009-10-12T17:50:30.000Z</LastModified>
+        <ETag>"fba9dede5f27731c9771645a39863328"</ETag>
+        <Size>100</Size>
+    </Version>
+    <CommonPrefixes>
+        <Prefix>dir/sub/</Prefix>
+    </CommonPrefixes>
+</ListVersionsResult>"#;
+        let result = parse_result_from_bytes(body).expect("should parse");
+
+        // Versions and delete markers stay in the order they were listed
+        assert_eq!(result.versions.len(), 3);
+        let ObjectVersionEntry::Version(version) = &result.versions[0] else {
+            panic!("expected a version, got {:?}", result.versions[0]);
+        };
+        assert_eq!(version.key, "dir/a");
+        assert_eq!(version.version_id, "null");
+        assert!(!version.is_latest);
+        assert_eq!(version.size, 434234);
+        assert_eq!(version.storage_class.as_deref(), Some("STANDARD"));
+        let ObjectVersionEntry::DeleteMarker(marker) = &result.versions[1] else {
+            panic!("expected a delete marker, got {:?}", result.versions[1]);
+        };
+        assert_eq!(marker.key, "dir/a");
+        assert!(marker.is_latest);
+        let ObjectVersionEntry::Version(version) = &result.versions[2] else {
+            panic!("expected a version, got {:?}", result.versions[2]);
+        };
+        assert_eq!(version.key, "dir/b");
+        assert!(version.is_latest);
+        assert_eq!(version.storage_class, None);
+
+        assert_eq!(result.common_prefixes, vec!["dir/sub/".to_string()]);
+        assert_eq!(result.next_key_marker.as_deref(), Some("dir/b"));
+        assert_eq!(
+            result.next_version_id_marker.as_deref(),
+            Some("3HL4kqtJlcpXroDTDmJ.rmSpXd3dIbrHY")
+        );
+    }
+}
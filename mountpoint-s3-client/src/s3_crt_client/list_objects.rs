@@ -37,7 +37,7 @@ pub enum ParseError {
 }
 
 /// Copy text out of an XML element, with the right error type.
-fn get_text(element: &xmltree::Element) -> Result<String, ParseError> {
+pub(super) fn get_text(element: &xmltree::Element) -> Result<String, ParseError> {
     Ok(element
         .get_text()
         .ok_or_else(|| ParseError::InvalidResponse(element.clone(), "field has no text".to_string()))?
@@ -52,7 +52,7 @@ fn get_child<'a>(element: &'a xmltree::Element, name: &str) -> Result<&'a xmltre
 }
 
 /// Get the text out of a child node, with the right error type.
-fn get_field(element: &xmltree::Element, name: &str) -> Result<String, ParseError> {
+pub(super) fn get_field(element: &xmltree::Element, name: &str) -> Result<String, ParseError> {
     get_text(get_child(element, name)?)
 }
 
@@ -198,7 +198,7 @@ impl S3CrtClient {
     }
 }
 
-fn parse_list_objects_error(result: &MetaRequestResult) -> Option<ListObjectsError> {
+pub(super) fn parse_list_objects_error(result: &MetaRequestResult) -> Option<ListObjectsError> {
     match result.response_status {
         404 => {
             let body = result.error_response_body.as_ref()?;
@@ -0,0 +1,480 @@
+use std::ops::{Deref, Range};
+use std::os::unix::prelude::OsStrExt;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::task::SpawnExt;
+use mountpoint_s3_crt::http::request_response::{Header, Headers};
+use mountpoint_s3_crt::s3::client::{MetaRequestResult, MetaRequestType};
+use percent_encoding::utf8_percent_encode;
+use thiserror::Error;
+use tracing::warn;
+use xmltree::{Element, XMLNode};
+
+use crate::object_client::{
+    ETag, MultipartUpload, ObjectClientError, ObjectClientResult, PutObjectError, PutObjectParams, PutObjectResult,
+};
+use crate::s3_crt_client::{S3CrtClient, S3RequestError};
+
+use super::copy_object::URLENCODE_COPY_SOURCE;
+use super::put_object::{set_new_object_headers, try_get_header_value, SSE_KEY_ID_HEADER_NAME, SSE_TYPE_HEADER_NAME};
+use super::{S3CrtClientInner, S3Message};
+
+#[derive(Error, Debug)]
+enum ParseError {
+    #[error("XML parsing error: {0:?}")]
+    Xml(#[from] xmltree::ParseError),
+
+    #[error("Missing field {1} from XML element {0:?}")]
+    MissingField(Element, String),
+
+    #[error("Missing ETag header in response")]
+    MissingETag,
+
+    #[error("Request failed with error code {0}")]
+    ErrorResponse(String),
+}
+
+impl S3CrtClient {
+    /// Create a new multipart upload with a CreateMultipartUpload request.
+    pub(super) async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        params: &PutObjectParams,
+    ) -> ObjectClientResult<S3MultipartUpload, PutObjectError, S3RequestError> {
+        let span = request_span!(self.inner, "create_multipart_upload", bucket, key);
+
+        // Scope the endpoint, message, etc. since otherwise rustc thinks we use Message across the await.
+        let request = {
+            let mut message = self
+                .inner
+                .new_request_template("POST", bucket)
+                .map_err(S3RequestError::construction_failure)?;
+            message
+                .set_request_path_and_query(format!("/{key}"), [("uploads", "")])
+                .map_err(S3RequestError::construction_failure)?;
+            set_new_object_headers(&mut message, params).map_err(S3RequestError::construction_failure)?;
+
+            self.inner.make_simple_http_request(
+                message,
+                MetaRequestType::Default,
+                span,
+                parse_multipart_upload_error,
+            )?
+        };
+
+        let body = request.await?;
+
+        let upload_id = parse_upload_id(&body).map_err(|e| S3RequestError::InternalError(e.into()))?;
+        Ok(S3MultipartUpload {
+            client: self.inner.clone(),
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            upload_id,
+            parts: Vec::new(),
+            finished: false,
+        })
+    }
+}
+
+/// An in-progress multipart upload to S3.
+///
+/// You can add parts to, complete, or abort the upload using the [`MultipartUpload`]
+/// implementation on this object. Dropping it before it completes, or after it fails to complete,
+/// aborts the upload in the background, on a best-effort basis.
+#[derive(Debug)]
+pub struct S3MultipartUpload {
+    client: Arc<S3CrtClientInner>,
+    bucket: String,
+    key: String,
+    upload_id: String,
+    /// ETags of the parts added so far, in part number order
+    parts: Vec<ETag>,
+    /// Whether the upload was completed, or an abort was requested
+    finished: bool,
+}
+
+impl Drop for S3MultipartUpload {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        let upload = S3MultipartUpload {
+            client: self.client.clone(),
+            bucket: self.bucket.clone(),
+            key: self.key.clone(),
+            upload_id: self.upload_id.clone(),
+            parts: Vec::new(),
+            finished: false,
+        };
+        let abort = async move {
+            let (key, upload_id) = (upload.key.clone(), upload.upload_id.clone());
+            if let Err(error) = upload.abort().await {
+                warn!(?key, ?upload_id, ?error, "failed to abort dropped multipart upload");
+            }
+        };
+        if let Err(error) = self.client.event_loop_group.spawn(abort) {
+            warn!(key=?self.key, upload_id=?self.upload_id, ?error, "failed to abort dropped multipart upload");
+        }
+    }
+}
+
+impl S3MultipartUpload {
+    fn next_part_number(&self) -> String {
+        (self.parts.len() + 1).to_string()
+    }
+
+    fn new_part_request(&self, part_number: &str) -> Result<S3Message, S3RequestError> {
+        let mut message = self
+            .client
+            .new_request_template("PUT", &self.bucket)
+            .map_err(S3RequestError::construction_failure)?;
+        message
+            .set_request_path_and_query(
+                format!("/{}", self.key),
+                [("partNumber", part_number), ("uploadId", self.upload_id.as_str())],
+            )
+            .map_err(S3RequestError::construction_failure)?;
+        Ok(message)
+    }
+}
+
+#[cfg_attr(not(docs_rs), async_trait)]
+impl MultipartUpload for S3MultipartUpload {
+    type ClientError = S3RequestError;
+
+    async fn upload_part(&mut self, data: &[u8]) -> ObjectClientResult<(), PutObjectError, Self::ClientError> {
+        let (bucket, key, upload_id) = (self.bucket.as_str(), self.key.as_str(), self.upload_id.as_str());
+        let span = request_span!(self.client, "upload_part", bucket, key, upload_id);
+        let part_number = self.next_part_number();
+        let etag: Arc<Mutex<Option<ETag>>> = Default::default();
+        let etag1 = etag.clone();
+
+        let request = {
+            let mut message = self.new_part_request(&part_number)?;
+            message
+                .set_body(&self.client.allocator, data)
+                .map_err(S3RequestError::construction_failure)?;
+
+            self.client.make_meta_request(
+                message,
+                MetaRequestType::Default,
+                span,
+                move |headers: &Headers, _status| {
+                    *etag1.lock().unwrap() = try_get_header_value(headers, "ETag").and_then(|etag| etag.parse().ok());
+                },
+                |_, _| (),
+                move |result| {
+                    if result.is_err() {
+                        Err(parse_multipart_upload_error(result).map(ObjectClientError::ServiceError))
+                    } else {
+                        Ok(())
+                    }
+                },
+            )?
+        };
+
+        request.await?;
+
+        let etag = etag.lock().unwrap().take();
+        let etag = etag.ok_or_else(|| S3RequestError::InternalError(ParseError::MissingETag.into()))?;
+        self.parts.push(etag);
+        Ok(())
+    }
+
+    async fn upload_part_copy(
+        &mut self,
+        source_key: &str,
+        range: Range<u64>,
+        if_match: Option<ETag>,
+    ) -> ObjectClientResult<(), PutObjectError, Self::ClientError> {
+        assert!(!range.is_empty(), "copied range must not be empty");
+        let (bucket, key, upload_id) = (self.bucket.as_str(), self.key.as_str(), self.upload_id.as_str());
+        let span = request_span!(self.client, "upload_part_copy", bucket, key, upload_id, source_key);
+        let part_number = self.next_part_number();
+
+        let request = {
+            let mut message = self.new_part_request(&part_number)?;
+
+            let copy_source = format!("{}/{}", self.bucket, source_key);
+            let copy_source = utf8_percent_encode(&copy_source, URLENCODE_COPY_SOURCE).to_string();
+            message
+                .set_header(&Header::new("x-amz-copy-source", copy_source))
+                .map_err(S3RequestError::construction_failure)?;
+            message
+                .set_header(&Header::new(
+                    "x-amz-copy-source-range",
+                    format!("bytes={}-{}", range.start, range.end - 1),
+                ))
+                .map_err(S3RequestError::construction_failure)?;
+            if let Some(etag) = if_match {
+                message
+                    .set_header(&Header::new("x-amz-copy-source-if-match", etag.into_inner()))
+                    .map_err(S3RequestError::construction_failure)?;
+            }
+
+            self.client.make_simple_http_request(
+                message,
+                MetaRequestType::Default,
+                span,
+                parse_multipart_upload_error,
+            )?
+        };
+
+        let body = request.await?;
+
+        let etag = parse_copy_part_etag(&body).map_err(|e| S3RequestError::InternalError(e.into()))?;
+        self.parts.push(etag);
+        Ok(())
+    }
+
+    async fn complete(
+        mut self,
+        if_match: Option<ETag>,
+    ) -> ObjectClientResult<PutObjectResult, PutObjectError, Self::ClientError> {
+        let (bucket, key, upload_id) = (self.bucket.as_str(), self.key.as_str(), self.upload_id.as_str());
+        let span = request_span!(self.client, "complete_multipart_upload", bucket, key, upload_id);
+        let response_headers: Arc<Mutex<Option<Headers>>> = Default::default();
+        let response_headers_writer = response_headers.clone();
+
+        let request = {
+            let mut message = self
+                .client
+                .new_request_template("POST", &self.bucket)
+                .map_err(S3RequestError::construction_failure)?;
+            message
+                .set_request_path_and_query(format!("/{}", self.key), [("uploadId", self.upload_id.as_str())])
+                .map_err(S3RequestError::construction_failure)?;
+            if let Some(etag) = &if_match {
+                message
+                    .set_header(&Header::new("If-Match", etag.as_str()))
+                    .map_err(S3RequestError::construction_failure)?;
+            }
+            message
+                .set_body(&self.client.allocator, complete_multipart_upload_body(&self.parts))
+                .map_err(S3RequestError::construction_failure)?;
+
+            let body: Arc<Mutex<Vec<u8>>> = Default::default();
+            let body_writer = body.clone();
+            self.client.make_meta_request(
+                message,
+                MetaRequestType::Default,
+                span,
+                move |headers: &Headers, _status| {
+                    *response_headers_writer.lock().unwrap() = Some(headers.clone());
+                },
+                move |_offset, data| body_writer.lock().unwrap().extend_from_slice(data),
+                move |result| {
+                    if result.is_err() {
+                        Err(parse_multipart_upload_error(result).map(ObjectClientError::ServiceError))
+                    } else {
+                        Ok(std::mem::take(&mut *body.lock().unwrap()))
+                    }
+                },
+            )?
+        };
+
+        let body = request.await?;
+
+        // CompleteMultipartUpload can fail after the response status has already been sent, in
+        // which case the error is in the body of a 200 response.
+        check_error_response(&body).map_err(|e| S3RequestError::InternalError(e.into()))?;
+        self.finished = true;
+
+        let response_headers = response_headers.lock().unwrap().take();
+        Ok(PutObjectResult {
+            etag: parse_complete_etag(&body),
+            sse_type: response_headers
+                .as_ref()
+                .and_then(|headers| try_get_header_value(headers, SSE_TYPE_HEADER_NAME)),
+            sse_kms_key_id: response_headers
+                .as_ref()
+                .and_then(|headers| try_get_header_value(headers, SSE_KEY_ID_HEADER_NAME)),
+        })
+    }
+
+    async fn abort(mut self) -> ObjectClientResult<(), PutObjectError, Self::ClientError> {
+        // Don't try to abort the upload again if this fails
+        self.finished = true;
+        let (bucket, key, upload_id) = (self.bucket.as_str(), self.key.as_str(), self.upload_id.as_str());
+        let span = request_span!(self.client, "abort_multipart_upload", bucket, key, upload_id);
+        let request = {
+            let mut message = self
+                .client
+                .new_request_template("DELETE", &self.bucket)
+                .map_err(S3RequestError::construction_failure)?;
+            message
+                .set_request_path_and_query(format!("/{}", self.key), [("uploadId", self.upload_id.as_str())])
+                .map_err(S3RequestError::construction_failure)?;
+
+            self.client.make_simple_http_request(
+                message,
+                MetaRequestType::Default,
+                span,
+                parse_multipart_upload_error,
+            )?
+        };
+
+        let _body = request.await?;
+        Ok(())
+    }
+}
+
+fn parse_upload_id(body: &[u8]) -> Result<String, ParseError> {
+    let root = Element::parse(body)?;
+    let upload_id = root
+        .get_child("UploadId")
+        .and_then(|element| element.get_text())
+        .ok_or_else(|| ParseError::MissingField(root.clone(), "UploadId".to_string()))?;
+    Ok(upload_id.into_owned())
+}
+
+fn parse_copy_part_etag(body: &[u8]) -> Result<ETag, ParseError> {
+    check_error_response(body)?;
+    let root = Element::parse(body)?;
+    let etag = root
+        .get_child("ETag")
+        .and_then(|element| element.get_text())
+        .ok_or_else(|| ParseError::MissingField(root.clone(), "ETag".to_string()))?;
+    Ok(etag.parse().unwrap())
+}
+
+/// Get the ETag of the new object from the body of a CompleteMultipartUpload response
+fn parse_complete_etag(body: &[u8]) -> Option<ETag> {
+    let root = Element::parse(body).ok()?;
+    let etag = root.get_child("ETag")?.get_text()?;
+    etag.parse().ok()
+}
+
+/// Check for an error in the body of a successful response, which S3 can return for requests that
+/// take a long time to process, like UploadPartCopy and CompleteMultipartUpload.
+fn check_error_response(body: &[u8]) -> Result<(), ParseError> {
+    let root = Element::parse(body)?;
+    if root.name != "Error" {
+        return Ok(());
+    }
+    let code = root
+        .get_child("Code")
+        .and_then(|element| element.get_text())
+        .unwrap_or_default();
+    Err(ParseError::ErrorResponse(code.into_owned()))
+}
+
+fn complete_multipart_upload_body(parts: &[ETag]) -> Vec<u8> {
+    fn text_element(name: &str, text: impl Into<String>) -> XMLNode {
+        let mut element = Element::new(name);
+        element.children.push(XMLNode::Text(text.into()));
+        XMLNode::Element(element)
+    }
+
+    let mut root = Element::new("CompleteMultipartUpload");
+    for (i, etag) in parts.iter().enumerate() {
+        let mut part = Element::new("Part");
+        part.children.push(text_element("ETag", etag.as_str()));
+        part.children.push(text_element("PartNumber", (i + 1).to_string()));
+        root.children.push(XMLNode::Element(part));
+    }
+
+    let mut body = Vec::new();
+    root.write(&mut body).expect("writing to a Vec cannot fail");
+    body
+}
+
+fn parse_multipart_upload_error(result: &MetaRequestResult) -> Option<PutObjectError> {
+    match result.response_status {
+        404 => {
+            let body = result.error_response_body.as_ref()?;
+            let root = Element::parse(body.as_bytes()).ok()?;
+            let error_code = root.get_child("Code")?;
+            let error_str = error_code.get_text()?;
+            match error_str.deref() {
+                "NoSuchBucket" => Some(PutObjectError::NoSuchBucket),
+                _ => None,
+            }
+        }
+        412 => Some(PutObjectError::PreconditionFailed),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{OsStr, OsString};
+
+    use super::*;
+
+    fn make_result(response_status: i32, body: Option<impl Into<OsString>>) -> MetaRequestResult {
+        MetaRequestResult {
+            response_status,
+            crt_error: 1i32.into(),
+            error_response_headers: None,
+            error_response_body: body.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn parse_create_multipart_upload_response() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><InitiateMultipartUploadResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/"><Bucket>amzn-s3-demo-bucket</Bucket><Key>hello</Key><UploadId>VXBsb2FkIElEIGZvciBlbHZpbmcncyBteS1tb3ZpZS5tMnRzIHVwbG9hZA</UploadId></InitiateMultipartUploadResult>"#;
+        let upload_id = parse_upload_id(body).unwrap();
+        assert_eq!(upload_id, "VXBsb2FkIElEIGZvciBlbHZpbmcncyBteS1tb3ZpZS5tMnRzIHVwbG9hZA");
+    }
+
+    #[test]
+    fn parse_upload_part_copy_response() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><CopyPartResult><LastModified>2024-06-20T17:32:35.000Z</LastModified><ETag>"b54357faf0632cce46e942fa68356b38"</ETag></CopyPartResult>"#;
+        let etag = parse_copy_part_etag(body).unwrap();
+        assert_eq!(etag.as_str(), "\"b54357faf0632cce46e942fa68356b38\"");
+    }
+
+    #[test]
+    fn parse_error_in_successful_response() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>InternalError</Code><Message>We encountered an internal error. Please try again.</Message><RequestId>656c76696e6727732072657175657374</RequestId><HostId>Uuag1LuByRx9e6j5Onimru9pO4ZVKnJ2Qz7/C1NPcfTWAtRPfTaOFg==</HostId></Error>"#;
+        let err = parse_copy_part_etag(body).expect_err("error response should fail");
+        assert!(matches!(err, ParseError::ErrorResponse(code) if code == "InternalError"));
+        let err = check_error_response(body).expect_err("error response should fail");
+        assert!(matches!(err, ParseError::ErrorResponse(code) if code == "InternalError"));
+    }
+
+    #[test]
+    fn build_complete_multipart_upload_body() {
+        let parts = ["\"a\"".parse().unwrap(), "\"b\"".parse().unwrap()];
+        let body = complete_multipart_upload_body(&parts);
+        let root = Element::parse(&body[..]).unwrap();
+        assert_eq!(root.name, "CompleteMultipartUpload");
+        let parts: Vec<_> = root
+            .children
+            .iter()
+            .filter_map(|node| node.as_element())
+            .map(|part| {
+                (
+                    part.get_child("ETag").unwrap().get_text().unwrap().into_owned(),
+                    part.get_child("PartNumber").unwrap().get_text().unwrap().into_owned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                ("\"a\"".to_owned(), "1".to_owned()),
+                ("\"b\"".to_owned(), "2".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_412_precondition_failed() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>PreconditionFailed</Code><Message>At least one of the pre-conditions you specified did not hold</Message><Condition>x-amz-copy-source-If-Match</Condition><RequestId>TX5YD2G1W0G3RJWY</RequestId><HostId>1ZR3hN0wG5ZHhbNQ0PTj5b1xPSHgZ6mbGRHiKZBeD8QwFC9dfuGmUQWb0cSJm8V9sXMVNRa2xXo=</HostId></Error>"#;
+        let result = make_result(412, Some(OsStr::from_bytes(&body[..])));
+        let result = parse_multipart_upload_error(&result);
+        assert_eq!(result, Some(PutObjectError::PreconditionFailed));
+    }
+
+    #[test]
+    fn parse_404_no_such_bucket() {
+        let body = br#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>NoSuchBucket</Code><Message>The specified bucket does not exist</Message><BucketName>amzn-s3-demo-bucket</BucketName><RequestId>BHCQ0FTYY0HKMV43</RequestId><HostId>ntCK1jQfPxY7sSNL/GB13RttgJLjSETfIuOiuRnwImO0dQP2ttj2Qqpn5S/jSLt3Ql0TgHWuYF0=</HostId></Error>"#;
+        let result = make_result(404, Some(OsStr::from_bytes(&body[..])));
+        let result = parse_multipart_upload_error(&result);
+        assert_eq!(result, Some(PutObjectError::NoSuchBucket));
+    }
+}
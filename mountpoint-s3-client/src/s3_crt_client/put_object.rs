@@ -1,18 +1,25 @@
+use std::ops::Deref;
+use std::os::unix::prelude::OsStrExt;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use crate::object_client::{ObjectClientResult, PutObjectError, PutObjectParams, PutObjectRequest, PutObjectResult};
+use crate::object_client::{
+    ObjectClientError, ObjectClientResult, PutObjectError, PutObjectParams, PutObjectRequest, PutObjectResult,
+};
 use crate::s3_crt_client::{emit_throughput_metric, PutObjectTrailingChecksums, S3CrtClient, S3RequestError};
 use async_trait::async_trait;
 use futures::channel::oneshot;
 use mountpoint_s3_crt::http::request_response::{Header, Headers};
-use mountpoint_s3_crt::s3::client::{ChecksumConfig, MetaRequestType, RequestType, UploadReview};
+use mountpoint_s3_crt::s3::client::{ChecksumConfig, MetaRequestResult, MetaRequestType, RequestType, UploadReview};
 use tracing::error;
+use xmltree::Element;
 
-use super::{S3CrtClientInner, S3HttpRequest};
+use super::head_object::USER_METADATA_HEADER_PREFIX;
+use super::{S3CrtClientInner, S3HttpRequest, S3Message};
 
-const SSE_TYPE_HEADER_NAME: &str = "x-amz-server-side-encryption";
-const SSE_KEY_ID_HEADER_NAME: &str = "x-amz-server-side-encryption-aws-kms-key-id";
+const ETAG_HEADER_NAME: &str = "ETag";
+pub(super) const SSE_TYPE_HEADER_NAME: &str = "x-amz-server-side-encryption";
+pub(super) const SSE_KEY_ID_HEADER_NAME: &str = "x-amz-server-side-encryption-aws-kms-key-id";
 
 impl S3CrtClient {
     pub(super) async fn put_object(
@@ -42,19 +49,10 @@ impl S3CrtClient {
         let review_callback = ReviewCallbackBox::default();
         let callback = review_callback.clone();
 
-        if let Some(storage_class) = params.storage_class.to_owned() {
+        set_new_object_headers(&mut message, params).map_err(S3RequestError::construction_failure)?;
+        if let Some(etag) = &params.if_match {
             message
-                .set_header(&Header::new("x-amz-storage-class", storage_class))
-                .map_err(S3RequestError::construction_failure)?;
-        }
-        if let Some(sse) = params.server_side_encryption.as_ref() {
-            message
-                .set_header(&Header::new(SSE_TYPE_HEADER_NAME, sse))
-                .map_err(S3RequestError::construction_failure)?;
-        }
-        if let Some(key_id) = params.ssekms_key_id.as_ref() {
-            message
-                .set_header(&Header::new(SSE_KEY_ID_HEADER_NAME, key_id))
+                .set_header(&Header::new("If-Match", etag.as_str()))
                 .map_err(S3RequestError::construction_failure)?;
         }
         // Variable `response_headers` will be accessed from different threads: from CRT thread which executes `on_headers` callback
@@ -109,6 +107,110 @@ impl S3CrtClient {
     }
 }
 
+impl S3CrtClient {
+    /// Append to an existing object with a PutObject request that sets the write offset.
+    pub(super) async fn append_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> ObjectClientResult<PutObjectResult, PutObjectError, S3RequestError> {
+        let span = request_span!(self.inner, "append_object", bucket, key, offset);
+        let response_headers: Arc<Mutex<Option<Headers>>> = Default::default();
+        let response_headers_writer = response_headers.clone();
+
+        // Scope the message since otherwise rustc thinks we use it across the await.
+        let request = {
+            let mut message = self
+                .inner
+                .new_request_template("PUT", bucket)
+                .map_err(S3RequestError::construction_failure)?;
+            message
+                .set_request_path(format!("/{key}"))
+                .map_err(S3RequestError::construction_failure)?;
+            message
+                .set_header(&Header::new("x-amz-write-offset-bytes", offset.to_string()))
+                .map_err(S3RequestError::construction_failure)?;
+            message
+                .set_body(&self.inner.allocator, data)
+                .map_err(S3RequestError::construction_failure)?;
+
+            self.inner.make_meta_request(
+                message,
+                MetaRequestType::Default,
+                span,
+                move |headers: &Headers, _status| {
+                    *response_headers_writer.lock().unwrap() = Some(headers.clone());
+                },
+                |_, _| (),
+                move |result| {
+                    if result.is_err() {
+                        Err(parse_append_object_error(result).map(ObjectClientError::ServiceError))
+                    } else {
+                        Ok(())
+                    }
+                },
+            )?
+        };
+
+        request.await?;
+
+        let response_headers = response_headers.lock().unwrap().take();
+        Ok(PutObjectResult {
+            etag: response_headers
+                .as_ref()
+                .and_then(|headers| try_get_header_value(headers, ETAG_HEADER_NAME))
+                .and_then(|etag| etag.parse().ok()),
+            sse_type: response_headers
+                .as_ref()
+                .and_then(|headers| try_get_header_value(headers, SSE_TYPE_HEADER_NAME)),
+            sse_kms_key_id: response_headers
+                .as_ref()
+                .and_then(|headers| try_get_header_value(headers, SSE_KEY_ID_HEADER_NAME)),
+        })
+    }
+}
+
+fn parse_append_object_error(result: &MetaRequestResult) -> Option<PutObjectError> {
+    match result.response_status {
+        400 | 404 => {
+            let body = result.error_response_body.as_ref()?;
+            let root = Element::parse(body.as_bytes()).ok()?;
+            let error_code = root.get_child("Code")?;
+            let error_str = error_code.get_text()?;
+            match error_str.deref() {
+                "InvalidWriteOffset" => Some(PutObjectError::InvalidWriteOffset),
+                "NoSuchBucket" => Some(PutObjectError::NoSuchBucket),
+                _ => None,
+            }
+        }
+        412 => Some(PutObjectError::PreconditionFailed),
+        _ => None,
+    }
+}
+
+/// Set the headers describing a new object (storage class, server-side encryption and user-defined
+/// metadata) on a PutObject or CreateMultipartUpload request.
+pub(super) fn set_new_object_headers(
+    message: &mut S3Message,
+    params: &PutObjectParams,
+) -> Result<(), mountpoint_s3_crt::common::error::Error> {
+    if let Some(storage_class) = params.storage_class.as_ref() {
+        message.set_header(&Header::new("x-amz-storage-class", storage_class))?;
+    }
+    if let Some(sse) = params.server_side_encryption.as_ref() {
+        message.set_header(&Header::new(SSE_TYPE_HEADER_NAME, sse))?;
+    }
+    if let Some(key_id) = params.ssekms_key_id.as_ref() {
+        message.set_header(&Header::new(SSE_KEY_ID_HEADER_NAME, key_id))?;
+    }
+    for (name, value) in &params.user_metadata {
+        message.set_header(&Header::new(format!("{USER_METADATA_HEADER_PREFIX}{name}"), value))?;
+    }
+    Ok(())
+}
+
 type ReviewCallback = dyn FnOnce(UploadReview) -> bool + Send;
 
 /// Holder for the upload review callback.
@@ -170,7 +272,7 @@ enum S3PutObjectRequestState {
     Idle,
 }
 
-fn try_get_header_value(headers: &Headers, key: &str) -> Option<String> {
+pub(super) fn try_get_header_value(headers: &Headers, key: &str) -> Option<String> {
     headers.get(key).ok()?.value().clone().into_string().ok()
 }
 
@@ -247,6 +349,7 @@ impl PutObjectRequest for S3PutObjectRequest {
             .take()
             .expect("PUT response headers must be available at this point");
         Ok(PutObjectResult {
+            etag: try_get_header_value(&response_headers, ETAG_HEADER_NAME).and_then(|etag| etag.parse().ok()),
             sse_type: try_get_header_value(&response_headers, SSE_TYPE_HEADER_NAME),
             sse_kms_key_id: try_get_header_value(&response_headers, SSE_KEY_ID_HEADER_NAME),
         })
@@ -260,3 +363,36 @@ impl S3PutObjectRequest {
         self.total_bytes
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::*;
+
+    fn make_result(response_status: i32, body: Option<&str>) -> MetaRequestResult {
+        MetaRequestResult {
+            response_status,
+            crt_error: 1i32.into(),
+            error_response_headers: None,
+            error_response_body: body.map(OsString::from),
+        }
+    }
+
+    #[test]
+    fn parse_invalid_write_offset() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>InvalidWriteOffset</Code><Message>The write offset value that you provided does not match the current object size.</Message></Error>"#;
+        let result = make_result(400, Some(body));
+        assert_eq!(
+            parse_append_object_error(&result),
+            Some(PutObjectError::InvalidWriteOffset)
+        );
+    }
+
+    #[test]
+    fn parse_other_bad_request() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>InvalidRequest</Code><Message>Bad request</Message></Error>"#;
+        let result = make_result(400, Some(body));
+        assert_eq!(parse_append_object_error(&result), None);
+    }
+}
@@ -0,0 +1,103 @@
+#![cfg(feature = "s3_tests")]
+
+pub mod common;
+
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use common::*;
+use mountpoint_s3_client::error::{CopyObjectError, ObjectClientError};
+use mountpoint_s3_client::types::CopyObjectParams;
+use mountpoint_s3_client::{ObjectClient, S3CrtClient};
+
+#[tokio::test]
+async fn test_copy_object() {
+    let sdk_client = get_test_sdk_client().await;
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_copy_object");
+
+    let source_key = format!("{prefix}/hello");
+    let destination_key = format!("{prefix}/hello copy");
+    let body = b"hello world!";
+    sdk_client
+        .put_object()
+        .bucket(&bucket)
+        .key(&source_key)
+        .body(ByteStream::from(Bytes::from_static(body)))
+        .send()
+        .await
+        .unwrap();
+
+    let client: S3CrtClient = get_test_client();
+    let _result = client
+        .copy_object(&bucket, &source_key, &bucket, &destination_key, &Default::default())
+        .await
+        .expect("copy_object should succeed");
+
+    let result = sdk_client
+        .get_object()
+        .bucket(&bucket)
+        .key(&destination_key)
+        .send()
+        .await
+        .expect("copied object should exist");
+    let copied = result.body.collect().await.unwrap().into_bytes();
+    assert_eq!(&copied[..], &body[..]);
+
+    let _source = sdk_client
+        .head_object()
+        .bucket(&bucket)
+        .key(&source_key)
+        .send()
+        .await
+        .expect("source object should still exist");
+}
+
+#[tokio::test]
+async fn test_copy_object_no_source() {
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_copy_object_no_source");
+
+    let source_key = format!("{prefix}/nonexistent_key");
+    let destination_key = format!("{prefix}/copy");
+
+    let client: S3CrtClient = get_test_client();
+    let result = client
+        .copy_object(&bucket, &source_key, &bucket, &destination_key, &Default::default())
+        .await;
+    assert!(matches!(
+        result,
+        Err(ObjectClientError::ServiceError(CopyObjectError::NotFound))
+    ));
+}
+
+#[tokio::test]
+async fn test_copy_object_source_if_match() {
+    let sdk_client = get_test_sdk_client().await;
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_copy_object_source_if_match");
+
+    let source_key = format!("{prefix}/hello");
+    let destination_key = format!("{prefix}/hello copy");
+    let put_result = sdk_client
+        .put_object()
+        .bucket(&bucket)
+        .key(&source_key)
+        .body(ByteStream::from(Bytes::from_static(b"hello world!")))
+        .send()
+        .await
+        .unwrap();
+    let etag = put_result.e_tag().unwrap().parse().unwrap();
+
+    let client: S3CrtClient = get_test_client();
+    let params = CopyObjectParams::new().source_if_match(Some("\"not-the-etag\"".parse().unwrap()));
+    let result = client
+        .copy_object(&bucket, &source_key, &bucket, &destination_key, &params)
+        .await;
+    assert!(matches!(
+        result,
+        Err(ObjectClientError::ServiceError(CopyObjectError::PreconditionFailed))
+    ));
+
+    let params = CopyObjectParams::new().source_if_match(Some(etag));
+    client
+        .copy_object(&bucket, &source_key, &bucket, &destination_key, &params)
+        .await
+        .expect("copy_object should succeed with the source's ETag");
+}
@@ -14,7 +14,7 @@ use bytes::Bytes;
 use common::*;
 use futures::stream::StreamExt;
 use mountpoint_s3_client::error::{GetObjectError, ObjectClientError};
-use mountpoint_s3_client::types::ETag;
+use mountpoint_s3_client::types::{ETag, GetObjectParams};
 use mountpoint_s3_client::{ObjectClient, S3CrtClient};
 
 use test_case::test_case;
@@ -45,7 +45,7 @@ async fn test_get_object(size: usize, range: Option<Range<u64>>) {
     let client: S3CrtClient = get_test_client();
 
     let result = client
-        .get_object(&bucket, &key, range.clone(), None)
+        .get_object(&bucket, &key, &GetObjectParams::new().range(range.clone()))
         .await
         .expect("get_object should succeed");
     let expected = match range {
@@ -82,7 +82,7 @@ async fn test_get_object_backpressure(size: usize, range: Option<Range<u64>>) {
     let client: S3CrtClient = get_test_backpressure_client(initial_window_size);
 
     let request = client
-        .get_object(&bucket, &key, range.clone(), None)
+        .get_object(&bucket, &key, &GetObjectParams::new().range(range.clone()))
         .await
         .expect("get_object should succeed");
     let expected = match range {
@@ -116,7 +116,7 @@ async fn verify_backpressure_get_object() {
         .unwrap();
 
     let mut get_request = client
-        .get_object(&bucket, &key, Some(range.clone()), None)
+        .get_object(&bucket, &key, &GetObjectParams::new().range(Some(range.clone())))
         .await
         .expect("should not fail");
 
@@ -153,7 +153,7 @@ async fn test_get_object_404_key() {
     let client: S3CrtClient = get_test_client();
 
     let mut result = client
-        .get_object(&bucket, &key, None, None)
+        .get_object(&bucket, &key, &GetObjectParams::new())
         .await
         .expect("get_object should succeed");
     let next = StreamExt::next(&mut result).await.expect("stream needs to return Err");
@@ -174,7 +174,7 @@ async fn test_get_object_404_bucket() {
     let client: S3CrtClient = get_test_client();
 
     let mut result = client
-        .get_object("DOC-EXAMPLE-BUCKET", &key, None, None)
+        .get_object("DOC-EXAMPLE-BUCKET", &key, &GetObjectParams::new())
         .await
         .expect("get_object failed");
     let next = StreamExt::next(&mut result).await.expect("stream needs to return Err");
@@ -206,7 +206,7 @@ async fn test_get_object_success_if_match() {
     let etag = Some(ETag::from_str(response.e_tag().expect("E-Tag should be set")).unwrap());
 
     let result = client
-        .get_object(&bucket, &key, None, etag)
+        .get_object(&bucket, &key, &GetObjectParams::new().if_match(etag))
         .await
         .expect("get_object should succeed");
     check_get_result(result, None, &body[..]).await;
@@ -233,7 +233,7 @@ async fn test_get_object_412_if_match() {
     let etag = Some(ETag::from_str("incorrect_etag").unwrap());
 
     let mut result = client
-        .get_object(&bucket, &key, None, etag)
+        .get_object(&bucket, &key, &GetObjectParams::new().if_match(etag))
         .await
         .expect("get_object should succeed");
 
@@ -269,7 +269,7 @@ async fn test_get_object_cancel(read: bool) {
     let client: S3CrtClient = get_test_client();
 
     let mut request = client
-        .get_object(&bucket, &key, None, None)
+        .get_object(&bucket, &key, &GetObjectParams::new())
         .await
         .expect("get_object should succeed");
 
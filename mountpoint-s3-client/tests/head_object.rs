@@ -2,6 +2,7 @@
 
 pub mod common;
 
+use std::collections::HashMap;
 #[cfg(not(feature = "s3express_tests"))]
 use std::time::{Duration, Instant};
 
@@ -41,6 +42,36 @@ async fn test_head_object() {
     assert_eq!(result.object.size as usize, body.len());
 }
 
+#[tokio::test]
+async fn test_head_object_user_metadata() {
+    let sdk_client = get_test_sdk_client().await;
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_head_object_user_metadata");
+
+    let key = format!("{prefix}/hello");
+    let body = b"hello world!";
+    sdk_client
+        .put_object()
+        .bucket(&bucket)
+        .key(&key)
+        .content_type("text/plain")
+        .metadata("owner", "data-team")
+        .metadata("Source", "pipeline")
+        .body(ByteStream::from(Bytes::from_static(body)))
+        .send()
+        .await
+        .unwrap();
+
+    let client: S3CrtClient = get_test_client();
+    let result = client.head_object(&bucket, &key).await.expect("head_object failed");
+
+    assert_eq!(result.content_type.as_deref(), Some("text/plain"));
+    let expected = HashMap::from([
+        ("owner".to_owned(), "data-team".to_owned()),
+        ("source".to_owned(), "pipeline".to_owned()),
+    ]);
+    assert_eq!(result.user_metadata, expected);
+}
+
 #[test_case("INTELLIGENT_TIERING")]
 #[test_case("GLACIER")]
 #[tokio::test]
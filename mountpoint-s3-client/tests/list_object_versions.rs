@@ -0,0 +1,84 @@
+#![cfg(all(feature = "s3_tests", not(feature = "s3express_tests")))]
+
+pub mod common;
+
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use common::*;
+use mountpoint_s3_client::error::{ListObjectsError, ObjectClientError};
+use mountpoint_s3_client::types::{GetObjectParams, ObjectVersionEntry};
+use mountpoint_s3_client::{ObjectClient, S3CrtClient};
+
+#[tokio::test]
+async fn test_list_object_versions() {
+    let sdk_client = get_test_sdk_client().await;
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_list_object_versions");
+    create_objects_for_test(&sdk_client, &bucket, &prefix, &["hello", "dir/a", "dir/b"]).await;
+
+    let client: S3CrtClient = get_test_client();
+
+    let result = client
+        .list_object_versions(&bucket, None, None, "/", 1000, &prefix)
+        .await
+        .expect("ListObjectVersions failed");
+
+    println!("{result:?}");
+    assert!(result.next_key_marker.is_none());
+    assert_eq!(result.versions.len(), 1);
+    let ObjectVersionEntry::Version(version) = &result.versions[0] else {
+        panic!("expected a version, got {:?}", result.versions[0]);
+    };
+    assert_eq!(version.key, format!("{}{}", prefix, "hello"));
+    assert!(version.is_latest);
+    assert_eq!(result.common_prefixes.len(), 1);
+    assert_eq!(result.common_prefixes[0], format!("{}{}", prefix, "dir/"));
+}
+
+#[tokio::test]
+async fn test_get_object_version() {
+    let sdk_client = get_test_sdk_client().await;
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_get_object_version");
+
+    let key = format!("{prefix}hello");
+    let put = sdk_client
+        .put_object()
+        .bucket(&bucket)
+        .key(&key)
+        .body(ByteStream::from(Bytes::from_static(b"hello world")))
+        .send()
+        .await
+        .unwrap();
+
+    let client: S3CrtClient = get_test_client();
+
+    let result = client
+        .list_object_versions(&bucket, None, None, "/", 1000, &prefix)
+        .await
+        .expect("ListObjectVersions failed");
+    assert_eq!(result.versions.len(), 1);
+    let ObjectVersionEntry::Version(version) = &result.versions[0] else {
+        panic!("expected a version, got {:?}", result.versions[0]);
+    };
+    let version_id = version.version_id.clone();
+    // Unversioned buckets report the version ID "null" for every object
+    assert_eq!(put.version_id().unwrap_or("null"), version_id);
+
+    let get = client
+        .get_object(&bucket, &key, &GetObjectParams::new().version_id(Some(version_id)))
+        .await
+        .expect("GetObject failed");
+    check_get_result(get, None, b"hello world").await;
+}
+
+#[tokio::test]
+async fn test_list_object_versions_404_bucket() {
+    let client: S3CrtClient = get_test_client();
+
+    let result = client
+        .list_object_versions("DOC-EXAMPLE-BUCKET", None, None, "/", 1000, "")
+        .await;
+    assert!(matches!(
+        result,
+        Err(ObjectClientError::ServiceError(ListObjectsError::NoSuchBucket))
+    ));
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "s3_tests")]
+
+pub mod common;
+
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use common::*;
+use mountpoint_s3_client::error::{ObjectClientError, PutObjectError};
+use mountpoint_s3_client::types::MIN_MULTIPART_PART_SIZE;
+use mountpoint_s3_client::{MultipartUpload, ObjectClient, S3CrtClient};
+
+#[tokio::test]
+async fn test_multipart_upload() {
+    let sdk_client = get_test_sdk_client().await;
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_multipart_upload");
+
+    let source_key = format!("{prefix}/source");
+    let key = format!("{prefix}/hello");
+
+    let source: Vec<u8> = (0..2 * MIN_MULTIPART_PART_SIZE).map(|i| (i % 251) as u8).collect();
+    let put_result = sdk_client
+        .put_object()
+        .bucket(&bucket)
+        .key(&source_key)
+        .body(ByteStream::from(Bytes::from(source.clone())))
+        .send()
+        .await
+        .unwrap();
+    let etag = put_result.e_tag().unwrap().parse().unwrap();
+
+    let client: S3CrtClient = get_test_client();
+    let mut upload = client
+        .create_multipart_upload(&bucket, &key, &Default::default())
+        .await
+        .expect("create_multipart_upload should succeed");
+    let copied = 1..MIN_MULTIPART_PART_SIZE + 1;
+    upload
+        .upload_part_copy(&source_key, copied.clone(), Some(etag))
+        .await
+        .expect("upload_part_copy should succeed");
+    upload
+        .upload_part(b"hello world")
+        .await
+        .expect("upload_part should succeed");
+    upload.complete(None).await.expect("complete should succeed");
+
+    let result = sdk_client
+        .get_object()
+        .bucket(&bucket)
+        .key(&key)
+        .send()
+        .await
+        .expect("uploaded object should exist");
+    let body = result.body.collect().await.unwrap().into_bytes();
+    let mut expected = source[copied.start as usize..copied.end as usize].to_vec();
+    expected.extend_from_slice(b"hello world");
+    assert_eq!(&body[..], &expected[..]);
+}
+
+#[tokio::test]
+async fn test_multipart_upload_copy_precondition_failed() {
+    let sdk_client = get_test_sdk_client().await;
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_multipart_upload_copy_precondition_failed");
+
+    let source_key = format!("{prefix}/source");
+    let key = format!("{prefix}/hello");
+    sdk_client
+        .put_object()
+        .bucket(&bucket)
+        .key(&source_key)
+        .body(ByteStream::from(Bytes::from_static(b"hello world")))
+        .send()
+        .await
+        .unwrap();
+
+    let client: S3CrtClient = get_test_client();
+    let mut upload = client
+        .create_multipart_upload(&bucket, &key, &Default::default())
+        .await
+        .expect("create_multipart_upload should succeed");
+    let result = upload
+        .upload_part_copy(&source_key, 0..5, Some("\"not-the-etag\"".parse().unwrap()))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed))
+    ));
+    upload.abort().await.expect("abort should succeed");
+
+    let result = sdk_client.head_object().bucket(&bucket).key(&key).send().await;
+    assert!(result.is_err(), "aborted upload should not create an object");
+}
@@ -2,6 +2,7 @@
 
 pub mod common;
 
+use std::collections::HashMap;
 use std::time::Duration;
 
 use common::*;
@@ -10,7 +11,8 @@ use mountpoint_s3_client::checksums::crc32c_to_base64;
 use mountpoint_s3_client::config::{EndpointConfig, S3ClientConfig};
 use mountpoint_s3_client::error::{GetObjectError, ObjectClientError};
 use mountpoint_s3_client::types::{
-    ChecksumAlgorithm, ObjectClientResult, PutObjectParams, PutObjectResult, PutObjectTrailingChecksums,
+    ChecksumAlgorithm, GetObjectParams, ObjectClientResult, PutObjectParams, PutObjectResult,
+    PutObjectTrailingChecksums,
 };
 use mountpoint_s3_client::{ObjectClient, PutObjectRequest, S3CrtClient, S3RequestError};
 use mountpoint_s3_crt::checksums::crc32c;
@@ -37,9 +39,10 @@ async fn test_put_object(
 
     request.write(&contents).await.unwrap();
     let put_object_result = request.complete().await.unwrap();
+    assert!(put_object_result.etag.is_some());
 
     let result = client
-        .get_object(bucket, key, None, None)
+        .get_object(bucket, key, &GetObjectParams::new())
         .await
         .expect("get_object should succeed");
     check_get_result(result, None, &contents[..]).await;
@@ -65,7 +68,7 @@ async fn test_put_object_empty(
     let put_object_result = request.complete().await.unwrap();
 
     let result = client
-        .get_object(bucket, key, None, None)
+        .get_object(bucket, key, &GetObjectParams::new())
         .await
         .expect("get_object should succeed");
     check_get_result(result, None, &[]).await;
@@ -100,7 +103,7 @@ async fn test_put_object_multi_part(
     let put_object_result = request.complete().await.unwrap();
 
     let result = client
-        .get_object(bucket, key, None, None)
+        .get_object(bucket, key, &GetObjectParams::new())
         .await
         .expect("get_object failed");
     check_get_result(result, None, &contents[..]).await;
@@ -137,7 +140,7 @@ async fn test_put_object_large(
     let put_object_result = request.complete().await.unwrap();
 
     let result = client
-        .get_object(bucket, key, None, None)
+        .get_object(bucket, key, &GetObjectParams::new())
         .await
         .expect("get_object failed");
     check_get_result(result, None, &contents[..]).await;
@@ -438,7 +441,7 @@ async fn check_get_object<Client: ObjectClient>(
     bucket: &str,
     key: &str,
 ) -> ObjectClientResult<(), GetObjectError, Client::ClientError> {
-    let result = client.get_object(bucket, key, None, None).await?;
+    let result = client.get_object(bucket, key, &GetObjectParams::new()).await?;
     pin_mut!(result);
     result.next().await.unwrap()?;
     Ok(())
@@ -480,6 +483,70 @@ async fn test_put_object_storage_class(storage_class: &str) {
     assert_eq!(storage_class, attributes.storage_class.unwrap().as_str());
 }
 
+#[tokio::test]
+async fn test_put_object_user_metadata() {
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_put_object_user_metadata");
+    let client = get_test_client();
+    let key = format!("{prefix}hello");
+
+    let mut rng = rand::thread_rng();
+    let mut contents = vec![0u8; 32];
+    rng.fill(&mut contents[..]);
+
+    let user_metadata = HashMap::from([
+        ("owner".to_owned(), "data-team".to_owned()),
+        ("source".to_owned(), "pipeline".to_owned()),
+    ]);
+    let params = PutObjectParams::new().user_metadata(user_metadata.clone());
+    let mut request = client
+        .put_object(&bucket, &key, &params)
+        .await
+        .expect("put_object should succeed");
+
+    request.write(&contents).await.unwrap();
+    request.complete().await.unwrap();
+
+    let sdk_client = get_test_sdk_client().await;
+    let head_object = sdk_client.head_object().bucket(bucket).key(key).send().await.unwrap();
+
+    assert_eq!(head_object.metadata, Some(user_metadata));
+}
+
+#[tokio::test]
+#[cfg(feature = "s3express_tests")]
+async fn test_append_object() {
+    let (bucket, prefix) = get_test_bucket_and_prefix("test_append_object");
+    let client = get_test_client();
+    let key = format!("{prefix}hello");
+
+    let mut request = client
+        .put_object(&bucket, &key, &Default::default())
+        .await
+        .expect("put_object should succeed");
+    request.write(b"hello").await.unwrap();
+    request.complete().await.unwrap();
+
+    client
+        .append_object(&bucket, &key, 5, b" world")
+        .await
+        .expect("append_object should succeed");
+
+    // Appends at any other offset than the end of the object fail
+    let result = client.append_object(&bucket, &key, 5, b"!").await;
+    assert!(matches!(
+        result,
+        Err(ObjectClientError::ServiceError(
+            mountpoint_s3_client::error::PutObjectError::InvalidWriteOffset
+        ))
+    ));
+
+    let result = client
+        .get_object(&bucket, &key, &GetObjectParams::new())
+        .await
+        .expect("get_object should succeed");
+    check_get_result(result, None, b"hello world").await;
+}
+
 #[cfg(not(feature = "s3express_tests"))]
 async fn check_sse(
     bucket: &String,
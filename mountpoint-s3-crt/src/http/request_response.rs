@@ -253,6 +253,9 @@ impl<'a> Iterator for HeadersIterator<'a> {
 pub struct Message {
     /// The pointer to the inner `aws_http_message`.
     pub(crate) inner: NonNull<aws_http_message>,
+    /// The body of the message, if any. The body stream of the inner message reads directly from
+    /// this buffer, so it must live as long as the message does.
+    body: Option<Box<[u8]>>,
 }
 
 impl Message {
@@ -264,7 +267,7 @@ impl Message {
         // SAFETY: `allocator.inner` is a valid `aws_allocator`.
         let inner = unsafe { aws_http_message_new_request(allocator.inner.as_ptr()).ok_or_last_error()? };
 
-        Ok(Self { inner })
+        Ok(Self { inner, body: None })
     }
 
     /// Add a header to this message. If the header already exists in the message, this will add a
@@ -306,6 +309,25 @@ impl Message {
         }
     }
 
+    /// Set the body of this message. Replaces any existing body.
+    pub fn set_body(&mut self, allocator: &Allocator, body: impl Into<Box<[u8]>>) -> Result<(), Error> {
+        let body = body.into();
+        // SAFETY: the cursor points into `body`, which we move into `self` below, so it lives as
+        // long as the message does. Moving a `Box` doesn't move the heap allocation it points to.
+        let stream = unsafe {
+            let cursor = body[..].as_aws_byte_cursor();
+            aws_input_stream_new_from_cursor(allocator.inner.as_ptr(), &cursor).ok_or_last_error()?
+        };
+        // SAFETY: `self.inner` is a valid `aws_http_message`, which acquires its own reference to
+        // the stream, so we can release ours straight away.
+        unsafe {
+            aws_http_message_set_body_stream(self.inner.as_ptr(), stream.as_ptr());
+            aws_input_stream_release(stream.as_ptr());
+        }
+        self.body = Some(body);
+        Ok(())
+    }
+
     /// get the headers from the message and increases the reference count for the Headers in CRT.
     pub fn get_headers(&mut self) -> Result<Headers, Error> {
         // SAFETY: `aws_http_message_get_headers` is safe because self.inner is a valid NonNull `aws_http_message`.
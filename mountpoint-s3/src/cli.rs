@@ -24,16 +24,22 @@ use mountpoint_s3_crt::io::event_loop::EventLoopGroup;
 use nix::sys::signal::Signal;
 use nix::unistd::ForkResult;
 use regex::Regex;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
 
 use crate::build_info;
-use crate::data_cache::{CacheLimit, DiskDataCache, DiskDataCacheConfig, ManagedCacheDir};
-use crate::fs::{CacheConfig, S3FilesystemConfig, ServerSideEncryption, TimeToLive};
+use crate::data_cache::{
+    CacheLimit, DiskDataCache, DiskDataCacheConfig, EvictionPolicyKind, ManagedCacheDir, TieredDataCache,
+};
+use crate::fs::{BucketMount, CacheConfig, S3FilesystemConfig, ServerSideEncryption, TimeToLive};
 use crate::fuse::session::FuseSession;
 use crate::fuse::S3FuseFilesystem;
+use crate::key_filter::{Glob, KeyFilter};
 use crate::logging::{init_logging, LoggingConfig};
-use crate::prefetch::{caching_prefetch, default_prefetch, Prefetch};
+use crate::prefetch::{caching_prefetch, default_prefetch, CacheAdmissionConfig, Prefetch};
 use crate::prefix::Prefix;
 use crate::s3::S3Personality;
+use crate::unicode::UnicodeNormalization;
 use crate::{autoconfigure, metrics};
 
 const CLIENT_OPTIONS_HEADER: &str = "Client options";
@@ -44,6 +50,9 @@ const LOGGING_OPTIONS_HEADER: &str = "Logging options";
 const CACHING_OPTIONS_HEADER: &str = "Caching options";
 const ADVANCED_OPTIONS_HEADER: &str = "Advanced options";
 
+/// The largest size in MiB that can be converted to bytes without overflowing
+const MAX_MIB: u64 = u64::MAX >> 20;
+
 #[derive(Parser, Debug)]
 #[clap(name = "mount-s3", about = "Mountpoint for Amazon S3", version = build_info::FULL_VERSION)]
 pub struct CliArgs {
@@ -60,6 +69,17 @@ pub struct CliArgs {
     )]
     pub prefix: Option<Prefix>,
 
+    #[clap(
+        long,
+        help = "Also mount this bucket, or only the given prefix of it. When set, each bucket is a directory named \
+                after it in the mount point, and all buckets share one S3 client and cache. The buckets must be in \
+                the same region. Can be repeated.",
+        help_heading = BUCKET_OPTIONS_HEADER,
+        value_name = "BUCKET[=PREFIX]",
+        value_parser = parse_bucket_mount
+    )]
+    pub add_bucket: Vec<BucketMount>,
+
     #[clap(
         long,
         help = "AWS region of the bucket [default: auto-detect region]",
@@ -123,6 +143,140 @@ pub struct CliArgs {
     )]
     pub allow_overwrite: bool,
 
+    #[clap(
+        long,
+        help = "Allow renaming directories. Each object under the directory is copied and deleted \
+                individually, so renames of large directories are slow and not atomic.",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        requires = "allow_delete"
+    )]
+    pub allow_directory_rename: bool,
+
+    #[clap(
+        long,
+        help = "Allow creating symlinks, stored like s3fs does as objects whose content is the target, \
+                and show existing s3fs symlinks as such",
+        help_heading = MOUNT_OPTIONS_HEADER
+    )]
+    pub allow_symlinks: bool,
+
+    #[clap(
+        long,
+        help = "Read and write the mode, owner and modification time of files as object metadata, \
+                compatible with s3fs. Listing directories makes a request for each object to read its metadata.",
+        help_heading = MOUNT_OPTIONS_HEADER
+    )]
+    pub posix_metadata: bool,
+
+    #[clap(
+        long,
+        help = "Persist new directories by creating a zero-byte marker object (`dir/`) for each of them, and \
+                allow removing empty directories by deleting their marker objects",
+        help_heading = MOUNT_OPTIONS_HEADER
+    )]
+    pub directory_markers: bool,
+
+    #[clap(
+        long,
+        help = "Handle POSIX file locks (fcntl and lockf) in Mountpoint rather than in the kernel. \
+                flock locks are always handled by the kernel. Either way, locks only apply to processes using this mount.",
+        help_heading = MOUNT_OPTIONS_HEADER
+    )]
+    pub file_locks: bool,
+
+    #[clap(
+        long,
+        help = "Show the noncurrent versions of each object as read-only files in a hidden `<name>.versions` \
+                directory next to it. Requires s3:ListBucketVersions and s3:GetObjectVersion permissions.",
+        help_heading = MOUNT_OPTIONS_HEADER
+    )]
+    pub show_versions: bool,
+
+    #[clap(
+        long,
+        help = "Mount a versioned bucket, read-only, as it was at the given time (for example, \
+                2024-09-01T00:00:00Z). Each file shows the newest version of its object created at or before that time. \
+                Requires s3:ListBucketVersions and s3:GetObjectVersion permissions.",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        value_name = "TIMESTAMP",
+        value_parser = parse_timestamp,
+        conflicts_with_all(["allow_delete", "allow_overwrite", "show_versions"])
+    )]
+    pub as_of: Option<OffsetDateTime>,
+
+    #[clap(
+        long,
+        help = "Only show files whose path relative to the mount matches this glob (for example, '*.parquet'). \
+                Patterns without a '/' match the file name. With --add-bucket, paths are relative to each \
+                bucket's prefix, not to the mount. Can be repeated.",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        value_name = "GLOB"
+    )]
+    pub include: Vec<Glob>,
+
+    #[clap(
+        long,
+        help = "Hide files and directories whose path relative to the mount matches this glob (for example, \
+                '_temporary' or '*.crc'). Patterns without a '/' match the file name. With --add-bucket, paths \
+                are relative to each bucket's prefix, not to the mount. Can be repeated.",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        value_name = "GLOB"
+    )]
+    pub exclude: Vec<Glob>,
+
+    #[clap(
+        long,
+        help = "Show objects whose keys are not valid file paths, like 'a//b' or 'a/../b', by escaping the \
+                invalid parts of their paths with '%'. The names of files containing '%' are escaped too.",
+        help_heading = MOUNT_OPTIONS_HEADER
+    )]
+    pub escape_invalid_names: bool,
+
+    #[clap(
+        long,
+        help = "Look up file names ignoring case when there is no exact match, and refuse to create names \
+                that only differ in case from existing ones. Looking up a name with no exact match lists its directory.",
+        help_heading = MOUNT_OPTIONS_HEADER
+    )]
+    pub case_insensitive: bool,
+
+    #[clap(
+        long,
+        help = "Compare file names in the given Unicode normalization form, so that looking up a name finds \
+                an object whose key uses another form. Keys are not renamed. [default: none]",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        value_name = "FORM"
+    )]
+    pub normalize_unicode: Option<UnicodeNormalization>,
+
+    #[clap(
+        long,
+        help = "Stage new files in the given local directory until they are closed, allowing them to be \
+                written at any offset and read back while open. With --allow-overwrite, existing files are also \
+                modified in place. Requires enough local space for each open file.",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        value_name = "DIRECTORY"
+    )]
+    pub write_staging_dir: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Total size of the file system to report to tools like df [default: effectively unlimited]",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        value_name = "MiB",
+        value_parser = value_parser!(u64).range(1..=MAX_MIB),
+    )]
+    pub reported_capacity: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Free space of the file system to report to tools like df [default: the reported capacity]",
+        help_heading = MOUNT_OPTIONS_HEADER,
+        value_name = "MiB",
+        value_parser = value_parser!(u64).range(..=MAX_MIB),
+    )]
+    pub reported_free_space: Option<u64>,
+
     #[clap(long, help = "Automatically unmount on exit", help_heading = MOUNT_OPTIONS_HEADER)]
     pub auto_unmount: bool,
 
@@ -261,6 +415,74 @@ pub struct CliArgs {
     )]
     pub max_cache_size: Option<u64>,
 
+    #[clap(
+        long,
+        help = "How to choose the content to evict from the cache directory when it is full [default: lru]",
+        value_name = "POLICY",
+        help_heading = CACHING_OPTIONS_HEADER,
+        requires = "cache",
+        conflicts_with = "shared_cache",
+    )]
+    pub cache_eviction_policy: Option<EvictionPolicyKind>,
+
+    #[clap(
+        long,
+        help = "Only cache the content of an object once it has been read this many times, counting each \
+                file handle that reads the object once [default: 1]",
+        value_name = "N",
+        value_parser = value_parser!(u32).range(1..),
+        help_heading = CACHING_OPTIONS_HEADER,
+        requires = "cache",
+    )]
+    pub cache_min_reads: Option<u32>,
+
+    #[clap(
+        long,
+        help = "Only cache the content of objects up to this size in MiB [default: no limit]",
+        value_name = "MiB",
+        value_parser = value_parser!(u64).range(..=MAX_MIB),
+        help_heading = CACHING_OPTIONS_HEADER,
+        requires = "cache",
+    )]
+    pub cache_max_object_size: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Stop caching a sequential read of an object once it has read this many MiB [default: no limit]",
+        value_name = "MiB",
+        value_parser = value_parser!(u64).range(..=MAX_MIB),
+        help_heading = CACHING_OPTIONS_HEADER,
+        requires = "cache",
+    )]
+    pub cache_max_sequential_read: Option<u64>,
+
+    #[clap(
+        long,
+        help = "Keep the content of the cache directory when unmounting, and reuse it at the next mount",
+        help_heading = CACHING_OPTIONS_HEADER,
+        requires = "cache",
+    )]
+    pub persist_cache: bool,
+
+    #[clap(
+        long,
+        help = "Share the cache directory with other Mountpoint processes on this host that set this flag. \
+                Implies --persist-cache.",
+        help_heading = CACHING_OPTIONS_HEADER,
+        requires = "cache",
+    )]
+    pub shared_cache: bool,
+
+    #[clap(
+        long,
+        help = "Also cache up to this much object content in memory, in front of the cache directory, in MiB",
+        value_name = "MiB",
+        value_parser = value_parser!(u64).range(1..=MAX_MIB),
+        help_heading = CACHING_OPTIONS_HEADER,
+        requires = "cache",
+    )]
+    pub max_memory_cache_size: Option<u64>,
+
     #[clap(
         long,
         help = "Configure a string to be prepended to the 'User-Agent' HTTP request header for all S3 requests",
@@ -342,6 +564,30 @@ impl ValueEnum for UploadChecksums {
     }
 }
 
+impl ValueEnum for UnicodeNormalization {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Nfc, Self::Nfd, Self::None]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        match self {
+            Self::Nfc => Some(clap::builder::PossibleValue::new("nfc")),
+            Self::Nfd => Some(clap::builder::PossibleValue::new("nfd")),
+            Self::None => Some(clap::builder::PossibleValue::new("none")),
+        }
+    }
+}
+
+impl ValueEnum for EvictionPolicyKind {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Lru, Self::Lfu, Self::S3Fifo]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.as_str()))
+    }
+}
+
 impl CliArgs {
     fn addressing_style(&self) -> AddressingStyle {
         if self.force_path_style {
@@ -379,9 +625,22 @@ impl CliArgs {
         }
     }
 
+    /// The buckets to mount, starting with the one given by `bucket_name` and `prefix`
+    fn bucket_mounts(&self) -> Vec<BucketMount> {
+        let mount = BucketMount {
+            name: bucket_directory_name(&self.bucket_name).to_owned(),
+            bucket: self.bucket_name.clone(),
+            prefix: self.prefix(),
+        };
+        std::iter::once(mount).chain(self.add_bucket.iter().cloned()).collect()
+    }
+
     /// Human-readable description of the bucket being mounted
     fn bucket_description(&self) -> String {
-        if let Some(prefix) = self.prefix.as_ref() {
+        if !self.add_bucket.is_empty() {
+            let buckets: Vec<_> = self.bucket_mounts().into_iter().map(|mount| mount.bucket).collect();
+            format!("buckets {}", buckets.join(", "))
+        } else if let Some(prefix) = self.prefix.as_ref() {
             format!("prefix {} of bucket {}", prefix, self.bucket_name)
         } else {
             format!("bucket {}", self.bucket_name)
@@ -395,7 +654,8 @@ impl CliArgs {
             MountOption::FSName(fs_name),
             MountOption::NoAtime,
         ];
-        if self.read_only {
+        // Point-in-time mounts are always read-only
+        if self.read_only || self.as_of.is_some() {
             options.push(MountOption::RO);
         }
         if self.auto_unmount {
@@ -600,7 +860,7 @@ pub fn create_s3_client(args: &CliArgs) -> anyhow::Result<(S3CrtClient, EventLoo
         format!("mountpoint-s3/{}", build_info::FULL_VERSION)
     };
     let mut user_agent = UserAgent::new_with_instance_info(Some(user_agent_prefix), &instance_info);
-    if args.read_only {
+    if args.read_only || args.as_of.is_some() {
         user_agent.value("mp-readonly");
     }
 
@@ -637,8 +897,27 @@ pub fn create_s3_client(args: &CliArgs) -> anyhow::Result<(S3CrtClient, EventLoo
         &instance_info,
     )
     .context("Failed to create S3 client")?;
+    // Other buckets share the client, so they must be in the region it was created for
+    for mount in &args.add_bucket {
+        let list_request = client.list_objects(&mount.bucket, None, "", 0, mount.prefix.as_str());
+        futures::executor::block_on(list_request)
+            .with_context(|| format!("initial ListObjectsV2 failed for bucket {}", mount.bucket))?;
+    }
     let runtime = client.event_loop_group();
     let s3_personality = infer_s3_personality(args.bucket_type.clone(), &args.bucket_name, client.endpoint_config());
+    // Buckets share the filesystem configuration, which depends on the type of bucket
+    for mount in &args.add_bucket {
+        let personality = infer_s3_personality(args.bucket_type.clone(), &mount.bucket, client.endpoint_config());
+        if std::mem::discriminant(&personality) != std::mem::discriminant(&s3_personality) {
+            return Err(anyhow!(
+                "bucket {} ({:?}) is not the same type of bucket as {} ({:?}), and only buckets of the same type can be mounted together",
+                mount.bucket,
+                personality,
+                args.bucket_name,
+                s3_personality,
+            ));
+        }
+    }
 
     Ok((client, runtime, s3_personality))
 }
@@ -653,6 +932,17 @@ where
     tracing::debug!("{:?}", args);
 
     validate_mount_point(&args.mount_point)?;
+    let bucket_mounts = args.bucket_mounts();
+    for (i, mount) in bucket_mounts.iter().enumerate() {
+        if let Some(other) = bucket_mounts[..i].iter().find(|other| other.name == mount.name) {
+            return Err(anyhow!(
+                "buckets {} and {} cannot both be mounted as directory {}",
+                other.bucket,
+                mount.bucket,
+                mount.name
+            ));
+        }
+    }
     {
         validate_sse_args(args.sse.as_deref(), args.sse_kms_key_id.as_deref())?;
     }
@@ -680,6 +970,38 @@ where
     filesystem_config.storage_class = args.storage_class;
     filesystem_config.allow_delete = args.allow_delete;
     filesystem_config.allow_overwrite = args.allow_overwrite;
+    filesystem_config.allow_directory_rename = args.allow_directory_rename;
+    filesystem_config.allow_symlinks = args.allow_symlinks;
+    filesystem_config.posix_metadata = args.posix_metadata;
+    filesystem_config.directory_markers = args.directory_markers;
+    filesystem_config.file_locks = args.file_locks;
+    filesystem_config.show_versions = args.show_versions;
+    filesystem_config.as_of = args.as_of;
+    filesystem_config.key_filter = KeyFilter::new(args.include, args.exclude);
+    filesystem_config.escape_names = args.escape_invalid_names;
+    filesystem_config.case_insensitive = args.case_insensitive;
+    filesystem_config.normalize_unicode = args.normalize_unicode.unwrap_or_default();
+    if let Some(staging_dir) = args.write_staging_dir {
+        if !staging_dir.is_dir() {
+            return Err(anyhow!(
+                "write staging directory {} does not exist",
+                staging_dir.display()
+            ));
+        }
+        filesystem_config.write_staging_dir = Some(staging_dir);
+    }
+    if let Some(capacity_in_mib) = args.reported_capacity {
+        filesystem_config.statfs_capacity = capacity_in_mib * 1024 * 1024;
+    }
+    filesystem_config.statfs_free_space = match args.reported_free_space {
+        Some(free_in_mib) if free_in_mib * 1024 * 1024 > filesystem_config.statfs_capacity => {
+            return Err(anyhow!(
+                "--reported-free-space must not be larger than the reported capacity"
+            ));
+        }
+        Some(free_in_mib) => free_in_mib * 1024 * 1024,
+        None => filesystem_config.statfs_capacity,
+    };
     filesystem_config.s3_personality = s3_personality;
     filesystem_config.server_side_encryption = ServerSideEncryption::new(args.sse, args.sse_kms_key_id);
 
@@ -720,6 +1042,7 @@ where
     filesystem_config.cache_config = CacheConfig::new(metadata_cache_ttl);
 
     if let Some(path) = args.cache {
+        let eviction_policy = args.cache_eviction_policy.unwrap_or_default();
         let cache_config = match args.max_cache_size {
             // Fallback to no data cache.
             Some(0) => None,
@@ -727,25 +1050,64 @@ where
                 limit: CacheLimit::TotalSize {
                     max_size: (max_size_in_mib * 1024 * 1024) as usize,
                 },
+                eviction_policy,
                 ..Default::default()
             }),
-            None => Some(DiskDataCacheConfig::default()),
+            None => Some(DiskDataCacheConfig {
+                eviction_policy,
+                ..Default::default()
+            }),
+        };
+
+        let admission_config = CacheAdmissionConfig {
+            min_reads: args.cache_min_reads.unwrap_or(1),
+            max_object_size: args.cache_max_object_size.map(|size_in_mib| size_in_mib * 1024 * 1024),
+            max_sequential_read: args
+                .cache_max_sequential_read
+                .map(|size_in_mib| size_in_mib * 1024 * 1024),
         };
 
         if let Some(cache_config) = cache_config {
-            let managed_cache_dir =
-                ManagedCacheDir::new_from_parent(path).context("failed to create cache directory")?;
-            let cache = DiskDataCache::new(managed_cache_dir.as_path_buf(), cache_config);
-            let prefetcher = caching_prefetch(cache, runtime, prefetcher_config);
-            let mut fuse_session = create_filesystem(
-                client,
-                prefetcher,
-                &args.bucket_name,
-                &args.prefix.unwrap_or_default(),
-                filesystem_config,
-                fuse_config,
-                &bucket_description,
-            )?;
+            let (managed_cache_dir, cache) = if args.shared_cache {
+                let managed_cache_dir =
+                    ManagedCacheDir::new_shared_from_parent(path).context("failed to create cache directory")?;
+                let cache = DiskDataCache::new_shared(managed_cache_dir.as_path_buf(), cache_config)
+                    .context("failed to load existing cache")?;
+                (managed_cache_dir, cache)
+            } else if args.persist_cache {
+                let managed_cache_dir =
+                    ManagedCacheDir::new_persistent_from_parent(path).context("failed to create cache directory")?;
+                let cache = DiskDataCache::new_from_existing(managed_cache_dir.as_path_buf(), cache_config)
+                    .context("failed to load existing cache")?;
+                (managed_cache_dir, cache)
+            } else {
+                let managed_cache_dir =
+                    ManagedCacheDir::new_from_parent(path).context("failed to create cache directory")?;
+                let cache = DiskDataCache::new(managed_cache_dir.as_path_buf(), cache_config);
+                (managed_cache_dir, cache)
+            };
+            let mut fuse_session = if let Some(max_memory_size_in_mib) = args.max_memory_cache_size {
+                let cache = TieredDataCache::new((max_memory_size_in_mib * 1024 * 1024) as usize, cache);
+                let prefetcher = caching_prefetch(cache, runtime, prefetcher_config, admission_config);
+                create_filesystem(
+                    client,
+                    prefetcher,
+                    bucket_mounts,
+                    filesystem_config,
+                    fuse_config,
+                    &bucket_description,
+                )?
+            } else {
+                let prefetcher = caching_prefetch(cache, runtime, prefetcher_config, admission_config);
+                create_filesystem(
+                    client,
+                    prefetcher,
+                    bucket_mounts,
+                    filesystem_config,
+                    fuse_config,
+                    &bucket_description,
+                )?
+            };
 
             fuse_session.run_on_close(Box::new(move || {
                 drop(managed_cache_dir);
@@ -759,8 +1121,7 @@ where
     create_filesystem(
         client,
         prefetcher,
-        &args.bucket_name,
-        &args.prefix.unwrap_or_default(),
+        bucket_mounts,
         filesystem_config,
         fuse_config,
         &bucket_description,
@@ -770,8 +1131,7 @@ where
 fn create_filesystem<Client, Prefetcher>(
     client: Client,
     prefetcher: Prefetcher,
-    bucket_name: &str,
-    prefix: &Prefix,
+    bucket_mounts: Vec<BucketMount>,
     filesystem_config: S3FilesystemConfig,
     fuse_session_config: FuseSessionConfig,
     bucket_description: &str,
@@ -780,7 +1140,11 @@ where
     Client: ObjectClient + Send + Sync + 'static,
     Prefetcher: Prefetch + Send + Sync + 'static,
 {
-    let fs = S3FuseFilesystem::new(client, prefetcher, bucket_name, prefix, filesystem_config);
+    let fs = if let [mount] = bucket_mounts.as_slice() {
+        S3FuseFilesystem::new(client, prefetcher, &mount.bucket, &mount.prefix, filesystem_config)
+    } else {
+        S3FuseFilesystem::new_multi_bucket(client, prefetcher, bucket_mounts, filesystem_config)
+    };
     let session = Session::new(fs, &fuse_session_config.mount_point, &fuse_session_config.options)
         .context("Failed to create FUSE session")?;
     let session = FuseSession::new(session, fuse_session_config.max_threads).context("Failed to start FUSE session")?;
@@ -884,6 +1248,32 @@ fn parse_bucket_name(bucket_name: &str) -> anyhow::Result<String> {
     Ok(bucket_name.to_owned())
 }
 
+/// Parse a bucket to mount in addition to the main one, given as `BUCKET[=PREFIX]`
+fn parse_bucket_mount(value: &str) -> anyhow::Result<BucketMount> {
+    let (bucket, prefix) = match value.split_once('=') {
+        Some((bucket, prefix)) => (bucket, Prefix::new(prefix)?),
+        None => (value, Prefix::default()),
+    };
+    let bucket = parse_bucket_name(bucket)?;
+    Ok(BucketMount {
+        name: bucket_directory_name(&bucket).to_owned(),
+        bucket,
+        prefix,
+    })
+}
+
+/// The name of the directory a bucket is mounted as when several buckets are mounted. For ARNs,
+/// this is the name of the resource, like the name of an access point.
+fn bucket_directory_name(bucket: &str) -> &str {
+    bucket.rsplit(['/', ':']).next().unwrap_or(bucket)
+}
+
+/// Parse an RFC 3339 timestamp, like `2024-09-01T00:00:00Z`
+fn parse_timestamp(timestamp: &str) -> anyhow::Result<OffsetDateTime> {
+    OffsetDateTime::parse(timestamp, &Rfc3339)
+        .map_err(|e| anyhow!("must be an RFC 3339 timestamp, like 2024-09-01T00:00:00Z: {e}"))
+}
+
 /// Validate a kms-key-id CLI parameter. Currently, Mountpoint only supports KMS Key ARNs.
 fn parse_kms_key_arn(kms_key_arn: &str) -> anyhow::Result<String> {
     if kms_key_arn.starts_with("arn:") && kms_key_arn.contains(":key") {
@@ -1025,6 +1415,24 @@ mod tests {
         }
     }
 
+    #[test_case("test-bucket", Some(("test-bucket", "test-bucket", "")); "bucket")]
+    #[test_case("test-bucket=logs/", Some(("test-bucket", "test-bucket", "logs/")); "bucket and prefix")]
+    #[test_case("arn:aws:s3:us-west-2:555555555555:accesspoint/my-ap=a/b/", Some(("my-ap", "arn:aws:s3:us-west-2:555555555555:accesspoint/my-ap", "a/b/")); "access point ARN and prefix")]
+    #[test_case("arn:aws:s3:::doc-example-bucket", Some(("doc-example-bucket", "arn:aws:s3:::doc-example-bucket", "")); "bucket ARN")]
+    #[test_case("test-bucket=logs", None; "prefix without final delimiter")]
+    #[test_case("s3://test-bucket", None; "S3 URI")]
+    fn test_parse_bucket_mount(value: &str, expected: Option<(&str, &str, &str)>) {
+        let parsed = parse_bucket_mount(value);
+        if let Some((name, bucket, prefix)) = expected {
+            let mount = parsed.expect("valid bucket mount");
+            assert_eq!(mount.name, name);
+            assert_eq!(mount.bucket, bucket);
+            assert_eq!(mount.prefix.as_str(), prefix);
+        } else {
+            parsed.expect_err("invalid bucket mount");
+        }
+    }
+
     // https://docs.aws.amazon.com/kms/latest/developerguide/concepts.html#key-id
     #[test_case("arn:aws:kms:eu-west-1:151381207180:key/dabe1478-fe48-47ca-b6f8-ca044b643a82", true; "KMS Key ARN")]
     #[test_case("arn:aws:kms:us-west-2:111122223333:key/mrk-1234abcd12ab34cd56ef1234567890ab", true; "Multi-region KMS Key ARN")]
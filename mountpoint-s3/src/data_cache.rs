@@ -6,14 +6,19 @@
 
 mod cache_directory;
 mod disk_data_cache;
+mod eviction_policy;
 mod in_memory_data_cache;
+mod shared_usage;
+mod tiered_data_cache;
 
 use thiserror::Error;
 
 pub use crate::checksums::ChecksummedBytes;
 pub use crate::data_cache::cache_directory::ManagedCacheDir;
 pub use crate::data_cache::disk_data_cache::{CacheLimit, DiskDataCache, DiskDataCacheConfig};
+pub use crate::data_cache::eviction_policy::EvictionPolicyKind;
 pub use crate::data_cache::in_memory_data_cache::InMemoryDataCache;
+pub use crate::data_cache::tiered_data_cache::TieredDataCache;
 
 use crate::object::ObjectId;
 
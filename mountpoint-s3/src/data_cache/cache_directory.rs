@@ -1,22 +1,35 @@
 //! Provides functionality related to the inner cache directory Mountpoint creates or uses.
-//! Mountpoint attempts to cleanup the contents during mount and exit.
+//! Mountpoint attempts to cleanup the contents during mount and exit, unless the directory is
+//! persistent, in which case its contents are kept for the next mount.
 //!
 //! Mountpoint uses a directory inside the user-provided cache directory
 //! to mitigate any impact from the user providing a directory that already contains data.
 //! Using a new sub-directory minimizes the interference with the existing directory structure,
 //! and limits the risk from deleting or overwriting data to files written within this sub-directory.
+//!
+//! While a Mountpoint process uses the sub-directory, it holds an exclusive lock on a lock file next
+//! to it, so that no other process can empty or write to the same directory. Processes that share
+//! the directory instead hold a shared lock, which only excludes processes that don't. The lock is
+//! released by the kernel when the process exits, even if it crashes.
 
 use std::fs;
 use std::io;
-use std::os::unix::fs::DirBuilderExt;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
 use std::path::{Path, PathBuf};
 
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
 use thiserror::Error;
 
-/// Cache directory that has been created and emptied, and will be emptied when dropped.
+/// Cache directory that has been created and locked. Unless it is persistent, it has been emptied
+/// and will be emptied when dropped.
 #[derive(Debug)]
 pub struct ManagedCacheDir {
     managed_path: PathBuf,
+    persistent: bool,
+    /// Open lock file, which holds the lock on the directory until it is dropped
+    _lock_file: fs::File,
 }
 
 #[derive(Debug, Error)]
@@ -25,21 +38,50 @@ pub enum ManagedCacheDirError {
     CreationFailure(#[source] io::Error),
     #[error("cleanup of cache sub-directory failed due to IO error: {0}")]
     CleanupFailure(#[source] io::Error),
+    #[error("locking of cache sub-directory failed due to IO error: {0}")]
+    LockFailure(#[source] io::Error),
+    #[error("cache sub-directory is in use by another Mountpoint process")]
+    AlreadyInUse,
 }
 
 impl ManagedCacheDir {
     /// Create a new directory inside the provided parent path.
     /// If the directory already exists, it will be deleted before being recreated.
     pub fn new_from_parent<P: AsRef<Path>>(parent_path: P) -> Result<Self, ManagedCacheDirError> {
+        Self::create(parent_path.as_ref(), false, false)
+    }
+
+    /// Create a directory inside the provided parent path, or reuse it and its contents if it
+    /// already exists. The contents are kept when the [ManagedCacheDir] is dropped.
+    pub fn new_persistent_from_parent<P: AsRef<Path>>(parent_path: P) -> Result<Self, ManagedCacheDirError> {
+        Self::create(parent_path.as_ref(), true, false)
+    }
+
+    /// Create a directory inside the provided parent path that other processes can use at the same
+    /// time, or reuse it and its contents if it already exists. The contents are kept when the
+    /// [ManagedCacheDir] is dropped, since other processes may still use them.
+    pub fn new_shared_from_parent<P: AsRef<Path>>(parent_path: P) -> Result<Self, ManagedCacheDirError> {
+        Self::create(parent_path.as_ref(), true, true)
+    }
+
+    fn create(parent_path: &Path, persistent: bool, shared: bool) -> Result<Self, ManagedCacheDirError> {
         let managed_cache_dir = Self {
-            managed_path: parent_path.as_ref().join("mountpoint-cache"),
+            managed_path: parent_path.join("mountpoint-cache"),
+            persistent,
+            _lock_file: Self::lock(&parent_path.join("mountpoint-cache.lock"), shared)?,
         };
 
-        managed_cache_dir.remove()?;
+        if !persistent {
+            managed_cache_dir.remove()?;
+        }
 
         let mkdir_result = fs::DirBuilder::new().mode(0o700).create(managed_cache_dir.as_path());
         if let Err(mkdir_err) = mkdir_result {
             match mkdir_err.kind() {
+                io::ErrorKind::AlreadyExists if persistent => tracing::debug!(
+                    cache_dir = ?managed_cache_dir.as_path(),
+                    "reusing existing cache sub-directory",
+                ),
                 io::ErrorKind::AlreadyExists => tracing::warn!(
                     cache_dir = ?managed_cache_dir.as_path(),
                     "cache sub-directory already existed immediately after removal",
@@ -51,6 +93,28 @@ impl ManagedCacheDir {
         Ok(managed_cache_dir)
     }
 
+    /// Open the lock file at the given path and lock it, failing if another process holds a lock
+    /// that conflicts. Shared locks only conflict with exclusive ones.
+    fn lock(lock_path: &Path, shared: bool) -> Result<fs::File, ManagedCacheDirError> {
+        let lock_file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(lock_path)
+            .map_err(ManagedCacheDirError::LockFailure)?;
+        let arg = if shared {
+            FlockArg::LockSharedNonblock
+        } else {
+            FlockArg::LockExclusiveNonblock
+        };
+        match flock(lock_file.as_raw_fd(), arg) {
+            Ok(()) => Ok(lock_file),
+            Err(Errno::EWOULDBLOCK) => Err(ManagedCacheDirError::AlreadyInUse),
+            Err(errno) => Err(ManagedCacheDirError::LockFailure(errno.into())),
+        }
+    }
+
     /// Remove the cache sub-directory, along with its contents if any
     fn remove(&self) -> Result<(), ManagedCacheDirError> {
         tracing::debug!(cache_subdirectory = ?self.managed_path, "removing the cache sub-directory and any contents");
@@ -77,6 +141,9 @@ impl ManagedCacheDir {
 
 impl Drop for ManagedCacheDir {
     fn drop(&mut self) {
+        if self.persistent {
+            return;
+        }
         if let Err(err) = self.remove() {
             tracing::error!(cache_subdirectory = ?self.managed_path, "failed to remove cache sub-directory: {err}");
         }
@@ -85,7 +152,7 @@ impl Drop for ManagedCacheDir {
 
 #[cfg(test)]
 mod tests {
-    use super::ManagedCacheDir;
+    use super::{ManagedCacheDir, ManagedCacheDirError};
 
     use std::fs;
     use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
@@ -186,4 +253,85 @@ mod tests {
 
         temp_dir.close().unwrap();
     }
+
+    #[test]
+    fn test_persistent() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let expected_path = temp_dir.path().join("mountpoint-cache");
+
+        let managed_dir =
+            ManagedCacheDir::new_persistent_from_parent(temp_dir.path()).expect("creating managed dir should succeed");
+        fs::File::create(expected_path.join("file.txt"))
+            .expect("should be able to create file within managed directory");
+        drop(managed_dir);
+        assert!(
+            expected_path.join("file.txt").try_exists().unwrap(),
+            "contents of a persistent directory should be kept when it is dropped"
+        );
+
+        let managed_dir =
+            ManagedCacheDir::new_persistent_from_parent(temp_dir.path()).expect("reusing managed dir should succeed");
+        assert!(
+            expected_path.join("file.txt").try_exists().unwrap(),
+            "contents of a persistent directory should be reused"
+        );
+        drop(managed_dir);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_in_use() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let expected_path = temp_dir.path().join("mountpoint-cache");
+
+        let managed_dir =
+            ManagedCacheDir::new_persistent_from_parent(temp_dir.path()).expect("creating managed dir should succeed");
+        fs::File::create(expected_path.join("file.txt")).unwrap();
+
+        let err = ManagedCacheDir::new_from_parent(temp_dir.path()).expect_err("directory is locked");
+        assert!(matches!(err, ManagedCacheDirError::AlreadyInUse));
+        let err = ManagedCacheDir::new_persistent_from_parent(temp_dir.path()).expect_err("directory is locked");
+        assert!(matches!(err, ManagedCacheDirError::AlreadyInUse));
+        assert!(
+            expected_path.join("file.txt").try_exists().unwrap(),
+            "a directory in use should not be emptied"
+        );
+
+        // The lock is released with the directory
+        drop(managed_dir);
+        let managed_dir =
+            ManagedCacheDir::new_from_parent(temp_dir.path()).expect("creating managed dir should succeed");
+        drop(managed_dir);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_shared() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let expected_path = temp_dir.path().join("mountpoint-cache");
+
+        let managed_dir_1 =
+            ManagedCacheDir::new_shared_from_parent(temp_dir.path()).expect("creating managed dir should succeed");
+        fs::File::create(expected_path.join("file.txt")).unwrap();
+        let managed_dir_2 =
+            ManagedCacheDir::new_shared_from_parent(temp_dir.path()).expect("sharing managed dir should succeed");
+        assert!(
+            expected_path.join("file.txt").try_exists().unwrap(),
+            "a shared directory should not be emptied"
+        );
+
+        let err = ManagedCacheDir::new_persistent_from_parent(temp_dir.path()).expect_err("directory is shared");
+        assert!(matches!(err, ManagedCacheDirError::AlreadyInUse));
+
+        drop(managed_dir_1);
+        drop(managed_dir_2);
+        assert!(
+            expected_path.join("file.txt").try_exists().unwrap(),
+            "contents of a shared directory should be kept when it is dropped"
+        );
+
+        temp_dir.close().unwrap();
+    }
 }
@@ -1,24 +1,29 @@
 //! Module for the on-disk data cache implementation.
 
+use std::collections::HashMap;
 use std::fs;
 use std::io::{ErrorKind, Read, Seek, Write};
 use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 
 use bytes::Bytes;
-use linked_hash_map::LinkedHashMap;
+use mountpoint_s3_client::types::ETag;
 use mountpoint_s3_crt::checksums::crc32c::{self, Crc32c};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use thiserror::Error;
-use tracing::{trace, warn};
+use tracing::{info, trace, warn};
 
 use crate::checksums::IntegrityError;
 use crate::data_cache::DataCacheError;
 use crate::object::ObjectId;
 use crate::sync::Mutex;
 
+use super::eviction_policy::{EvictionPolicy, EvictionPolicyKind};
+use super::shared_usage::{EvictionCandidate, LockedUsage, SharedUsage};
 use super::{BlockIndex, ChecksummedBytes, DataCache, DataCacheResult};
 
 /// Disk and file-layout versioning.
@@ -27,12 +32,27 @@ const CACHE_VERSION: &str = "V1";
 /// Index where hashed directory names for the cache are split to avoid FS-specific limits.
 const HASHED_DIR_SPLIT_INDEX: usize = 2;
 
+/// Name of the file that holds the total size of a cache shared by several processes.
+const SHARED_USAGE_FILE_NAME: &str = "usage";
+
+/// Extension of the files that blocks are written to before they are moved into place.
+const TEMPORARY_BLOCK_EXTENSION: &str = "tmp";
+
+/// Age after which a temporary block file is assumed to be left over by a process that crashed.
+const TEMPORARY_BLOCK_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Distinguishes the temporary files of blocks that are written concurrently.
+static NEXT_TEMPORARY_BLOCK_ID: AtomicU64 = AtomicU64::new(0);
+
 /// On-disk implementation of [DataCache].
 pub struct DiskDataCache {
     cache_directory: PathBuf,
     config: DiskDataCacheConfig,
-    /// Tracks blocks usage. `None` when no cache limit was set.
+    /// Tracks blocks usage. `None` when no cache limit was set, or when the cache is shared.
     usage: Option<Mutex<UsageInfo<DiskBlockKey>>>,
+    /// Tracks blocks usage across the processes that share the cache directory. `None` unless the
+    /// cache is shared.
+    shared: Option<SharedUsage>,
 }
 
 /// Configuration for a [DiskDataCache].
@@ -42,6 +62,9 @@ pub struct DiskDataCacheConfig {
     pub block_size: u64,
     /// How to limit the cache size.
     pub limit: CacheLimit,
+    /// How to choose the blocks to evict when the cache size is limited. Ignored when the cache is
+    /// shared, where the least recently used blocks are evicted.
+    pub eviction_policy: EvictionPolicyKind,
 }
 
 impl Default for DiskDataCacheConfig {
@@ -49,6 +72,7 @@ impl Default for DiskDataCacheConfig {
         Self {
             block_size: 1024 * 1024,                               // 1 MiB block size
             limit: CacheLimit::AvailableSpace { min_ratio: 0.05 }, // Preserve 5% available space
+            eviction_policy: EvictionPolicyKind::default(),
         }
     }
 }
@@ -123,6 +147,19 @@ impl DiskBlockHeader {
         hasher.finalize()
     }
 
+    /// Check the header's own checksum, without comparing its fields to an expected block
+    fn is_intact(&self) -> bool {
+        Self::compute_checksum(
+            self.block_idx,
+            self.block_offset,
+            &self.etag,
+            &self.s3_key,
+            self.data_checksum,
+        )
+        .value()
+            == self.header_checksum
+    }
+
     /// Validate the integrity of the contained data and return the stored data checksum.
     ///
     /// Execute this method before acting on the data contained within.
@@ -207,15 +244,206 @@ impl DiskDataCache {
     pub fn new(cache_directory: PathBuf, config: DiskDataCacheConfig) -> Self {
         let usage = match config.limit {
             CacheLimit::Unbounded => None,
-            CacheLimit::TotalSize { .. } | CacheLimit::AvailableSpace { .. } => Some(Mutex::new(UsageInfo::new())),
+            CacheLimit::TotalSize { .. } | CacheLimit::AvailableSpace { .. } => {
+                Some(Mutex::new(UsageInfo::new(config.eviction_policy.build())))
+            }
         };
         DiskDataCache {
             cache_directory,
             config,
             usage,
+            shared: None,
         }
     }
 
+    /// Create a new instance of an [DiskDataCache] that reuses the blocks that an earlier instance
+    /// left in the cache directory.
+    ///
+    /// The header of each existing block is validated, and blocks that are corrupt, were written with
+    /// another version or block size, or are not where their key would place them are removed. The
+    /// remaining blocks are tracked from the least to the most recently written, and blocks are
+    /// evicted if they exceed the configured limit.
+    pub fn new_from_existing(cache_directory: PathBuf, config: DiskDataCacheConfig) -> DataCacheResult<Self> {
+        let cache = Self::new(cache_directory, config);
+        cache.load_existing_blocks()?;
+        Ok(cache)
+    }
+
+    /// Create a new instance of an [DiskDataCache] in a cache directory that other processes may
+    /// be using at the same time.
+    ///
+    /// Existing blocks are reused and validated as in [DiskDataCache::new_from_existing]. Each
+    /// process evicts blocks to keep the total size of the shared cache within its own limit, so
+    /// processes that share a cache should use the same limit.
+    pub fn new_shared(cache_directory: PathBuf, config: DiskDataCacheConfig) -> DataCacheResult<Self> {
+        let shared = SharedUsage::open(&cache_directory.join(SHARED_USAGE_FILE_NAME))?;
+        let cache = DiskDataCache {
+            cache_directory,
+            config,
+            usage: None,
+            shared: Some(shared),
+        };
+        cache.load_existing_blocks()?;
+        Ok(cache)
+    }
+
+    /// Validate and start tracking the blocks in the cache directory, removing invalid ones
+    fn load_existing_blocks(&self) -> DataCacheResult<()> {
+        let start = Instant::now();
+
+        let mut blocks = Vec::new();
+        let mut discarded = 0;
+        for entry in fs::read_dir(&self.cache_directory)? {
+            let entry = entry?;
+            if entry.file_name() == CACHE_VERSION {
+                self.scan_blocks(&entry.path(), &mut blocks, &mut discarded)?;
+            } else if entry.file_name() == SHARED_USAGE_FILE_NAME {
+                continue;
+            } else {
+                // Blocks of other versions of the cache can never be read
+                trace!(path = ?entry.path(), "removing content of another cache version");
+                let result = if entry.file_type()?.is_dir() {
+                    fs::remove_dir_all(entry.path())
+                } else {
+                    fs::remove_file(entry.path())
+                };
+                if let Err(remove_err) = result {
+                    warn!("unable to remove stale cache content: {:?}", remove_err);
+                }
+            }
+        }
+
+        if let Some(usage) = &self.usage {
+            blocks.sort_by_key(|&(_, _, modified)| modified);
+            let mut usage = usage.lock().unwrap();
+            for (block_key, size, _) in blocks.iter() {
+                usage.add(*block_key, *size);
+            }
+        }
+        if let Some(shared) = &self.shared {
+            let candidates = blocks
+                .iter()
+                .map(|(block_key, size, modified)| EvictionCandidate {
+                    path: self.get_path_for_block_key(block_key),
+                    size: *size as u64,
+                    modified: *modified,
+                })
+                .collect();
+            shared.lock()?.reset(candidates)?;
+        }
+        info!(
+            reused = blocks.len(),
+            discarded,
+            "loaded existing cache blocks in {:?}",
+            start.elapsed()
+        );
+
+        self.evict_if_needed()
+    }
+
+    /// Find the valid blocks under a directory of the cache, removing any invalid ones
+    fn scan_blocks(
+        &self,
+        dir: &Path,
+        blocks: &mut Vec<(DiskBlockKey, usize, SystemTime)>,
+        discarded: &mut usize,
+    ) -> DataCacheResult<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                self.scan_blocks(&path, blocks, discarded)?;
+                continue;
+            }
+            if is_temporary_block(&path) {
+                // Other processes that share the cache may still be writing their blocks
+                if self.shared.is_none() || is_older_than(&entry.metadata()?, TEMPORARY_BLOCK_MAX_AGE) {
+                    if let Err(remove_err) = fs::remove_file(&path) {
+                        warn!("unable to remove temporary block: {:?}", remove_err);
+                    }
+                }
+                continue;
+            }
+            match self.validate_existing_block(&path) {
+                Some(block_key) => {
+                    let metadata = entry.metadata()?;
+                    blocks.push((block_key, metadata.len() as usize, metadata.modified()?));
+                }
+                None => {
+                    *discarded += 1;
+                    if let Err(remove_err) = fs::remove_file(&path) {
+                        warn!("unable to remove invalid block: {:?}", remove_err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// List the blocks under a directory of the cache without validating them, to find blocks to
+    /// evict from a shared cache
+    fn list_blocks(dir: &Path, blocks: &mut Vec<EvictionCandidate>) -> DataCacheResult<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            // Removed by another process
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            if metadata.is_dir() {
+                Self::list_blocks(&path, blocks)?;
+            } else if !is_temporary_block(&path) {
+                blocks.push(EvictionCandidate {
+                    path,
+                    size: metadata.len(),
+                    modified: metadata.modified()?,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the header of an existing block file and return its key, if the block is valid and at
+    /// the path of its key. The data of the block is only validated when it is read.
+    fn validate_existing_block(&self, path: &Path) -> Option<DiskBlockKey> {
+        let mut file = fs::File::open(path).ok()?;
+        let mut block_version = [0; CACHE_VERSION.len()];
+        file.read_exact(&mut block_version).ok()?;
+        if block_version != CACHE_VERSION.as_bytes() {
+            trace!(?path, "discarding block of another version");
+            return None;
+        }
+        let header: DiskBlockHeader = match bincode::deserialize_from(&file) {
+            Ok(header) => header,
+            Err(e) => {
+                trace!(
+                    ?path,
+                    "discarding block whose header could not be deserialized: {:?}",
+                    e
+                );
+                return None;
+            }
+        };
+        if !header.is_intact() || header.block_idx.checked_mul(self.config.block_size) != Some(header.block_offset) {
+            trace!(?path, "discarding corrupt block or block of another block size");
+            return None;
+        }
+        let etag = ETag::from_str(&header.etag).ok()?;
+        let block_key = DiskBlockKey::new(&ObjectId::new(header.s3_key, etag), header.block_idx);
+        if self.get_path_for_block_key(&block_key) != path {
+            trace!(?path, "discarding block at the wrong path");
+            return None;
+        }
+        Some(block_key)
+    }
+
     /// Get the relative path for the given block.
     fn get_path_for_block_key(&self, block_key: &DiskBlockKey) -> PathBuf {
         let mut path = self.cache_directory.join(CACHE_VERSION);
@@ -261,14 +489,31 @@ impl DiskDataCache {
                 }
             })?;
 
+        if self.shared.is_some() {
+            // Processes that share the cache see how recently a block was used from its
+            // modification time
+            if let Err(err) = file.set_modified(SystemTime::now()) {
+                warn!(path = ?path.as_ref(), "unable to update block modification time: {:?}", err);
+            }
+        }
+
         Ok(Some(bytes))
     }
 
     fn write_block(&self, path: impl AsRef<Path>, block: DiskBlock) -> DataCacheResult<usize> {
-        let cache_path_for_key = path
-            .as_ref()
-            .parent()
-            .expect("path should include cache key in directory name");
+        let (temporary_path, size) = self.write_temporary_block(path.as_ref(), block)?;
+        if let Err(err) = fs::rename(&temporary_path, path.as_ref()) {
+            let _ = fs::remove_file(&temporary_path);
+            return Err(err.into());
+        }
+        Ok(size)
+    }
+
+    /// Write a block to a temporary file next to the given path, and return the path of the
+    /// temporary file and its size. Moving it into place then means that readers never see a
+    /// partially written block, even in other processes that share the cache.
+    fn write_temporary_block(&self, path: &Path, block: DiskBlock) -> DataCacheResult<(PathBuf, usize)> {
+        let cache_path_for_key = path.parent().expect("path should include cache key in directory name");
         fs::DirBuilder::new()
             .mode(0o700)
             .recursive(true)
@@ -278,16 +523,26 @@ impl DiskDataCache {
             key = block.header.s3_key,
             offset = block.header.block_offset,
             "writing block at {}",
-            path.as_ref().display()
+            path.display()
         );
+        let temporary_path = temporary_block_path(path);
+        match Self::write_block_file(&temporary_path, &block) {
+            Ok(size) => Ok((temporary_path, size)),
+            Err(err) => {
+                let _ = fs::remove_file(&temporary_path);
+                Err(err)
+            }
+        }
+    }
+
+    fn write_block_file(path: &Path, block: &DiskBlock) -> DataCacheResult<usize> {
         let mut file = fs::OpenOptions::new()
             .write(true)
-            .create(true)
-            .truncate(true)
+            .create_new(true)
             .mode(0o600)
-            .open(path.as_ref())?;
+            .open(path)?;
         file.write_all(CACHE_VERSION.as_bytes())?;
-        let serialize_result = bincode::serialize_into(&mut file, &block);
+        let serialize_result = bincode::serialize_into(&mut file, block);
         if let Err(err) = serialize_result {
             return match *err {
                 bincode::ErrorKind::Io(io_err) => return Err(DataCacheError::from(io_err)),
@@ -324,10 +579,12 @@ impl DiskDataCache {
         };
 
         while self.is_limit_exceeded(usage.lock().unwrap().size) {
-            let Some(to_remove) = usage.lock().unwrap().evict_lru() else {
+            let Some(to_remove) = usage.lock().unwrap().evict() else {
                 warn!("cache limit exceeded but nothing to evict");
                 return Err(DataCacheError::EvictionFailure);
             };
+            metrics::counter!("disk_data_cache.evicted_blocks", "policy" => self.config.eviction_policy.as_str())
+                .increment(1);
             let path_to_remove = self.get_path_for_block_key(&to_remove);
             trace!("evicting block at {}", path_to_remove.display());
             if let Err(remove_err) = fs::remove_file(&path_to_remove) {
@@ -336,6 +593,110 @@ impl DiskDataCache {
         }
         Ok(())
     }
+
+    /// Count a hit or a miss for the eviction policy, so that the hit rates of policies can be
+    /// compared. Shared caches always evict the least recently used blocks, so they aren't counted.
+    fn record_policy_hit(&self, hit: bool) {
+        if self.usage.is_some() {
+            metrics::counter!("disk_data_cache.policy_block_hit", "policy" => self.config.eviction_policy.as_str())
+                .increment(hit as u64);
+        }
+    }
+
+    /// Evict blocks until the cache is within its limit. The caller holds the lock on the usage of
+    /// the cache, so that other processes don't evict at the same time.
+    fn evict_shared_if_needed(&self, shared: &mut LockedUsage<'_>) -> DataCacheResult<()> {
+        let mut listed = false;
+        while self.is_limit_exceeded(shared.size()? as usize) {
+            let Some(candidate) = shared.next_candidate() else {
+                if listed {
+                    warn!("cache limit exceeded but nothing to evict");
+                    return Err(DataCacheError::EvictionFailure);
+                }
+                // Other processes may have written blocks since the blocks were last listed
+                let mut blocks = Vec::new();
+                Self::list_blocks(&self.cache_directory.join(CACHE_VERSION), &mut blocks)?;
+                shared.reset(blocks)?;
+                listed = true;
+                continue;
+            };
+
+            // Skip the blocks that another process evicted or used since they were listed
+            match fs::metadata(&candidate.path) {
+                Ok(metadata) if metadata.modified()? == candidate.modified => {}
+                Ok(_) => continue,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            }
+            trace!("evicting block at {}", candidate.path.display());
+            match fs::remove_file(&candidate.path) {
+                Ok(()) => {
+                    shared.remove(candidate.size)?;
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(remove_err) => warn!("unable to remove block: {:?}", remove_err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a block to a shared cache. The block is written to a temporary file first, and then
+    /// evicting blocks, moving the new block into place and accounting for it all happen under the
+    /// lock on the usage of the cache.
+    fn put_shared_block(
+        &self,
+        shared: &SharedUsage,
+        path: &Path,
+        block: DiskBlock,
+        bytes_len: usize,
+    ) -> DataCacheResult<()> {
+        let write_start = Instant::now();
+        let (temporary_path, size) = self.write_temporary_block(path, block)?;
+        metrics::histogram!("disk_data_cache.write_duration_us").record(write_start.elapsed().as_micros() as f64);
+
+        let result = shared.lock().map_err(DataCacheError::from).and_then(|mut usage| {
+            let eviction_start = Instant::now();
+            let result = self.evict_shared_if_needed(&mut usage);
+            metrics::histogram!("disk_data_cache.eviction_duration_us")
+                .record(eviction_start.elapsed().as_micros() as f64);
+            result?;
+
+            let replaced = match fs::metadata(path) {
+                Ok(metadata) => metadata.len(),
+                Err(err) if err.kind() == ErrorKind::NotFound => 0,
+                Err(err) => return Err(err.into()),
+            };
+            fs::rename(&temporary_path, path)?;
+            usage.add(size as u64, replaced)?;
+            Ok(())
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary_path);
+        }
+        result?;
+
+        metrics::counter!("disk_data_cache.total_bytes", "type" => "write").increment(bytes_len as u64);
+        Ok(())
+    }
+}
+
+/// The path of a new temporary file to write the block at the given path to
+fn temporary_block_path(path: &Path) -> PathBuf {
+    let id = NEXT_TEMPORARY_BLOCK_ID.fetch_add(1, Ordering::Relaxed);
+    path.with_extension(format!("{}-{}.{}", std::process::id(), id, TEMPORARY_BLOCK_EXTENSION))
+}
+
+fn is_temporary_block(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == TEMPORARY_BLOCK_EXTENSION)
+}
+
+fn is_older_than(metadata: &fs::Metadata, age: Duration) -> bool {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|elapsed| elapsed > age)
 }
 
 /// Hash the cache key using its fields as well as the [CACHE_VERSION].
@@ -367,6 +728,7 @@ impl DataCache for DiskDataCache {
             Ok(None) => {
                 // Cache miss.
                 metrics::counter!("disk_data_cache.block_hit").increment(0);
+                self.record_policy_hit(false);
                 Ok(None)
             }
             Ok(Some(bytes)) => {
@@ -377,12 +739,14 @@ impl DataCache for DiskDataCache {
                 if let Some(usage) = &self.usage {
                     usage.lock().unwrap().refresh(&block_key);
                 }
+                self.record_policy_hit(true);
                 Ok(Some(bytes))
             }
             Err(err) => {
                 // Invalid block. Count as cache miss.
                 metrics::counter!("disk_data_cache.block_hit").increment(0);
                 metrics::counter!("disk_data_cache.block_err").increment(1);
+                self.record_policy_hit(false);
                 match fs::remove_file(&path) {
                     Ok(()) => {
                         if let Some(usage) = &self.usage {
@@ -416,6 +780,10 @@ impl DataCache for DiskDataCache {
             DiskBlockCreationError::IntegrityError(_e) => DataCacheError::InvalidBlockContent,
         })?;
 
+        if let Some(shared) = &self.shared {
+            return self.put_shared_block(shared, &path, block, bytes_len);
+        }
+
         {
             let eviction_start = Instant::now();
             let result = self.evict_if_needed();
@@ -476,33 +844,44 @@ impl DiskBlockKey {
     }
 }
 
-/// Keeps track of entries usage and total size.
+/// Keeps track of entries usage and total size, and chooses the entries to evict with an
+/// [EvictionPolicy].
 struct UsageInfo<K> {
-    entries: LinkedHashMap<K, usize>,
+    entries: HashMap<K, usize>,
+    policy: Box<dyn EvictionPolicy<K>>,
     size: usize,
 }
 
 impl<K> UsageInfo<K>
 where
-    K: std::hash::Hash + Eq + std::fmt::Debug,
+    K: std::hash::Hash + Eq + Clone + std::fmt::Debug,
 {
-    fn new() -> Self {
+    fn new(policy: Box<dyn EvictionPolicy<K>>) -> Self {
         Self {
-            entries: LinkedHashMap::new(),
+            entries: HashMap::new(),
+            policy,
             size: 0,
         }
     }
 
-    /// Refresh the given key if present, marking it as the most recently used.
+    /// Record a use of the given key if present.
     /// Returns `false` if the key is not in the cache.
     fn refresh(&mut self, key: &K) -> bool {
-        self.entries.get_refresh(key).is_some()
+        if !self.entries.contains_key(key) {
+            return false;
+        }
+        self.policy.access(key);
+        true
     }
 
-    /// Add or replace a key and update the total size.
+    /// Add or replace a key and update the total size. Replacing a key counts as a use.
     fn add(&mut self, key: K, size: usize) {
-        if let Some(previous_size) = self.entries.insert(key, size) {
-            self.size = self.size.saturating_sub(previous_size);
+        match self.entries.insert(key.clone(), size) {
+            Some(previous_size) => {
+                self.size = self.size.saturating_sub(previous_size);
+                self.policy.access(&key);
+            }
+            None => self.policy.insert(key),
         }
 
         self.size = self.size.saturating_add(size);
@@ -512,14 +891,17 @@ where
     fn remove(&mut self, key: &K) {
         if let Some(size) = self.entries.remove(key) {
             self.size = self.size.saturating_sub(size);
+            self.policy.remove(key);
         }
     }
 
-    /// Remove the least recently used key and update the total size.
+    /// Remove the key chosen by the eviction policy and update the total size.
     /// Return `None` if empty.
-    fn evict_lru(&mut self) -> Option<K> {
-        let (key, size) = self.entries.pop_front()?;
-        self.size = self.size.saturating_sub(size);
+    fn evict(&mut self) -> Option<K> {
+        let key = self.policy.evict()?;
+        if let Some(size) = self.entries.remove(&key) {
+            self.size = self.size.saturating_sub(size);
+        }
         Some(key)
     }
 }
@@ -534,6 +916,7 @@ mod tests {
     use mountpoint_s3_client::types::ETag;
     use rand::{Rng, SeedableRng};
     use rand_chacha::ChaCha20Rng;
+    use test_case::test_case;
 
     #[test]
     fn test_block_format_version_requires_update() {
@@ -570,6 +953,7 @@ mod tests {
             DiskDataCacheConfig {
                 block_size: 1024,
                 limit: CacheLimit::Unbounded,
+                ..Default::default()
             },
         );
 
@@ -600,6 +984,7 @@ mod tests {
             DiskDataCacheConfig {
                 block_size: 1024,
                 limit: CacheLimit::Unbounded,
+                ..Default::default()
             },
         );
 
@@ -635,6 +1020,7 @@ mod tests {
             DiskDataCacheConfig {
                 block_size,
                 limit: CacheLimit::Unbounded,
+                ..Default::default()
             },
         );
         let cache_key_1 = ObjectId::new("a".into(), ETag::for_tests());
@@ -711,6 +1097,7 @@ mod tests {
             DiskDataCacheConfig {
                 block_size: 8 * 1024 * 1024,
                 limit: CacheLimit::Unbounded,
+                ..Default::default()
             },
         );
         let cache_key = ObjectId::new("a".into(), ETag::for_tests());
@@ -729,8 +1116,10 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_eviction() {
+    #[test_case(EvictionPolicyKind::Lru; "lru")]
+    #[test_case(EvictionPolicyKind::Lfu; "lfu")]
+    #[test_case(EvictionPolicyKind::S3Fifo; "s3-fifo")]
+    fn test_eviction(eviction_policy: EvictionPolicyKind) {
         const BLOCK_SIZE: usize = 100 * 1024;
         const LARGE_OBJECT_SIZE: usize = 1024 * 1024;
         const SMALL_OBJECT_SIZE: usize = LARGE_OBJECT_SIZE / 2;
@@ -787,6 +1176,7 @@ mod tests {
             DiskDataCacheConfig {
                 block_size: BLOCK_SIZE as u64,
                 limit: CacheLimit::TotalSize { max_size: CACHE_LIMIT },
+                eviction_policy,
             },
         );
 
@@ -836,6 +1226,135 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_reuse_existing_blocks() {
+        const BLOCK_SIZE: u64 = 1024;
+        let data_1 = ChecksummedBytes::new("Foo".into());
+        let data_2 = ChecksummedBytes::new("Bar".into());
+        let cache_key_1 = ObjectId::new("a".into(), ETag::for_tests());
+        let cache_key_2 = ObjectId::new("b".into(), ETag::for_tests());
+        let config = || DiskDataCacheConfig {
+            block_size: BLOCK_SIZE,
+            limit: CacheLimit::TotalSize { max_size: 1024 * 1024 },
+            ..Default::default()
+        };
+
+        let cache_directory = tempfile::tempdir().unwrap();
+        let cache = DiskDataCache::new(cache_directory.path().to_owned(), config());
+        cache.put_block(cache_key_1.clone(), 0, 0, data_1.clone()).unwrap();
+        cache
+            .put_block(cache_key_1.clone(), 1, BLOCK_SIZE, data_2.clone())
+            .unwrap();
+        cache.put_block(cache_key_2.clone(), 0, 0, data_2.clone()).unwrap();
+        let expected_size = cache.usage.as_ref().unwrap().lock().unwrap().size;
+
+        // Corrupt one block, and leave a file of another cache version
+        let corrupt_path = cache.get_path_for_block_key(&DiskBlockKey::new(&cache_key_2, 0));
+        let corrupt_size = fs::metadata(&corrupt_path).unwrap().len() as usize;
+        let mut bytes = fs::read(&corrupt_path).unwrap();
+        bytes[CACHE_VERSION.len()] ^= 1;
+        fs::write(&corrupt_path, bytes).unwrap();
+        let stale_path = cache_directory.path().join("V0");
+        fs::create_dir(&stale_path).unwrap();
+        fs::write(stale_path.join("block"), b"stale").unwrap();
+        drop(cache);
+
+        let cache = DiskDataCache::new_from_existing(cache_directory.path().to_owned(), config())
+            .expect("existing cache should be loaded");
+        assert_eq!(
+            cache.usage.as_ref().unwrap().lock().unwrap().size,
+            expected_size - corrupt_size,
+            "valid blocks should be tracked"
+        );
+        assert!(!corrupt_path.try_exists().unwrap(), "corrupt block should be removed");
+        assert!(!stale_path.try_exists().unwrap(), "stale version should be removed");
+
+        let entry = cache
+            .get_block(&cache_key_1, 1, BLOCK_SIZE)
+            .expect("cache should be accessible")
+            .expect("existing block should be returned");
+        assert_eq!(data_2, entry);
+        let entry = cache.get_block(&cache_key_2, 0, 0).expect("cache should be accessible");
+        assert!(entry.is_none(), "corrupt block should not be returned");
+
+        // Blocks written with another block size are discarded
+        drop(cache);
+        let cache = DiskDataCache::new_from_existing(
+            cache_directory.path().to_owned(),
+            DiskDataCacheConfig {
+                block_size: BLOCK_SIZE * 2,
+                ..config()
+            },
+        )
+        .expect("existing cache should be loaded");
+        let first_block_path = cache.get_path_for_block_key(&DiskBlockKey::new(&cache_key_1, 0));
+        assert_eq!(
+            cache.usage.as_ref().unwrap().lock().unwrap().size,
+            fs::metadata(first_block_path).unwrap().len() as usize,
+            "only the block at offset 0 should be kept"
+        );
+        let second_block_path = cache.get_path_for_block_key(&DiskBlockKey::new(&cache_key_1, 1));
+        assert!(!second_block_path.try_exists().unwrap());
+    }
+
+    #[test]
+    fn test_shared_eviction() {
+        const BLOCK_SIZE: usize = 1024;
+        const CACHE_LIMIT: usize = 8 * BLOCK_SIZE;
+
+        let cache_directory = tempfile::tempdir().unwrap();
+        let config = || DiskDataCacheConfig {
+            block_size: BLOCK_SIZE as u64,
+            limit: CacheLimit::TotalSize { max_size: CACHE_LIMIT },
+            ..Default::default()
+        };
+        let cache_1 = DiskDataCache::new_shared(cache_directory.path().to_owned(), config()).unwrap();
+        let cache_2 = DiskDataCache::new_shared(cache_directory.path().to_owned(), config()).unwrap();
+        let data = ChecksummedBytes::new(vec![0u8; BLOCK_SIZE].into());
+        let key_1 = ObjectId::new("one".into(), ETag::for_tests());
+        let key_2 = ObjectId::new("two".into(), ETag::for_tests());
+
+        // Blocks written by one process can be read by the other
+        for block_idx in 0..4 {
+            let offset = block_idx * BLOCK_SIZE as u64;
+            cache_1
+                .put_block(key_1.clone(), block_idx, offset, data.clone())
+                .unwrap();
+        }
+        assert!(cache_2.get_block(&key_1, 0, 0).unwrap().is_some());
+        let total_size = cache_2.shared.as_ref().unwrap().lock().unwrap().size().unwrap() as usize;
+        assert!(total_size > 4 * BLOCK_SIZE, "both processes should see the total size");
+        cache_2.put_block(key_1.clone(), 0, 0, data.clone()).unwrap();
+        let new_total_size = cache_1.shared.as_ref().unwrap().lock().unwrap().size().unwrap() as usize;
+        assert_eq!(
+            new_total_size, total_size,
+            "replaced blocks should not be counted twice"
+        );
+
+        // Writing blocks from the other process evicts the least recently used blocks of the first,
+        // which are not the first block, since it was just read
+        std::thread::sleep(Duration::from_millis(20));
+        cache_2.get_block(&key_1, 0, 0).unwrap();
+        for block_idx in 0..5 {
+            let offset = block_idx * BLOCK_SIZE as u64;
+            cache_2
+                .put_block(key_2.clone(), block_idx, offset, data.clone())
+                .unwrap();
+        }
+        assert!(cache_1.get_block(&key_1, 0, 0).unwrap().is_some());
+        let remaining_blocks = (1..4)
+            .filter(|&block_idx| {
+                let offset = block_idx * BLOCK_SIZE as u64;
+                cache_1.get_block(&key_1, block_idx, offset).unwrap().is_some()
+            })
+            .count();
+        assert_eq!(remaining_blocks, 2, "one block should have been evicted");
+        for block_idx in 0..5 {
+            let offset = block_idx * BLOCK_SIZE as u64;
+            assert!(cache_1.get_block(&key_2, block_idx, offset).unwrap().is_some());
+        }
+    }
+
     #[test]
     fn data_block_extract_checks() {
         let data_1 = ChecksummedBytes::new("Foo".into());
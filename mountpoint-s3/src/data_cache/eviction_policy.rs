@@ -0,0 +1,321 @@
+//! Policies that decide which blocks a size-limited cache evicts.
+//!
+//! A least recently used policy works well for a working set that is read over and over, but one
+//! large sequential read of objects that are never read again can flush the whole working set out
+//! of the cache. The least frequently used and S3-FIFO policies keep blocks that were read more
+//! than once in preference to the ones that were only written.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use linked_hash_map::LinkedHashMap;
+
+/// How a size-limited cache chooses the blocks to evict.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EvictionPolicyKind {
+    /// Evict the least recently used blocks first
+    #[default]
+    Lru,
+    /// Evict the least frequently used blocks first, and the least recently used of those
+    Lfu,
+    /// Evict with S3-FIFO, which keeps new blocks in a small queue, and only moves them to the main
+    /// queue if they are used again before they reach its end. See "FIFO queues are all you need
+    /// for cache eviction" (Yang et al., SOSP 2023).
+    S3Fifo,
+}
+
+impl EvictionPolicyKind {
+    /// The name of the policy, as used in metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lru => "lru",
+            Self::Lfu => "lfu",
+            Self::S3Fifo => "s3-fifo",
+        }
+    }
+
+    pub(super) fn build<K>(self) -> Box<dyn EvictionPolicy<K>>
+    where
+        K: Hash + Eq + Clone + Send + 'static,
+    {
+        match self {
+            Self::Lru => Box::<LruPolicy<K>>::default(),
+            Self::Lfu => Box::<LfuPolicy<K>>::default(),
+            Self::S3Fifo => Box::<S3FifoPolicy<K>>::default(),
+        }
+    }
+}
+
+/// Tracks the keys of a cache to decide which one to evict next.
+pub(super) trait EvictionPolicy<K>: Send {
+    /// Start tracking a key that is not tracked yet.
+    fn insert(&mut self, key: K);
+
+    /// Record a use of a tracked key.
+    fn access(&mut self, key: &K);
+
+    /// Stop tracking a key that was removed from the cache for another reason than eviction.
+    fn remove(&mut self, key: &K);
+
+    /// Choose the key to evict and stop tracking it. Returns `None` if no keys are tracked.
+    fn evict(&mut self) -> Option<K>;
+}
+
+/// Evicts the least recently used key.
+struct LruPolicy<K> {
+    entries: LinkedHashMap<K, ()>,
+}
+
+impl<K: Hash + Eq> Default for LruPolicy<K> {
+    fn default() -> Self {
+        Self {
+            entries: LinkedHashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Send> EvictionPolicy<K> for LruPolicy<K> {
+    fn insert(&mut self, key: K) {
+        self.entries.insert(key, ());
+    }
+
+    fn access(&mut self, key: &K) {
+        self.entries.get_refresh(key);
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.entries.remove(key);
+    }
+
+    fn evict(&mut self) -> Option<K> {
+        self.entries.pop_front().map(|(key, ())| key)
+    }
+}
+
+/// Evicts the least frequently used key, breaking ties by evicting the least recently used one.
+struct LfuPolicy<K> {
+    /// The number of uses of each key, and the sequence number of its last use
+    entries: HashMap<K, (u64, u64)>,
+    /// The keys, ordered by number of uses and then by last use
+    order: BTreeMap<(u64, u64), K>,
+    next_sequence: u64,
+}
+
+impl<K> Default for LfuPolicy<K> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_sequence: 0,
+        }
+    }
+}
+
+impl<K> LfuPolicy<K> {
+    fn next_sequence(&mut self) -> u64 {
+        self.next_sequence += 1;
+        self.next_sequence
+    }
+}
+
+impl<K: Hash + Eq + Clone + Send> EvictionPolicy<K> for LfuPolicy<K> {
+    fn insert(&mut self, key: K) {
+        let rank = (1, self.next_sequence());
+        self.entries.insert(key.clone(), rank);
+        self.order.insert(rank, key);
+    }
+
+    fn access(&mut self, key: &K) {
+        let sequence = self.next_sequence();
+        if let Some(rank) = self.entries.get_mut(key) {
+            self.order.remove(rank);
+            *rank = (rank.0.saturating_add(1), sequence);
+            self.order.insert(*rank, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(rank) = self.entries.remove(key) {
+            self.order.remove(&rank);
+        }
+    }
+
+    fn evict(&mut self) -> Option<K> {
+        let (_, key) = self.order.pop_first()?;
+        self.entries.remove(&key);
+        Some(key)
+    }
+}
+
+/// How many times the uses of a key are counted in [S3FifoPolicy]
+const S3_FIFO_MAX_FREQUENCY: u8 = 3;
+
+/// The share of the keys that [S3FifoPolicy] keeps in its small queue, in percent
+const S3_FIFO_SMALL_QUEUE_PERCENT: usize = 10;
+
+/// Evicts keys with S3-FIFO. New keys enter a small FIFO queue, and most of them are evicted when
+/// they reach its end without having been used. Keys that were used move to the main queue, which
+/// is a FIFO queue where keys that were used since they were last at its end go round again. The
+/// keys evicted from the small queue are remembered in a ghost queue, and enter the main queue
+/// directly if they are inserted again while they are remembered.
+///
+/// The queues are sized relative to the number of keys that are tracked, since the cache limit is
+/// in bytes, or in available space, rather than a number of keys.
+struct S3FifoPolicy<K> {
+    small: LinkedHashMap<K, u8>,
+    main: LinkedHashMap<K, u8>,
+    ghost: LinkedHashMap<K, ()>,
+}
+
+impl<K: Hash + Eq> Default for S3FifoPolicy<K> {
+    fn default() -> Self {
+        Self {
+            small: LinkedHashMap::new(),
+            main: LinkedHashMap::new(),
+            ghost: LinkedHashMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone + Send> EvictionPolicy<K> for S3FifoPolicy<K> {
+    fn insert(&mut self, key: K) {
+        if self.ghost.remove(&key).is_some() {
+            self.main.insert(key, 0);
+        } else {
+            self.small.insert(key, 0);
+        }
+    }
+
+    fn access(&mut self, key: &K) {
+        let frequency = match self.small.get_mut(key) {
+            Some(frequency) => Some(frequency),
+            None => self.main.get_mut(key),
+        };
+        if let Some(frequency) = frequency {
+            *frequency = (*frequency + 1).min(S3_FIFO_MAX_FREQUENCY);
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        if self.small.remove(key).is_none() {
+            self.main.remove(key);
+        }
+    }
+
+    fn evict(&mut self) -> Option<K> {
+        loop {
+            let total = self.small.len() + self.main.len();
+            if total == 0 {
+                return None;
+            }
+
+            let small_is_full = self.small.len() * 100 >= total * S3_FIFO_SMALL_QUEUE_PERCENT;
+            if !self.small.is_empty() && (small_is_full || self.main.is_empty()) {
+                let (key, frequency) = self.small.pop_front()?;
+                if frequency > 0 {
+                    self.main.insert(key, 0);
+                    continue;
+                }
+                self.ghost.insert(key.clone(), ());
+                while self.ghost.len() > total {
+                    self.ghost.pop_front();
+                }
+                return Some(key);
+            }
+
+            let (key, frequency) = self.main.pop_front()?;
+            if frequency > 0 {
+                self.main.insert(key, frequency - 1);
+                continue;
+            }
+            return Some(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    fn evict_all(policy: &mut dyn EvictionPolicy<u64>) -> Vec<u64> {
+        std::iter::from_fn(|| policy.evict()).collect()
+    }
+
+    #[test_case(EvictionPolicyKind::Lru)]
+    #[test_case(EvictionPolicyKind::Lfu)]
+    #[test_case(EvictionPolicyKind::S3Fifo)]
+    fn test_evicts_each_key_once(kind: EvictionPolicyKind) {
+        let mut policy = kind.build();
+        for key in 0..20 {
+            policy.insert(key);
+        }
+        for key in (0..20).step_by(3) {
+            policy.access(&key);
+        }
+        policy.remove(&5);
+        policy.access(&5);
+
+        let mut evicted = evict_all(policy.as_mut());
+        evicted.sort();
+        let expected: Vec<_> = (0..20).filter(|&key| key != 5).collect();
+        assert_eq!(evicted, expected);
+        assert_eq!(policy.evict(), None);
+    }
+
+    #[test]
+    fn test_lru() {
+        let mut policy = EvictionPolicyKind::Lru.build();
+        for key in 0..4 {
+            policy.insert(key);
+        }
+        policy.access(&0);
+        assert_eq!(evict_all(policy.as_mut()), vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_lfu() {
+        let mut policy = EvictionPolicyKind::Lfu.build();
+        for key in 0..4 {
+            policy.insert(key);
+        }
+        policy.access(&0);
+        policy.access(&0);
+        policy.access(&2);
+        policy.access(&1);
+        assert_eq!(evict_all(policy.as_mut()), vec![3, 2, 1, 0]);
+    }
+
+    #[test_case(EvictionPolicyKind::Lru, false; "lru")]
+    #[test_case(EvictionPolicyKind::Lfu, true; "lfu")]
+    #[test_case(EvictionPolicyKind::S3Fifo, true; "s3-fifo")]
+    fn test_scan_resistance(kind: EvictionPolicyKind, keeps_working_set: bool) {
+        const CAPACITY: usize = 50;
+        let mut policy = kind.build();
+        let mut cached = 0;
+        let mut insert = |policy: &mut Box<dyn EvictionPolicy<u64>>, key| {
+            if cached == CAPACITY {
+                policy.evict().unwrap();
+            } else {
+                cached += 1;
+            }
+            policy.insert(key);
+        };
+
+        // A working set of 10 keys, each used a few times
+        for key in 0..10 {
+            insert(&mut policy, key);
+            policy.access(&key);
+            policy.access(&key);
+        }
+        // A scan of keys that are never used again, larger than the cache
+        for key in 100..200 {
+            insert(&mut policy, key);
+        }
+
+        let remaining = evict_all(policy.as_mut());
+        let working_set_kept = (0..10).all(|key| remaining.contains(&key));
+        assert_eq!(working_set_kept, keeps_working_set);
+    }
+}
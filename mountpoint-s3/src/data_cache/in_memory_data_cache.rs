@@ -1,24 +1,46 @@
-//! Module for the in-memory data cache implementation used for testing.
+//! Module for the in-memory data cache implementation.
 
-use std::collections::HashMap;
-use std::default::Default;
+use linked_hash_map::LinkedHashMap;
 
 use super::{BlockIndex, ChecksummedBytes, DataCache, DataCacheError, DataCacheResult};
 use crate::object::ObjectId;
-use crate::sync::RwLock;
+use crate::sync::Mutex;
 
-/// Simple in-memory (RAM) implementation of [DataCache]. Recommended for use in testing only.
+/// Simple in-memory (RAM) implementation of [DataCache]. Once the size limit, if any, is reached,
+/// the least recently used blocks are evicted.
+///
+/// On its own, it is recommended for use in testing only. See
+/// [TieredDataCache](super::TieredDataCache) to cache blocks in memory in front of another cache.
 pub struct InMemoryDataCache {
-    data: RwLock<HashMap<ObjectId, HashMap<BlockIndex, ChecksummedBytes>>>,
+    blocks: Mutex<Blocks>,
     block_size: u64,
+    max_size: Option<usize>,
+}
+
+/// The cached blocks, from the least to the most recently used, and their total size.
+#[derive(Default)]
+struct Blocks {
+    entries: LinkedHashMap<(ObjectId, BlockIndex), ChecksummedBytes>,
+    size: usize,
 }
 
 impl InMemoryDataCache {
     /// Create a new instance of an [InMemoryDataCache] with the specified `block_size`.
     pub fn new(block_size: u64) -> Self {
         InMemoryDataCache {
-            data: Default::default(),
+            blocks: Default::default(),
+            block_size,
+            max_size: None,
+        }
+    }
+
+    /// Create a new instance of an [InMemoryDataCache] with the specified `block_size`, that holds
+    /// at most `max_size` bytes of blocks.
+    pub fn new_with_limit(block_size: u64, max_size: usize) -> Self {
+        InMemoryDataCache {
+            blocks: Default::default(),
             block_size,
+            max_size: Some(max_size),
         }
     }
 }
@@ -33,8 +55,8 @@ impl DataCache for InMemoryDataCache {
         if block_offset != block_idx * self.block_size {
             return Err(DataCacheError::InvalidBlockOffset);
         }
-        let data = self.data.read().unwrap();
-        let block_data = data.get(cache_key).and_then(|blocks| blocks.get(&block_idx)).cloned();
+        let mut blocks = self.blocks.lock().unwrap();
+        let block_data = blocks.entries.get_refresh(&(cache_key.clone(), block_idx)).cloned();
         Ok(block_data)
     }
 
@@ -48,9 +70,21 @@ impl DataCache for InMemoryDataCache {
         if block_offset != block_idx * self.block_size {
             return Err(DataCacheError::InvalidBlockOffset);
         }
-        let mut data = self.data.write().unwrap();
-        let blocks = data.entry(cache_key).or_default();
-        blocks.insert(block_idx, bytes);
+        let mut blocks = self.blocks.lock().unwrap();
+        let size = bytes.len();
+        if let Some(previous) = blocks.entries.insert((cache_key, block_idx), bytes) {
+            blocks.size = blocks.size.saturating_sub(previous.len());
+        }
+        blocks.size = blocks.size.saturating_add(size);
+
+        // Evict the least recently used blocks to stay within the limit. A block larger than the
+        // limit is evicted immediately.
+        while self.max_size.is_some_and(|max_size| blocks.size > max_size) {
+            let Some((_, evicted)) = blocks.entries.pop_front() else {
+                break;
+            };
+            blocks.size = blocks.size.saturating_sub(evicted.len());
+        }
         Ok(())
     }
 
@@ -136,4 +170,43 @@ mod tests {
             "cache entry returned should match original bytes after put"
         );
     }
+
+    #[test]
+    fn test_eviction() {
+        let block_size = 1024;
+        let cache = InMemoryDataCache::new_with_limit(block_size, 2 * block_size as usize);
+        let cache_key = ObjectId::new("a".into(), ETag::for_tests());
+        let block = |idx: u8| ChecksummedBytes::new(vec![idx; block_size as usize].into());
+        let is_cached = |block_idx: BlockIndex| {
+            cache
+                .get_block(&cache_key, block_idx, block_idx * block_size)
+                .expect("cache is accessible")
+                .is_some()
+        };
+
+        let put = |block_idx: BlockIndex| {
+            cache
+                .put_block(
+                    cache_key.clone(),
+                    block_idx,
+                    block_idx * block_size,
+                    block(block_idx as u8),
+                )
+                .expect("cache is accessible")
+        };
+
+        put(0);
+        put(1);
+        assert!(is_cached(1), "blocks within the limit should not be evicted");
+
+        // Block 1 is now the least recently used
+        assert!(is_cached(0));
+        put(2);
+        assert!(!is_cached(1));
+
+        put(3);
+        assert!(!is_cached(0));
+        assert!(is_cached(2));
+        assert!(is_cached(3));
+    }
 }
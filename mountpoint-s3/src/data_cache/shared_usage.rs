@@ -0,0 +1,188 @@
+//! Usage accounting for a disk cache directory that several Mountpoint processes share.
+//!
+//! The total size of the cached blocks is kept in a small file in the cache directory. Each process
+//! holds an exclusive lock on that file while it checks the size, evicts blocks and moves a new
+//! block into place, so that processes don't all evict for the same new blocks. The order in
+//! which blocks were used is kept by the file system itself: blocks are written with a fresh
+//! modification time, and each cache hit updates it, so every process sees the same recency. To
+//! evict blocks, a process lists the cached blocks ordered by modification time, and then evicts
+//! them oldest first, skipping the ones that another process evicted or used since the listing.
+//! Listing the blocks also corrects the total size, which can drift when processes crash.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use nix::fcntl::{flock, FlockArg};
+use tracing::warn;
+
+use crate::sync::{Mutex, MutexGuard};
+
+/// A cached block that may be evicted, as it was when the blocks were last listed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct EvictionCandidate {
+    pub(super) path: PathBuf,
+    pub(super) size: u64,
+    pub(super) modified: SystemTime,
+}
+
+/// The usage of a shared cache directory
+#[derive(Debug)]
+pub(super) struct SharedUsage {
+    /// File holding the total size of the cache. Threads of this process take the mutex before
+    /// locking the file, since file locks don't exclude threads that share an open file.
+    size_file: Mutex<fs::File>,
+    /// Blocks to evict, from the least to the most recently used
+    candidates: Mutex<VecDeque<EvictionCandidate>>,
+}
+
+impl SharedUsage {
+    /// Open the file holding the total size of a shared cache, creating it if needed
+    pub(super) fn open(path: &Path) -> io::Result<Self> {
+        let size_file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(path)?;
+        Ok(Self {
+            size_file: Mutex::new(size_file),
+            candidates: Default::default(),
+        })
+    }
+
+    /// Lock the usage of the cache against all other threads and processes, until the returned
+    /// guard is dropped. Blocks must only be added to or removed from the cache directory while it
+    /// is locked, so that the total size matches what each process sees in the directory.
+    pub(super) fn lock(&self) -> io::Result<LockedUsage<'_>> {
+        let size_file = self.size_file.lock().unwrap();
+        flock(size_file.as_raw_fd(), FlockArg::LockExclusive)?;
+        Ok(LockedUsage {
+            size_file,
+            candidates: &self.candidates,
+        })
+    }
+}
+
+/// The usage of a shared cache directory, locked by [SharedUsage::lock]
+pub(super) struct LockedUsage<'a> {
+    size_file: MutexGuard<'a, fs::File>,
+    candidates: &'a Mutex<VecDeque<EvictionCandidate>>,
+}
+
+impl LockedUsage<'_> {
+    /// The total size of the cached blocks
+    pub(super) fn size(&self) -> io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        match self.size_file.read_exact_at(&mut bytes, 0) {
+            Ok(()) => Ok(u64::from_le_bytes(bytes)),
+            // A new file, which no process has written to yet
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Account for a new block, which replaced a block of size `replaced`, if any
+    pub(super) fn add(&mut self, size: u64, replaced: u64) -> io::Result<u64> {
+        self.update_size(|total| total.saturating_sub(replaced).saturating_add(size))
+    }
+
+    /// Account for a removed block
+    pub(super) fn remove(&mut self, size: u64) -> io::Result<u64> {
+        self.update_size(|total| total.saturating_sub(size))
+    }
+
+    /// Replace the candidates for eviction with a new listing of the cached blocks, and the total
+    /// size with their size
+    pub(super) fn reset(&mut self, mut blocks: Vec<EvictionCandidate>) -> io::Result<()> {
+        blocks.sort_by_key(|block| block.modified);
+        let total = blocks.iter().map(|block| block.size).sum::<u64>();
+        *self.candidates.lock().unwrap() = blocks.into();
+        self.update_size(|_| total)?;
+        Ok(())
+    }
+
+    /// The next block to evict, if any are left from the last listing
+    pub(super) fn next_candidate(&mut self) -> Option<EvictionCandidate> {
+        self.candidates.lock().unwrap().pop_front()
+    }
+
+    fn update_size(&mut self, f: impl FnOnce(u64) -> u64) -> io::Result<u64> {
+        let size = self.size()?;
+        let new_size = f(size);
+        if new_size != size {
+            self.size_file.write_all_at(&new_size.to_le_bytes(), 0)?;
+        }
+        Ok(new_size)
+    }
+}
+
+impl Drop for LockedUsage<'_> {
+    fn drop(&mut self) {
+        if let Err(error) = flock(self.size_file.as_raw_fd(), FlockArg::Unlock) {
+            warn!(?error, "unable to unlock the shared cache usage");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn candidate(name: &str, size: u64, age_secs: u64) -> EvictionCandidate {
+        EvictionCandidate {
+            path: PathBuf::from(name),
+            size,
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1000 - age_secs),
+        }
+    }
+
+    #[test]
+    fn test_shared_size() {
+        let cache_directory = tempfile::tempdir().unwrap();
+        let path = cache_directory.path().join("usage");
+        let usage_1 = SharedUsage::open(&path).unwrap();
+        let usage_2 = SharedUsage::open(&path).unwrap();
+        assert_eq!(usage_1.lock().unwrap().size().unwrap(), 0);
+
+        usage_1.lock().unwrap().add(100, 0).unwrap();
+        usage_2.lock().unwrap().add(50, 0).unwrap();
+        assert_eq!(usage_1.lock().unwrap().size().unwrap(), 150);
+        usage_2.lock().unwrap().add(70, 50).unwrap();
+        assert_eq!(
+            usage_1.lock().unwrap().size().unwrap(),
+            170,
+            "replaced blocks should not be counted"
+        );
+        usage_1.lock().unwrap().remove(200).unwrap();
+        assert_eq!(usage_2.lock().unwrap().size().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let cache_directory = tempfile::tempdir().unwrap();
+        let usage = SharedUsage::open(&cache_directory.path().join("usage")).unwrap();
+        let mut usage = usage.lock().unwrap();
+        usage.add(1, 0).unwrap();
+
+        usage
+            .reset(vec![
+                candidate("new", 10, 1),
+                candidate("old", 20, 100),
+                candidate("mid", 30, 50),
+            ])
+            .unwrap();
+        assert_eq!(usage.size().unwrap(), 60, "listing should correct the total size");
+        assert_eq!(usage.next_candidate(), Some(candidate("old", 20, 100)));
+        assert_eq!(usage.next_candidate(), Some(candidate("mid", 30, 50)));
+        assert_eq!(usage.next_candidate(), Some(candidate("new", 10, 1)));
+        assert_eq!(usage.next_candidate(), None);
+    }
+}
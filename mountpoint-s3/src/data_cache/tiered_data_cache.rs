@@ -0,0 +1,131 @@
+//! Module for a data cache that keeps recently used blocks in memory, in front of another cache.
+
+use super::in_memory_data_cache::InMemoryDataCache;
+use super::{BlockIndex, ChecksummedBytes, DataCache, DataCacheResult};
+use crate::object::ObjectId;
+
+/// Implementation of [DataCache] with two tiers: a size-limited [InMemoryDataCache], in front of
+/// another cache, usually a [DiskDataCache](super::DiskDataCache).
+///
+/// New blocks are written to both tiers, so that the lower tier holds every cached block even if
+/// it outlives this cache, for example with a persistent or shared cache directory. Blocks read
+/// from the lower tier are promoted to memory, and blocks evicted from memory are dropped.
+pub struct TieredDataCache<Cache> {
+    memory: InMemoryDataCache,
+    lower: Cache,
+}
+
+impl<Cache: DataCache> TieredDataCache<Cache> {
+    /// Create a new instance of a [TieredDataCache] that holds at most `max_memory_size` bytes of
+    /// blocks in memory, in front of `lower`.
+    pub fn new(max_memory_size: usize, lower: Cache) -> Self {
+        let memory = InMemoryDataCache::new_with_limit(lower.block_size(), max_memory_size);
+        Self { memory, lower }
+    }
+}
+
+impl<Cache: DataCache> DataCache for TieredDataCache<Cache> {
+    fn get_block(
+        &self,
+        cache_key: &ObjectId,
+        block_idx: BlockIndex,
+        block_offset: u64,
+    ) -> DataCacheResult<Option<ChecksummedBytes>> {
+        if let Some(bytes) = self.memory.get_block(cache_key, block_idx, block_offset)? {
+            metrics::counter!("tiered_data_cache.memory_hit").increment(1);
+            return Ok(Some(bytes));
+        }
+        metrics::counter!("tiered_data_cache.memory_miss").increment(1);
+
+        let Some(bytes) = self.lower.get_block(cache_key, block_idx, block_offset)? else {
+            return Ok(None);
+        };
+        metrics::counter!("tiered_data_cache.promoted_blocks").increment(1);
+        self.memory
+            .put_block(cache_key.clone(), block_idx, block_offset, bytes.clone())?;
+        Ok(Some(bytes))
+    }
+
+    fn put_block(
+        &self,
+        cache_key: ObjectId,
+        block_idx: BlockIndex,
+        block_offset: u64,
+        bytes: ChecksummedBytes,
+    ) -> DataCacheResult<()> {
+        self.memory
+            .put_block(cache_key.clone(), block_idx, block_offset, bytes.clone())?;
+        self.lower.put_block(cache_key, block_idx, block_offset, bytes)
+    }
+
+    fn block_size(&self) -> u64 {
+        self.lower.block_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use mountpoint_s3_client::types::ETag;
+
+    const BLOCK_SIZE: u64 = 1024;
+
+    fn block(block_idx: BlockIndex) -> ChecksummedBytes {
+        ChecksummedBytes::new(vec![block_idx as u8; BLOCK_SIZE as usize].into())
+    }
+
+    fn is_cached(cache: &impl DataCache, cache_key: &ObjectId, block_idx: BlockIndex) -> bool {
+        cache
+            .get_block(cache_key, block_idx, block_idx * BLOCK_SIZE)
+            .expect("cache is accessible")
+            .is_some()
+    }
+
+    #[test]
+    fn test_write_through() {
+        let cache = TieredDataCache::new(2 * BLOCK_SIZE as usize, InMemoryDataCache::new(BLOCK_SIZE));
+        let cache_key = ObjectId::new("a".into(), ETag::for_tests());
+
+        for block_idx in 0..3 {
+            cache
+                .put_block(cache_key.clone(), block_idx, block_idx * BLOCK_SIZE, block(block_idx))
+                .expect("cache is accessible");
+        }
+        assert!(
+            !is_cached(&cache.memory, &cache_key, 0),
+            "block 0 should be evicted from memory"
+        );
+        for block_idx in 0..3 {
+            assert!(
+                is_cached(&cache.lower, &cache_key, block_idx),
+                "all blocks should be in the lower tier"
+            );
+        }
+    }
+
+    #[test]
+    fn test_promote() {
+        let lower = InMemoryDataCache::new(BLOCK_SIZE);
+        let cache_key = ObjectId::new("a".into(), ETag::for_tests());
+        lower
+            .put_block(cache_key.clone(), 0, 0, block(0))
+            .expect("cache is accessible");
+        let cache = TieredDataCache::new(BLOCK_SIZE as usize, lower);
+        assert!(!is_cached(&cache.memory, &cache_key, 0));
+
+        let bytes = cache
+            .get_block(&cache_key, 0, 0)
+            .expect("cache is accessible")
+            .expect("block 0 should be cached");
+        assert_eq!(bytes, block(0));
+        assert!(is_cached(&cache.memory, &cache_key, 0), "block 0 should be promoted");
+
+        // Blocks evicted from memory are still available from the lower tier
+        cache
+            .put_block(cache_key.clone(), 1, BLOCK_SIZE, block(1))
+            .expect("cache is accessible");
+        assert!(!is_cached(&cache.memory, &cache_key, 0));
+        assert!(is_cached(&cache, &cache_key, 0));
+    }
+}
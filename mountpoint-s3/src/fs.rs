@@ -5,29 +5,35 @@ use mountpoint_s3_crt::checksums::crc32c::{Crc32c, Hasher};
 use nix::unistd::{getgid, getuid};
 use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::{Duration, UNIX_EPOCH};
 use thiserror::Error;
 use time::OffsetDateTime;
-use tracing::{debug, error, trace, Level};
+use tracing::{debug, error, trace, warn, Level};
 
 use fuser::consts::FOPEN_DIRECT_IO;
 use fuser::{FileAttr, KernelConfig};
-use mountpoint_s3_client::error::{GetObjectError, ObjectClientError};
-use mountpoint_s3_client::types::ETag;
+use mountpoint_s3_client::error::{CopyObjectError, GetObjectError, ObjectClientError};
+use mountpoint_s3_client::types::{CopyObjectParams, ETag};
 use mountpoint_s3_client::ObjectClient;
 
 use crate::fs::error_metadata::{ErrorMetadata, MOUNTPOINT_ERROR_LOOKUP_NONEXISTENT};
 use crate::inode::{
-    Inode, InodeError, InodeKind, LookedUp, ReadHandle, ReaddirHandle, Superblock, SuperblockConfig, WriteHandle,
+    posix_metadata, symlink_metadata, Inode, InodeError, InodeKind, LookedUp, ReadHandle, ReaddirHandle, Superblock,
+    SuperblockConfig, WriteHandle,
 };
+use crate::key_filter::KeyFilter;
 use crate::logging;
+use crate::object::ObjectId;
 use crate::prefetch::{Prefetch, PrefetchReadError, PrefetchResult};
 use crate::prefix::Prefix;
 use crate::s3::S3Personality;
 use crate::sync::atomic::{AtomicI64, AtomicU64, Ordering};
-use crate::sync::{Arc, AsyncMutex, AsyncRwLock};
-use crate::upload::{UploadRequest, Uploader};
+use crate::sync::{Arc, AsyncMutex, AsyncMutexGuard, AsyncRwLock};
+use crate::unicode::UnicodeNormalization;
+use crate::upload::{AppendUploadRequest, BaseObject, StagedUploadRequest, UploadRequest, Uploader};
 
 pub use crate::inode::InodeNo;
 
@@ -38,10 +44,41 @@ pub use error::{Error, ToErrno};
 mod time_to_live;
 pub use time_to_live::TimeToLive;
 
+mod xattr;
+pub(crate) use xattr::ENOATTR;
+
 pub mod error_metadata;
 
+mod locks;
+use locks::{FileLock, LockError, LockManager, LockType};
+
+mod multi_bucket;
+pub use multi_bucket::BucketMount;
+use multi_bucket::MountedBucket;
+
 pub const FUSE_ROOT_INODE: InodeNo = 1u64;
 
+/// From man stat(2): `st_blksize`: "This field gives the "preferred" block size for efficient
+/// filesystem I/O."
+const PREFERRED_IO_BLOCK_SIZE: u32 = 4096;
+
+/// Capacity that `statfs` reports by default. It's large enough that no one will run out of it, but
+/// still fits in a signed 64-bit size, which some applications convert it to.
+const DEFAULT_STATFS_CAPACITY: u64 = 1 << 60;
+
+/// Block size that `statfs` reports when the client doesn't have a part size
+const DEFAULT_STATFS_BLOCK_SIZE: u32 = 8 * 1024 * 1024;
+
+/// Number of inodes that `statfs` reports. The real limit is much higher, but some tools keep inode
+/// counts in 32 bits.
+const STATFS_INODES: u64 = u32::MAX as u64;
+
+/// Longest file name that `statfs` reports, which is the longest key S3 accepts
+const STATFS_MAX_NAME_LENGTH: u32 = 1024;
+
+/// Largest length the kernel accepts in a single reply to `copy_file_range`
+const MAX_COPY_FILE_RANGE_LEN: u64 = u32::MAX as u64 & !0xfff;
+
 #[derive(Debug)]
 struct DirHandle {
     #[allow(unused)]
@@ -105,7 +142,7 @@ where
 
 impl<Client, Prefetcher> FileHandleState<Client, Prefetcher>
 where
-    Client: ObjectClient + Send + Sync,
+    Client: ObjectClient + Send + Sync + 'static,
     Prefetcher: Prefetch,
 {
     async fn new_write_handle(
@@ -116,23 +153,72 @@ where
         fs: &S3Filesystem<Client, Prefetcher>,
     ) -> Result<FileHandleState<Client, Prefetcher>, Error> {
         let is_truncate = flags & libc::O_TRUNC != 0;
-        let handle = fs
+        let mounted = fs.mounted_bucket(ino)?;
+        let handle = mounted
             .superblock
             .write(&fs.client, ino, fs.config.allow_overwrite, is_truncate)
             .await?;
         let key = lookup.inode.full_key();
-        let handle = match fs.uploader.put(&fs.bucket, key).await {
-            Err(e) => {
-                return Err(err!(libc::EIO, source:e, "put failed to start"));
-            }
-            Ok(request) => FileHandleState::Write(UploadState::InProgress {
-                request,
-                handle,
-                open_pid: pid,
-            }),
+        let user_metadata = fs.upload_metadata(ino).await?;
+        let state = match &fs.config.write_staging_dir {
+            Some(staging_dir) => match fs
+                .uploader
+                .stage(staging_dir, &mounted.bucket, key, user_metadata, None)
+            {
+                Err(e) => {
+                    return Err(err!(libc::EIO, source:e, "failed to create staging file"));
+                }
+                Ok(request) => UploadState::Staged {
+                    request,
+                    handle,
+                    open_pid: pid,
+                },
+            },
+            None => match fs.uploader.put(&mounted.bucket, key, user_metadata).await {
+                Err(e) => {
+                    return Err(err!(libc::EIO, source:e, "put failed to start"));
+                }
+                Ok(request) => UploadState::InProgress {
+                    request,
+                    handle,
+                    open_pid: pid,
+                },
+            },
         };
         metrics::gauge!("fs.current_handles", "type" => "write").increment(1.0);
-        Ok(handle)
+        Ok(FileHandleState::Write(state))
+    }
+
+    /// Create a write handle that modifies the existing object of a remote file, starting from its
+    /// current content
+    async fn new_modify_handle(
+        lookup: &LookedUp,
+        pid: u32,
+        staging_dir: &Path,
+        fs: &S3Filesystem<Client, Prefetcher>,
+    ) -> Result<FileHandleState<Client, Prefetcher>, Error> {
+        let (request, handle) = fs.start_modify(lookup, staging_dir).await?;
+        metrics::gauge!("fs.current_handles", "type" => "write").increment(1.0);
+        Ok(FileHandleState::Write(UploadState::Staged {
+            request,
+            handle,
+            open_pid: pid,
+        }))
+    }
+
+    /// Create a write handle that appends to the existing object of a remote file
+    async fn new_append_handle(
+        lookup: &LookedUp,
+        pid: u32,
+        fs: &S3Filesystem<Client, Prefetcher>,
+    ) -> Result<FileHandleState<Client, Prefetcher>, Error> {
+        let (request, handle) = fs.start_append(lookup).await?;
+        metrics::gauge!("fs.current_handles", "type" => "write").increment(1.0);
+        Ok(FileHandleState::Write(UploadState::Append {
+            request,
+            handle,
+            open_pid: pid,
+        }))
     }
 
     async fn new_read_handle(
@@ -145,16 +231,21 @@ where
                 "objects in flexible retrieval storage classes are not accessible",
             ));
         }
-        let handle = fs.superblock.read(&fs.client, lookup.inode.ino()).await?;
-        let full_key = lookup.inode.full_key().to_owned();
+        let mounted = fs.mounted_bucket(lookup.inode.ino())?;
+        let handle = mounted.superblock.read(&fs.client, lookup.inode.ino()).await?;
         let object_size = lookup.stat.size as u64;
         let etag = match &lookup.stat.etag {
             None => return Err(err!(libc::EBADF, "no E-Tag for inode {}", lookup.inode.ino())),
             Some(etag) => ETag::from_str(etag).expect("E-Tag should be set"),
         };
+        let object_id = ObjectId::new_with_version(
+            handle.object_key().to_owned(),
+            etag,
+            handle.version_id().map(str::to_owned),
+        );
         let request = fs
             .prefetcher
-            .prefetch(fs.client.clone(), &fs.bucket, &full_key, object_size, etag.clone());
+            .prefetch(fs.client.clone(), &mounted.bucket, object_id, object_size);
         let handle = FileHandleState::Read { handle, request };
         metrics::gauge!("fs.current_handles", "type" => "read").increment(1.0);
         Ok(handle)
@@ -169,7 +260,28 @@ enum UploadState<Client: ObjectClient> {
         /// Process that created the upload
         open_pid: u32,
     },
+    /// Writes go to a local staging file, which is uploaded when the file is synced and when the
+    /// upload completes
+    Staged {
+        request: StagedUploadRequest<Client>,
+        handle: WriteHandle,
+        /// Process that created the upload
+        open_pid: u32,
+    },
+    /// Writes are appended to the existing object of the file
+    Append {
+        request: AppendUploadRequest<Client>,
+        handle: WriteHandle,
+        /// Process that created the upload
+        open_pid: u32,
+    },
     Completed,
+    /// The file was copied from another object on the S3 side by `copy_file_range`
+    Copied {
+        size: u64,
+        /// Bytes of the copy reported to the kernel so far
+        reported: u64,
+    },
     // Remember the failure reason to respond to retries
     Failed(libc::c_int),
 }
@@ -178,7 +290,33 @@ impl<Client: ObjectClient> UploadState<Client> {
     async fn write(&mut self, offset: i64, data: &[u8], key: &str) -> Result<u32, Error> {
         let (upload, handle) = match self {
             Self::InProgress { request, handle, .. } => (request, handle),
-            Self::Completed => return Err(err!(libc::EIO, "upload already completed for key {:?}", key)),
+            Self::Staged { request, handle, .. } => {
+                // Unlike a streaming upload, a staged upload is still usable after a failed write
+                let len = request.write(offset, data)?;
+                handle.set_file_size(request.size() as usize);
+                handle.touch();
+                return Ok(len as u32);
+            }
+            Self::Append { request, handle, .. } => {
+                return match request.write(offset, data).await {
+                    Ok(len) => {
+                        handle.inc_file_size(len);
+                        Ok(len as u32)
+                    }
+                    Err(e) => {
+                        // Give up on the append, leaving the existing object as it was
+                        let errno = e.to_errno();
+                        if let Self::Append { request, handle, .. } = std::mem::replace(self, Self::Failed(errno)) {
+                            request.abort().await;
+                            Self::finish_handle(handle, key);
+                        }
+                        Err(e.into())
+                    }
+                };
+            }
+            Self::Completed | Self::Copied { .. } => {
+                return Err(err!(libc::EIO, "upload already completed for key {:?}", key))
+            }
             Self::Failed(e) => return Err(err!(*e, "upload already aborted for key {:?}", key)),
         };
 
@@ -196,7 +334,13 @@ impl<Client: ObjectClient> UploadState<Client> {
                             error!(?err, ?key, "error updating the inode status");
                         }
                     }
-                    Self::Failed(_) | Self::Completed => unreachable!("checked above"),
+                    Self::Staged { .. }
+                    | Self::Append { .. }
+                    | Self::Failed(_)
+                    | Self::Completed
+                    | Self::Copied { .. } => {
+                        unreachable!("checked above")
+                    }
                 };
                 Err(e.into())
             }
@@ -206,7 +350,9 @@ impl<Client: ObjectClient> UploadState<Client> {
     async fn complete(&mut self, key: &str, ignore_if_empty: bool, pid: Option<u32>) -> Result<(), Error> {
         let (request_size, open_pid) = match self {
             Self::InProgress { request, open_pid, .. } => (request.size(), *open_pid),
-            Self::Completed => return Ok(()),
+            Self::Staged { request, open_pid, .. } => (request.size(), *open_pid),
+            Self::Append { request, open_pid, .. } => (request.size(), *open_pid),
+            Self::Completed | Self::Copied { .. } => return Ok(()),
             Self::Failed(e) => return Err(err!(*e, "upload already aborted for key {:?}", key)),
         };
 
@@ -226,12 +372,31 @@ impl<Client: ObjectClient> UploadState<Client> {
             }
         }
 
-        let (upload, handle) = match std::mem::replace(self, Self::Completed) {
-            Self::InProgress { request, handle, .. } => (request, handle),
-            Self::Failed(_) | Self::Completed => unreachable!("checked above"),
-        };
+        if let Self::Staged { request, .. } = self {
+            // Staged uploads stay writable, so upload a snapshot of the file and keep the handle
+            let size = request.size();
+            return match request.sync().await {
+                Ok(()) => {
+                    debug!(key, size, "staged sync succeeded");
+                    Ok(())
+                }
+                Err(e) => {
+                    let errno = e.to_errno();
+                    if let Self::Staged { handle, .. } = std::mem::replace(self, Self::Failed(errno)) {
+                        Self::finish_handle(handle, key);
+                    }
+                    Err(e.into())
+                }
+            };
+        }
 
-        let result = Self::complete_upload(upload, key, handle).await;
+        let result = match std::mem::replace(self, Self::Completed) {
+            Self::InProgress { request, handle, .. } => Self::complete_upload(request, key, handle).await,
+            Self::Append { request, handle, .. } => Self::complete_append_upload(request, key, handle).await,
+            Self::Staged { .. } | Self::Failed(_) | Self::Completed | Self::Copied { .. } => {
+                unreachable!("checked above")
+            }
+        };
         if let Err(e) = &result {
             *self = Self::Failed(e.to_errno());
         }
@@ -241,7 +406,9 @@ impl<Client: ObjectClient> UploadState<Client> {
     async fn complete_if_in_progress(self, key: &str) -> Result<(), Error> {
         match self {
             Self::InProgress { request, handle, .. } => Self::complete_upload(request, key, handle).await,
-            Self::Failed(_) | Self::Completed => Ok(()),
+            Self::Staged { request, handle, .. } => Self::complete_staged_upload(request, key, handle).await,
+            Self::Append { request, handle, .. } => Self::complete_append_upload(request, key, handle).await,
+            Self::Failed(_) | Self::Completed | Self::Copied { .. } => Ok(()),
         }
     }
 
@@ -254,11 +421,49 @@ impl<Client: ObjectClient> UploadState<Client> {
             }
             Err(e) => Err(err!(libc::EIO, source:e, "put failed")),
         };
+        Self::finish_handle(handle, key);
+        put_result
+    }
+
+    async fn complete_staged_upload(
+        upload: StagedUploadRequest<Client>,
+        key: &str,
+        handle: WriteHandle,
+    ) -> Result<(), Error> {
+        let size = upload.size();
+        let put_result = match upload.complete().await {
+            Ok(_) => {
+                debug!(key, size, "staged put succeeded");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        };
+        Self::finish_handle(handle, key);
+        put_result
+    }
+
+    async fn complete_append_upload(
+        upload: AppendUploadRequest<Client>,
+        key: &str,
+        handle: WriteHandle,
+    ) -> Result<(), Error> {
+        let size = upload.size();
+        let put_result = match upload.complete().await {
+            Ok(_) => {
+                debug!(key, size, "append succeeded");
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        };
+        Self::finish_handle(handle, key);
+        put_result
+    }
+
+    fn finish_handle(handle: WriteHandle, key: &str) {
         if let Err(err) = handle.finish() {
-            // Log the issue but still return put_result.
+            // Log the issue but still return the result of the upload.
             error!(?err, ?key, "error updating the inode status");
         }
-        put_result
     }
 }
 
@@ -299,6 +504,24 @@ fn are_from_same_process(pid1: u32, pid2: u32) -> bool {
     tgid1 == tgid2
 }
 
+/// Lock conflicts and cancelled waits are expected, so don't log them as warnings
+fn lock_error(e: LockError) -> Error {
+    match e {
+        LockError::Conflict(_) => err!(libc::EAGAIN, source: e, Level::DEBUG, "file is locked"),
+        LockError::Cancelled => err!(libc::EINTR, source: e, Level::DEBUG, "lock request was cancelled"),
+    }
+}
+
+/// The type of a lock from its `F_*LCK` value, or [None] for an unlock
+fn lock_type(typ: i32) -> Result<Option<LockType>, Error> {
+    match typ {
+        libc::F_RDLCK => Ok(Some(LockType::Read)),
+        libc::F_WRLCK => Ok(Some(LockType::Write)),
+        libc::F_UNLCK => Ok(None),
+        _ => Err(err!(libc::EINVAL, "invalid lock type {}", typ)),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CacheConfig {
     /// Should the file system serve lookup requests including open from cached entries,
@@ -386,6 +609,37 @@ pub struct S3FilesystemConfig {
     pub allow_delete: bool,
     /// Allow overwrite
     pub allow_overwrite: bool,
+    /// Allow renaming directories by copying every object under their prefix
+    pub allow_directory_rename: bool,
+    /// Allow creating symlinks, and show objects created as symlinks as such
+    pub allow_symlinks: bool,
+    /// Store the mode, owner and modification time of files in their objects' metadata
+    pub posix_metadata: bool,
+    /// Persist new directories with marker objects, and delete the markers of empty directories on rmdir
+    pub directory_markers: bool,
+    /// Handle POSIX file locks in Mountpoint rather than in the kernel
+    pub file_locks: bool,
+    /// Show the noncurrent versions of each object, read-only, in a `<name>.versions` directory
+    pub show_versions: bool,
+    /// Show the bucket, read-only, as it was at this time, by reading the version of each object that
+    /// was current then
+    pub as_of: Option<OffsetDateTime>,
+    /// Hide the files and directories whose paths relative to the mount don't pass this filter
+    pub key_filter: KeyFilter,
+    /// Show keys that contain components that are not valid file names, like `.` or an empty
+    /// component, by escaping those components
+    pub escape_names: bool,
+    /// Look up names ignoring case when there's no exact match, and refuse to create names that
+    /// only differ in case from existing ones
+    pub case_insensitive: bool,
+    /// Unicode normalization form to compare names in, so that lookups find keys in other forms
+    pub normalize_unicode: UnicodeNormalization,
+    /// Stage new files in this local directory, allowing writes at any offset, and upload them when closed
+    pub write_staging_dir: Option<PathBuf>,
+    /// Total size in bytes that `statfs` reports for the file system
+    pub statfs_capacity: u64,
+    /// Free space in bytes that `statfs` reports for the file system
+    pub statfs_free_space: u64,
     /// Storage class to be used for new object uploads
     pub storage_class: Option<String>,
     /// S3 personality (for different S3 semantics)
@@ -410,6 +664,20 @@ impl Default for S3FilesystemConfig {
             file_mode: 0o644,
             allow_delete: false,
             allow_overwrite: false,
+            allow_directory_rename: false,
+            allow_symlinks: false,
+            posix_metadata: false,
+            directory_markers: false,
+            file_locks: false,
+            show_versions: false,
+            as_of: None,
+            key_filter: Default::default(),
+            escape_names: false,
+            case_insensitive: false,
+            normalize_unicode: UnicodeNormalization::None,
+            write_staging_dir: None,
+            statfs_capacity: DEFAULT_STATFS_CAPACITY,
+            statfs_free_space: DEFAULT_STATFS_CAPACITY,
             storage_class: None,
             s3_personality: S3Personality::default(),
             server_side_encryption: Default::default(),
@@ -527,15 +795,17 @@ where
 {
     config: S3FilesystemConfig,
     client: Arc<Client>,
-    superblock: Superblock,
+    /// The mounted buckets. The root directory of a filesystem that mounts a single bucket is the
+    /// root of that bucket, and otherwise lists the buckets as directories.
+    buckets: Vec<MountedBucket>,
+    multi_bucket: bool,
+    mount_time: OffsetDateTime,
     prefetcher: Prefetcher,
     uploader: Uploader<Client>,
-    bucket: String,
-    #[allow(unused)]
-    prefix: Prefix,
     next_handle: AtomicU64,
     dir_handles: AsyncRwLock<HashMap<u64, Arc<DirHandle>>>,
     file_handles: AsyncRwLock<HashMap<u64, Arc<FileHandle<Client, Prefetcher>>>>,
+    locks: LockManager,
 }
 
 impl<Client, Prefetcher> S3Filesystem<Client, Prefetcher>
@@ -552,12 +822,47 @@ where
     ) -> Self {
         trace!(?bucket, ?prefix, ?config, "new filesystem");
 
-        let superblock_config = SuperblockConfig {
-            cache_config: config.cache_config.clone(),
-            s3_personality: config.s3_personality,
+        let superblock = Superblock::new(bucket, prefix, Self::superblock_config(&config, 0));
+        let mounted = MountedBucket {
+            name: String::new(),
+            bucket: bucket.to_owned(),
+            superblock,
         };
-        let superblock = Superblock::new(bucket, prefix, superblock_config);
+        Self::new_with_buckets(client, prefetcher, vec![mounted], false, config)
+    }
 
+    /// Create a filesystem whose root directory contains a directory for each of the given buckets.
+    /// The buckets share the client, prefetcher, and data cache of the filesystem.
+    pub fn new_multi_bucket(
+        client: Client,
+        prefetcher: Prefetcher,
+        buckets: Vec<BucketMount>,
+        config: S3FilesystemConfig,
+    ) -> Self {
+        trace!(?buckets, ?config, "new multi-bucket filesystem");
+
+        let buckets = buckets
+            .into_iter()
+            .enumerate()
+            .map(|(index, mount)| {
+                let superblock_config = Self::superblock_config(&config, multi_bucket::ino_offset(index));
+                MountedBucket {
+                    superblock: Superblock::new(&mount.bucket, &mount.prefix, superblock_config),
+                    name: mount.name,
+                    bucket: mount.bucket,
+                }
+            })
+            .collect();
+        Self::new_with_buckets(client, prefetcher, buckets, true, config)
+    }
+
+    fn new_with_buckets(
+        client: Client,
+        prefetcher: Prefetcher,
+        buckets: Vec<MountedBucket>,
+        multi_bucket: bool,
+        config: S3FilesystemConfig,
+    ) -> Self {
         let client = Arc::new(client);
 
         let uploader = Uploader::new(
@@ -570,20 +875,66 @@ where
         Self {
             config,
             client,
-            superblock,
+            buckets,
+            multi_bucket,
+            mount_time: OffsetDateTime::now_utc(),
             prefetcher,
             uploader,
-            bucket: bucket.to_string(),
-            prefix: prefix.clone(),
             next_handle: AtomicU64::new(1),
             dir_handles: AsyncRwLock::new(HashMap::new()),
             file_handles: AsyncRwLock::new(HashMap::new()),
+            locks: Default::default(),
+        }
+    }
+
+    fn superblock_config(config: &S3FilesystemConfig, ino_offset: InodeNo) -> SuperblockConfig {
+        SuperblockConfig {
+            cache_config: config.cache_config.clone(),
+            s3_personality: config.s3_personality,
+            allow_directory_rename: config.allow_directory_rename,
+            allow_symlinks: config.allow_symlinks,
+            posix_metadata: config.posix_metadata,
+            directory_markers: config.directory_markers,
+            show_versions: config.show_versions,
+            as_of: config.as_of,
+            key_filter: config.key_filter.clone(),
+            escape_names: config.escape_names,
+            case_insensitive: config.case_insensitive,
+            normalize_unicode: config.normalize_unicode,
+            ino_offset,
         }
     }
 
     fn next_handle(&self) -> u64 {
         self.next_handle.fetch_add(1, Ordering::SeqCst)
     }
+
+    /// Whether the inode is the root directory of a multi-bucket filesystem, which belongs to no bucket
+    fn is_multi_bucket_root(&self, ino: InodeNo) -> bool {
+        self.multi_bucket && ino == FUSE_ROOT_INODE
+    }
+
+    /// The mounted bucket that an inode belongs to
+    fn mounted_bucket(&self, ino: InodeNo) -> Result<&MountedBucket, Error> {
+        if !self.multi_bucket {
+            return Ok(&self.buckets[0]);
+        }
+        match multi_bucket::bucket_index(ino) {
+            None => Err(err!(
+                libc::EPERM,
+                "the root directory of a multi-bucket mount cannot be modified"
+            )),
+            Some(index) => self
+                .buckets
+                .get(index)
+                .ok_or_else(|| err!(libc::ENOENT, "no bucket for inode {}", ino)),
+        }
+    }
+
+    /// The superblock of the bucket that an inode belongs to
+    fn superblock(&self, ino: InodeNo) -> Result<&Superblock, Error> {
+        Ok(&self.mounted_bucket(ino)?.superblock)
+    }
 }
 
 /// Reply to a `lookup` call
@@ -601,6 +952,36 @@ pub struct Attr {
     pub attr: FileAttr,
 }
 
+/// Reply to a `statfs` call
+#[derive(Debug)]
+pub struct StatFs {
+    /// Total number of blocks of `fragment_size` bytes
+    pub total_blocks: u64,
+    /// Number of free blocks
+    pub free_blocks: u64,
+    /// Number of free blocks available to unprivileged users
+    pub available_blocks: u64,
+    /// Total number of inodes
+    pub total_inodes: u64,
+    /// Number of free inodes
+    pub free_inodes: u64,
+    /// Preferred size of reads and writes
+    pub block_size: u32,
+    /// Maximum length of a file name
+    pub max_name_length: u32,
+    /// Size of the blocks that the block counts are in
+    pub fragment_size: u32,
+}
+
+/// Reply to a `getlk` call
+#[derive(Debug)]
+pub struct Lock {
+    pub start: u64,
+    pub end: u64,
+    pub typ: i32,
+    pub pid: u32,
+}
+
 /// Reply to a `open` or `opendir` call
 #[derive(Debug)]
 pub struct Opened {
@@ -623,7 +1004,8 @@ pub struct DirectoryEntry {
     pub attr: FileAttr,
     pub generation: u64,
     pub ttl: Duration,
-    lookup: LookedUp,
+    /// The inode of the entry, if it belongs to a bucket
+    lookup: Option<LookedUp>,
 }
 
 impl<Client, Prefetcher> S3Filesystem<Client, Prefetcher>
@@ -633,6 +1015,11 @@ where
 {
     pub async fn init(&self, config: &mut KernelConfig) -> Result<(), libc::c_int> {
         let _ = config.add_capabilities(fuser::consts::FUSE_DO_READDIRPLUS);
+        if self.config.file_locks {
+            // Handle POSIX locks ourselves. BSD locks are still left to the kernel, which keeps them
+            // independent of POSIX locks, as FUSE doesn't tell us which kind a lock request is for.
+            let _ = config.add_capabilities(fuser::consts::FUSE_POSIX_LOCKS);
+        }
         if self.config.allow_overwrite {
             // Overwrites require FUSE_ATOMIC_O_TRUNC capability on the host, so we will panic if the
             // host doesn't support it.
@@ -650,9 +1037,6 @@ where
         /// From man stat(2): `st_blocks`: "This field indicates the number of blocks allocated to
         /// the file, in 512-byte units."
         const STAT_BLOCK_SIZE: u64 = 512;
-        /// From man stat(2): `st_blksize`: "This field gives the "preferred" block size for
-        /// efficient filesystem I/O."
-        const PREFERRED_IO_BLOCK_SIZE: u32 = 4096;
 
         // We don't implement hard links, and don't want to have to list a directory to count its
         // hard links, so we just assume one link for files (itself) and two links for directories
@@ -660,12 +1044,14 @@ where
         let (perm, nlink) = match lookup.inode.kind() {
             InodeKind::File => {
                 if lookup.stat.is_readable {
-                    (self.config.file_mode, 1)
+                    (lookup.stat.mode.unwrap_or(self.config.file_mode), 1)
                 } else {
                     (0o000, 1)
                 }
             }
-            InodeKind::Directory => (self.config.dir_mode, 2),
+            InodeKind::Directory => (lookup.stat.mode.unwrap_or(self.config.dir_mode), 2),
+            // Permissions of symlinks are never checked, so report them the way Linux does
+            InodeKind::Symlink => (0o777, 1),
         };
 
         FileAttr {
@@ -679,8 +1065,8 @@ where
             kind: lookup.inode.kind().into(),
             perm,
             nlink,
-            uid: self.config.uid,
-            gid: self.config.gid,
+            uid: lookup.stat.uid.unwrap_or(self.config.uid),
+            gid: lookup.stat.gid.unwrap_or(self.config.gid),
             rdev: 0,
             flags: 0,
             blksize: PREFERRED_IO_BLOCK_SIZE,
@@ -690,8 +1076,11 @@ where
     pub async fn lookup(&self, parent: InodeNo, name: &OsStr) -> Result<Entry, Error> {
         trace!("fs:lookup with parent {:?} name {:?}", parent, name);
 
+        if self.is_multi_bucket_root(parent) {
+            return self.lookup_bucket(name).await;
+        }
         let lookup = self
-            .superblock
+            .superblock(parent)?
             .lookup(&self.client, parent, name)
             .await
             .map_err(|err| match err {
@@ -712,7 +1101,13 @@ where
     pub async fn getattr(&self, ino: InodeNo) -> Result<Attr, Error> {
         trace!("fs:getattr with ino {:?}", ino);
 
-        let lookup = self.superblock.getattr(&self.client, ino, false).await?;
+        if self.is_multi_bucket_root(ino) {
+            return Ok(Attr {
+                ttl: multi_bucket::ROOT_TTL,
+                attr: self.multi_bucket_root_attr(),
+            });
+        }
+        let lookup = self.superblock(ino)?.getattr(&self.client, ino, false).await?;
         let attr = self.make_attr(&lookup);
 
         Ok(Attr {
@@ -721,23 +1116,79 @@ where
         })
     }
 
+    pub async fn statfs(&self, ino: InodeNo) -> Result<StatFs, Error> {
+        trace!("fs:statfs with ino {:?}", ino);
+
+        // Report the part size as the block size, since that's the unit reads and uploads are split into
+        let block_size = self
+            .client
+            .part_size()
+            .map(|size| size.min(u32::MAX as usize) as u32)
+            .unwrap_or(DEFAULT_STATFS_BLOCK_SIZE);
+        let total_blocks = self.config.statfs_capacity / block_size as u64;
+        let free_blocks = self.config.statfs_free_space.min(self.config.statfs_capacity) / block_size as u64;
+        let inodes = self
+            .buckets
+            .iter()
+            .map(|mounted| mounted.superblock.inode_count() as u64)
+            .sum::<u64>();
+
+        Ok(StatFs {
+            total_blocks,
+            free_blocks,
+            available_blocks: free_blocks,
+            total_inodes: STATFS_INODES,
+            free_inodes: STATFS_INODES.saturating_sub(inodes),
+            block_size,
+            max_name_length: STATFS_MAX_NAME_LENGTH,
+            fragment_size: block_size,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)] // We don't get to choose this interface
     pub async fn setattr(
         &self,
         ino: InodeNo,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
         atime: Option<OffsetDateTime>,
         mtime: Option<OffsetDateTime>,
         size: Option<u64>,
         _flags: Option<u32>,
     ) -> Result<Attr, Error> {
         tracing::info!(
-            "fs:setattr with ino {:?} flags {:?} atime {:?} mtime {:?} size {:?}",
+            "fs:setattr with ino {:?} flags {:?} mode {:?} uid {:?} gid {:?} atime {:?} mtime {:?} size {:?}",
             ino,
             _flags,
+            mode,
+            uid,
+            gid,
             atime,
             mtime,
             size
         );
-        let setattr_result = self.superblock.setattr(&self.client, ino, atime, mtime).await;
+        if self.config.posix_metadata && (mode.is_some() || uid.is_some() || gid.is_some()) {
+            // The new values are uploaded with the object, so restart any upload that has begun
+            let handles = self.file_handles_for(ino).await;
+            let mut states = Vec::with_capacity(handles.len());
+            for handle in &handles {
+                states.push(handle.state.lock().await);
+            }
+            let perm = mode.map(|mode| (mode & 0o7777) as u16);
+            self.superblock(ino)?.set_mode_and_owner(ino, perm, uid, gid)?;
+            self.restart_uploads(ino, &handles, &mut states).await?;
+        }
+        if let Some(size) = size {
+            if let Some(lookup) = self.resize(ino, size, atime, mtime).await? {
+                let attr = self.make_attr(&lookup);
+                return Ok(Attr {
+                    ttl: lookup.validity(),
+                    attr,
+                });
+            }
+        }
+        let setattr_result = self.superblock(ino)?.setattr(&self.client, ino, atime, mtime).await;
         let lookup = match (setattr_result, size) {
             (Ok(lookup), _) => lookup,
             (Err(InodeError::SetAttrNotPermittedOnRemoteInode(_)), Some(0)) if !self.config.allow_overwrite => {
@@ -759,7 +1210,15 @@ where
 
     pub async fn forget(&self, ino: InodeNo, n: u64) {
         trace!("fs:forget with ino {:?} n {:?}", ino, n);
-        self.superblock.forget(ino, n);
+        let Ok(superblock) = self.superblock(ino) else {
+            return;
+        };
+        // The kernel looks up the roots of the buckets of a multi-bucket filesystem in its root
+        // directory, not in their superblocks, and they live as long as the filesystem
+        if self.multi_bucket && ino == superblock.root_ino() {
+            return;
+        }
+        superblock.forget(ino, n);
     }
 
     pub async fn open(&self, ino: InodeNo, flags: i32, pid: u32) -> Result<Opened, Error> {
@@ -771,10 +1230,14 @@ where
         let direct_io = flags & libc::O_DIRECT != 0;
 
         let force_revalidate = !self.config.cache_config.serve_lookup_from_cache || direct_io;
-        let lookup = self.superblock.getattr(&self.client, ino, force_revalidate).await?;
+        let lookup = self
+            .superblock(ino)?
+            .getattr(&self.client, ino, force_revalidate)
+            .await?;
 
         match lookup.inode.kind() {
             InodeKind::Directory => return Err(InodeError::IsDirectory(lookup.inode.err()).into()),
+            InodeKind::Symlink => return Err(err!(libc::ELOOP, "cannot open a symlink")),
             InodeKind::File => (),
         }
 
@@ -782,20 +1245,38 @@ where
         let full_key = lookup.inode.full_key().to_owned();
         let remote_file = lookup.inode.is_remote()?;
 
-        // Open with O_APPEND is ok for new files because it's same as creating a new one.
-        // but we can't support it on existing files and we should explicitly say we don't allow that.
-        if remote_file && (flags & libc::O_APPEND != 0) {
-            return Err(err!(libc::EINVAL, "O_APPEND is not supported on existing files"));
-        }
-
         // We can't support O_SYNC writes because they require the data to go to stable storage
         // at `write` time, but we only commit a PUT at `close` time.
         if flags & (libc::O_SYNC | libc::O_DSYNC) != 0 {
             return Err(err!(libc::EINVAL, "O_SYNC and O_DSYNC are not supported"));
         }
 
-        let state = if flags & libc::O_RDWR != 0 {
-            let is_truncate = flags & libc::O_TRUNC != 0;
+        // Without O_TRUNC, writes to an existing object modify it in place through a staging file
+        let is_truncate = flags & libc::O_TRUNC != 0;
+        let is_write = flags & (libc::O_RDWR | libc::O_WRONLY) != 0;
+        let modify_staging_dir = self
+            .config
+            .write_staging_dir
+            .as_deref()
+            .filter(|_| remote_file && is_write && !is_truncate && self.config.allow_overwrite);
+
+        // Open with O_APPEND is ok for new files because it's same as creating a new one. Existing
+        // files can only be appended to if overwriting them is allowed.
+        let is_append = remote_file && (flags & libc::O_APPEND != 0);
+        if is_append && !(is_write && self.config.allow_overwrite) {
+            return Err(err!(
+                libc::EINVAL,
+                "O_APPEND is only supported on existing files with --allow-overwrite"
+            ));
+        }
+
+        let state = if let Some(staging_dir) = modify_staging_dir {
+            debug!("fs:open choosing modify handle");
+            FileHandleState::new_modify_handle(&lookup, pid, staging_dir, self).await?
+        } else if is_append && !is_truncate {
+            debug!("fs:open choosing append handle");
+            FileHandleState::new_append_handle(&lookup, pid, self).await?
+        } else if flags & libc::O_RDWR != 0 {
             if !remote_file || (self.config.allow_overwrite && is_truncate) {
                 // If the file is new or opened in truncate mode, we know it must be a write handle.
                 debug!("fs:open choosing write handle for O_RDWR");
@@ -854,6 +1335,9 @@ where
         let mut state = handle.state.lock().await;
         let request = match &mut *state {
             FileHandleState::Read { request, .. } => request,
+            FileHandleState::Write(UploadState::Staged { request, .. }) => {
+                return request.read(offset as u64, size as usize).await.map_err(Error::from);
+            }
             FileHandleState::Write(_) => return Err(err!(libc::EBADF, "file handle is not open for reads")),
         };
 
@@ -878,7 +1362,7 @@ where
         parent: InodeNo,
         name: &OsStr,
         mode: libc::mode_t,
-        _umask: u32,
+        umask: u32,
         _rdev: u32,
     ) -> Result<Entry, Error> {
         if mode & libc::S_IFMT != libc::S_IFREG {
@@ -889,10 +1373,12 @@ where
             ));
         }
 
-        let lookup = self
-            .superblock
-            .create(&self.client, parent, name, InodeKind::File)
-            .await?;
+        let superblock = self.superblock(parent)?;
+        let mut lookup = superblock.create(&self.client, parent, name, InodeKind::File).await?;
+        if self.config.posix_metadata {
+            let perm = (u32::from(mode) & !umask & 0o7777) as u16;
+            lookup = superblock.set_mode_and_owner(lookup.inode.ino(), Some(perm), None, None)?;
+        }
         let attr = self.make_attr(&lookup);
         Ok(Entry {
             ttl: lookup.validity(),
@@ -903,9 +1389,52 @@ where
 
     pub async fn mkdir(&self, parent: InodeNo, name: &OsStr, _mode: libc::mode_t, _umask: u32) -> Result<Entry, Error> {
         let lookup = self
-            .superblock
+            .superblock(parent)?
             .create(&self.client, parent, name, InodeKind::Directory)
             .await?;
+        if self.config.directory_markers {
+            self.put_directory_marker(&lookup).await?;
+        }
+        let attr = self.make_attr(&lookup);
+        Ok(Entry {
+            ttl: lookup.validity(),
+            attr,
+            generation: 0,
+        })
+    }
+
+    pub async fn symlink(&self, parent: InodeNo, name: &OsStr, target: &OsStr) -> Result<Entry, Error> {
+        trace!("fs:symlink with parent {:?} name {:?}", parent, name);
+
+        if !self.config.allow_symlinks {
+            return Err(err!(
+                libc::EPERM,
+                "Symlinks are disabled. Use '--allow-symlinks' mount option to enable them."
+            ));
+        }
+        let mounted = self.mounted_bucket(parent)?;
+        let lookup = mounted
+            .superblock
+            .symlink(&self.client, parent, name, target.as_bytes())
+            .await?;
+        let ino = lookup.inode.ino();
+        let handle = mounted.superblock.write(&self.client, ino, false, false).await?;
+        let key = lookup.inode.full_key();
+        // The object's content is the target, which is also the size of the symlink already
+        let mut request = match self.uploader.put(&mounted.bucket, key, symlink_metadata()).await {
+            Ok(request) => request,
+            Err(e) => {
+                // The next lookup of the name will find no object and forget the inode
+                let _ = handle.finish();
+                return Err(err!(libc::EIO, source:e, "put failed to start"));
+            }
+        };
+        if let Err(e) = request.write(0, target.as_bytes()).await {
+            let _ = handle.finish();
+            return Err(e.into());
+        }
+        UploadState::complete_upload(request, key, handle).await?;
+
         let attr = self.make_attr(&lookup);
         Ok(Entry {
             ttl: lookup.validity(),
@@ -914,6 +1443,12 @@ where
         })
     }
 
+    pub async fn readlink(&self, ino: InodeNo) -> Result<Vec<u8>, Error> {
+        trace!("fs:readlink with ino {:?}", ino);
+
+        Ok(self.superblock(ino)?.readlink(&self.client, ino).await?)
+    }
+
     #[allow(clippy::too_many_arguments)] // We don't get to choose this interface
     pub async fn write(
         &self,
@@ -955,14 +1490,142 @@ where
         Ok(len)
     }
 
+    /// Copy a whole remote file into a new file that has not been written to yet. The object is
+    /// copied on the S3 side, so its content is not transferred through Mountpoint. Other copies fail
+    /// with `EOPNOTSUPP`, so that callers fall back to reading and writing the data.
+    #[allow(clippy::too_many_arguments)] // We don't get to choose this interface
+    pub async fn copy_file_range(
+        &self,
+        ino_in: InodeNo,
+        fh_in: u64,
+        offset_in: i64,
+        ino_out: InodeNo,
+        fh_out: u64,
+        offset_out: i64,
+        len: u64,
+        _flags: u32,
+    ) -> Result<u32, Error> {
+        trace!(
+            "fs:copy_file_range with ino_in {:?} fh_in {:?} offset_in {:?} ino_out {:?} fh_out {:?} offset_out {:?} len {:?}",
+            ino_in,
+            fh_in,
+            offset_in,
+            ino_out,
+            fh_out,
+            offset_out,
+            len
+        );
+
+        let handle = {
+            let file_handles = self.file_handles.read().await;
+            match (file_handles.contains_key(&fh_in), file_handles.get(&fh_out)) {
+                (true, Some(handle)) => handle.clone(),
+                _ => return Err(err!(libc::EBADF, "invalid file handle")),
+            }
+        };
+        logging::record_name(handle.inode.name());
+
+        let mut state = handle.state.lock().await;
+        let upload = match &mut *state {
+            FileHandleState::Read { .. } => return Err(err!(libc::EBADF, "file handle is not open for writes")),
+            FileHandleState::Write(upload) => upload,
+        };
+        let is_new = match upload {
+            // The kernel splits copies longer than it can report at once into consecutive calls
+            UploadState::Copied { size, reported } => {
+                if offset_in as u64 != *reported || offset_out as u64 != *reported {
+                    return Err(err!(libc::EOPNOTSUPP, "file was already copied"));
+                }
+                let len = len.min(*size - *reported).min(MAX_COPY_FILE_RANGE_LEN);
+                *reported += len;
+                return Ok(len as u32);
+            }
+            UploadState::InProgress { request, .. } => request.size() == 0,
+            UploadState::Staged { request, .. } => request.size() == 0,
+            _ => false,
+        };
+        if !is_new || offset_in != 0 || offset_out != 0 {
+            return Err(err!(
+                libc::EOPNOTSUPP,
+                "only copies of whole files into new files are supported"
+            ));
+        }
+
+        let source = self.mounted_bucket(ino_in)?;
+        let target = self.mounted_bucket(ino_out)?;
+        let lookup = source.superblock.getattr(&self.client, ino_in, false).await?;
+        if lookup.inode.kind() != InodeKind::File
+            || !lookup.inode.is_remote()?
+            || source.superblock.is_object_version(ino_in)?
+        {
+            return Err(err!(libc::EOPNOTSUPP, "only files in S3 can be copied"));
+        }
+        if !lookup.stat.is_readable {
+            return Err(err!(
+                libc::EACCES,
+                "objects in flexible retrieval storage classes are not accessible",
+            ));
+        }
+        let size = lookup.stat.size as u64;
+        if len < size.min(MAX_COPY_FILE_RANGE_LEN) {
+            return Err(err!(
+                libc::EOPNOTSUPP,
+                "only copies of whole files into new files are supported"
+            ));
+        }
+
+        let source_key = lookup.inode.full_key();
+        let etag = lookup
+            .stat
+            .etag
+            .as_ref()
+            .map(|etag| ETag::from_str(etag).expect("E-Tag should be set"));
+        // The new file gets its own metadata, like one written with `write`, rather than the source's
+        let user_metadata = self.upload_metadata(ino_out).await?;
+        let params = CopyObjectParams::new()
+            .source_if_match(etag)
+            .replace_user_metadata(Some(user_metadata));
+        match self
+            .client
+            .copy_object(&source.bucket, source_key, &target.bucket, &handle.full_key, &params)
+            .await
+        {
+            Ok(_) => debug!(source_key, key = handle.full_key, size, "copy succeeded"),
+            Err(ObjectClientError::ServiceError(CopyObjectError::PreconditionFailed)) => {
+                return Err(err!(libc::ESTALE, "object was mutated remotely"));
+            }
+            Err(e) => return Err(err!(libc::EIO, source:e, "copy failed")),
+        }
+
+        // The upload that was started for the new file is dropped without uploading anything
+        let reported = size.min(MAX_COPY_FILE_RANGE_LEN);
+        match std::mem::replace(upload, UploadState::Copied { size, reported }) {
+            UploadState::InProgress {
+                handle: write_handle, ..
+            }
+            | UploadState::Staged {
+                handle: write_handle, ..
+            } => UploadState::<Client>::finish_handle(write_handle, &handle.full_key),
+            _ => unreachable!("checked above"),
+        }
+        Ok(reported as u32)
+    }
+
     /// Creates a new ReaddirHandle for the provided parent and default page size
-    async fn readdir_handle(&self, parent: InodeNo) -> Result<ReaddirHandle, InodeError> {
-        self.superblock.readdir(&self.client, parent, 1000).await
+    async fn readdir_handle(&self, parent: InodeNo) -> Result<ReaddirHandle, Error> {
+        Ok(self.superblock(parent)?.readdir(&self.client, parent, 1000).await?)
     }
 
     pub async fn opendir(&self, parent: InodeNo, _flags: i32) -> Result<Opened, Error> {
         trace!("fs:opendir with parent {:?} flags {:#b}", parent, _flags);
 
+        if self.is_multi_bucket_root(parent) {
+            // Listing the buckets needs no state, so there is no directory handle to keep
+            return Ok(Opened {
+                fh: self.next_handle(),
+                flags: 0,
+            });
+        }
         let inode_handle = self.readdir_handle(parent).await?;
 
         let fh = self.next_handle();
@@ -1009,6 +1672,10 @@ where
         is_readdirplus: bool,
         mut reply: R,
     ) -> Result<R, Error> {
+        if self.is_multi_bucket_root(parent) {
+            return self.readdir_buckets(offset, reply).await;
+        }
+
         let dir_handle = {
             let dir_handles = self.dir_handles.read().await;
             dir_handles
@@ -1045,7 +1712,9 @@ where
                         // must remember it again, except that readdirplus specifies that . and ..
                         // are never incremented.
                         if is_readdirplus && entry.name != "." && entry.name != ".." {
-                            readdir_handle.remember(&entry.lookup);
+                            if let Some(lookup) = &entry.lookup {
+                                readdir_handle.remember(lookup);
+                            }
                         }
                     }
                     return Ok(reply);
@@ -1087,7 +1756,7 @@ where
         let mut reply = Reply { reply, entries: vec![] };
 
         if dir_handle.offset() < 1 {
-            let lookup = self.superblock.getattr(&self.client, parent, false).await?;
+            let lookup = self.superblock(parent)?.getattr(&self.client, parent, false).await?;
             let attr = self.make_attr(&lookup);
             let entry = DirectoryEntry {
                 ino: parent,
@@ -1096,7 +1765,7 @@ where
                 attr,
                 generation: 0,
                 ttl: lookup.validity(),
-                lookup,
+                lookup: Some(lookup),
             };
             if reply.add(entry) {
                 return Ok(reply.finish(offset, &dir_handle).await);
@@ -1105,7 +1774,7 @@ where
         }
         if dir_handle.offset() < 2 {
             let lookup = self
-                .superblock
+                .superblock(parent)?
                 .getattr(&self.client, readdir_handle.parent(), false)
                 .await?;
             let attr = self.make_attr(&lookup);
@@ -1116,7 +1785,7 @@ where
                 attr,
                 generation: 0,
                 ttl: lookup.validity(),
-                lookup,
+                lookup: Some(lookup),
             };
             if reply.add(entry) {
                 return Ok(reply.finish(offset, &dir_handle).await);
@@ -1138,7 +1807,7 @@ where
                 attr,
                 generation: 0,
                 ttl: next.validity(),
-                lookup: next.clone(),
+                lookup: Some(next.clone()),
             };
 
             if reply.add(entry) {
@@ -1154,11 +1823,13 @@ where
 
     async fn complete_upload(
         &self,
+        ino: InodeNo,
         request: &mut UploadState<Client>,
         full_key: &str,
         ignore_if_empty: bool,
         pid: Option<u32>,
     ) -> Result<(), Error> {
+        self.refresh_staged_metadata(ino, request).await;
         match request.complete(full_key, ignore_if_empty, pid).await {
             // According to the `fsync` man page we should return ENOSPC instead of EFBIG if it's a
             // space-related failure.
@@ -1167,7 +1838,7 @@ where
         }
     }
 
-    pub async fn fsync(&self, _ino: InodeNo, fh: u64, _datasync: bool) -> Result<(), Error> {
+    pub async fn fsync(&self, ino: InodeNo, fh: u64, _datasync: bool) -> Result<(), Error> {
         let file_handle = {
             let file_handles = self.file_handles.read().await;
             match file_handles.get(&fh) {
@@ -1181,10 +1852,14 @@ where
             FileHandleState::Read { .. } => return Ok(()),
             FileHandleState::Write(request) => request,
         };
-        self.complete_upload(request, &file_handle.full_key, false, None).await
+        self.complete_upload(ino, request, &file_handle.full_key, false, None)
+            .await
     }
 
-    pub async fn flush(&self, _ino: InodeNo, fh: u64, _lock_owner: u64, pid: u32) -> Result<(), Error> {
+    pub async fn flush(&self, ino: InodeNo, fh: u64, lock_owner: u64, pid: u32) -> Result<(), Error> {
+        // Closing any file descriptor of a file releases the POSIX locks its process holds on it
+        self.locks.unlock_all(ino, lock_owner);
+
         // We generally want to complete the upload when users close a file descriptor (and flush
         // is invoked), so that we can notify them of the outcome. However, since different file
         // descriptors can point to the same file handle, flush can be invoked multiple times on
@@ -1210,7 +1885,7 @@ where
         match &mut *state {
             FileHandleState::Read { .. } => Ok(()),
             FileHandleState::Write(request) => {
-                self.complete_upload(request, &file_handle.full_key, true, Some(pid))
+                self.complete_upload(ino, request, &file_handle.full_key, true, Some(pid))
                     .await
             }
         }
@@ -1244,7 +1919,7 @@ where
             }
         };
 
-        let request = match file_handle.state.into_inner() {
+        let mut request = match file_handle.state.into_inner() {
             FileHandleState::Read { handle, .. } => {
                 // TODO make sure we cancel the inflight PrefetchingGetRequest. is just dropping enough?
                 metrics::gauge!("fs.current_handles", "type" => "read").decrement(1.0);
@@ -1254,6 +1929,8 @@ where
             FileHandleState::Write(request) => request,
         };
 
+        self.refresh_staged_metadata(file_handle.inode.ino(), &mut request)
+            .await;
         let result = request.complete_if_in_progress(&file_handle.full_key).await;
         metrics::gauge!("fs.current_handles", "type" => "write").decrement(1.0);
         // Errors won't actually be seen by the user because `release` is async,
@@ -1261,12 +1938,123 @@ where
         result
     }
 
+    #[allow(clippy::too_many_arguments)] // We don't get to choose this interface
+    pub async fn getlk(
+        &self,
+        ino: InodeNo,
+        fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: i32,
+        pid: u32,
+    ) -> Result<Lock, Error> {
+        trace!("fs:getlk with ino {:?} fh {:?} owner {:?}", ino, fh, lock_owner);
+
+        let Some(typ) = lock_type(typ)? else {
+            return Err(err!(libc::EINVAL, "cannot test for an unlock"));
+        };
+        let lock = FileLock {
+            owner: lock_owner,
+            pid,
+            typ,
+            start,
+            end,
+        };
+        match self.locks.test(ino, &lock) {
+            Some(conflict) => Ok(Lock {
+                start: conflict.start,
+                end: conflict.end,
+                typ: match conflict.typ {
+                    LockType::Read => libc::F_RDLCK,
+                    LockType::Write => libc::F_WRLCK,
+                },
+                pid: conflict.pid,
+            }),
+            None => Ok(Lock {
+                start,
+                end,
+                typ: libc::F_UNLCK,
+                pid: 0,
+            }),
+        }
+    }
+
+    /// Take or release a lock, failing with `EAGAIN` if it conflicts with another lock
+    #[allow(clippy::too_many_arguments)] // We don't get to choose this interface
+    pub async fn setlk(
+        &self,
+        ino: InodeNo,
+        fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: i32,
+        pid: u32,
+    ) -> Result<(), Error> {
+        trace!("fs:setlk with ino {:?} fh {:?} owner {:?}", ino, fh, lock_owner);
+
+        let Some(typ) = lock_type(typ)? else {
+            self.locks.unlock(ino, lock_owner, start, end);
+            return Ok(());
+        };
+        let lock = FileLock {
+            owner: lock_owner,
+            pid,
+            typ,
+            start,
+            end,
+        };
+        self.locks.lock(ino, lock).map_err(lock_error)
+    }
+
+    /// Take or release a lock, waiting for conflicting locks to be released. The request doesn't
+    /// block the calling thread while it waits: `reply` is called once the lock is granted, or the
+    /// file is closed by the lock's owner, possibly from another thread.
+    #[allow(clippy::too_many_arguments)] // We don't get to choose this interface
+    pub fn setlkw(
+        &self,
+        ino: InodeNo,
+        fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: i32,
+        pid: u32,
+        reply: impl FnOnce(Result<(), Error>) + Send + 'static,
+    ) {
+        trace!("fs:setlkw with ino {:?} fh {:?} owner {:?}", ino, fh, lock_owner);
+
+        let typ = match lock_type(typ) {
+            Ok(Some(typ)) => typ,
+            Ok(None) => {
+                self.locks.unlock(ino, lock_owner, start, end);
+                return reply(Ok(()));
+            }
+            Err(e) => return reply(Err(e)),
+        };
+        let lock = FileLock {
+            owner: lock_owner,
+            pid,
+            typ,
+            start,
+            end,
+        };
+        self.locks
+            .lock_or_wait(ino, lock, Box::new(move |result| reply(result.map_err(lock_error))));
+    }
+
     pub async fn rmdir(&self, parent_ino: InodeNo, name: &OsStr) -> Result<(), Error> {
-        self.superblock.rmdir(&self.client, parent_ino, name).await?;
+        self.superblock(parent_ino)?
+            .rmdir(&self.client, parent_ino, name)
+            .await?;
         Ok(())
     }
 
-    pub async fn releasedir(&self, _ino: InodeNo, fh: u64, _flags: i32) -> Result<(), Error> {
+    pub async fn releasedir(&self, ino: InodeNo, fh: u64, _flags: i32) -> Result<(), Error> {
+        if self.is_multi_bucket_root(ino) {
+            return Ok(());
+        }
         let mut dir_handles = self.dir_handles.write().await;
         dir_handles
             .remove(&fh)
@@ -1281,7 +2069,428 @@ where
                 "Deletes are disabled. Use '--allow-delete' mount option to enable it."
             ));
         }
-        Ok(self.superblock.unlink(&self.client, parent_ino, name).await?)
+        Ok(self
+            .superblock(parent_ino)?
+            .unlink(&self.client, parent_ino, name)
+            .await?)
+    }
+
+    pub async fn rename(
+        &self,
+        parent_ino: InodeNo,
+        name: &OsStr,
+        new_parent_ino: InodeNo,
+        new_name: &OsStr,
+        flags: u32,
+    ) -> Result<(), Error> {
+        if flags & !libc::RENAME_NOREPLACE != 0 {
+            return Err(err!(libc::EINVAL, "unsupported rename flags {:#x}", flags));
+        }
+        if !self.config.allow_delete {
+            return Err(err!(
+                libc::EPERM,
+                "Renames require deleting the source object. Use '--allow-delete' mount option to enable it."
+            ));
+        }
+        let superblock = self.superblock(parent_ino)?;
+        if !std::ptr::eq(superblock, self.superblock(new_parent_ino)?) {
+            return Err(err!(libc::EXDEV, "cannot rename between buckets"));
+        }
+        let no_replace = flags & libc::RENAME_NOREPLACE != 0;
+        Ok(superblock
+            .rename(
+                &self.client,
+                parent_ino,
+                name,
+                new_parent_ino,
+                new_name,
+                self.config.allow_overwrite,
+                no_replace,
+            )
+            .await?)
+    }
+
+    /// Get the value of an extended attribute, or `None` if the inode doesn't have it.
+    pub async fn getxattr(&self, ino: InodeNo, name: &OsStr) -> Result<Option<Vec<u8>>, Error> {
+        trace!("fs:getxattr with ino {:?} name {:?}", ino, name);
+
+        // Only attributes in our namespace can exist, so avoid a request to S3 for any others (like
+        // `security.selinux`, which some tools query for every file).
+        let Some(name) = name.to_str().filter(|name| name.starts_with(xattr::XATTR_PREFIX)) else {
+            return Ok(None);
+        };
+        let xattrs = self.xattrs(ino).await?;
+        Ok(xattrs.into_iter().find(|(n, _)| n == name).map(|(_, value)| value))
+    }
+
+    /// List the names of the extended attributes of an inode
+    pub async fn listxattr(&self, ino: InodeNo) -> Result<Vec<String>, Error> {
+        trace!("fs:listxattr with ino {:?}", ino);
+
+        let xattrs = self.xattrs(ino).await?;
+        Ok(xattrs.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Set an extended attribute. Only user-defined metadata (`user.s3.meta.<name>`) can be set, and
+    /// only on new files before any data has been written to them.
+    pub async fn setxattr(&self, ino: InodeNo, name: &OsStr, value: &[u8], flags: i32) -> Result<(), Error> {
+        trace!("fs:setxattr with ino {:?} name {:?} flags {:#x}", ino, name, flags);
+
+        let Some(name) = name.to_str().filter(|name| name.starts_with(xattr::XATTR_PREFIX)) else {
+            return Err(err!(libc::ENOTSUP, "only user.s3 extended attributes are supported"));
+        };
+        if !name.starts_with(xattr::USER_METADATA_XATTR_PREFIX) {
+            return Err(err!(libc::EPERM, "extended attribute {:?} is read-only", name));
+        }
+        let Some(metadata_name) = xattr::user_metadata_name(name) else {
+            return Err(err!(libc::EINVAL, "invalid user metadata name in {:?}", name));
+        };
+        let Some(value) = std::str::from_utf8(value)
+            .ok()
+            .filter(|v| xattr::valid_user_metadata_value(v))
+        else {
+            return Err(err!(libc::EINVAL, "user metadata values must be printable ASCII"));
+        };
+
+        // Uploads start as soon as a file is opened, so we need to restart any upload to include the
+        // new metadata. Holding the handles' locks prevents concurrent writes until we're done.
+        let handles = self.file_handles_for(ino).await;
+        let mut states = Vec::with_capacity(handles.len());
+        for handle in &handles {
+            states.push(handle.state.lock().await);
+        }
+
+        let superblock = self.superblock(ino)?;
+        let mut user_metadata = superblock.user_metadata(ino)?;
+        let exists = user_metadata.contains_key(&metadata_name);
+        if flags & libc::XATTR_CREATE != 0 && exists {
+            return Err(err!(libc::EEXIST, "extended attribute {:?} already exists", name));
+        }
+        if flags & libc::XATTR_REPLACE != 0 && !exists {
+            return Err(err!(ENOATTR, "extended attribute {:?} does not exist", name));
+        }
+        user_metadata.insert(metadata_name, value.to_owned());
+        if xattr::user_metadata_size(&user_metadata) > xattr::MAX_USER_METADATA_SIZE {
+            return Err(err!(
+                libc::E2BIG,
+                "user metadata is limited to {} bytes",
+                xattr::MAX_USER_METADATA_SIZE
+            ));
+        }
+        superblock.set_user_metadata(ino, user_metadata)?;
+        self.restart_uploads(ino, &handles, &mut states).await
+    }
+
+    /// Get the open handles of an inode
+    async fn file_handles_for(&self, ino: InodeNo) -> Vec<Arc<FileHandle<Client, Prefetcher>>> {
+        let file_handles = self.file_handles.read().await;
+        file_handles
+            .values()
+            .filter(|handle| handle.inode.ino() == ino)
+            .cloned()
+            .collect()
+    }
+
+    /// Restart the uploads of the given (locked) handles of an inode, so that they include its
+    /// current metadata. Must only be used before any data has been written to the uploads.
+    async fn restart_uploads(
+        &self,
+        ino: InodeNo,
+        handles: &[Arc<FileHandle<Client, Prefetcher>>],
+        states: &mut [AsyncMutexGuard<'_, FileHandleState<Client, Prefetcher>>],
+    ) -> Result<(), Error> {
+        for (handle, state) in handles.iter().zip(states.iter_mut()) {
+            match &mut **state {
+                FileHandleState::Write(UploadState::InProgress { request, .. }) => {
+                    debug!(key=?handle.full_key, "restarting upload to include new metadata");
+                    let user_metadata = self.upload_metadata(ino).await?;
+                    let bucket = &self.mounted_bucket(ino)?.bucket;
+                    *request = match self.uploader.put(bucket, &handle.full_key, user_metadata).await {
+                        Ok(request) => request,
+                        Err(e) => return Err(err!(libc::EIO, source:e, "put failed to restart")),
+                    };
+                }
+                FileHandleState::Write(UploadState::Staged { request, .. }) => {
+                    request.set_user_metadata(self.upload_metadata(ino).await?);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Start modifying the existing object of a remote file. Returns the object being modified, the
+    /// metadata to upload with its replacement, and the write handle of the file.
+    async fn begin_modify(
+        &self,
+        lookup: &LookedUp,
+    ) -> Result<(BaseObject, HashMap<String, String>, WriteHandle), Error> {
+        if !lookup.stat.is_readable {
+            return Err(err!(
+                libc::EACCES,
+                "objects in flexible retrieval storage classes are not accessible",
+            ));
+        }
+        let ino = lookup.inode.ino();
+        let superblock = self.superblock(ino)?;
+        if superblock.is_object_version(ino)? {
+            return Err(err!(libc::EROFS, "object versions cannot be modified"));
+        }
+        let Some(head) = superblock.head_object(&self.client, ino).await? else {
+            return Err(err!(libc::EIO, "no object to modify for inode {}", ino));
+        };
+        let base = BaseObject {
+            etag: ETag::from_str(&head.object.etag).expect("E-Tag should be set"),
+            size: head.object.size,
+        };
+        let handle = superblock
+            .modify(&self.client, ino, self.config.allow_overwrite, head.user_metadata)
+            .await?;
+        match self.upload_metadata(ino).await {
+            Ok(user_metadata) => Ok((base, user_metadata, handle)),
+            Err(e) => {
+                // Nothing was modified, so the file goes back to being remote
+                UploadState::<Client>::finish_handle(handle, lookup.inode.full_key());
+                Err(e)
+            }
+        }
+    }
+
+    /// Start modifying the existing object of a remote file through a staging file in `staging_dir`
+    async fn start_modify(
+        &self,
+        lookup: &LookedUp,
+        staging_dir: &Path,
+    ) -> Result<(StagedUploadRequest<Client>, WriteHandle), Error> {
+        let bucket = &self.mounted_bucket(lookup.inode.ino())?.bucket;
+        let (base, user_metadata, handle) = self.begin_modify(lookup).await?;
+        let key = lookup.inode.full_key();
+        match self.uploader.stage(staging_dir, bucket, key, user_metadata, Some(base)) {
+            Ok(request) => {
+                handle.set_file_size(request.size() as usize);
+                Ok((request, handle))
+            }
+            Err(e) => {
+                UploadState::<Client>::finish_handle(handle, key);
+                Err(err!(libc::EIO, source:e, "failed to create staging file"))
+            }
+        }
+    }
+
+    /// Start appending to the existing object of a remote file
+    async fn start_append(&self, lookup: &LookedUp) -> Result<(AppendUploadRequest<Client>, WriteHandle), Error> {
+        let bucket = &self.mounted_bucket(lookup.inode.ino())?.bucket;
+        let (base, user_metadata, handle) = self.begin_modify(lookup).await?;
+        let key = lookup.inode.full_key();
+        let use_write_offset = self.config.s3_personality.supports_append();
+        match self
+            .uploader
+            .append(bucket, key, base, user_metadata, use_write_offset)
+            .await
+        {
+            Ok(request) => {
+                handle.set_file_size(request.size() as usize);
+                Ok((request, handle))
+            }
+            Err(e) => {
+                UploadState::<Client>::finish_handle(handle, key);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Resize a file on `setattr`. Staged uploads of the file are resized in place. Otherwise, a
+    /// remote file is resized by modifying its object, if that is enabled, and its new attributes
+    /// are returned.
+    async fn resize(
+        &self,
+        ino: InodeNo,
+        size: u64,
+        atime: Option<OffsetDateTime>,
+        mtime: Option<OffsetDateTime>,
+    ) -> Result<Option<LookedUp>, Error> {
+        let mut resized = false;
+        for file_handle in self.file_handles_for(ino).await {
+            if let FileHandleState::Write(UploadState::Staged { request, handle, .. }) =
+                &mut *file_handle.state.lock().await
+            {
+                request.set_size(size)?;
+                handle.set_file_size(size as usize);
+                handle.touch();
+                resized = true;
+            }
+        }
+        let Some(staging_dir) = &self.config.write_staging_dir else {
+            return Ok(None);
+        };
+        if resized || !self.config.allow_overwrite {
+            return Ok(None);
+        }
+
+        let superblock = self.superblock(ino)?;
+        let lookup = superblock.getattr(&self.client, ino, false).await?;
+        if lookup.inode.kind() != InodeKind::File || !lookup.inode.is_remote()? {
+            return Ok(None);
+        }
+        let key = lookup.inode.full_key();
+        let (mut request, handle) = self.start_modify(&lookup, staging_dir).await?;
+        if let Err(e) = request.set_size(size) {
+            UploadState::<Client>::finish_handle(handle, key);
+            return Err(e.into());
+        }
+        handle.set_file_size(size as usize);
+        handle.touch();
+        let setattr_result = superblock.setattr(&self.client, ino, atime, mtime).await;
+        UploadState::complete_staged_upload(request, key, handle).await?;
+        Ok(Some(setattr_result?))
+    }
+
+    /// Upload a zero-byte marker object (`dir/`) for a new directory, so that it persists even if
+    /// nothing is written into it
+    async fn put_directory_marker(&self, lookup: &LookedUp) -> Result<(), Error> {
+        let ino = lookup.inode.ino();
+        let mounted = self.mounted_bucket(ino)?;
+        let handle = mounted.superblock.write(&self.client, ino, false, false).await?;
+        let key = lookup.inode.full_key();
+        let request = match self.uploader.put(&mounted.bucket, key, HashMap::new()).await {
+            Ok(request) => request,
+            Err(e) => {
+                // The next lookup of the name will find no object and forget the inode
+                let _ = handle.finish();
+                return Err(err!(libc::EIO, source:e, "put failed to start"));
+            }
+        };
+        UploadState::complete_upload(request, key, handle).await
+    }
+
+    /// Metadata to upload with the object of a new file: the user-defined metadata set on it and,
+    /// if enabled, its POSIX attributes
+    async fn upload_metadata(&self, ino: InodeNo) -> Result<HashMap<String, String>, Error> {
+        let superblock = self.superblock(ino)?;
+        let mut metadata = superblock.user_metadata(ino)?;
+        if self.config.posix_metadata {
+            let lookup = superblock.getattr(&self.client, ino, false).await?;
+            let attr = self.make_attr(&lookup);
+            metadata.extend(posix_metadata(attr.perm, attr.uid, attr.gid, lookup.stat.mtime));
+        }
+        Ok(metadata)
+    }
+
+    /// Update the metadata of a modified staged upload just before it is completed, so that it
+    /// records the modification time of the file when it was last written rather than when it was
+    /// opened. Other uploads send their metadata when they start, so it can't change anymore.
+    async fn refresh_staged_metadata(&self, ino: InodeNo, state: &mut UploadState<Client>) {
+        let UploadState::Staged { request, .. } = state else {
+            return;
+        };
+        if !request.is_modified() {
+            // Unchanged files are not uploaded again
+            return;
+        }
+        match self.upload_metadata(ino).await {
+            Ok(user_metadata) => request.set_user_metadata(user_metadata),
+            // The metadata from when the file was opened is still mostly right
+            Err(e) => warn!(ino, error=?e, "failed to refresh the metadata of the staged upload"),
+        }
+    }
+
+    /// Extended attributes of an inode, built from the metadata of its object. Files that have not
+    /// been uploaded yet only have the user metadata set on them so far, and directories have none.
+    async fn xattrs(&self, ino: InodeNo) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        if self.is_multi_bucket_root(ino) {
+            return Ok(Vec::new());
+        }
+        let superblock = self.superblock(ino)?;
+        match superblock.head_object(&self.client, ino).await? {
+            Some(head) => Ok(xattr::object_xattrs(&head)),
+            None => Ok(xattr::user_metadata_xattrs(&superblock.user_metadata(ino)?)),
+        }
+    }
+
+    /// Look up the directory of a bucket in the root of a multi-bucket filesystem
+    async fn lookup_bucket(&self, name: &OsStr) -> Result<Entry, Error> {
+        let Some(mounted) = self.buckets.iter().find(|mounted| name == OsStr::new(&mounted.name)) else {
+            return Err(Error {
+                level: Level::DEBUG,
+                ..err!(libc::ENOENT, "no bucket is mounted as {:?}", name)
+            });
+        };
+        let root_ino = mounted.superblock.root_ino();
+        let lookup = mounted.superblock.getattr(&self.client, root_ino, false).await?;
+        let attr = self.make_attr(&lookup);
+        Ok(Entry {
+            ttl: lookup.validity(),
+            attr,
+            generation: 0,
+        })
+    }
+
+    /// List the buckets in the root of a multi-bucket filesystem. The list never changes, so the
+    /// offset is simply the position in it.
+    async fn readdir_buckets<R: DirectoryReplier>(&self, offset: i64, mut reply: R) -> Result<R, Error> {
+        let root_attr = self.multi_bucket_root_attr();
+        let mut entries = vec![
+            DirectoryEntry {
+                ino: FUSE_ROOT_INODE,
+                offset: 1,
+                name: ".".into(),
+                attr: root_attr,
+                generation: 0,
+                ttl: multi_bucket::ROOT_TTL,
+                lookup: None,
+            },
+            DirectoryEntry {
+                ino: FUSE_ROOT_INODE,
+                offset: 2,
+                name: "..".into(),
+                attr: root_attr,
+                generation: 0,
+                ttl: multi_bucket::ROOT_TTL,
+                lookup: None,
+            },
+        ];
+        for mounted in &self.buckets {
+            let root_ino = mounted.superblock.root_ino();
+            let lookup = mounted.superblock.getattr(&self.client, root_ino, false).await?;
+            let attr = self.make_attr(&lookup);
+            entries.push(DirectoryEntry {
+                ino: root_ino,
+                offset: entries.len() as i64 + 1,
+                name: mounted.name.clone().into(),
+                attr,
+                generation: 0,
+                ttl: lookup.validity(),
+                lookup: Some(lookup),
+            });
+        }
+
+        for entry in entries.into_iter().skip(offset.max(0) as usize) {
+            if reply.add(entry) {
+                break;
+            }
+        }
+        Ok(reply)
+    }
+
+    /// Attributes of the root directory of a multi-bucket filesystem, which is read-only
+    fn multi_bucket_root_attr(&self) -> FileAttr {
+        FileAttr {
+            ino: FUSE_ROOT_INODE,
+            size: 0,
+            blocks: 0,
+            atime: self.mount_time.into(),
+            mtime: self.mount_time.into(),
+            ctime: self.mount_time.into(),
+            crtime: UNIX_EPOCH,
+            kind: InodeKind::Directory.into(),
+            perm: self.config.dir_mode & 0o555,
+            nlink: 2,
+            uid: self.config.uid,
+            gid: self.config.gid,
+            rdev: 0,
+            flags: 0,
+            blksize: PREFERRED_IO_BLOCK_SIZE,
+        }
     }
 }
 
@@ -1,10 +1,11 @@
 //! Utilities for handling errors generated by the `fs` module and mapping them to FUSE errors
 
+use mountpoint_s3_client::error::{GetObjectError, ObjectClientError, PutObjectError};
 use tracing::Level;
 
 use crate::fs::error_metadata::ErrorMetadata;
 use crate::inode::InodeError;
-use crate::upload::UploadWriteError;
+use crate::upload::{AppendUploadError, StagedUploadError, UploadWriteError};
 
 /// Generate an error that includes a conversion to a libc errno for use in replies to FUSE.
 ///
@@ -120,6 +121,34 @@ impl<E: std::error::Error + Send + Sync + 'static> From<UploadWriteError<E>> for
     }
 }
 
+impl<E: std::error::Error + Send + Sync + 'static> From<StagedUploadError<E>> for Error {
+    fn from(err: StagedUploadError<E>) -> Self {
+        let errno = err.to_errno();
+        Error {
+            errno,
+            message: String::from("staged upload error"),
+            source: Some(anyhow::anyhow!(err)),
+            // We are having WARN as the default level of logging for fuse errors
+            level: Level::WARN,
+            metadata: Default::default(),
+        }
+    }
+}
+
+impl<E: std::error::Error + Send + Sync + 'static> From<AppendUploadError<E>> for Error {
+    fn from(err: AppendUploadError<E>) -> Self {
+        let errno = err.to_errno();
+        Error {
+            errno,
+            message: String::from("append upload error"),
+            source: Some(anyhow::anyhow!(err)),
+            // We are having WARN as the default level of logging for fuse errors
+            level: Level::WARN,
+            metadata: Default::default(),
+        }
+    }
+}
+
 /// Errors that can be converted to a raw OS error (errno)
 pub trait ToErrno {
     fn to_errno(&self) -> libc::c_int;
@@ -151,8 +180,14 @@ impl ToErrno for InodeError {
             InodeError::CannotRemoveRemoteDirectory(_) => libc::EPERM,
             InodeError::DirectoryNotEmpty(_) => libc::ENOTEMPTY,
             InodeError::UnlinkNotPermittedWhileWriting(_) => libc::EPERM,
+            InodeError::RenameNotPermittedWhileWriting(_) => libc::EPERM,
+            InodeError::CannotRenameDirectory(_) => libc::EPERM,
+            InodeError::UserMetadataNotWritable(_) => libc::EPERM,
+            InodeError::NotASymlink(_) => libc::EINVAL,
             InodeError::CorruptedMetadata(_) => libc::EIO,
             InodeError::SetAttrNotPermittedOnRemoteInode(_) => libc::EPERM,
+            InodeError::ReadOnlyVersion(_) => libc::EROFS,
+            InodeError::FilteredOut(_) => libc::EPERM,
             InodeError::StaleInode { .. } => libc::ESTALE,
         }
     }
@@ -168,6 +203,46 @@ impl<E: std::error::Error> ToErrno for UploadWriteError<E> {
     }
 }
 
+impl<E: std::error::Error> ToErrno for StagedUploadError<E> {
+    fn to_errno(&self) -> libc::c_int {
+        match self {
+            // Surface errors like ENOSPC from the staging directory's file system as they are
+            StagedUploadError::StagingFile(e) => e.raw_os_error().unwrap_or(libc::EIO),
+            StagedUploadError::ObjectTooBig { .. } => libc::EFBIG,
+            // The object was changed by someone else while it was being modified
+            StagedUploadError::PutRequestFailed(ObjectClientError::ServiceError(
+                PutObjectError::PreconditionFailed,
+            ))
+            | StagedUploadError::GetRequestFailed(ObjectClientError::ServiceError(
+                GetObjectError::PreconditionFailed,
+            )) => libc::ESTALE,
+            StagedUploadError::PutRequestFailed(_) => libc::EIO,
+            StagedUploadError::GetRequestFailed(_) => libc::EIO,
+            StagedUploadError::SseCorruptedError(_) => libc::EIO,
+            StagedUploadError::MissingETag => libc::EIO,
+        }
+    }
+}
+
+impl<E: std::error::Error> ToErrno for AppendUploadError<E> {
+    fn to_errno(&self) -> libc::c_int {
+        match self {
+            // The object was changed by someone else since it was opened for appending
+            AppendUploadError::PutRequestFailed(ObjectClientError::ServiceError(
+                PutObjectError::PreconditionFailed | PutObjectError::InvalidWriteOffset,
+            ))
+            | AppendUploadError::GetRequestFailed(ObjectClientError::ServiceError(
+                GetObjectError::PreconditionFailed,
+            )) => libc::ESTALE,
+            AppendUploadError::PutRequestFailed(_) => libc::EIO,
+            AppendUploadError::GetRequestFailed(_) => libc::EIO,
+            AppendUploadError::OutOfOrderWrite { .. } => libc::EINVAL,
+            AppendUploadError::ObjectTooBig { .. } => libc::EFBIG,
+            AppendUploadError::SseCorruptedError(_) => libc::EIO,
+        }
+    }
+}
+
 impl Error {
     pub fn meta(&self) -> &ErrorMetadata {
         &self.metadata
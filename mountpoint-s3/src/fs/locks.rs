@@ -0,0 +1,356 @@
+//! Advisory POSIX file locks for the processes that use a mount on this host.
+//!
+//! Once the filesystem asks for `FUSE_POSIX_LOCKS`, the kernel forwards `fcntl` (POSIX) locks to us
+//! rather than handling them itself. They cover a range of bytes and are owned by a process. We
+//! don't ask for `FUSE_FLOCK_LOCKS`: the requests for `flock` (BSD) locks don't say which kind of
+//! lock they are for, so the kernel keeps handling them, independently of POSIX locks as on Linux.
+//!
+//! Requests that wait for a conflicting lock to be released don't block a thread: they are parked
+//! with the function that replies to them, which is called when the lock is granted, or when the
+//! owner of the lock closes the file. The locks only exist in this Mountpoint process: they don't
+//! exclude processes on other hosts, or that use another mount of the same bucket.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+
+use thiserror::Error;
+
+use crate::inode::InodeNo;
+use crate::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum LockType {
+    Read,
+    Write,
+}
+
+/// A lock on the bytes `start..=end` of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct FileLock {
+    pub(super) owner: u64,
+    /// The process that took the lock, or 0 if it isn't visible in our PID namespace
+    pub(super) pid: u32,
+    pub(super) typ: LockType,
+    pub(super) start: u64,
+    pub(super) end: u64,
+}
+
+impl FileLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start <= end && start <= self.end
+    }
+
+    fn conflicts_with(&self, other: &FileLock) -> bool {
+        self.owner != other.owner
+            && self.overlaps(other.start, other.end)
+            && (self.typ == LockType::Write || other.typ == LockType::Write)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(super) enum LockError {
+    #[error("range is locked by process {}", .0.pid)]
+    Conflict(FileLock),
+    #[error("file was closed by the lock owner while the request waited")]
+    Cancelled,
+}
+
+/// Replies to a lock request that waited for conflicting locks to be released
+pub(super) type LockReply = Box<dyn FnOnce(Result<(), LockError>) + Send>;
+
+/// A lock request that waits for conflicting locks to be released
+struct Waiter {
+    lock: FileLock,
+    reply: LockReply,
+}
+
+impl Debug for Waiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Waiter")
+            .field("lock", &self.lock)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Default)]
+struct InodeLocks {
+    locks: Vec<FileLock>,
+    /// Requests that are waiting for a conflicting lock to be released, in the order they were made
+    waiters: VecDeque<Waiter>,
+}
+
+impl InodeLocks {
+    /// The first lock that conflicts with the given one
+    fn find_conflict(&self, lock: &FileLock) -> Option<FileLock> {
+        self.locks.iter().find(|held| held.conflicts_with(lock)).copied()
+    }
+
+    /// Remove the bytes `start..=end` from the locks of an owner, splitting the locks that extend
+    /// past either end of the range
+    fn remove_range(&mut self, owner: u64, start: u64, end: u64) {
+        let mut remaining = Vec::with_capacity(self.locks.len());
+        for held in self.locks.drain(..) {
+            if held.owner != owner || !held.overlaps(start, end) {
+                remaining.push(held);
+                continue;
+            }
+            if held.start < start {
+                remaining.push(FileLock { end: start - 1, ..held });
+            }
+            if held.end > end {
+                remaining.push(FileLock { start: end + 1, ..held });
+            }
+        }
+        self.locks = remaining;
+    }
+
+    /// Add a lock, replacing the owner's existing locks on the same bytes and merging it with the
+    /// owner's adjacent locks of the same type
+    fn insert(&mut self, mut lock: FileLock) {
+        self.locks.retain(|held| {
+            let mergeable = held.owner == lock.owner
+                && held.typ == lock.typ
+                && held.start <= lock.end.saturating_add(1)
+                && lock.start <= held.end.saturating_add(1);
+            if mergeable {
+                lock.start = lock.start.min(held.start);
+                lock.end = lock.end.max(held.end);
+            }
+            !mergeable
+        });
+        self.remove_range(lock.owner, lock.start, lock.end);
+        self.locks.push(lock);
+    }
+
+    /// Grant the waiting requests that no longer conflict with any lock, and return their replies
+    fn grant_waiters(&mut self) -> Vec<LockReply> {
+        let mut granted = Vec::new();
+        let mut index = 0;
+        while index < self.waiters.len() {
+            if self.find_conflict(&self.waiters[index].lock).is_some() {
+                index += 1;
+                continue;
+            }
+            let waiter = self.waiters.remove(index).unwrap();
+            self.insert(waiter.lock);
+            granted.push(waiter.reply);
+            // The new lock can replace locks of its owner that earlier requests waited for
+            index = 0;
+        }
+        granted
+    }
+
+    fn is_empty(&self) -> bool {
+        self.locks.is_empty() && self.waiters.is_empty()
+    }
+}
+
+/// The locks held on each inode of a filesystem
+#[derive(Debug, Default)]
+pub(super) struct LockManager {
+    locks: Mutex<HashMap<InodeNo, InodeLocks>>,
+}
+
+impl LockManager {
+    /// The first lock held on an inode that conflicts with the given one, if any
+    pub(super) fn test(&self, ino: InodeNo, lock: &FileLock) -> Option<FileLock> {
+        let locks = self.locks.lock().unwrap();
+        locks.get(&ino)?.find_conflict(lock)
+    }
+
+    /// Take a lock on an inode, replacing the owner's existing locks on the same bytes, or fail if
+    /// it conflicts with another lock
+    pub(super) fn lock(&self, ino: InodeNo, lock: FileLock) -> Result<(), LockError> {
+        let granted = {
+            let mut locks = self.locks.lock().unwrap();
+            let inode_locks = locks.entry(ino).or_default();
+            if let Some(conflict) = inode_locks.find_conflict(&lock) {
+                return Err(LockError::Conflict(conflict));
+            }
+            inode_locks.insert(lock);
+            // Changing the type of a lock can unblock readers
+            inode_locks.grant_waiters()
+        };
+        reply_granted(granted);
+        Ok(())
+    }
+
+    /// Take a lock on an inode like [Self::lock], but wait for conflicting locks to be released
+    /// rather than fail. `reply` is called once the lock is granted, or the request is cancelled by
+    /// [Self::unlock_all], which may be after this function returns, from another thread.
+    pub(super) fn lock_or_wait(&self, ino: InodeNo, lock: FileLock, reply: LockReply) {
+        let mut locks = self.locks.lock().unwrap();
+        let inode_locks = locks.entry(ino).or_default();
+        if inode_locks.find_conflict(&lock).is_some() {
+            inode_locks.waiters.push_back(Waiter { lock, reply });
+            return;
+        }
+        inode_locks.insert(lock);
+        let granted = inode_locks.grant_waiters();
+        drop(locks);
+        reply_granted(granted);
+        reply(Ok(()));
+    }
+
+    /// Release an owner's locks on the bytes `start..=end` of an inode
+    pub(super) fn unlock(&self, ino: InodeNo, owner: u64, start: u64, end: u64) {
+        self.unlock_and_cancel(ino, owner, start, end, false);
+    }
+
+    /// Release all of an owner's locks on an inode when it closes the file, and cancel its waiting
+    /// requests, which fail with [LockError::Cancelled]. A process that is killed while it waits
+    /// for a lock closes its files, so it doesn't get the lock after it exits.
+    pub(super) fn unlock_all(&self, ino: InodeNo, owner: u64) {
+        self.unlock_and_cancel(ino, owner, 0, u64::MAX, true);
+    }
+
+    fn unlock_and_cancel(&self, ino: InodeNo, owner: u64, start: u64, end: u64, cancel: bool) {
+        let (granted, cancelled) = {
+            let mut locks = self.locks.lock().unwrap();
+            let Some(inode_locks) = locks.get_mut(&ino) else {
+                return;
+            };
+            let cancelled = if cancel {
+                let (cancelled, others) = inode_locks
+                    .waiters
+                    .drain(..)
+                    .partition(|waiter| waiter.lock.owner == owner);
+                inode_locks.waiters = others;
+                cancelled
+            } else {
+                VecDeque::new()
+            };
+            inode_locks.remove_range(owner, start, end);
+            let granted = inode_locks.grant_waiters();
+            if inode_locks.is_empty() {
+                locks.remove(&ino);
+            }
+            (granted, cancelled)
+        };
+        for waiter in cancelled {
+            (waiter.reply)(Err(LockError::Cancelled));
+        }
+        reply_granted(granted);
+    }
+}
+
+/// Reply to the waiting requests that were granted their locks, once the locks are no longer held
+fn reply_granted(granted: Vec<LockReply>) {
+    for reply in granted {
+        reply(Ok(()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    const INO: InodeNo = 2;
+
+    fn lock(owner: u64, typ: LockType, start: u64, end: u64) -> FileLock {
+        FileLock {
+            owner,
+            pid: std::process::id(),
+            typ,
+            start,
+            end,
+        }
+    }
+
+    /// Make a waiting lock request, and return a channel that receives its result
+    fn lock_or_wait(manager: &LockManager, lock: FileLock) -> mpsc::Receiver<Result<(), LockError>> {
+        let (sender, receiver) = mpsc::channel();
+        manager.lock_or_wait(INO, lock, Box::new(move |result| sender.send(result).unwrap()));
+        receiver
+    }
+
+    #[test_case(LockType::Read, LockType::Read, false; "shared")]
+    #[test_case(LockType::Read, LockType::Write, true; "read then write")]
+    #[test_case(LockType::Write, LockType::Read, true; "write then read")]
+    #[test_case(LockType::Write, LockType::Write, true; "exclusive")]
+    fn test_conflicts(held: LockType, requested: LockType, conflicts: bool) {
+        let manager = LockManager::default();
+        manager.lock(INO, lock(1, held, 0, u64::MAX)).unwrap();
+
+        let result = manager.lock(INO, lock(2, requested, 0, 99));
+        assert_eq!(result.is_err(), conflicts);
+        assert_eq!(manager.test(INO, &lock(2, requested, 0, 99)).is_some(), conflicts);
+
+        // The owner of a lock can change its type
+        manager.lock(INO, lock(1, requested, 0, u64::MAX)).unwrap();
+    }
+
+    #[test]
+    fn test_ranges() {
+        let manager = LockManager::default();
+        manager.lock(INO, lock(1, LockType::Write, 0, 99)).unwrap();
+        assert!(manager.test(INO, &lock(2, LockType::Read, 100, 199)).is_none());
+
+        // Unlocking the middle of a lock splits it
+        manager.unlock(INO, 1, 40, 59);
+        assert!(manager.test(INO, &lock(2, LockType::Write, 40, 59)).is_none());
+        assert_eq!(
+            manager.test(INO, &lock(2, LockType::Write, 50, 69)),
+            Some(lock(1, LockType::Write, 60, 99))
+        );
+
+        // Adjacent locks of the same type merge
+        manager.lock(INO, lock(1, LockType::Write, 40, 59)).unwrap();
+        assert_eq!(
+            manager.test(INO, &lock(2, LockType::Write, 50, 50)),
+            Some(lock(1, LockType::Write, 0, 99))
+        );
+
+        manager.unlock_all(INO, 1);
+        assert!(manager.test(INO, &lock(2, LockType::Write, 0, u64::MAX)).is_none());
+        assert!(manager.locks.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_wait_for_unlock() {
+        let manager = LockManager::default();
+        manager.lock(INO, lock(1, LockType::Write, 0, u64::MAX)).unwrap();
+
+        let waiting_reader = lock_or_wait(&manager, lock(2, LockType::Read, 0, u64::MAX));
+        let waiting_writer = lock_or_wait(&manager, lock(3, LockType::Write, 0, u64::MAX));
+        assert!(waiting_reader.try_recv().is_err(), "request should be waiting");
+
+        // Downgrading the lock unblocks the reader, but not the writer
+        manager.lock(INO, lock(1, LockType::Read, 0, u64::MAX)).unwrap();
+        assert_eq!(waiting_reader.try_recv(), Ok(Ok(())));
+        assert!(waiting_writer.try_recv().is_err(), "request should be waiting");
+
+        manager.unlock_all(INO, 1);
+        assert!(waiting_writer.try_recv().is_err(), "request should be waiting");
+        manager.unlock_all(INO, 2);
+        assert_eq!(waiting_writer.try_recv(), Ok(Ok(())));
+        assert!(manager.test(INO, &lock(1, LockType::Read, 0, 0)).is_some());
+    }
+
+    #[test]
+    fn test_cancel_on_close() {
+        let manager = LockManager::default();
+        manager.lock(INO, lock(1, LockType::Write, 0, u64::MAX)).unwrap();
+
+        let cancelled = lock_or_wait(&manager, lock(2, LockType::Write, 0, u64::MAX));
+        let waiting = lock_or_wait(&manager, lock(3, LockType::Write, 0, 99));
+
+        // Unlocking a range doesn't cancel the owner's waiting requests, but closing the file does
+        manager.unlock(INO, 2, 0, u64::MAX);
+        assert!(cancelled.try_recv().is_err(), "request should be waiting");
+        manager.unlock_all(INO, 2);
+        assert_eq!(cancelled.try_recv(), Ok(Err(LockError::Cancelled)));
+        assert!(waiting.try_recv().is_err(), "request should be waiting");
+
+        // Cancelled requests don't get the lock once it's released
+        manager.unlock_all(INO, 1);
+        assert_eq!(waiting.try_recv(), Ok(Ok(())));
+        assert!(manager.test(INO, &lock(2, LockType::Write, 100, u64::MAX)).is_none());
+        manager.unlock_all(INO, 3);
+        assert!(manager.locks.lock().unwrap().is_empty());
+    }
+}
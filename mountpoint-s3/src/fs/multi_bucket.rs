@@ -0,0 +1,45 @@
+//! Mounting several buckets, or prefixes of buckets, as the directories of one filesystem's root.
+//!
+//! Every bucket has its own [Superblock], but they share the filesystem's client, prefetcher, and
+//! data cache. The inode numbers of each superblock are offset by the bucket's position, so that
+//! the inodes of different buckets never collide. The root directory itself belongs to no bucket:
+//! it only lists the buckets and cannot be modified.
+
+use std::time::Duration;
+
+use crate::inode::{InodeNo, Superblock};
+use crate::prefix::Prefix;
+
+/// Number of low bits of an inode number that are allocated by the superblock of its bucket
+const BUCKET_INO_BITS: u32 = 48;
+
+/// The buckets of a filesystem never change, so the kernel can cache the root directory forever
+pub(super) const ROOT_TTL: Duration = Duration::from_secs(200 * 365 * 24 * 60 * 60);
+
+/// A bucket, or a prefix of a bucket, to mount as a directory in the root of the filesystem
+#[derive(Debug, Clone)]
+pub struct BucketMount {
+    /// Name of the directory in the root of the filesystem
+    pub name: String,
+    pub bucket: String,
+    pub prefix: Prefix,
+}
+
+/// A bucket mounted by a filesystem, with its own inodes
+#[derive(Debug)]
+pub(super) struct MountedBucket {
+    pub(super) name: String,
+    pub(super) bucket: String,
+    pub(super) superblock: Superblock,
+}
+
+/// The offset of the inode numbers of the bucket at the given position of a multi-bucket filesystem
+pub(super) fn ino_offset(index: usize) -> InodeNo {
+    (index as InodeNo + 1) << BUCKET_INO_BITS
+}
+
+/// The position of the bucket an inode of a multi-bucket filesystem belongs to, or [None] for the
+/// root directory
+pub(super) fn bucket_index(ino: InodeNo) -> Option<usize> {
+    ((ino >> BUCKET_INO_BITS) as usize).checked_sub(1)
+}
@@ -0,0 +1,91 @@
+//! Extended attributes that expose the metadata of S3 objects
+
+use std::collections::HashMap;
+
+use mountpoint_s3_client::types::HeadObjectResult;
+use time::format_description::well_known::Rfc3339;
+
+/// Namespace of all extended attributes provided by Mountpoint
+pub const XATTR_PREFIX: &str = "user.s3.";
+
+/// Namespace of the extended attributes that hold user-defined object metadata (the
+/// `x-amz-meta-*` headers)
+pub const USER_METADATA_XATTR_PREFIX: &str = "user.s3.meta.";
+
+/// Maximum size of the user-defined metadata of an object, counted as the sum of the sizes of all
+/// names and values
+pub const MAX_USER_METADATA_SIZE: usize = 2 * 1024;
+
+/// Error returned when an extended attribute does not exist
+#[cfg(target_os = "macos")]
+pub const ENOATTR: libc::c_int = libc::ENOATTR;
+#[cfg(not(target_os = "macos"))]
+pub const ENOATTR: libc::c_int = libc::ENODATA;
+
+/// Storage class reported for objects that HeadObject returns no storage class for
+const DEFAULT_STORAGE_CLASS: &str = "STANDARD";
+
+/// Build the extended attributes of an object from its metadata, as (name, value) pairs.
+pub fn object_xattrs(head: &HeadObjectResult) -> Vec<(String, Vec<u8>)> {
+    let mut xattrs = vec![
+        (xattr_name("etag"), head.object.etag.clone().into_bytes()),
+        (
+            xattr_name("storage_class"),
+            head.object
+                .storage_class
+                .as_deref()
+                .unwrap_or(DEFAULT_STORAGE_CLASS)
+                .as_bytes()
+                .to_vec(),
+        ),
+    ];
+    if let Ok(last_modified) = head.object.last_modified.format(&Rfc3339) {
+        xattrs.push((xattr_name("last_modified"), last_modified.into_bytes()));
+    }
+    if let Some(version_id) = &head.version_id {
+        xattrs.push((xattr_name("version_id"), version_id.clone().into_bytes()));
+    }
+    if let Some(content_type) = &head.content_type {
+        xattrs.push((xattr_name("content_type"), content_type.clone().into_bytes()));
+    }
+    xattrs.extend(user_metadata_xattrs(&head.user_metadata));
+    xattrs
+}
+
+/// Build the extended attributes for user-defined metadata, sorted by name.
+pub fn user_metadata_xattrs(user_metadata: &HashMap<String, String>) -> Vec<(String, Vec<u8>)> {
+    let mut user_metadata: Vec<_> = user_metadata.iter().collect();
+    user_metadata.sort();
+    user_metadata
+        .into_iter()
+        .map(|(name, value)| {
+            (
+                format!("{USER_METADATA_XATTR_PREFIX}{name}"),
+                value.clone().into_bytes(),
+            )
+        })
+        .collect()
+}
+
+/// Get the name of a user-defined metadata entry from the name of its extended attribute, or `None`
+/// if the attribute is not in the user metadata namespace or the name is not a valid HTTP header
+/// name. S3 stores names in lowercase, so the returned name is always lowercase too.
+pub fn user_metadata_name(xattr_name: &str) -> Option<String> {
+    let name = xattr_name.strip_prefix(USER_METADATA_XATTR_PREFIX)?;
+    let is_token_char = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+    (!name.is_empty() && name.chars().all(is_token_char)).then(|| name.to_ascii_lowercase())
+}
+
+/// Check that a user-defined metadata value can be sent in an HTTP header
+pub fn valid_user_metadata_value(value: &str) -> bool {
+    value.chars().all(|c| c == ' ' || c.is_ascii_graphic())
+}
+
+/// Size of user-defined metadata, as counted against [MAX_USER_METADATA_SIZE]
+pub fn user_metadata_size(user_metadata: &HashMap<String, String>) -> usize {
+    user_metadata.iter().map(|(name, value)| name.len() + value.len()).sum()
+}
+
+fn xattr_name(name: &str) -> String {
+    format!("{XATTR_PREFIX}{name}")
+}
@@ -6,16 +6,18 @@ use std::ffi::OsStr;
 use std::path::Path;
 use std::time::SystemTime;
 use time::OffsetDateTime;
-use tracing::{field, instrument, Instrument};
+use tracing::{field, instrument, Instrument, Span};
 
-use crate::fs::{DirectoryEntry, DirectoryReplier, InodeNo, S3Filesystem, S3FilesystemConfig, ToErrno};
+use crate::fs::{
+    BucketMount, DirectoryEntry, DirectoryReplier, InodeNo, S3Filesystem, S3FilesystemConfig, ToErrno, ENOATTR,
+};
 use crate::prefetch::Prefetch;
 use crate::prefix::Prefix;
 #[cfg(target_os = "macos")]
 use fuser::ReplyXTimes;
 use fuser::{
     Filesystem, KernelConfig, ReplyAttr, ReplyBmap, ReplyCreate, ReplyData, ReplyEmpty, ReplyEntry, ReplyIoctl,
-    ReplyLock, ReplyLseek, ReplyOpen, ReplyWrite, ReplyXattr, Request, TimeOrNow,
+    ReplyLock, ReplyLseek, ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr, Request, TimeOrNow,
 };
 
 pub mod session;
@@ -87,6 +89,18 @@ where
 
         Self { fs }
     }
+
+    /// Create a filesystem whose root directory contains a directory for each of the given buckets
+    pub fn new_multi_bucket(
+        client: Client,
+        prefetcher: Prefetcher,
+        buckets: Vec<BucketMount>,
+        config: S3FilesystemConfig,
+    ) -> Self {
+        let fs = S3Filesystem::new_multi_bucket(client, prefetcher, buckets, config);
+
+        Self { fs }
+    }
 }
 
 impl<Client, Prefetcher> Filesystem for S3FuseFilesystem<Client, Prefetcher>
@@ -115,6 +129,23 @@ where
         }
     }
 
+    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino))]
+    fn statfs(&self, _req: &Request<'_>, ino: u64, reply: ReplyStatfs) {
+        match block_on(self.fs.statfs(ino).in_current_span()) {
+            Ok(statfs) => reply.statfs(
+                statfs.total_blocks,
+                statfs.free_blocks,
+                statfs.available_blocks,
+                statfs.total_inodes,
+                statfs.free_inodes,
+                statfs.block_size,
+                statfs.max_name_length,
+                statfs.fragment_size,
+            ),
+            Err(e) => fuse_error!("statfs", reply, e),
+        }
+    }
+
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino, nlookup, name=field::Empty))]
     fn forget(&self, _req: &Request<'_>, ino: u64, nlookup: u64) {
         block_on(self.fs.forget(ino, nlookup));
@@ -360,9 +391,9 @@ where
         &self,
         _req: &Request<'_>,
         ino: u64,
-        _mode: Option<u32>,
-        _uid: Option<u32>,
-        _gid: Option<u32>,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
         size: Option<u64>,
         atime: Option<TimeOrNow>,
         mtime: Option<TimeOrNow>,
@@ -382,23 +413,32 @@ where
             TimeOrNow::SpecificTime(st) => OffsetDateTime::from(st),
             TimeOrNow::Now => OffsetDateTime::now_utc(),
         });
-        match block_on(self.fs.setattr(ino, atime, mtime, size, flags).in_current_span()) {
+        match block_on(
+            self.fs
+                .setattr(ino, mode, uid, gid, atime, mtime, size, flags)
+                .in_current_span(),
+        ) {
             Ok(attr) => reply.attr(&attr.ttl, &attr.attr),
             Err(e) => fuse_error!("setattr", reply, e),
         }
     }
 
-    // Everything below here is stubs for unsupported functions so we log them correctly
-
-    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino))]
+    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, name=field::Empty))]
     fn readlink(&self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
-        fuse_unsupported!("readlink", reply);
+        match block_on(self.fs.readlink(ino).in_current_span()) {
+            Ok(target) => reply.data(&target),
+            Err(e) => fuse_error!("readlink", reply, e),
+        }
     }
 
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), parent=parent, name=?name, link=?link))]
     fn symlink(&self, _req: &Request<'_>, parent: u64, name: &OsStr, link: &Path, reply: ReplyEntry) {
-        // Userspace expects EPERM for link/symlink if unsupported
-        fuse_unsupported!("symlink", reply, libc::EPERM);
+        // Userspace expects EPERM for link/symlink if unsupported, which is what we return when
+        // symlinks are disabled
+        match block_on(self.fs.symlink(parent, name, link.as_os_str()).in_current_span()) {
+            Ok(entry) => reply.entry(&entry.ttl, &entry.attr, entry.generation),
+            Err(e) => fuse_error!("symlink", reply, e),
+        }
     }
 
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), parent=parent, name=?name, newparent=newparent, newname=?newname))]
@@ -409,21 +449,17 @@ where
         name: &OsStr,
         newparent: u64,
         newname: &OsStr,
-        _flags: u32,
+        flags: u32,
         reply: ReplyEmpty,
     ) {
-        fuse_unsupported!("rename", reply);
-    }
-
-    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, newparent=newparent, newname=?newname))]
-    fn link(&self, _req: &Request<'_>, ino: u64, newparent: u64, newname: &OsStr, reply: ReplyEntry) {
-        // Userspace expects EPERM for link/symlink if unsupported
-        fuse_unsupported!("link", reply, libc::EPERM);
-    }
-
-    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, fh=fh, datasync=datasync))]
-    fn fsyncdir(&self, _req: &Request<'_>, ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
-        fuse_unsupported!("fsyncdir", reply);
+        match block_on(
+            self.fs
+                .rename(parent, name, newparent, newname, flags)
+                .in_current_span(),
+        ) {
+            Ok(()) => reply.ok(),
+            Err(e) => fuse_error!("rename", reply, e),
+        }
     }
 
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, name=?name))]
@@ -432,22 +468,137 @@ where
         _req: &Request<'_>,
         ino: u64,
         name: &OsStr,
-        _value: &[u8],
-        _flags: i32,
+        value: &[u8],
+        flags: i32,
         _position: u32,
         reply: ReplyEmpty,
     ) {
-        fuse_unsupported!("setxattr", reply);
+        match block_on(self.fs.setxattr(ino, name, value, flags).in_current_span()) {
+            Ok(()) => reply.ok(),
+            Err(e) => fuse_error!("setxattr", reply, e),
+        }
     }
 
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, name=?name))]
-    fn getxattr(&self, _req: &Request<'_>, ino: u64, name: &OsStr, _size: u32, reply: ReplyXattr) {
-        fuse_unsupported!("getxattr", reply);
+    fn getxattr(&self, _req: &Request<'_>, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
+        match block_on(self.fs.getxattr(ino, name).in_current_span()) {
+            Ok(Some(value)) => reply_xattr(reply, &value, size),
+            Ok(None) => reply.error(ENOATTR),
+            Err(e) => fuse_error!("getxattr", reply, e),
+        }
     }
 
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino))]
-    fn listxattr(&self, _req: &Request<'_>, ino: u64, _size: u32, reply: ReplyXattr) {
-        fuse_unsupported!("listxattr", reply);
+    fn listxattr(&self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        match block_on(self.fs.listxattr(ino).in_current_span()) {
+            Ok(names) => {
+                // The list is a sequence of NUL-terminated names
+                let mut data = Vec::new();
+                for name in names {
+                    data.extend_from_slice(name.as_bytes());
+                    data.push(0);
+                }
+                reply_xattr(reply, &data, size)
+            }
+            Err(e) => fuse_error!("listxattr", reply, e),
+        }
+    }
+
+    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, fh=fh, pid=pid))]
+    fn getlk(
+        &self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: i32,
+        pid: u32,
+        reply: ReplyLock,
+    ) {
+        match block_on(
+            self.fs
+                .getlk(ino, fh, lock_owner, start, end, typ, pid)
+                .in_current_span(),
+        ) {
+            Ok(lock) => reply.locked(lock.start, lock.end, lock.typ, lock.pid),
+            Err(e) => fuse_error!("getlk", reply, e),
+        }
+    }
+
+    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, fh=fh, pid=pid))]
+    fn setlk(
+        &self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        lock_owner: u64,
+        start: u64,
+        end: u64,
+        typ: i32,
+        pid: u32,
+        sleep: bool,
+        reply: ReplyEmpty,
+    ) {
+        if sleep {
+            // Waiting for a lock doesn't block this thread, so the reply may come from another one
+            let span = Span::current();
+            self.fs
+                .setlkw(ino, fh, lock_owner, start, end, typ, pid, move |result| {
+                    let _guard = span.enter();
+                    match result {
+                        Ok(()) => reply.ok(),
+                        Err(e) => fuse_error!("setlk", reply, e),
+                    }
+                });
+            return;
+        }
+        match block_on(
+            self.fs
+                .setlk(ino, fh, lock_owner, start, end, typ, pid)
+                .in_current_span(),
+        ) {
+            Ok(()) => reply.ok(),
+            Err(e) => fuse_error!("setlk", reply, e),
+        }
+    }
+
+    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino_in=ino_in, fh_in=fh_in, offset_in=offset_in, ino_out=ino_out, fh_out=fh_out, offset_out=offset_out, len=len))]
+    fn copy_file_range(
+        &self,
+        _req: &Request<'_>,
+        ino_in: u64,
+        fh_in: u64,
+        offset_in: i64,
+        ino_out: u64,
+        fh_out: u64,
+        offset_out: i64,
+        len: u64,
+        flags: u32,
+        reply: ReplyWrite,
+    ) {
+        match block_on(
+            self.fs
+                .copy_file_range(ino_in, fh_in, offset_in, ino_out, fh_out, offset_out, len, flags)
+                .in_current_span(),
+        ) {
+            Ok(bytes_copied) => reply.written(bytes_copied),
+            Err(e) => fuse_error!("copy_file_range", reply, e),
+        }
+    }
+
+    // Everything below here is stubs for unsupported functions so we log them correctly
+
+    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, newparent=newparent, newname=?newname))]
+    fn link(&self, _req: &Request<'_>, ino: u64, newparent: u64, newname: &OsStr, reply: ReplyEntry) {
+        // Userspace expects EPERM for link/symlink if unsupported
+        fuse_unsupported!("link", reply, libc::EPERM);
+    }
+
+    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, fh=fh, datasync=datasync))]
+    fn fsyncdir(&self, _req: &Request<'_>, ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        fuse_unsupported!("fsyncdir", reply);
     }
 
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, name=?name))]
@@ -474,39 +625,6 @@ where
         fuse_unsupported!("create", reply, libc::ENOSYS, tracing::Level::DEBUG);
     }
 
-    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, fh=fh, pid=pid))]
-    fn getlk(
-        &self,
-        _req: &Request<'_>,
-        ino: u64,
-        fh: u64,
-        _lock_owner: u64,
-        _start: u64,
-        _end: u64,
-        _typ: i32,
-        pid: u32,
-        reply: ReplyLock,
-    ) {
-        fuse_unsupported!("getlk", reply);
-    }
-
-    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino, fh=fh, pid=pid))]
-    fn setlk(
-        &self,
-        _req: &Request<'_>,
-        ino: u64,
-        fh: u64,
-        _lock_owner: u64,
-        _start: u64,
-        _end: u64,
-        _typ: i32,
-        pid: u32,
-        _sleep: bool,
-        reply: ReplyEmpty,
-    ) {
-        fuse_unsupported!("setlk", reply);
-    }
-
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino=ino))]
     fn bmap(&self, _req: &Request<'_>, ino: u64, _blocksize: u32, _idx: u64, reply: ReplyBmap) {
         fuse_unsupported!("bmap", reply);
@@ -546,23 +664,6 @@ where
         fuse_unsupported!("lseek", reply);
     }
 
-    #[instrument(level="warn", skip_all, fields(req=_req.unique(), ino_in=ino_in, fh_in=fh_in, offset_in=offset_in, ino_out=ino_out, fh_out=fh_out, offset_out=offset_out, len=len))]
-    fn copy_file_range(
-        &self,
-        _req: &Request<'_>,
-        ino_in: u64,
-        fh_in: u64,
-        offset_in: i64,
-        ino_out: u64,
-        fh_out: u64,
-        offset_out: i64,
-        len: u64,
-        _flags: u32,
-        reply: ReplyWrite,
-    ) {
-        fuse_unsupported!("copy_file_range", reply);
-    }
-
     #[cfg(target_os = "macos")]
     #[instrument(level="warn", skip_all, fields(req=_req.unique(), name=?name))]
     fn setvolname(&self, _req: &Request<'_>, name: &OsStr, reply: ReplyEmpty) {
@@ -590,3 +691,15 @@ where
         fuse_unsupported!("getxtimes", reply);
     }
 }
+
+/// Reply to a `getxattr` or `listxattr` request. A zero `size` asks only for the size of the data,
+/// otherwise the data has to fit in `size` bytes.
+fn reply_xattr(reply: ReplyXattr, data: &[u8], size: u32) {
+    if size == 0 {
+        reply.size(data.len() as u32);
+    } else if data.len() > size as usize {
+        reply.error(libc::ERANGE);
+    } else {
+        reply.data(data);
+    }
+}
@@ -21,6 +21,7 @@
 //! Some cached state is dependent on the inode kind; that state is hidden behind a [InodeStatKind]
 //! enum.
 
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::fmt::{Debug, Display};
@@ -30,18 +31,22 @@ use std::time::{Duration, SystemTime};
 
 use anyhow::anyhow;
 use fuser::FileType;
-use futures::{select_biased, FutureExt};
+use futures::{pin_mut, select_biased, FutureExt, StreamExt};
 use mountpoint_s3_client::error::{HeadObjectError, ObjectClientError};
 use mountpoint_s3_client::error_metadata::ProvideErrorMetadata;
-use mountpoint_s3_client::types::{HeadObjectResult, RestoreStatus};
+use mountpoint_s3_client::types::{
+    CopyObjectParams, ETag, GetObjectParams, GetObjectRequest, HeadObjectResult, ObjectInfo, ObjectVersionInfo,
+    RestoreStatus,
+};
 use mountpoint_s3_client::ObjectClient;
 use mountpoint_s3_crt::checksums::crc32c::{self, Crc32c};
 use thiserror::Error;
 use time::OffsetDateTime;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::fs::error_metadata::{ErrorMetadata, MOUNTPOINT_ERROR_CLIENT};
 use crate::fs::CacheConfig;
+use crate::key_filter::KeyFilter;
 use crate::logging;
 use crate::prefix::Prefix;
 use crate::s3::S3Personality;
@@ -49,6 +54,7 @@ use crate::sync::atomic::{AtomicU64, Ordering};
 use crate::sync::RwLockReadGuard;
 use crate::sync::RwLockWriteGuard;
 use crate::sync::{Arc, RwLock};
+use crate::unicode::UnicodeNormalization;
 
 mod expiry;
 use expiry::Expiry;
@@ -59,6 +65,16 @@ use negative_cache::NegativeCache;
 mod readdir;
 pub use readdir::ReaddirHandle;
 
+mod posix_metadata;
+pub use posix_metadata::posix_metadata;
+
+mod symlink;
+pub use symlink::symlink_metadata;
+
+mod as_of;
+mod escape;
+mod versions;
+
 pub type InodeNo = u64;
 
 pub const ROOT_INODE_NO: InodeNo = 1;
@@ -66,6 +82,12 @@ pub const ROOT_INODE_NO: InodeNo = 1;
 // 200 years seems long enough
 const NEVER_EXPIRE_TTL: Duration = Duration::from_secs(200 * 365 * 24 * 60 * 60);
 
+/// Maximum number of concurrent CopyObject or DeleteObject requests issued by a directory rename
+const DIRECTORY_RENAME_CONCURRENCY: usize = 16;
+
+/// How often (in objects copied) a directory rename logs its progress
+const DIRECTORY_RENAME_PROGRESS_INTERVAL: usize = 1000;
+
 pub fn valid_inode_name<T: AsRef<OsStr>>(name: T) -> bool {
     let name = name.as_ref();
     // Names cannot be empty
@@ -79,6 +101,64 @@ pub fn valid_inode_name<T: AsRef<OsStr>>(name: T) -> bool {
     !name.as_bytes().contains(&b'\0')
 }
 
+/// How lookups compare names, following [SuperblockConfig::case_insensitive] and
+/// [SuperblockConfig::normalize_unicode]
+#[derive(Debug, Clone, Copy, Default)]
+struct NameComparison {
+    case_insensitive: bool,
+    normalization: UnicodeNormalization,
+}
+
+impl NameComparison {
+    /// Whether names are only equal to themselves
+    fn is_exact(&self) -> bool {
+        !self.case_insensitive && self.normalization == UnicodeNormalization::None
+    }
+
+    /// The form of a name that is the same for all the names equal to it
+    fn fold<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let name = self.normalization.normalize(name);
+        if self.case_insensitive {
+            Cow::Owned(name.to_lowercase())
+        } else {
+            name
+        }
+    }
+
+    fn are_equal(&self, name1: &str, name2: &str) -> bool {
+        name1 == name2 || (!self.is_exact() && self.fold(name1) == self.fold(name2))
+    }
+
+    /// The name of the child equal to `name` in the index of the folded names of a directory's
+    /// children
+    fn find_child<'a>(&self, folded_children: &'a HashMap<String, String>, name: &str) -> Option<&'a String> {
+        if folded_children.is_empty() {
+            return None;
+        }
+        folded_children.get(self.fold(name).as_ref())
+    }
+
+    /// Add a child to the index of the folded names of its parent's children, unless names are
+    /// compared exactly
+    fn index_child(&self, folded_children: &mut HashMap<String, String>, name: &str) {
+        if !self.is_exact() {
+            folded_children.insert(self.fold(name).into_owned(), name.to_owned());
+        }
+    }
+
+    /// Remove a child from the index of the folded names of its parent's children, unless the
+    /// index refers to another child with an equal name
+    fn unindex_child(&self, folded_children: &mut HashMap<String, String>, name: &str) {
+        if folded_children.is_empty() {
+            return;
+        }
+        let folded = self.fold(name);
+        if folded_children.get(folded.as_ref()).is_some_and(|child| child == name) {
+            folded_children.remove(folded.as_ref());
+        }
+    }
+}
+
 /// Superblock is the root object of the file system
 #[derive(Debug)]
 pub struct Superblock {
@@ -88,10 +168,12 @@ pub struct Superblock {
 #[derive(Debug)]
 struct SuperblockInner {
     bucket: String,
+    prefix: Prefix,
     inodes: RwLock<InodeMap>,
     negative_cache: NegativeCache,
     next_ino: AtomicU64,
     mount_time: OffsetDateTime,
+    name_comparison: NameComparison,
     config: SuperblockConfig,
 }
 
@@ -100,16 +182,43 @@ struct SuperblockInner {
 pub struct SuperblockConfig {
     pub cache_config: CacheConfig,
     pub s3_personality: S3Personality,
+    /// Allow renaming directories by copying and deleting every object under their prefix
+    pub allow_directory_rename: bool,
+    /// Surface objects whose metadata marks them as symlinks as such
+    pub allow_symlinks: bool,
+    /// Read the mode, owner and modification time of files from their objects' metadata
+    pub posix_metadata: bool,
+    /// Allow removing empty remote directories by deleting their marker objects
+    pub directory_markers: bool,
+    /// Expose the noncurrent versions of objects in read-only `<name>.versions` directories
+    pub show_versions: bool,
+    /// Show the bucket, read-only, as it was at this time
+    pub as_of: Option<OffsetDateTime>,
+    /// Hide the files and directories whose paths don't pass this filter, and refuse to create them
+    pub key_filter: KeyFilter,
+    /// Show the components of keys that are not valid file names with `%` escapes, rather than
+    /// hiding them
+    pub escape_names: bool,
+    /// Fall back to a child whose name only differs in case when looking up a name that doesn't
+    /// exist, and refuse to create names that only differ in case from an existing one
+    pub case_insensitive: bool,
+    /// Compare names in this normalization form, like [SuperblockConfig::case_insensitive] compares
+    /// them ignoring case
+    pub normalize_unicode: UnicodeNormalization,
+    /// Added to the number of every inode, so that superblocks sharing a filesystem have disjoint
+    /// inode numbers
+    pub ino_offset: InodeNo,
 }
 
 impl Superblock {
     /// Create a new Superblock that targets the given bucket/prefix
     pub fn new(bucket: &str, prefix: &Prefix, config: SuperblockConfig) -> Self {
         let mount_time = OffsetDateTime::now_utc();
+        let root_ino = ROOT_INODE_NO + config.ino_offset;
 
         let root = Inode::new(
-            ROOT_INODE_NO,
-            ROOT_INODE_NO,
+            root_ino,
+            root_ino,
             String::new(),
             prefix.to_string(),
             InodeKind::Directory,
@@ -125,21 +234,41 @@ impl Superblock {
         );
 
         let mut inodes = InodeMap::default();
-        inodes.insert(ROOT_INODE_NO, root);
+        inodes.insert(root_ino, root);
 
-        let negative_cache = NegativeCache::new(config.cache_config.negative_cache_size, config.cache_config.file_ttl);
+        let name_comparison = NameComparison {
+            case_insensitive: config.case_insensitive,
+            normalization: config.normalize_unicode,
+        };
+        let negative_cache = NegativeCache::new(
+            config.cache_config.negative_cache_size,
+            config.cache_config.file_ttl,
+            name_comparison,
+        );
 
         let inner = SuperblockInner {
             bucket: bucket.to_owned(),
+            prefix: prefix.clone(),
             inodes: RwLock::new(inodes),
             negative_cache,
-            next_ino: AtomicU64::new(2),
+            next_ino: AtomicU64::new(root_ino + 1),
             mount_time,
+            name_comparison,
             config,
         };
         Self { inner: Arc::new(inner) }
     }
 
+    /// The number of the root inode of this superblock
+    pub fn root_ino(&self) -> InodeNo {
+        self.inner.root_ino()
+    }
+
+    /// The number of inodes this superblock currently tracks
+    pub fn inode_count(&self) -> usize {
+        self.inner.inodes.read().unwrap().len()
+    }
+
     /// The kernel tells us when it removes a reference to an [InodeNo] from its internal caches via a forget call.
     /// The kernel may forget a number of references (`n`) in one forget message to our FUSE implementation.
     /// If the lookup count reaches zero, it is safe for the [Superblock] to delete the [Inode].
@@ -181,6 +310,7 @@ impl Superblock {
             let InodeKindData::Directory {
                 children,
                 writing_children,
+                folded_children,
                 ..
             } = &mut parent_state.kind_data
             else {
@@ -190,6 +320,7 @@ impl Superblock {
                 // Don't accidentally remove a newer inode (e.g. remote shadowing local)
                 if child.ino() == ino {
                     children.remove(inode.name());
+                    self.inner.name_comparison.unindex_child(folded_children, inode.name());
                 }
             }
             writing_children.remove(&ino);
@@ -274,7 +405,7 @@ impl Superblock {
         }
 
         let validity = match inode.kind() {
-            InodeKind::File => self.inner.config.cache_config.file_ttl,
+            InodeKind::File | InodeKind::Symlink => self.inner.config.cache_config.file_ttl,
             InodeKind::Directory => self.inner.config.cache_config.dir_ttl,
         };
 
@@ -293,6 +424,107 @@ impl Superblock {
         Ok(LookedUp { inode, stat })
     }
 
+    /// Fetch the metadata of the object backing an inode, or `None` if the inode is a directory, a
+    /// file that has not been uploaded yet, or a file pinned to a version of its object.
+    pub async fn head_object<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        ino: InodeNo,
+    ) -> Result<Option<HeadObjectResult>, InodeError> {
+        let inode = self.inner.get(ino)?;
+        logging::record_name(inode.name());
+
+        if inode.kind() != InodeKind::File || !inode.is_remote()? || self.inner.object_version(&inode)?.is_some() {
+            return Ok(None);
+        }
+
+        let bucket = self.inner.bucket.as_str();
+        match client.head_object(bucket, inode.full_key()).await {
+            Ok(result) => Ok(Some(result)),
+            Err(ObjectClientError::ServiceError(HeadObjectError::NotFound)) => {
+                let parent = self.inner.get(inode.parent())?;
+                Err(InodeError::FileDoesNotExist(inode.name().to_owned(), parent.err()))
+            }
+            Err(e) => Err(InodeError::client_error(
+                e,
+                "HeadObject failed",
+                bucket,
+                inode.full_key(),
+            )),
+        }
+    }
+
+    /// Get the user-defined metadata that will be attached to the object of a file being written.
+    /// Directories and remote files have none.
+    pub fn user_metadata(&self, ino: InodeNo) -> Result<HashMap<String, String>, InodeError> {
+        let inode = self.inner.get(ino)?;
+        let state = inode.get_inode_state()?;
+        match &state.kind_data {
+            InodeKindData::File { user_metadata, .. } if state.write_status != WriteStatus::Remote => {
+                Ok(user_metadata.clone())
+            }
+            _ => Ok(HashMap::new()),
+        }
+    }
+
+    /// Replace the user-defined metadata to attach to the object of a file being written. The
+    /// metadata is sent when the upload starts, so it can only be changed before any data is
+    /// written to the file.
+    pub fn set_user_metadata(&self, ino: InodeNo, metadata: HashMap<String, String>) -> Result<(), InodeError> {
+        let inode = self.inner.get(ino)?;
+        logging::record_name(inode.name());
+        let mut state = inode.get_mut_inode_state()?;
+
+        let writable = state.is_metadata_writable();
+        let InodeKindData::File { user_metadata, .. } = &mut state.kind_data else {
+            return Err(InodeError::UserMetadataNotWritable(inode.err()));
+        };
+        if !writable {
+            warn!(
+                ino,
+                "user metadata can only be set on new files before any data is written to them"
+            );
+            return Err(InodeError::UserMetadataNotWritable(inode.err()));
+        }
+        *user_metadata = metadata;
+        Ok(())
+    }
+
+    /// Change the permissions or owner of a file being written. They are stored in the object's
+    /// metadata, so like [Superblock::set_user_metadata] they can only be changed before any data is
+    /// written to the file.
+    pub fn set_mode_and_owner(
+        &self,
+        ino: InodeNo,
+        mode: Option<u16>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    ) -> Result<LookedUp, InodeError> {
+        let inode = self.inner.get(ino)?;
+        logging::record_name(inode.name());
+        let mut state = inode.get_mut_inode_state()?;
+
+        if inode.kind() != InodeKind::File {
+            return Err(InodeError::UserMetadataNotWritable(inode.err()));
+        }
+        if state.write_status == WriteStatus::Remote {
+            return Err(InodeError::SetAttrNotPermittedOnRemoteInode(inode.err()));
+        }
+        if !state.is_metadata_writable() {
+            warn!(
+                ino,
+                "mode and owner can only be changed on new files before any data is written to them"
+            );
+            return Err(InodeError::UserMetadataNotWritable(inode.err()));
+        }
+        state.stat.mode = mode.or(state.stat.mode);
+        state.stat.uid = uid.or(state.stat.uid);
+        state.stat.gid = gid.or(state.stat.gid);
+        let stat = state.stat.clone();
+        drop(state);
+        Ok(LookedUp { inode, stat })
+    }
+
     /// Create a new handle for a file being written. The handle can be used to update the state of
     /// the inflight write and commit it once finished.
     pub async fn write<OC: ObjectClient>(
@@ -305,6 +537,7 @@ impl Superblock {
         trace!(?ino, "write");
 
         let inode = self.inner.get(ino)?;
+        self.inner.check_not_version(&inode)?;
         let mut state = inode.get_mut_inode_state()?;
         if state.reader_count > 0 {
             return Err(InodeError::InodeNotWritableWhileReading(inode.err()));
@@ -332,6 +565,53 @@ impl Superblock {
 
                 state.write_status = WriteStatus::LocalOpen;
                 state.stat.size = 0;
+                // The new object will replace the old one, including its metadata
+                if let InodeKindData::File { user_metadata, .. } = &mut state.kind_data {
+                    user_metadata.clear();
+                }
+            }
+        }
+        drop(state);
+
+        Ok(WriteHandle::new(self.inner.clone(), inode))
+    }
+
+    /// Create a new handle for modifying the existing object of a remote file in place, rather than
+    /// replacing it. The file keeps its size, and `user_metadata` (usually the existing object's)
+    /// becomes the metadata the modified object will be uploaded with.
+    pub async fn modify<OC: ObjectClient>(
+        &self,
+        _client: &OC,
+        ino: InodeNo,
+        allow_overwrite: bool,
+        user_metadata: HashMap<String, String>,
+    ) -> Result<WriteHandle, InodeError> {
+        trace!(?ino, "modify");
+
+        let inode = self.inner.get(ino)?;
+        self.inner.check_not_version(&inode)?;
+        let mut state = inode.get_mut_inode_state()?;
+        if state.reader_count > 0 {
+            return Err(InodeError::InodeNotWritableWhileReading(inode.err()));
+        }
+        match state.write_status {
+            WriteStatus::LocalUnopened => return Err(InodeError::InodeInvalidWriteStatus(inode.err())),
+            WriteStatus::LocalOpen => return Err(InodeError::InodeAlreadyWriting(inode.err())),
+            WriteStatus::Remote if !allow_overwrite => {
+                tracing::warn!(
+                    "file overwrite is disabled by default, you need to remount with --allow-overwrite flag to modify existing files"
+                );
+                return Err(InodeError::InodeNotWritable(inode.err()));
+            }
+            WriteStatus::Remote => {
+                state.write_status = WriteStatus::LocalOpen;
+                if let InodeKindData::File {
+                    user_metadata: metadata,
+                    ..
+                } = &mut state.kind_data
+                {
+                    *metadata = user_metadata;
+                }
             }
         }
         drop(state);
@@ -345,13 +625,17 @@ impl Superblock {
         trace!(?ino, "read");
 
         let inode = self.inner.get(ino)?;
+        let (object_key, version_id) = match self.inner.object_version(&inode)? {
+            Some((key, version_id)) => (key, Some(version_id)),
+            None => (inode.full_key().to_owned(), None),
+        };
         let mut state = inode.get_mut_inode_state()?;
         if state.write_status != WriteStatus::Remote {
             return Err(InodeError::InodeNotReadableWhileWriting(inode.err()));
         }
         state.reader_count += 1;
         drop(state);
-        Ok(ReadHandle::new(inode))
+        Ok(ReadHandle::new(inode, object_key, version_id))
     }
 
     /// Start a readdir stream for the given directory inode
@@ -388,6 +672,8 @@ impl Superblock {
     ) -> Result<LookedUp, InodeError> {
         trace!(parent=?dir, ?name, "create");
 
+        self.inner.check_not_version(&self.inner.get(dir)?)?;
+
         let existing = self
             .inner
             .lookup_by_name(
@@ -407,6 +693,17 @@ impl Superblock {
         let name = name
             .to_str()
             .ok_or_else(|| InodeError::InvalidFileName(name.to_owned()))?;
+        let parent_key = self.inner.get(dir)?.full_key().to_owned();
+        let full_key = self
+            .inner
+            .child_key(&parent_key, name)
+            // Only directories can have an empty component, since the key of such a file would
+            // be its parent's
+            .filter(|key| kind == InodeKind::Directory || key.len() > parent_key.len())
+            .ok_or_else(|| InodeError::InvalidFileName(name.into()))?;
+        if !self.inner.is_shown(&full_key, kind) {
+            return Err(InodeError::FilteredOut(full_key));
+        }
 
         // Put inode creation in a block so we don't hold the lock on the parent state longer than needed.
         let lookup = {
@@ -416,16 +713,29 @@ impl Superblock {
             // Check again for the child now that the parent is locked, since we might have lost to a
             // racing lookup. (It would be nice to lock the parent and *then* lookup, but we'd have to
             // hold that lock across the remote API calls).
-            let InodeKindData::Directory { children, .. } = &mut parent_state.kind_data else {
+            let InodeKindData::Directory {
+                children,
+                folded_children,
+                ..
+            } = &mut parent_state.kind_data
+            else {
                 return Err(InodeError::NotADirectory(parent_inode.err()));
             };
             if let Some(inode) = children.get(name) {
                 return Err(InodeError::FileAlreadyExists(inode.err()));
             }
+            if let Some(inode) = self
+                .inner
+                .name_comparison
+                .find_child(folded_children, name)
+                .and_then(|name| children.get(name))
+            {
+                return Err(InodeError::FileAlreadyExists(inode.err()));
+            }
 
             let stat = match kind {
                 // Objects don't have an ETag until they are uploaded to S3
-                InodeKind::File => InodeStat::for_file(
+                InodeKind::File | InodeKind::Symlink => InodeStat::for_file(
                     0,
                     OffsetDateTime::now_utc(),
                     None,
@@ -455,8 +765,80 @@ impl Superblock {
         Ok(lookup)
     }
 
+    /// Create a new symlink inode pointing at `target`. Like a new file, it only exists locally
+    /// until its object, whose content is the target, is written with a [WriteHandle].
+    pub async fn symlink<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        dir: InodeNo,
+        name: &OsStr,
+        target: &[u8],
+    ) -> Result<LookedUp, InodeError> {
+        trace!(parent=?dir, ?name, "symlink");
+
+        let mut lookup = self.create(client, dir, name, InodeKind::Symlink).await?;
+        let mut state = lookup.inode.get_mut_inode_state()?;
+        state.kind_data = InodeKindData::Symlink {
+            target: Some(target.to_owned()),
+        };
+        state.stat.size = target.len();
+        lookup.stat = state.stat.clone();
+        drop(state);
+        Ok(lookup)
+    }
+
+    /// Whether the inode is a file pinned to a version of an object, rather than showing the current
+    /// object at its own key
+    pub fn is_object_version(&self, ino: InodeNo) -> Result<bool, InodeError> {
+        let inode = self.inner.get(ino)?;
+        Ok(self.inner.object_version(&inode)?.is_some())
+    }
+
+    /// Get the target of a symlink. The target of a remote symlink is read from its object the
+    /// first time it is needed.
+    pub async fn readlink<OC: ObjectClient>(&self, client: &OC, ino: InodeNo) -> Result<Vec<u8>, InodeError> {
+        let inode = self.inner.get(ino)?;
+        logging::record_name(inode.name());
+        let (etag, size) = {
+            let state = inode.get_inode_state()?;
+            match &state.kind_data {
+                InodeKindData::Symlink { target: Some(target) } => return Ok(target.clone()),
+                InodeKindData::Symlink { target: None } => {
+                    let etag = state.stat.etag.as_deref().and_then(|etag| etag.parse::<ETag>().ok());
+                    (etag, state.stat.size)
+                }
+                _ => return Err(InodeError::NotASymlink(inode.err())),
+            }
+        };
+
+        let bucket = &self.inner.bucket;
+        let key = inode.full_key();
+        let map_err = |e| InodeError::client_error(e, "GetObject failed", bucket, key);
+        let request = client
+            .get_object(bucket, key, &GetObjectParams::new().if_match(etag))
+            .await
+            .map_err(map_err)?;
+        pin_mut!(request);
+        request.as_mut().increment_read_window(size);
+        let mut target = vec![0u8; size];
+        while let Some(part) = request.next().await {
+            let (offset, body) = part.map_err(map_err)?;
+            let offset = offset as usize;
+            target[offset..offset + body.len()].copy_from_slice(&body);
+        }
+
+        let mut state = inode.get_mut_inode_state()?;
+        if let InodeKindData::Symlink { target: cached } = &mut state.kind_data {
+            *cached = Some(target.clone());
+        }
+        Ok(target)
+    }
+
     /// Remove local-only empty directory, i.e., the ones created by mkdir.
-    /// It does not affect empty directories represented remotely with directory markers.
+    ///
+    /// If [SuperblockConfig::directory_markers] is set, empty remote directories can also be removed.
+    /// A remote directory is empty if the only object under its prefix is its marker object (`dir/`),
+    /// which is deleted.
     pub async fn rmdir<OC: ObjectClient>(
         &self,
         client: &OC,
@@ -473,9 +855,18 @@ impl Superblock {
             )
             .await?;
 
-        if inode.kind() == InodeKind::File {
+        if inode.kind() != InodeKind::Directory {
             return Err(InodeError::NotADirectory(inode.err()));
         }
+        self.inner.check_not_version(&inode)?;
+
+        let is_remote = inode.get_inode_state()?.write_status == WriteStatus::Remote;
+        if is_remote {
+            if !self.inner.config.directory_markers {
+                return Err(InodeError::CannotRemoveRemoteDirectory(inode.err()));
+            }
+            self.inner.delete_directory_marker(client, &inode).await?;
+        }
 
         let parent = self.inner.get(parent_ino)?;
         let mut parent_state = parent.get_mut_inode_state()?;
@@ -483,11 +874,22 @@ impl Superblock {
 
         match &inode_state.write_status {
             WriteStatus::LocalOpen => unreachable!("A directory cannot be in Local open state"),
-            WriteStatus::Remote => {
-                return Err(InodeError::CannotRemoveRemoteDirectory(inode.err()));
-            }
+            WriteStatus::Remote => match &mut inode_state.kind_data {
+                InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
+                    unreachable!("Already checked that inode is a directory")
+                }
+                InodeKindData::Directory { deleted, .. } => {
+                    if !is_remote {
+                        // A file written into the directory made it remote while we checked it
+                        return Err(InodeError::DirectoryNotEmpty(inode.err()));
+                    }
+                    *deleted = true;
+                }
+            },
             WriteStatus::LocalUnopened => match &mut inode_state.kind_data {
-                InodeKindData::File {} => unreachable!("Already checked that inode is a directory"),
+                InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
+                    unreachable!("Already checked that inode is a directory")
+                }
                 InodeKindData::Directory {
                     writing_children,
                     deleted,
@@ -502,21 +904,23 @@ impl Superblock {
         }
 
         match &mut parent_state.kind_data {
-            InodeKindData::File {} => {
+            InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
                 debug_assert!(false, "inodes never change kind");
                 return Err(InodeError::NotADirectory(parent.err()));
             }
             InodeKindData::Directory {
                 children,
                 writing_children,
+                folded_children,
                 ..
             } => {
                 let removed = writing_children.remove(&inode.ino());
                 debug_assert!(
-                    removed,
-                    "should be able to remove the directory from its parents writing children as it was local"
+                    removed != is_remote,
+                    "should be able to remove the directory from its parents writing children if it was local"
                 );
                 children.remove(inode.name());
+                self.inner.name_comparison.unindex_child(folded_children, inode.name());
             }
         }
 
@@ -550,6 +954,7 @@ impl Superblock {
         if inode.kind() == InodeKind::Directory {
             return Err(InodeError::IsDirectory(inode.err()));
         }
+        self.inner.check_not_version(&inode)?;
 
         let write_status = {
             let inode_state = inode.get_inode_state()?;
@@ -587,7 +992,7 @@ impl Superblock {
 
         let mut parent_state = parent.get_mut_inode_state()?;
         match &mut parent_state.kind_data {
-            InodeKindData::File { .. } => {
+            InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
                 debug_assert!(false, "inodes never change kind");
                 return Err(InodeError::NotADirectory(parent.err()));
             }
@@ -609,9 +1014,200 @@ impl Superblock {
 
         Ok(())
     }
+
+    /// Rename the entry described by `src_parent_ino` and `src_name` to `dst_name` in `dst_parent_ino`.
+    ///
+    /// S3 has no native rename, so the object is copied to its new key and the old key is deleted.
+    /// Directories can only be renamed if [SuperblockConfig::allow_directory_rename] is set, in which
+    /// case every object under the directory's prefix is copied and deleted. The inode keeps its
+    /// number, so the kernel's references to it remain valid, but it is re-parented under the
+    /// destination directory.
+    ///
+    /// As with [Superblock::unlink], we rely on the Linux Kernel's VFS to lock both parents and the
+    /// children involved, so we don't need to worry about concurrent operations on these entries.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn rename<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        src_parent_ino: InodeNo,
+        src_name: &OsStr,
+        dst_parent_ino: InodeNo,
+        dst_name: &OsStr,
+        allow_overwrite: bool,
+        no_replace: bool,
+    ) -> Result<(), InodeError> {
+        trace!(src_parent=?src_parent_ino, ?src_name, dst_parent=?dst_parent_ino, ?dst_name, "rename");
+
+        let serve_lookup_from_cache = self.inner.config.cache_config.serve_lookup_from_cache;
+        let LookedUp { inode, .. } = self
+            .inner
+            .lookup_by_name(client, src_parent_ino, src_name, serve_lookup_from_cache)
+            .await?;
+        self.inner.check_not_version(&inode)?;
+
+        match inode.kind() {
+            InodeKind::File | InodeKind::Symlink => {
+                if inode.get_inode_state()?.write_status != WriteStatus::Remote {
+                    warn!(
+                        parent = src_parent_ino,
+                        name = ?src_name,
+                        "rename on local file not allowed until write is complete",
+                    );
+                    return Err(InodeError::RenameNotPermittedWhileWriting(inode.err()));
+                }
+            }
+            InodeKind::Directory => {
+                if !self.inner.config.allow_directory_rename {
+                    warn!("directory rename is disabled by default, you need to remount with --allow-directory-rename flag to rename directories");
+                    return Err(InodeError::CannotRenameDirectory(inode.err()));
+                }
+                self.inner.check_no_writing_descendants(&inode)?;
+            }
+        }
+
+        let dst_parent = self.inner.get(dst_parent_ino)?;
+        if dst_parent.kind() != InodeKind::Directory {
+            return Err(InodeError::NotADirectory(dst_parent.err()));
+        }
+        self.inner.check_not_version(&dst_parent)?;
+
+        let dst_name = dst_name
+            .to_str()
+            .ok_or_else(|| InodeError::InvalidFileName(dst_name.to_owned()))?;
+        if !valid_inode_name(dst_name) {
+            return Err(InodeError::InvalidFileName(dst_name.into()));
+        }
+        let mut dst_key = self
+            .inner
+            .child_key(dst_parent.full_key(), dst_name)
+            .filter(|key| inode.kind() == InodeKind::Directory || key.len() > dst_parent.full_key().len())
+            .ok_or_else(|| InodeError::InvalidFileName(dst_name.into()))?;
+        if !self.inner.is_shown(&dst_key, inode.kind()) {
+            return Err(InodeError::FilteredOut(dst_key));
+        }
+
+        let existing = self
+            .inner
+            .lookup_by_name(client, dst_parent_ino, dst_name.as_ref(), serve_lookup_from_cache)
+            .await;
+        match existing {
+            Ok(LookedUp { inode: existing, .. }) => {
+                // A case-insensitive lookup found a name that only differs in case
+                if no_replace || existing.name() != dst_name {
+                    return Err(InodeError::FileAlreadyExists(existing.err()));
+                }
+                if existing.ino() == inode.ino() {
+                    // Renaming an entry to itself is a no-op
+                    return Ok(());
+                }
+                match (inode.kind(), existing.kind()) {
+                    (InodeKind::File | InodeKind::Symlink, InodeKind::Directory) => {
+                        return Err(InodeError::IsDirectory(existing.err()))
+                    }
+                    (InodeKind::Directory, InodeKind::File | InodeKind::Symlink) => {
+                        return Err(InodeError::NotADirectory(existing.err()))
+                    }
+                    // An empty directory is replaced. Its marker object, if any, is kept as the
+                    // marker of the renamed directory.
+                    (InodeKind::Directory, InodeKind::Directory) => {
+                        self.inner.check_directory_empty(client, &existing).await?;
+                    }
+                    (InodeKind::File | InodeKind::Symlink, InodeKind::File | InodeKind::Symlink) => {
+                        if !existing.is_remote()? {
+                            return Err(InodeError::InodeAlreadyWriting(existing.err()));
+                        }
+                        if !allow_overwrite {
+                            warn!("file overwrite is disabled by default, you need to remount with --allow-overwrite flag to rename over an existing file");
+                            return Err(InodeError::InodeNotWritable(existing.err()));
+                        }
+                    }
+                }
+            }
+            Err(InodeError::FileDoesNotExist(_, _)) => (),
+            Err(e) => return Err(e),
+        }
+
+        let stat = match inode.kind() {
+            InodeKind::File | InodeKind::Symlink => self.inner.rename_object(client, &inode, &dst_key).await?,
+            InodeKind::Directory => {
+                dst_key.push('/');
+                self.inner.rename_prefix(client, &inode, &dst_key).await?;
+                InodeStat::for_directory(self.inner.mount_time, self.inner.config.cache_config.dir_ttl)
+            }
+        };
+
+        let src_parent = self.inner.get(src_parent_ino)?;
+        self.inner
+            .reparent(&inode, &src_parent, &dst_parent, dst_name, dst_key, stat)?;
+
+        if serve_lookup_from_cache {
+            self.inner.negative_cache.insert(src_parent_ino, inode.name());
+            self.inner.negative_cache.remove(dst_parent_ino, dst_name);
+        }
+
+        Ok(())
+    }
 }
 
 impl SuperblockInner {
+    /// Check that nothing under the given directory exists only locally, as far as the [Superblock]
+    /// knows about it. Files being written and new subdirectories have no objects to copy yet, so
+    /// they would be lost by a rename.
+    ///
+    /// Besides the cached descendants of the directory, this checks every inode the kernel holds
+    /// under its prefix, because an inode can outlive its entry in a cached parent, for example when
+    /// a lookup replaces the parent's inode.
+    fn check_no_writing_descendants(&self, dir: &Inode) -> Result<(), InodeError> {
+        for inode in self.known_descendants(dir) {
+            if !inode.is_remote()? {
+                return Err(InodeError::RenameNotPermittedWhileWriting(inode.err()));
+            }
+        }
+
+        let mut to_visit = vec![dir.clone()];
+        while let Some(dir) = to_visit.pop() {
+            let state = dir.get_inode_state()?;
+            let InodeKindData::Directory {
+                children,
+                writing_children,
+                ..
+            } = &state.kind_data
+            else {
+                continue;
+            };
+            if !writing_children.is_empty() {
+                return Err(InodeError::RenameNotPermittedWhileWriting(dir.err()));
+            }
+            for child in children.values().filter(|child| child.kind() == InodeKind::Directory) {
+                if !child.is_remote()? {
+                    return Err(InodeError::RenameNotPermittedWhileWriting(child.err()));
+                }
+                to_visit.push(child.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// The inodes the kernel holds under the prefix of the given directory, whether or not they are
+    /// cached in their parents. Deleted directories are skipped.
+    fn known_descendants(&self, dir: &Inode) -> Vec<Inode> {
+        let prefix = dir.full_key();
+        let mut known = self
+            .inodes
+            .read()
+            .unwrap()
+            .values()
+            .filter(|inode| inode.ino() != dir.ino() && inode.full_key().starts_with(prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        known.retain(|inode| inode.get_inode_state().is_ok());
+        known
+    }
+
+    fn root_ino(&self) -> InodeNo {
+        ROOT_INODE_NO + self.config.ino_offset
+    }
+
     /// Retrieve the inode for the given number if it exists.
     ///
     /// The expiry of its stat field is not checked.
@@ -671,14 +1267,62 @@ impl SuperblockInner {
             Some(lookup) => lookup?,
             None => {
                 let remote = self.remote_lookup(client, parent_ino, name).await?;
-                self.update_from_remote(parent_ino, name, remote)?
+                match self.update_from_remote(parent_ino, name, remote) {
+                    Err(InodeError::FileDoesNotExist(_, _)) if !self.name_comparison.is_exact() => {
+                        self.lookup_equal_name(client, parent_ino, name).await?
+                    }
+                    lookup => lookup?,
+                }
             }
         };
 
-        lookup.inode.verify_child(parent_ino, name.as_ref())?;
+        // The child may have a different name that is equal to `name`
+        let expected_name = match lookup.inode.name() {
+            child_name if self.name_comparison.are_equal(child_name, name) => child_name,
+            _ => name,
+        };
+        lookup.inode.verify_child(parent_ino, expected_name)?;
         Ok(lookup)
     }
 
+    /// Lookup the child of a directory whose name is equal to `name` without being identical, after
+    /// a lookup of `name` itself found nothing. Unless a child with such a name is already known,
+    /// this lists the whole directory.
+    async fn lookup_equal_name<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        parent_ino: InodeNo,
+        name: &str,
+    ) -> Result<LookedUp, InodeError> {
+        let parent = self.get(parent_ino)?;
+        let known_name = {
+            let state = parent.get_inode_state()?;
+            match &state.kind_data {
+                // The names of the files in versions directories are case-sensitive version IDs
+                InodeKindData::Directory {
+                    folded_children,
+                    versions_of: None,
+                    ..
+                } => self.name_comparison.find_child(folded_children, name).cloned(),
+                _ => return Err(InodeError::FileDoesNotExist(name.to_owned(), parent.err())),
+            }
+        };
+        // Normalization leaves ASCII names alone, so apart from a few singletons like the Kelvin sign
+        // there are no other names equal to them unless case is ignored
+        let can_have_equal_names = self.config.case_insensitive || !name.is_ascii();
+        let child_name = match known_name {
+            Some(child_name) => Some(child_name),
+            None if can_have_equal_names => readdir::find_equal_name(self, client, parent.full_key(), name).await?,
+            None => None,
+        };
+        let Some(child_name) = child_name.filter(|child_name| child_name != name) else {
+            return Err(InodeError::FileDoesNotExist(name.to_owned(), parent.err()));
+        };
+        trace!(parent=?parent_ino, ?name, ?child_name, "found equal name");
+        let remote = self.remote_lookup(client, parent_ino, &child_name).await?;
+        self.update_from_remote(parent_ino, &child_name, remote)
+    }
+
     /// Lookup an [Inode] against known directory entries in the parent,
     /// verifying any returned entry has not expired.
     /// If no record for the given `name` is found, returns [None].
@@ -690,11 +1334,20 @@ impl SuperblockInner {
             name: &str,
         ) -> Option<Result<LookedUp, InodeError>> {
             match &parent.get_inode_state().ok()?.kind_data {
-                InodeKindData::File { .. } => unreachable!("parent should be a directory!"),
-                InodeKindData::Directory { children, .. } => {
-                    if let Some(inode) = children.get(name) {
-                        let inode_stat = &inode.get_inode_state().ok()?.stat;
-                        if inode_stat.is_valid() {
+                InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
+                    unreachable!("parent should be a directory!")
+                }
+                InodeKindData::Directory {
+                    children,
+                    folded_children,
+                    ..
+                } => {
+                    let child = children
+                        .get(name)
+                        .or_else(|| children.get(superblock.name_comparison.find_child(folded_children, name)?));
+                    if let Some(inode) = child {
+                        let inode_stat = &inode.get_inode_state().ok()?.stat;
+                        if inode_stat.is_valid() {
                             let lookup = LookedUp {
                                 inode: inode.clone(),
                                 stat: inode_stat.clone(),
@@ -727,20 +1380,60 @@ impl SuperblockInner {
     }
 
     /// Lookup an inode in the parent directory with the given name
-    /// on the remote client.
+    /// on the remote client, hiding it if it's filtered out by [SuperblockConfig::key_filter].
     async fn remote_lookup<OC: ObjectClient>(
         &self,
         client: &OC,
         parent_ino: InodeNo,
         name: &str,
+    ) -> Result<Option<RemoteLookup>, InodeError> {
+        if self.config.key_filter.is_empty() {
+            return self.remote_lookup_unfiltered(client, parent_ino, name).await;
+        }
+
+        let parent = self.get(parent_ino)?;
+        // The files of a versions directory are filtered like the object they are versions of
+        let versions_of = parent.get_inode_state()?.kind_data.versions_of().map(str::to_owned);
+        let Some(full_key) = self.child_key(parent.full_key(), name) else {
+            return Ok(None);
+        };
+        if versions_of.is_none() && !self.is_shown(&full_key, InodeKind::Directory) {
+            // Excluded whatever its kind, so there's no need to ask S3
+            return Ok(None);
+        }
+
+        let lookup = self.remote_lookup_unfiltered(client, parent_ino, name).await?;
+        Ok(lookup.filter(|lookup| match (&lookup.versions_of, &versions_of) {
+            (Some(key), _) | (None, Some(key)) => self.is_shown(key, InodeKind::File),
+            (None, None) => self.is_shown(&full_key, lookup.kind),
+        }))
+    }
+
+    async fn remote_lookup_unfiltered<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        parent_ino: InodeNo,
+        name: &str,
     ) -> Result<Option<RemoteLookup>, InodeError> {
         let parent = self.get(parent_ino)?;
         if parent.kind() != InodeKind::Directory {
             return Err(InodeError::NotADirectory(parent.err()));
         }
-        let mut full_path = parent.full_key().to_owned();
-        assert!(full_path.is_empty() || full_path.ends_with('/'));
-        full_path.push_str(name);
+        if self.config.show_versions {
+            let versions_of = parent.get_inode_state()?.kind_data.versions_of().map(str::to_owned);
+            if let Some(key) = versions_of {
+                return self.version_lookup(client, &key, name).await;
+            }
+        }
+
+        let Some(full_path) = self.child_key(parent.full_key(), name) else {
+            // Not the escaped name of any key
+            return Ok(None);
+        };
+
+        if let Some(as_of) = self.config.as_of {
+            return self.as_of_lookup(client, &full_path, as_of).await;
+        }
 
         let mut full_path_suffixed = full_path.clone();
         full_path_suffixed.push('/');
@@ -780,9 +1473,8 @@ impl SuperblockInner {
             select_biased! {
                 result = file_lookup => {
                     match result {
-                        Ok(HeadObjectResult { object, .. }) => {
-                            let stat = InodeStat::for_file(object.size as usize, object.last_modified, Some(object.etag.clone()), object.storage_class, object.restore_status, self.config.cache_config.file_ttl);
-                            file_state = Some(stat);
+                        Ok(HeadObjectResult { object, user_metadata, .. }) => {
+                            file_state = Some(self.object_lookup(&object, Some(&user_metadata)));
                         }
                         // If the object is not found, might be a directory, so keep going
                         Err(ObjectClientError::ServiceError(HeadObjectError::NotFound)) => {},
@@ -832,7 +1524,13 @@ impl SuperblockInner {
                     if found_directory {
                         trace!(parent = ?parent_ino, ?name, "lookup ListObjects found a directory");
                         let stat = InodeStat::for_directory(self.mount_time, self.config.cache_config.dir_ttl);
-                        return Ok(Some(RemoteLookup { kind: InodeKind::Directory, stat }));
+                        return Ok(Some(RemoteLookup {
+                            kind: InodeKind::Directory,
+                            stat,
+                            maybe_symlink: false,
+                            versions_of: None,
+                            version_id: None,
+                        }));
                     }
                 }
             }
@@ -840,20 +1538,238 @@ impl SuperblockInner {
 
         // If we reach here, the ListObjects didn't find a shadowing directory, so we know we either
         // have a valid file, or both requests failed to find the object so the file must not exist remotely
-        if let Some(mut stat) = file_state {
-            trace!(parent = ?parent_ino, ?name, etag =? stat.etag, "found a regular file in S3");
+        if let Some(mut lookup) = file_state {
+            trace!(parent = ?parent_ino, ?name, etag =? lookup.stat.etag, "found a {} in S3", lookup.kind.as_str());
             // Update the validity of the stat in case the racing ListObjects took a long time
-            stat.update_validity(self.config.cache_config.file_ttl);
-            Ok(Some(RemoteLookup {
-                kind: InodeKind::File,
-                stat,
-            }))
+            lookup.stat.update_validity(self.config.cache_config.file_ttl);
+            Ok(Some(lookup))
+        } else if self.config.show_versions && name.len() > versions::VERSIONS_SUFFIX.len() {
+            match full_path.strip_suffix(versions::VERSIONS_SUFFIX) {
+                Some(key) => self.versions_directory_lookup(client, key).await,
+                None => Ok(None),
+            }
         } else {
             trace!(parent = ?parent_ino, ?name, "not found");
             Ok(None)
         }
     }
 
+    /// Look up the versions directory of the object with the given key. It only exists if the
+    /// object has noncurrent versions.
+    async fn versions_directory_lookup<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        key: &str,
+    ) -> Result<Option<RemoteLookup>, InodeError> {
+        let versions = versions::list_noncurrent_versions(client, &self.bucket, key).await?;
+        if versions.is_empty() {
+            trace!(key, "no noncurrent versions");
+            return Ok(None);
+        }
+        let stat = InodeStat::for_directory(self.mount_time, self.config.cache_config.dir_ttl);
+        Ok(Some(RemoteLookup {
+            kind: InodeKind::Directory,
+            stat,
+            maybe_symlink: false,
+            versions_of: Some(key.to_owned()),
+            version_id: None,
+        }))
+    }
+
+    /// Look up a file in the versions directory of the object with the given key
+    async fn version_lookup<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        key: &str,
+        name: &str,
+    ) -> Result<Option<RemoteLookup>, InodeError> {
+        let Some(version_id) = versions::version_id_from_name(name) else {
+            return Ok(None);
+        };
+        let versions = versions::list_noncurrent_versions(client, &self.bucket, key).await?;
+        let version = versions
+            .into_iter()
+            .find(|version| version.version_id == version_id && versions::version_file_name(version) == name);
+        Ok(version.map(|version| self.version_lookup_from_info(version)))
+    }
+
+    /// Build the [RemoteLookup] for a file pinned to a version of an object
+    fn version_lookup_from_info(&self, version: ObjectVersionInfo) -> RemoteLookup {
+        let version_id = version.version_id.clone();
+        let mut lookup = self.object_lookup(&versions::version_object_info(version), None);
+        lookup.version_id = Some(version_id);
+        lookup
+    }
+
+    /// Look up a name as of [SuperblockConfig::as_of]. Like [SuperblockInner::remote_lookup],
+    /// directories shadow files.
+    async fn as_of_lookup<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        full_path: &str,
+        as_of: OffsetDateTime,
+    ) -> Result<Option<RemoteLookup>, InodeError> {
+        let full_path_suffixed = format!("{full_path}/");
+        let (found_directory, version) = futures::try_join!(
+            as_of::directory_exists(client, &self.bucket, &full_path_suffixed),
+            as_of::object_version(client, &self.bucket, full_path, as_of),
+        )?;
+        if found_directory {
+            let stat = InodeStat::for_directory(self.mount_time, self.config.cache_config.dir_ttl);
+            return Ok(Some(RemoteLookup {
+                kind: InodeKind::Directory,
+                stat,
+                maybe_symlink: false,
+                versions_of: None,
+                version_id: None,
+            }));
+        }
+        Ok(version.map(|version| self.version_lookup_from_info(version)))
+    }
+
+    /// The key and version ID of the object version that a file is pinned to, or [None] if it
+    /// shows the current version of the object at its own key
+    fn object_version(&self, inode: &Inode) -> Result<Option<(String, String)>, InodeError> {
+        if inode.kind() != InodeKind::File {
+            return Ok(None);
+        }
+        let Some(version_id) = inode.get_inode_state()?.kind_data.version_id().map(str::to_owned) else {
+            return Ok(None);
+        };
+        // Files in versions directories show versions of the directory's object
+        let parent = self.get(inode.parent())?;
+        let key = match parent.get_inode_state()?.kind_data.versions_of() {
+            Some(key) => key.to_owned(),
+            None => inode.full_key().to_owned(),
+        };
+        Ok(Some((key, version_id)))
+    }
+
+    /// The key of the child with the given name of the directory with the given key, or [None] if
+    /// the name is not the escaped form of any key component when [SuperblockConfig::escape_names]
+    /// is set
+    fn child_key(&self, parent_key: &str, name: &str) -> Option<String> {
+        assert!(parent_key.is_empty() || parent_key.ends_with('/'));
+        if !self.config.escape_names {
+            return Some(format!("{parent_key}{name}"));
+        }
+        escape::unescape_name(name).map(|component| format!("{parent_key}{component}"))
+    }
+
+    /// Whether [SuperblockConfig::key_filter] shows the file or directory with the given key, which
+    /// must not end in `/`
+    fn is_shown(&self, key: &str, kind: InodeKind) -> bool {
+        let filter = &self.config.key_filter;
+        let path = key.strip_prefix(self.prefix.as_str()).unwrap_or(key);
+        match kind {
+            InodeKind::File | InodeKind::Symlink => filter.includes_file(path),
+            InodeKind::Directory => filter.includes_directory(path),
+        }
+    }
+
+    /// Fail if the inode can't be modified because it shows an object version: it's a versions
+    /// directory or a file in one, or the whole file system is a view of [SuperblockConfig::as_of].
+    fn check_not_version(&self, inode: &Inode) -> Result<(), InodeError> {
+        if self.config.as_of.is_some() {
+            return Err(InodeError::ReadOnlyVersion(inode.err()));
+        }
+        if !self.config.show_versions {
+            return Ok(());
+        }
+        let is_versions_directory = inode.get_inode_state()?.kind_data.versions_of().is_some();
+        if is_versions_directory || self.object_version(inode)?.is_some() {
+            return Err(InodeError::ReadOnlyVersion(inode.err()));
+        }
+        Ok(())
+    }
+
+    /// Build the [RemoteLookup] for an object, given its user-defined metadata if it is known. The
+    /// metadata is needed to recognize symlinks and to read POSIX attributes, if those are enabled.
+    fn object_lookup(&self, object: &ObjectInfo, user_metadata: Option<&HashMap<String, String>>) -> RemoteLookup {
+        let mut stat = InodeStat::for_file(
+            object.size as usize,
+            object.last_modified,
+            Some(object.etag.clone()),
+            object.storage_class.clone(),
+            object.restore_status,
+            self.config.cache_config.file_ttl,
+        );
+        let Some(user_metadata) = user_metadata else {
+            return RemoteLookup {
+                kind: InodeKind::File,
+                stat,
+                maybe_symlink: false,
+                versions_of: None,
+                version_id: None,
+            };
+        };
+
+        if self.config.posix_metadata {
+            posix_metadata::apply_posix_metadata(&mut stat, user_metadata);
+        }
+        let kind = if self.may_be_symlink(object.size) && symlink::is_symlink(user_metadata) {
+            InodeKind::Symlink
+        } else {
+            InodeKind::File
+        };
+        RemoteLookup {
+            kind,
+            stat,
+            maybe_symlink: false,
+            versions_of: None,
+            version_id: None,
+        }
+    }
+
+    /// Delete the marker object of an empty remote directory, if it has one. Fails if the directory
+    /// has any other content, remote or local.
+    async fn delete_directory_marker<OC: ObjectClient>(&self, client: &OC, inode: &Inode) -> Result<(), InodeError> {
+        let dir_key = inode.full_key();
+        if self.check_directory_empty(client, inode).await? {
+            debug!(key = dir_key, "rmdir will delete directory marker");
+            client
+                .delete_object(&self.bucket, dir_key)
+                .await
+                .map_err(|e| InodeError::client_error(e, "DeleteObject failed", &self.bucket, dir_key))?;
+        }
+        Ok(())
+    }
+
+    /// Check that a directory has no content, remote or local, other than its marker object.
+    /// Returns whether the directory has a marker object.
+    async fn check_directory_empty<OC: ObjectClient>(&self, client: &OC, inode: &Inode) -> Result<bool, InodeError> {
+        {
+            let state = inode.get_inode_state()?;
+            if let InodeKindData::Directory { writing_children, .. } = &state.kind_data {
+                if !writing_children.is_empty() {
+                    return Err(InodeError::DirectoryNotEmpty(inode.err()));
+                }
+            }
+        }
+
+        let dir_key = inode.full_key();
+        let result = client
+            .list_objects(&self.bucket, None, "/", 2, dir_key)
+            .await
+            .map_err(|e| InodeError::client_error(e, "ListObjectsV2 failed", &self.bucket, dir_key))?;
+        let has_marker = result.objects.iter().any(|object| object.key == dir_key);
+        if !result.common_prefixes.is_empty() || result.objects.len() > has_marker as usize {
+            return Err(InodeError::DirectoryNotEmpty(inode.err()));
+        }
+        Ok(has_marker)
+    }
+
+    /// Whether [SuperblockInner::object_lookup] needs the user-defined metadata of an object of
+    /// the given size, which ListObjectsV2 doesn't return, to get its attributes right
+    fn needs_user_metadata(&self, size: u64) -> bool {
+        self.config.posix_metadata || self.may_be_symlink(size)
+    }
+
+    /// Whether an object of the given size could be a symlink, depending on its metadata
+    fn may_be_symlink(&self, size: u64) -> bool {
+        self.config.allow_symlinks && symlink::is_target_size(size)
+    }
+
     /// Update the inode with the given name in a parent directory with the remote data.
     /// It may update or delete an existing inode, or insert a new one.
     pub fn update_from_remote(
@@ -895,7 +1811,7 @@ impl SuperblockInner {
     ) -> Result<Option<LookedUp>, InodeError> {
         let parent_state = parent.get_inode_state()?;
         let inode = match &parent_state.kind_data {
-            InodeKindData::File { .. } => unreachable!("we know parent is a directory"),
+            InodeKindData::File { .. } | InodeKindData::Symlink { .. } => unreachable!("we know parent is a directory"),
             InodeKindData::Directory { children, .. } => children.get(name),
         };
         match (remote, inode) {
@@ -903,7 +1819,7 @@ impl SuperblockInner {
             (Some(remote), Some(existing_inode)) => {
                 let mut existing_state = existing_inode.get_mut_inode_state()?;
                 let existing_is_remote = existing_state.write_status == WriteStatus::Remote;
-                if remote.kind == existing_inode.kind()
+                if remote.same_kind(existing_inode.kind(), &existing_state.kind_data)
                     && existing_is_remote
                     && existing_state.stat.etag == remote.stat.etag
                 {
@@ -932,7 +1848,7 @@ impl SuperblockInner {
     ) -> Result<LookedUp, InodeError> {
         let mut parent_state = parent.get_mut_inode_state()?;
         let inode = match &parent_state.kind_data {
-            InodeKindData::File { .. } => unreachable!("we know parent is a directory"),
+            InodeKindData::File { .. } | InodeKindData::Symlink { .. } => unreachable!("we know parent is a directory"),
             InodeKindData::Directory { children, .. } => children.get(name).cloned(),
         };
         match (remote, inode) {
@@ -941,6 +1857,7 @@ impl SuperblockInner {
                 let InodeKindData::Directory {
                     children,
                     writing_children,
+                    folded_children,
                     ..
                 } = &mut parent_state.kind_data
                 else {
@@ -950,7 +1867,7 @@ impl SuperblockInner {
                     let mut sync = existing_inode.get_mut_inode_state()?;
 
                     let validity = match existing_inode.kind() {
-                        InodeKind::File => self.config.cache_config.file_ttl,
+                        InodeKind::File | InodeKind::Symlink => self.config.cache_config.file_ttl,
                         InodeKind::Directory => self.config.cache_config.dir_ttl,
                     };
                     sync.stat.update_validity(validity);
@@ -966,13 +1883,14 @@ impl SuperblockInner {
                     // being written. It must have previously existed but been removed on the remote
                     // side.
                     children.remove(name);
+                    self.name_comparison.unindex_child(folded_children, name);
                     Err(InodeError::FileDoesNotExist(name.to_owned(), parent.err()))
                 }
             }
             (Some(remote), None) => {
                 let state = InodeState {
                     stat: remote.stat.clone(),
-                    kind_data: InodeKindData::default_for(remote.kind),
+                    kind_data: remote.kind_data(),
                     write_status: WriteStatus::Remote,
                     lookup_count: 0,
                     reader_count: 0,
@@ -993,7 +1911,7 @@ impl SuperblockInner {
 
                 // Remote files are always shadowed by existing local files/directories, so do
                 // nothing and return the existing inode.
-                if remote.kind == InodeKind::File && !existing_is_remote {
+                if remote.kind != InodeKind::Directory && !existing_is_remote {
                     return Ok(LookedUp {
                         inode: existing_inode.clone(),
                         stat: existing_state.stat.clone(),
@@ -1003,7 +1921,7 @@ impl SuperblockInner {
                 // Try to update in place if we can. The fast path does this too, but here we can
                 // also handle the case of a local directory becoming remote, which requires
                 // updating the parent.
-                let same_kind = remote.kind == existing_inode.kind();
+                let same_kind = remote.same_kind(existing_inode.kind(), &existing_state.kind_data);
                 let same_etag = existing_state.stat.etag == remote.stat.etag;
                 if same_kind && same_etag && (existing_is_remote || remote.kind == InodeKind::Directory) {
                     trace!(parent=?existing_inode.parent(), name=?existing_inode.name(), ino=?existing_inode.ino(), "updating inode in place (slow path)");
@@ -1041,7 +1959,7 @@ impl SuperblockInner {
                 );
                 let state = InodeState {
                     stat: remote.stat.clone(),
-                    kind_data: InodeKindData::default_for(remote.kind),
+                    kind_data: remote.kind_data(),
                     write_status: WriteStatus::Remote,
                     lookup_count: 0,
                     reader_count: 0,
@@ -1073,12 +1991,11 @@ impl SuperblockInner {
             warn!(?name, "invalid file name; {} will not be available", kind.as_str());
             return Err(InodeError::InvalidFileName(OsString::from(name)));
         }
+        let Some(mut full_key) = self.child_key(parent.full_key(), name) else {
+            return Err(InodeError::InvalidFileName(OsString::from(name)));
+        };
 
         let next_ino = self.next_ino.fetch_add(1, Ordering::SeqCst);
-
-        let mut full_key = parent.full_key().to_owned();
-        assert!(full_key.is_empty() || full_key.ends_with('/'));
-        full_key.push_str(name);
         if kind == InodeKind::Directory {
             full_key.push('/');
         }
@@ -1088,16 +2005,18 @@ impl SuperblockInner {
         let inode = Inode::new(next_ino, parent.ino(), name.to_owned(), full_key, kind, state);
 
         match &mut parent_locked.kind_data {
-            InodeKindData::File {} => {
+            InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
                 debug_assert!(false, "inodes never change kind");
                 return Err(InodeError::NotADirectory(parent.err()));
             }
             InodeKindData::Directory {
                 children,
                 writing_children,
+                folded_children,
                 ..
             } => {
                 let existing_inode = children.insert(name.to_owned(), inode.clone());
+                self.name_comparison.index_child(folded_children, name);
                 if is_new_file {
                     writing_children.insert(next_ino);
                 }
@@ -1109,6 +2028,375 @@ impl SuperblockInner {
 
         Ok(inode)
     }
+
+    /// Copy the object for a file inode to `dst_key` and delete the original, returning the stat of
+    /// the new object.
+    async fn rename_object<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        inode: &Inode,
+        dst_key: &str,
+    ) -> Result<InodeStat, InodeError> {
+        let bucket = self.bucket.as_str();
+        let src_key = inode.full_key();
+
+        debug!(?src_key, ?dst_key, "rename will copy and then delete the source key");
+        if let Err(e) = client
+            .copy_object(bucket, src_key, bucket, dst_key, &CopyObjectParams::new())
+            .await
+        {
+            error!(inode=%inode.err(), error=?e, "CopyObject failed for rename");
+            return Err(InodeError::client_error(e, "CopyObject failed", bucket, dst_key));
+        }
+
+        // A multi-part copy doesn't preserve the ETag, so we need to ask for the new object's metadata
+        // before we can serve reads from it.
+        let stat = match client.head_object(bucket, dst_key).await {
+            Ok(HeadObjectResult {
+                object, user_metadata, ..
+            }) => self.object_lookup(&object, Some(&user_metadata)).stat,
+            Err(e) => return Err(InodeError::client_error(e, "HeadObject failed", bucket, dst_key)),
+        };
+
+        if let Err(e) = client.delete_object(bucket, src_key).await {
+            // We don't try to undo the copy, since it might have replaced an existing object. Both
+            // keys will exist until the next lookup of either name.
+            error!(inode=%inode.err(), error=?e, "DeleteObject failed for rename");
+            return Err(InodeError::client_error(e, "DeleteObject failed", bucket, src_key));
+        }
+
+        Ok(stat)
+    }
+
+    /// Move every object under the prefix of a directory inode to `dst_prefix`.
+    ///
+    /// All objects are copied before any of them are deleted, so if a copy fails the source
+    /// directory is left intact (although some objects may already exist under the destination).
+    async fn rename_prefix<OC: ObjectClient>(
+        &self,
+        client: &OC,
+        dir: &Inode,
+        dst_prefix: &str,
+    ) -> Result<(), InodeError> {
+        let bucket = self.bucket.as_str();
+        let src_prefix = dir.full_key();
+        assert!(src_prefix.ends_with('/') && dst_prefix.ends_with('/'));
+
+        // Collect all the keys up front, so that new keys appearing during the rename don't affect it.
+        let mut keys = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let result = client
+                .list_objects(bucket, continuation_token.as_deref(), "", 1000, src_prefix)
+                .await
+                .map_err(|e| InodeError::client_error(e, "ListObjectsV2 failed", bucket, src_prefix))?;
+            keys.extend(result.objects.into_iter().map(|object| object.key));
+            continuation_token = result.next_continuation_token;
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        let total = keys.len();
+        info!(?src_prefix, ?dst_prefix, total, "renaming directory");
+
+        let copies = keys.iter().map(|key| {
+            let dst_key = format!("{dst_prefix}{}", &key[src_prefix.len()..]);
+            async move {
+                let result = client
+                    .copy_object(bucket, key, bucket, &dst_key, &CopyObjectParams::new())
+                    .await;
+                (result, dst_key)
+            }
+        });
+        let mut copies = futures::stream::iter(copies).buffer_unordered(DIRECTORY_RENAME_CONCURRENCY);
+        let mut copied = 0;
+        while let Some((result, dst_key)) = copies.next().await {
+            if let Err(e) = result {
+                metrics::counter!("fs.directory_rename.failures", "op" => "copy").increment(1);
+                error!(
+                    ?src_prefix, ?dst_prefix, copied, total, error=?e,
+                    "CopyObject failed for directory rename, no source objects were deleted",
+                );
+                return Err(InodeError::client_error(e, "CopyObject failed", bucket, &dst_key));
+            }
+            copied += 1;
+            metrics::counter!("fs.directory_rename.objects", "op" => "copy").increment(1);
+            if copied % DIRECTORY_RENAME_PROGRESS_INTERVAL == 0 {
+                info!(?src_prefix, ?dst_prefix, copied, total, "directory rename in progress");
+            }
+        }
+
+        let deletes = keys
+            .iter()
+            .map(|key| async move { (client.delete_object(bucket, key).await, key) });
+        let mut deletes = futures::stream::iter(deletes).buffer_unordered(DIRECTORY_RENAME_CONCURRENCY);
+        let mut deleted = 0;
+        while let Some((result, key)) = deletes.next().await {
+            if let Err(e) = result {
+                metrics::counter!("fs.directory_rename.failures", "op" => "delete").increment(1);
+                error!(
+                    ?src_prefix, ?dst_prefix, deleted, total, error=?e,
+                    "DeleteObject failed for directory rename, objects may remain under both prefixes",
+                );
+                return Err(InodeError::client_error(e, "DeleteObject failed", bucket, key));
+            }
+            deleted += 1;
+            metrics::counter!("fs.directory_rename.objects", "op" => "delete").increment(1);
+        }
+
+        info!(?src_prefix, ?dst_prefix, total, "finished renaming directory");
+        Ok(())
+    }
+
+    /// Move an inode to a new name in a (possibly different) parent directory, keeping its inode
+    /// number.
+    ///
+    /// Because the location of an [Inode] is immutable, this replaces it with a new [Inode] that
+    /// takes over its state. Any handles still holding the old [Inode] keep referring to the old key.
+    /// The descendants of a directory are replaced the same way, under their new keys, both the
+    /// cached ones and any others the kernel knows about.
+    fn reparent(
+        &self,
+        inode: &Inode,
+        src_parent: &Inode,
+        dst_parent: &Inode,
+        dst_name: &str,
+        dst_key: String,
+        stat: InodeStat,
+    ) -> Result<Inode, InodeError> {
+        // Acquire locks on both parents in ascending order by inode number (ancestors always have
+        // lower numbers than their descendants), and only once if they are the same directory.
+        let (mut src_parent_state, mut dst_parent_state) = if src_parent.ino() == dst_parent.ino() {
+            (src_parent.get_mut_inode_state()?, None)
+        } else if src_parent.ino() < dst_parent.ino() {
+            let src_parent_state = src_parent.get_mut_inode_state()?;
+            (src_parent_state, Some(dst_parent.get_mut_inode_state()?))
+        } else {
+            let dst_parent_state = dst_parent.get_mut_inode_state()?;
+            (src_parent.get_mut_inode_state()?, Some(dst_parent_state))
+        };
+
+        let state = inode.get_inode_state()?;
+        let mut descendants = Vec::new();
+        let kind_data = match &state.kind_data {
+            InodeKindData::Directory {
+                children,
+                folded_children,
+                ..
+            } => {
+                let (children, folded_children) =
+                    self.rekey_children(children, folded_children, &dst_key, &mut descendants)?;
+                self.rekey_uncached_descendants(inode, &dst_key, &mut descendants)?;
+                InodeKindData::Directory {
+                    children,
+                    writing_children: Default::default(),
+                    deleted: false,
+                    versions_of: None,
+                    folded_children,
+                }
+            }
+            InodeKindData::Symlink { target } => InodeKindData::Symlink { target: target.clone() },
+            InodeKindData::File { .. } => InodeKindData::default_for(inode.kind()),
+        };
+        let new_state = InodeState {
+            stat,
+            write_status: state.write_status,
+            kind_data,
+            lookup_count: state.lookup_count,
+            // Readers that are still open hold the old inode and will decrement its reader count
+            reader_count: 0,
+        };
+        let new_inode = Inode::new(
+            inode.ino(),
+            dst_parent.ino(),
+            dst_name.to_owned(),
+            dst_key,
+            inode.kind(),
+            new_state,
+        );
+        trace!(ino=?inode.ino(), parent=?dst_parent.ino(), name=?dst_name, "re-parenting inode");
+
+        let InodeKindData::Directory {
+            children,
+            folded_children,
+            ..
+        } = &mut src_parent_state.kind_data
+        else {
+            return Err(InodeError::NotADirectory(src_parent.err()));
+        };
+        if children.get(inode.name()).map(Inode::ino) == Some(inode.ino()) {
+            children.remove(inode.name());
+            self.name_comparison.unindex_child(folded_children, inode.name());
+        }
+
+        let dst_state = dst_parent_state.as_deref_mut().unwrap_or(&mut *src_parent_state);
+        let InodeKindData::Directory {
+            children,
+            writing_children,
+            folded_children,
+            ..
+        } = &mut dst_state.kind_data
+        else {
+            return Err(InodeError::NotADirectory(dst_parent.err()));
+        };
+        let replaced = children.insert(dst_name.to_owned(), new_inode.clone());
+        if let Some(replaced) = &replaced {
+            writing_children.remove(&replaced.ino());
+        }
+        self.name_comparison.index_child(folded_children, dst_name);
+
+        // Only replace the inodes the kernel knows about, the others get added on [remember].
+        let mut inodes = self.inodes.write().unwrap();
+        for replacement in std::iter::once(&new_inode).chain(&descendants) {
+            if inodes.get(&replacement.ino()).is_some() {
+                inodes.insert(replacement.ino(), replacement.clone());
+            }
+        }
+        drop(inodes);
+        drop(state);
+        drop(src_parent_state);
+        drop(dst_parent_state);
+
+        if let Some(replaced) = replaced {
+            Self::mark_replaced(&replaced);
+        }
+
+        Ok(new_inode)
+    }
+
+    /// Mark the inode of a destination that a rename replaced as stale. The kernel may still hold
+    /// it until it forgets it, so it stays in the superblock, but a replaced directory no longer
+    /// exists, like one removed by `rmdir`, and a replaced file must be looked up again, which
+    /// finds the inode that replaced it.
+    fn mark_replaced(replaced: &Inode) {
+        let Ok(mut state) = replaced.get_mut_inode_state() else {
+            return;
+        };
+        trace!(ino=?replaced.ino(), "rename replaced inode");
+        state.stat.update_validity(Duration::ZERO);
+        if let InodeKindData::Directory { deleted, .. } = &mut state.kind_data {
+            *deleted = true;
+        }
+    }
+
+    /// Build the children of a renamed directory, now at `dir_key`, by replacing each cached child
+    /// with an inode under the new key that keeps its inode number, recursively. The kernel moves
+    /// its own entries for the children along with the directory, so they must keep working.
+    ///
+    /// Returns the new children with their folded names, and adds every new inode to `descendants`.
+    /// Copies made by a multi-part CopyObject have new ETags, so the stats of the new inodes expire
+    /// immediately. Versions directories are left to be looked up again.
+    fn rekey_children(
+        &self,
+        children: &HashMap<String, Inode>,
+        folded_children: &HashMap<String, String>,
+        dir_key: &str,
+        descendants: &mut Vec<Inode>,
+    ) -> Result<(HashMap<String, Inode>, HashMap<String, String>), InodeError> {
+        let mut new_children = HashMap::with_capacity(children.len());
+        let mut new_folded_children = folded_children.clone();
+        for (name, child) in children {
+            let mut full_key = format!("{dir_key}{name}");
+            if child.kind() == InodeKind::Directory {
+                full_key.push('/');
+            }
+            match self.rekey_inode(child, full_key, descendants)? {
+                Some(new_child) => {
+                    new_children.insert(name.clone(), new_child);
+                }
+                None => self.name_comparison.unindex_child(&mut new_folded_children, name),
+            }
+        }
+        Ok((new_children, new_folded_children))
+    }
+
+    /// Rekey the inodes the kernel knows about under the old prefix of a renamed directory that
+    /// [Self::rekey_children] missed because they are not cached in their parent. These are the
+    /// same inodes that [Self::check_no_writing_descendants] checks.
+    fn rekey_uncached_descendants(
+        &self,
+        dir: &Inode,
+        dir_key: &str,
+        descendants: &mut Vec<Inode>,
+    ) -> Result<(), InodeError> {
+        let prefix = dir.full_key();
+        let mut uncached = self.known_descendants(dir);
+        // Rekey ancestors first, so the cached children they take along aren't rekeyed twice
+        uncached.sort_by_key(|inode| inode.full_key().len());
+        for inode in uncached {
+            if descendants.iter().any(|rekeyed| rekeyed.ino() == inode.ino()) {
+                continue;
+            }
+            let full_key = format!("{dir_key}{}", &inode.full_key()[prefix.len()..]);
+            self.rekey_inode(&inode, full_key, descendants)?;
+        }
+        Ok(())
+    }
+
+    /// Replace an inode under a renamed directory with an inode at `full_key` that keeps its inode
+    /// number, parent and name, along with its cached children. See [Self::rekey_children].
+    ///
+    /// Returns `None` for versions directories, which are left to be looked up again.
+    fn rekey_inode(
+        &self,
+        child: &Inode,
+        full_key: String,
+        descendants: &mut Vec<Inode>,
+    ) -> Result<Option<Inode>, InodeError> {
+        let state = child.get_inode_state()?;
+        let kind_data = match &state.kind_data {
+            InodeKindData::Directory {
+                versions_of: Some(_), ..
+            } => return Ok(None),
+            InodeKindData::Directory {
+                children,
+                folded_children,
+                deleted,
+                ..
+            } => {
+                let (children, folded_children) =
+                    self.rekey_children(children, folded_children, &full_key, descendants)?;
+                InodeKindData::Directory {
+                    children,
+                    writing_children: Default::default(),
+                    deleted: *deleted,
+                    versions_of: None,
+                    folded_children,
+                }
+            }
+            InodeKindData::File {
+                user_metadata,
+                version_id,
+            } => InodeKindData::File {
+                user_metadata: user_metadata.clone(),
+                version_id: version_id.clone(),
+            },
+            InodeKindData::Symlink { target } => InodeKindData::Symlink { target: target.clone() },
+        };
+        let mut stat = state.stat.clone();
+        stat.update_validity(Duration::ZERO);
+        let new_state = InodeState {
+            stat,
+            write_status: state.write_status,
+            kind_data,
+            lookup_count: state.lookup_count,
+            // Readers that are still open hold the old inode and will decrement its reader count
+            reader_count: 0,
+        };
+        let new_child = Inode::new(
+            child.ino(),
+            child.parent(),
+            child.name().to_owned(),
+            full_key,
+            child.kind(),
+            new_state,
+        );
+        trace!(ino=?child.ino(), parent=?child.parent(), name=?child.name(), "re-keying descendant of renamed directory");
+
+        descendants.push(new_child.clone());
+        Ok(Some(new_child))
+    }
 }
 
 /// Data from a remote object.
@@ -1116,6 +2404,34 @@ impl SuperblockInner {
 pub struct RemoteLookup {
     kind: InodeKind,
     stat: InodeStat,
+    /// Whether this is a file that may turn out to be a symlink once its metadata is read. An
+    /// existing symlink inode for the same object is kept as it is.
+    maybe_symlink: bool,
+    /// Key of the object whose versions are listed, for versions directories
+    versions_of: Option<String>,
+    /// Version of the object to pin reads to, for files that don't show the current version
+    version_id: Option<String>,
+}
+
+impl RemoteLookup {
+    fn kind_data(&self) -> InodeKindData {
+        let mut kind_data = InodeKindData::default_for(self.kind);
+        match &mut kind_data {
+            InodeKindData::File { version_id, .. } => version_id.clone_from(&self.version_id),
+            InodeKindData::Directory { versions_of, .. } => versions_of.clone_from(&self.versions_of),
+            InodeKindData::Symlink { .. } => {}
+        }
+        kind_data
+    }
+
+    /// Whether an existing inode with the given kind and data can be updated in place to match
+    /// this lookup
+    fn same_kind(&self, kind: InodeKind, kind_data: &InodeKindData) -> bool {
+        let same_kind = self.kind == kind || (self.maybe_symlink && kind == InodeKind::Symlink);
+        same_kind
+            && kind_data.versions_of() == self.versions_of.as_deref()
+            && kind_data.version_id() == self.version_id.as_deref()
+    }
 }
 
 /// Result of a call to [Superblock::lookup] or [Superblock::getattr]. `stat` is a copy of the
@@ -1147,9 +2463,22 @@ impl WriteHandle {
         Self { inner, inode }
     }
 
+    /// Record data written at the end of the file, which also updates its modification time
     pub fn inc_file_size(&self, len: usize) {
         let mut state = self.inode.inner.sync.write().unwrap();
         state.stat.size += len;
+        state.stat.mtime = OffsetDateTime::now_utc();
+    }
+
+    pub fn set_file_size(&self, size: usize) {
+        let mut state = self.inode.inner.sync.write().unwrap();
+        state.stat.size = size;
+    }
+
+    /// Update the modification time of the file after its content changed
+    pub fn touch(&self) {
+        let mut state = self.inode.inner.sync.write().unwrap();
+        state.stat.mtime = OffsetDateTime::now_utc();
     }
 
     /// Update status of the inode and of containing "local" directories.
@@ -1164,7 +2493,9 @@ impl WriteHandle {
                 assert!(visited.insert(ancestor_ino), "cycle detected in inode ancestors");
                 let ancestor = self.inner.get(ancestor_ino)?;
                 ancestors.push(ancestor.clone());
-                if ancestor.ino() == ROOT_INODE_NO || ancestor.get_inode_state()?.write_status == WriteStatus::Remote {
+                if ancestor.ino() == self.inner.root_ino()
+                    || ancestor.get_inode_state()?.write_status == WriteStatus::Remote
+                {
                     break;
                 }
                 ancestor_ino = ancestor.parent();
@@ -1193,7 +2524,9 @@ impl WriteHandle {
                     std::iter::once(self.inode.ino()).chain(ancestors.iter().map(|ancestor| ancestor.ino()));
                 for (ancestor_state, child_ino) in ancestors_states.iter_mut().rev().zip(children_inos) {
                     match &mut ancestor_state.kind_data {
-                        InodeKindData::File { .. } => unreachable!("we know the ancestor is a directory"),
+                        InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
+                            unreachable!("we know the ancestor is a directory")
+                        }
                         InodeKindData::Directory { writing_children, .. } => {
                             writing_children.remove(&child_ino);
                         }
@@ -1212,12 +2545,30 @@ impl WriteHandle {
 #[derive(Debug)]
 pub struct ReadHandle {
     inode: Inode,
+    /// Key of the object to read, which differs from the inode's key for files in versions directories
+    object_key: String,
+    /// Version of the object to read, or [None] for the current version
+    version_id: Option<String>,
 }
 
 impl ReadHandle {
     /// Create a new read handle
-    fn new(inode: Inode) -> Self {
-        Self { inode }
+    fn new(inode: Inode, object_key: String, version_id: Option<String>) -> Self {
+        Self {
+            inode,
+            object_key,
+            version_id,
+        }
+    }
+
+    /// Key of the object to read
+    pub fn object_key(&self) -> &str {
+        &self.object_key
+    }
+
+    /// Version of the object to read, or [None] for the current version
+    pub fn version_id(&self) -> Option<&str> {
+        self.version_id.as_deref()
     }
 
     /// Update status of the inode to reflect the read being finished
@@ -1411,10 +2762,23 @@ struct InodeState {
     reader_count: u64,
 }
 
+impl InodeState {
+    /// Whether the metadata of the object can still be changed. Metadata is sent when the upload
+    /// starts, so this is only possible for new files that have no data written to them yet.
+    fn is_metadata_writable(&self) -> bool {
+        match self.write_status {
+            WriteStatus::LocalUnopened => true,
+            WriteStatus::LocalOpen => self.stat.size == 0,
+            WriteStatus::Remote => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InodeKind {
     File,
     Directory,
+    Symlink,
 }
 
 impl InodeKind {
@@ -1422,6 +2786,7 @@ impl InodeKind {
         match self {
             InodeKind::File => "file",
             InodeKind::Directory => "directory",
+            InodeKind::Symlink => "symlink",
         }
     }
 }
@@ -1431,13 +2796,21 @@ impl From<InodeKind> for FileType {
         match kind {
             InodeKind::File => FileType::RegularFile,
             InodeKind::Directory => FileType::Directory,
+            InodeKind::Symlink => FileType::Symlink,
         }
     }
 }
 
 #[derive(Debug)]
 enum InodeKindData {
-    File {},
+    File {
+        /// User-defined metadata to attach to the object when it is uploaded. Only used while the
+        /// file is being written.
+        user_metadata: HashMap<String, String>,
+
+        /// The version of the object that reads are pinned to, or [None] for its current version
+        version_id: Option<String>,
+    },
     Directory {
         /// Mapping from child names to previously seen [Inode]s.
         ///
@@ -1451,18 +2824,50 @@ enum InodeKindData {
 
         /// True if this directory has been deleted (`rmdir`) from its parent
         deleted: bool,
+
+        /// If this is a versions directory, the key of the object whose versions it lists
+        versions_of: Option<String>,
+
+        /// Names of the [children](Self::Directory::children) folded by the superblock's
+        /// [NameComparison], mapped to their names. Empty if names are compared exactly.
+        folded_children: HashMap<String, String>,
+    },
+    Symlink {
+        /// Path the link points to, once it is known. The target of a remote symlink is read from
+        /// its object by [Superblock::readlink].
+        target: Option<Vec<u8>>,
     },
 }
 
 impl InodeKindData {
     fn default_for(kind: InodeKind) -> Self {
         match kind {
-            InodeKind::File => Self::File {},
+            InodeKind::File => Self::File {
+                user_metadata: Default::default(),
+                version_id: None,
+            },
             InodeKind::Directory => Self::Directory {
                 children: Default::default(),
                 writing_children: Default::default(),
                 deleted: false,
+                versions_of: None,
+                folded_children: Default::default(),
             },
+            InodeKind::Symlink => Self::Symlink { target: None },
+        }
+    }
+
+    fn version_id(&self) -> Option<&str> {
+        match self {
+            Self::File { version_id, .. } => version_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn versions_of(&self) -> Option<&str> {
+        match self {
+            Self::Directory { versions_of, .. } => versions_of.as_deref(),
+            _ => None,
         }
     }
 }
@@ -1487,6 +2892,13 @@ pub struct InodeStat {
     /// are only readable after restoration. For objects with other storage classes
     /// this field should be always `true`.
     pub is_readable: bool,
+
+    /// Permission bits, if known from the object's metadata or set locally
+    pub mode: Option<u16>,
+    /// Owner user id, if known from the object's metadata or set locally
+    pub uid: Option<u32>,
+    /// Owner group id, if known from the object's metadata or set locally
+    pub gid: Option<u32>,
 }
 
 /// Inode write status (local vs remote)
@@ -1544,6 +2956,9 @@ impl InodeStat {
             mtime: datetime,
             etag,
             is_readable,
+            mode: None,
+            uid: None,
+            gid: None,
         }
     }
 
@@ -1557,6 +2972,9 @@ impl InodeStat {
             mtime: datetime,
             etag: None,
             is_readable: true,
+            mode: None,
+            uid: None,
+            gid: None,
         }
     }
 
@@ -1587,6 +3005,14 @@ impl InodeMap {
         self.map.remove(ino).inspect(Self::remove_metrics)
     }
 
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn values(&self) -> impl Iterator<Item = &Inode> {
+        self.map.values()
+    }
+
     fn remove_metrics(inode: &Inode) {
         metrics::gauge!("fs.inodes").decrement(1.0);
         metrics::gauge!("fs.inode_kinds", "kind" => inode.kind().as_str()).decrement(1.0);
@@ -1629,10 +3055,22 @@ pub enum InodeError {
     DirectoryNotEmpty(InodeErrorInfo),
     #[error("inode {0} cannot be unlinked while being written")]
     UnlinkNotPermittedWhileWriting(InodeErrorInfo),
+    #[error("inode {0} cannot be renamed while being written")]
+    RenameNotPermittedWhileWriting(InodeErrorInfo),
+    #[error("directory cannot be renamed at inode {0}")]
+    CannotRenameDirectory(InodeErrorInfo),
+    #[error("user metadata cannot be modified at inode {0}")]
+    UserMetadataNotWritable(InodeErrorInfo),
+    #[error("inode {0} is not a symlink")]
+    NotASymlink(InodeErrorInfo),
     #[error("corrupted metadata for inode {0}")]
     CorruptedMetadata(InodeErrorInfo),
     #[error("inode {0} is a remote inode and its attributes cannot be modified")]
     SetAttrNotPermittedOnRemoteInode(InodeErrorInfo),
+    #[error("inode {0} is part of a versions directory and cannot be modified")]
+    ReadOnlyVersion(InodeErrorInfo),
+    #[error("key {0:?} is filtered out of the mount")]
+    FilteredOut(String),
     #[error("inode {old_inode} for remote key {remote_key:?} is stale, replaced by inode {new_inode}")]
     StaleInode {
         remote_key: String,
@@ -1857,6 +3295,7 @@ mod tests {
                     ..Default::default()
                 },
                 s3_personality: S3Personality::Standard,
+                ..Default::default()
             },
         );
 
@@ -1912,6 +3351,7 @@ mod tests {
                     ..Default::default()
                 },
                 s3_personality: S3Personality::Standard,
+                ..Default::default()
             },
         );
 
@@ -1962,7 +3402,7 @@ mod tests {
             InodeState {
                 write_status: WriteStatus::Remote,
                 stat: InodeStat::for_file(0, OffsetDateTime::now_utc(), None, None, None, Default::default()),
-                kind_data: InodeKindData::File {},
+                kind_data: InodeKindData::default_for(InodeKind::File),
                 lookup_count: 5,
                 reader_count: 0,
             },
@@ -2488,7 +3928,9 @@ mod tests {
             .get_inode_state()
             .expect("should get parent state with read lock");
         match &parent_state.kind_data {
-            InodeKindData::File {} => unreachable!("Parent can only be a Directory"),
+            InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
+                unreachable!("Parent can only be a Directory")
+            }
             InodeKindData::Directory {
                 children,
                 writing_children,
@@ -2580,6 +4022,733 @@ mod tests {
         assert_eq!(libc::ENOENT, err, "lookup should return no existing entry error");
     }
 
+    #[test_case(""; "unprefixed")]
+    #[test_case("test_prefix/"; "prefixed")]
+    #[tokio::test]
+    async fn test_rename(prefix: &str) {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let prefix = Prefix::new(prefix).expect("valid prefix");
+        let superblock = Superblock::new("test_bucket", &prefix, Default::default());
+
+        client.add_object(
+            &format!("{prefix}file.txt"),
+            MockObject::constant(0xaa, 30, ETag::for_tests()),
+        );
+        client.add_object(
+            &format!("{prefix}dir/other.txt"),
+            MockObject::constant(0xbb, 10, ETag::for_tests()),
+        );
+
+        let file = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "file.txt".as_ref())
+            .await
+            .expect("file should exist");
+        let dir = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dir".as_ref())
+            .await
+            .expect("dir should exist");
+
+        superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "file.txt".as_ref(),
+                dir.inode.ino(),
+                "renamed.txt".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect("rename should succeed");
+
+        assert!(!client.contains_key(&format!("{prefix}file.txt")));
+        assert!(client.contains_key(&format!("{prefix}dir/renamed.txt")));
+
+        let renamed = superblock
+            .lookup(&client, dir.inode.ino(), "renamed.txt".as_ref())
+            .await
+            .expect("renamed file should exist");
+        assert_eq!(renamed.inode.ino(), file.inode.ino(), "inode number should not change");
+        assert_eq!(renamed.inode.parent(), dir.inode.ino());
+        assert_eq!(renamed.inode.full_key(), format!("{prefix}dir/renamed.txt"));
+        assert_eq!(renamed.stat.size, 30);
+
+        let err = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "file.txt".as_ref())
+            .await
+            .expect_err("source should no longer exist");
+        assert_eq!(err.to_errno(), libc::ENOENT);
+
+        // The inode can be found by its number and still has both lookups
+        let getattr = superblock
+            .getattr(&client, file.inode.ino(), true)
+            .await
+            .expect("getattr should find the renamed inode");
+        assert_eq!(getattr.inode.name(), "renamed.txt");
+        superblock.forget(file.inode.ino(), 2);
+        assert!(superblock.inner.get(file.inode.ino()).is_err());
+    }
+
+    #[test_case(false; "overwrite disallowed")]
+    #[test_case(true; "overwrite allowed")]
+    #[tokio::test]
+    async fn test_rename_overwrite(allow_overwrite: bool) {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new("test_bucket", &Default::default(), Default::default());
+
+        client.add_object("src.txt", MockObject::constant(0xaa, 30, ETag::for_tests()));
+        client.add_object("dst.txt", MockObject::constant(0xbb, 10, ETag::for_tests()));
+        let src = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "src.txt".as_ref())
+            .await
+            .expect("src should exist");
+        let dst = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dst.txt".as_ref())
+            .await
+            .expect("dst should exist");
+
+        let result = superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "src.txt".as_ref(),
+                FUSE_ROOT_INODE,
+                "dst.txt".as_ref(),
+                allow_overwrite,
+                false,
+            )
+            .await;
+        if allow_overwrite {
+            result.expect("rename should overwrite the destination");
+            assert!(!client.contains_key("src.txt"));
+            let head = client.head_object("test_bucket", "dst.txt").await.unwrap();
+            assert_eq!(head.object.size, 30);
+
+            // The kernel may still hold the replaced inode, which is now stale
+            let err = superblock
+                .getattr(&client, dst.inode.ino(), false)
+                .await
+                .expect_err("replaced inode should be stale");
+            assert_eq!(err.to_errno(), libc::ESTALE);
+            let renamed = superblock
+                .getattr(&client, src.inode.ino(), false)
+                .await
+                .expect("renamed inode should exist");
+            assert_eq!(renamed.inode.full_key(), "dst.txt");
+            assert_eq!(renamed.stat.size, 30);
+        } else {
+            let err = result.expect_err("rename should not overwrite the destination");
+            assert_eq!(err.to_errno(), libc::EPERM);
+            assert!(client.contains_key("src.txt"));
+            let head = client.head_object("test_bucket", "dst.txt").await.unwrap();
+            assert_eq!(head.object.size, 10);
+        }
+
+        // RENAME_NOREPLACE never overwrites
+        client.add_object("src.txt", MockObject::constant(0xaa, 30, ETag::for_tests()));
+        let err = superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "src.txt".as_ref(),
+                FUSE_ROOT_INODE,
+                "dst.txt".as_ref(),
+                allow_overwrite,
+                true,
+            )
+            .await
+            .expect_err("rename with no_replace should fail if destination exists");
+        assert_eq!(err.to_errno(), libc::EEXIST);
+    }
+
+    #[tokio::test]
+    async fn test_rename_local_file() {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new("test_bucket", &Default::default(), Default::default());
+
+        superblock
+            .create(&client, FUSE_ROOT_INODE, "local.txt".as_ref(), InodeKind::File)
+            .await
+            .expect("create should succeed");
+
+        let err = superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "local.txt".as_ref(),
+                FUSE_ROOT_INODE,
+                "renamed.txt".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect_err("rename of a file being written should fail");
+        assert!(matches!(err, InodeError::RenameNotPermittedWhileWriting(_)));
+    }
+
+    #[test_case(true; "allow overwrite")]
+    #[test_case(false; "disallow overwrite")]
+    #[tokio::test]
+    async fn test_modify(allow_overwrite: bool) {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new("test_bucket", &Default::default(), Default::default());
+
+        client.add_object("file.txt", MockObject::constant(0xaa, 30, ETag::for_tests()));
+        let lookup = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "file.txt".as_ref())
+            .await
+            .expect("lookup should succeed");
+        let ino = lookup.inode.ino();
+
+        let user_metadata = HashMap::from([("owner".to_owned(), "data-team".to_owned())]);
+        let result = superblock
+            .modify(&client, ino, allow_overwrite, user_metadata.clone())
+            .await;
+        if !allow_overwrite {
+            assert!(matches!(result, Err(InodeError::InodeNotWritable(_))));
+            return;
+        }
+        let handle = result.expect("modify should succeed");
+
+        // The file keeps its content, and its metadata can be read back while it is being modified
+        let lookup = superblock.getattr(&client, ino, false).await.unwrap();
+        assert_eq!(lookup.stat.size, 30);
+        assert_eq!(superblock.user_metadata(ino).unwrap(), user_metadata);
+
+        let err = superblock
+            .modify(&client, ino, allow_overwrite, Default::default())
+            .await
+            .expect_err("file can only be modified by one handle at a time");
+        assert!(matches!(err, InodeError::InodeAlreadyWriting(_)));
+        superblock
+            .read(&client, ino)
+            .await
+            .expect_err("file should not be readable while being modified");
+
+        handle.finish().expect("finish should succeed");
+        let read_handle = superblock.read(&client, ino).await.expect("file should be readable");
+        let err = superblock
+            .modify(&client, ino, allow_overwrite, Default::default())
+            .await
+            .expect_err("file should not be modifiable while being read");
+        assert!(matches!(err, InodeError::InodeNotWritableWhileReading(_)));
+        read_handle.finish().unwrap();
+    }
+
+    #[test_case(""; "unprefixed")]
+    #[test_case("test_prefix/"; "prefixed")]
+    #[tokio::test]
+    async fn test_rename_directory(prefix: &str) {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let prefix = Prefix::new(prefix).expect("valid prefix");
+        let superblock = Superblock::new(
+            "test_bucket",
+            &prefix,
+            SuperblockConfig {
+                allow_directory_rename: true,
+                ..Default::default()
+            },
+        );
+
+        let keys = ["dir/a.txt", "dir/sub/b.txt", "dir/sub/deeper/c.txt", "dirx/d.txt"];
+        for key in keys {
+            client.add_object(&format!("{prefix}{key}"), b"hello".into());
+        }
+
+        let dir = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dir".as_ref())
+            .await
+            .expect("dir should exist");
+        let sub = superblock
+            .lookup(&client, dir.inode.ino(), "sub".as_ref())
+            .await
+            .expect("sub should exist");
+
+        superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "dir".as_ref(),
+                FUSE_ROOT_INODE,
+                "renamed".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect("rename should succeed");
+
+        for key in ["a.txt", "sub/b.txt", "sub/deeper/c.txt"] {
+            assert!(!client.contains_key(&format!("{prefix}dir/{key}")));
+            assert!(client.contains_key(&format!("{prefix}renamed/{key}")));
+        }
+        assert!(
+            client.contains_key(&format!("{prefix}dirx/d.txt")),
+            "sibling prefix should be untouched"
+        );
+
+        let renamed = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "renamed".as_ref())
+            .await
+            .expect("renamed dir should exist");
+        assert_eq!(renamed.inode.ino(), dir.inode.ino(), "inode number should not change");
+        assert_eq!(renamed.inode.full_key(), format!("{prefix}renamed/"));
+
+        // Cached children keep their inode numbers, which the kernel still holds, under the new prefix
+        let moved_sub = superblock
+            .inner
+            .get(sub.inode.ino())
+            .expect("sub should still be known");
+        assert_eq!(moved_sub.full_key(), format!("{prefix}renamed/sub/"));
+        assert_eq!(moved_sub.parent(), dir.inode.ino());
+        let new_sub = superblock
+            .lookup(&client, renamed.inode.ino(), "sub".as_ref())
+            .await
+            .expect("sub should exist under the renamed dir");
+        assert_eq!(new_sub.inode.ino(), sub.inode.ino());
+        assert_eq!(new_sub.inode.full_key(), format!("{prefix}renamed/sub/"));
+
+        let err = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dir".as_ref())
+            .await
+            .expect_err("source should no longer exist");
+        assert_eq!(err.to_errno(), libc::ENOENT);
+    }
+
+    #[test_case(false; "directory rename disallowed")]
+    #[test_case(true; "directory rename allowed")]
+    #[tokio::test]
+    async fn test_rename_directory_not_permitted(allow_directory_rename: bool) {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new(
+            "test_bucket",
+            &Default::default(),
+            SuperblockConfig {
+                allow_directory_rename,
+                ..Default::default()
+            },
+        );
+
+        client.add_object("dir/a.txt", b"hello".into());
+        client.add_object("other/b.txt", b"hello".into());
+
+        let dir = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dir".as_ref())
+            .await
+            .expect("dir should exist");
+        superblock
+            .create(&client, dir.inode.ino(), "local.txt".as_ref(), InodeKind::File)
+            .await
+            .expect("create should succeed");
+
+        let err = superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "dir".as_ref(),
+                FUSE_ROOT_INODE,
+                "renamed".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect_err("rename should fail");
+        if allow_directory_rename {
+            assert!(matches!(err, InodeError::RenameNotPermittedWhileWriting(_)));
+        } else {
+            assert!(matches!(err, InodeError::CannotRenameDirectory(_)));
+        }
+        assert!(client.contains_key("dir/a.txt"));
+
+        if allow_directory_rename {
+            let err = superblock
+                .rename(
+                    &client,
+                    FUSE_ROOT_INODE,
+                    "other".as_ref(),
+                    FUSE_ROOT_INODE,
+                    "dir".as_ref(),
+                    false,
+                    false,
+                )
+                .await
+                .expect_err("rename over an existing directory should fail");
+            assert_eq!(err.to_errno(), libc::ENOTEMPTY);
+            assert!(client.contains_key("other/b.txt"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rename_directory_with_uncached_writing_descendant() {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new(
+            "test_bucket",
+            &Default::default(),
+            SuperblockConfig {
+                allow_directory_rename: true,
+                ..Default::default()
+            },
+        );
+
+        client.add_object("dir/sub/a.txt", b"hello".into());
+        let dir = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dir".as_ref())
+            .await
+            .expect("dir should exist");
+        let sub = superblock
+            .lookup(&client, dir.inode.ino(), "sub".as_ref())
+            .await
+            .expect("sub should exist");
+        superblock
+            .create(&client, sub.inode.ino(), "local.txt".as_ref(), InodeKind::File)
+            .await
+            .expect("create should succeed");
+
+        // The directory's cache loses track of the subdirectory, but the kernel still holds the new file
+        {
+            let mut dir_state = dir.inode.get_mut_inode_state().unwrap();
+            let InodeKindData::Directory { children, .. } = &mut dir_state.kind_data else {
+                panic!("dir is a directory");
+            };
+            children.clear();
+        }
+
+        let err = superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "dir".as_ref(),
+                FUSE_ROOT_INODE,
+                "renamed".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect_err("rename should fail");
+        assert!(matches!(err, InodeError::RenameNotPermittedWhileWriting(_)));
+        assert!(client.contains_key("dir/sub/a.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_rename_directory_with_uncached_descendant() {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new(
+            "test_bucket",
+            &Default::default(),
+            SuperblockConfig {
+                allow_directory_rename: true,
+                ..Default::default()
+            },
+        );
+
+        client.add_object("dir/sub/a.txt", b"hello".into());
+        let dir = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dir".as_ref())
+            .await
+            .expect("dir should exist");
+        let sub = superblock
+            .lookup(&client, dir.inode.ino(), "sub".as_ref())
+            .await
+            .expect("sub should exist");
+        let file = superblock
+            .lookup(&client, sub.inode.ino(), "a.txt".as_ref())
+            .await
+            .expect("file should exist");
+
+        // The directory's cache loses track of the subdirectory, but the kernel still holds it and its file
+        {
+            let mut dir_state = dir.inode.get_mut_inode_state().unwrap();
+            let InodeKindData::Directory { children, .. } = &mut dir_state.kind_data else {
+                panic!("dir is a directory");
+            };
+            children.clear();
+        }
+
+        superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "dir".as_ref(),
+                FUSE_ROOT_INODE,
+                "renamed".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect("rename should succeed");
+
+        for (ino, key) in [
+            (sub.inode.ino(), "renamed/sub/"),
+            (file.inode.ino(), "renamed/sub/a.txt"),
+        ] {
+            let moved = superblock.inner.get(ino).expect("inode should still be known");
+            assert_eq!(moved.full_key(), key);
+        }
+        let moved_sub = superblock.inner.get(sub.inode.ino()).unwrap();
+        let moved_file = superblock
+            .lookup(&client, moved_sub.ino(), "a.txt".as_ref())
+            .await
+            .expect("file should exist under the renamed dir");
+        assert_eq!(moved_file.inode.ino(), file.inode.ino());
+    }
+
+    #[test_case(false; "remote directory")]
+    #[test_case(true; "local directory")]
+    #[tokio::test]
+    async fn test_rename_directory_over_empty_directory(local: bool) {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new(
+            "test_bucket",
+            &Default::default(),
+            SuperblockConfig {
+                allow_directory_rename: true,
+                ..Default::default()
+            },
+        );
+
+        client.add_object("dir/a.txt", b"hello".into());
+        client.add_object("full/b.txt", b"hello".into());
+        if local {
+            superblock
+                .create(&client, FUSE_ROOT_INODE, "empty".as_ref(), InodeKind::Directory)
+                .await
+                .expect("mkdir should succeed");
+        } else {
+            client.add_object("empty/", b"".into());
+        }
+        let empty = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "empty".as_ref())
+            .await
+            .expect("empty dir should exist");
+
+        let err = superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "dir".as_ref(),
+                FUSE_ROOT_INODE,
+                "full".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect_err("rename over a non-empty directory should fail");
+        assert_eq!(err.to_errno(), libc::ENOTEMPTY);
+        assert!(client.contains_key("dir/a.txt"));
+
+        superblock
+            .rename(
+                &client,
+                FUSE_ROOT_INODE,
+                "dir".as_ref(),
+                FUSE_ROOT_INODE,
+                "empty".as_ref(),
+                false,
+                false,
+            )
+            .await
+            .expect("rename over an empty directory should succeed");
+        assert!(!client.contains_key("dir/a.txt"));
+        assert!(client.contains_key("empty/a.txt"));
+
+        let err = superblock
+            .getattr(&client, empty.inode.ino(), false)
+            .await
+            .expect_err("replaced directory should no longer exist");
+        assert_eq!(err.to_errno(), libc::ENOENT);
+
+        let renamed = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "empty".as_ref())
+            .await
+            .expect("renamed dir should exist");
+        superblock
+            .lookup(&client, renamed.inode.ino(), "a.txt".as_ref())
+            .await
+            .expect("file should exist under the renamed dir");
+    }
+
+    #[test_case(false; "symlinks disabled")]
+    #[test_case(true; "symlinks enabled")]
+    #[tokio::test]
+    async fn test_symlink_lookup_and_readdir(allow_symlinks: bool) {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new(
+            "test_bucket",
+            &Default::default(),
+            SuperblockConfig {
+                allow_symlinks,
+                ..Default::default()
+            },
+        );
+
+        let target = b"../target file";
+        let mut link = MockObject::from_bytes(target, ETag::from_object_bytes(target));
+        link.set_user_metadata(symlink_metadata());
+        client.add_object("dir/link", link);
+        client.add_object("dir/file", MockObject::constant(0xaa, 30, ETag::for_tests()));
+
+        let dir = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "dir".as_ref())
+            .await
+            .expect("dir should exist");
+        async fn readdir_kinds(
+            superblock: &Superblock,
+            client: &Arc<MockClient>,
+            dir_ino: InodeNo,
+        ) -> Vec<(String, InodeKind, bool)> {
+            let dir_handle = superblock.readdir(client, dir_ino, 10).await.unwrap();
+            let entries = dir_handle.collect(client).await.unwrap();
+            entries
+                .iter()
+                .map(|entry| {
+                    (
+                        entry.inode.name().to_owned(),
+                        entry.inode.kind(),
+                        entry.validity().is_zero(),
+                    )
+                })
+                .collect()
+        }
+
+        // Without the metadata, a small object might be a symlink, so it needs a lookup before use
+        assert_eq!(
+            readdir_kinds(&superblock, &client, dir.inode.ino()).await,
+            &[
+                ("file".to_owned(), InodeKind::File, allow_symlinks),
+                ("link".to_owned(), InodeKind::File, allow_symlinks),
+            ]
+        );
+
+        let expected_kind = if allow_symlinks {
+            InodeKind::Symlink
+        } else {
+            InodeKind::File
+        };
+        let lookup = superblock
+            .lookup(&client, dir.inode.ino(), "link".as_ref())
+            .await
+            .expect("link should exist");
+        assert_eq!(lookup.inode.kind(), expected_kind);
+        assert_eq!(lookup.stat.size, target.len());
+        let result = superblock.readlink(&client, lookup.inode.ino()).await;
+        if allow_symlinks {
+            assert_eq!(result.expect("readlink should succeed"), target);
+        } else {
+            assert_eq!(result.expect_err("not a symlink").to_errno(), libc::EINVAL);
+        }
+
+        // Once looked up, the symlink stays one when the directory is listed again
+        let link_kind = readdir_kinds(&superblock, &client, dir.inode.ino()).await[1].1;
+        assert_eq!(link_kind, expected_kind);
+    }
+
+    #[tokio::test]
+    async fn test_symlink_replaced() {
+        let client_config = MockClientConfig {
+            bucket: "test_bucket".to_string(),
+            part_size: 1024 * 1024,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(client_config));
+        let superblock = Superblock::new(
+            "test_bucket",
+            &Default::default(),
+            SuperblockConfig {
+                cache_config: CacheConfig {
+                    file_ttl: std::time::Duration::ZERO,
+                    ..Default::default()
+                },
+                allow_symlinks: true,
+                ..Default::default()
+            },
+        );
+
+        let mut link = MockObject::from_bytes(b"first", ETag::from_object_bytes(b"first"));
+        link.set_user_metadata(symlink_metadata());
+        client.add_object("link", link);
+        let lookup = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "link".as_ref())
+            .await
+            .expect("link should exist");
+        assert_eq!(
+            superblock.readlink(&client, lookup.inode.ino()).await.unwrap(),
+            b"first"
+        );
+
+        // The cached target is forgotten when the object changes
+        let mut link = MockObject::from_bytes(b"second", ETag::from_object_bytes(b"second"));
+        link.set_user_metadata(symlink_metadata());
+        client.add_object("link", link);
+        let lookup = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "link".as_ref())
+            .await
+            .expect("link should exist");
+        assert_eq!(
+            superblock.readlink(&client, lookup.inode.ino()).await.unwrap(),
+            b"second"
+        );
+
+        // A regular file with the same content replaces the symlink
+        client.add_object(
+            "link",
+            MockObject::from_bytes(b"second", ETag::from_object_bytes(b"second")),
+        );
+        let lookup = superblock
+            .lookup(&client, FUSE_ROOT_INODE, "link".as_ref())
+            .await
+            .expect("file should exist");
+        assert_eq!(lookup.inode.kind(), InodeKind::File);
+    }
+
     #[tokio::test]
     async fn test_unlink_verify_checksum() {
         let client_config = MockClientConfig {
@@ -2615,7 +4784,7 @@ mod tests {
                         NEVER_EXPIRE_TTL,
                     ),
                     write_status: WriteStatus::Remote,
-                    kind_data: InodeKindData::File {},
+                    kind_data: InodeKindData::default_for(InodeKind::File),
                     lookup_count: 1,
                     reader_count: 0,
                 }),
@@ -2629,7 +4798,7 @@ mod tests {
             let parent = inodes.get(&parent_ino).unwrap();
             let mut parent_state = parent.get_mut_inode_state().unwrap();
             match &mut parent_state.kind_data {
-                InodeKindData::File {} => panic!("root is always a directory"),
+                InodeKindData::File { .. } | InodeKindData::Symlink { .. } => panic!("root is always a directory"),
                 InodeKindData::Directory { children, .. } => _ = children.insert(file_name.into(), inode.clone()),
             }
         }
@@ -2921,7 +5090,7 @@ mod tests {
                 sync: RwLock::new(InodeState {
                     write_status: WriteStatus::LocalOpen,
                     stat: InodeStat::for_file(0, OffsetDateTime::UNIX_EPOCH, None, None, None, Default::default()),
-                    kind_data: InodeKindData::File {},
+                    kind_data: InodeKindData::default_for(InodeKind::File),
                     lookup_count: 5,
                     reader_count: 0,
                 }),
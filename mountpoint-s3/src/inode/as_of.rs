@@ -0,0 +1,219 @@
+//! A point-in-time view of a versioned bucket.
+//!
+//! When [SuperblockConfig::as_of](super::SuperblockConfig::as_of) is set, each key shows the newest
+//! of its versions that was created at or before that time, and is hidden if that version is a
+//! delete marker or if the key has no versions that old. Lookups and listings use
+//! ListObjectVersions rather than HeadObject and ListObjectsV2, and reads are pinned to the chosen
+//! versions.
+//!
+//! Directories are only implied by common prefixes, which ListObjectVersions returns whenever any
+//! version of any key under them exists, so a directory can appear even if all of its objects were
+//! created after the timestamp or deleted before it.
+
+use mountpoint_s3_client::types::{ListObjectVersionsResult, ObjectVersionEntry, ObjectVersionInfo};
+use mountpoint_s3_client::ObjectClient;
+use time::OffsetDateTime;
+use tracing::trace;
+
+use super::InodeError;
+
+/// Number of versions to ask for in each ListObjectVersions request made by a lookup
+const LOOKUP_PAGE_SIZE: usize = 1000;
+
+/// Picks the version of each key as of a point in time from consecutive pages of a
+/// ListObjectVersions listing
+#[derive(Debug)]
+pub(super) struct VersionPicker {
+    as_of: OffsetDateTime,
+    /// The last key whose version has been picked. The rest of its (older) versions, possibly on
+    /// the next page, are skipped.
+    picked_key: Option<String>,
+}
+
+impl VersionPicker {
+    pub(super) fn new(as_of: OffsetDateTime) -> Self {
+        Self {
+            as_of,
+            picked_key: None,
+        }
+    }
+
+    /// The versions picked from the next page of a listing, in key order. Keys whose picked version
+    /// is a delete marker are omitted.
+    pub(super) fn pick(&mut self, mut entries: Vec<ObjectVersionEntry>) -> Vec<ObjectVersionInfo> {
+        // Order the entries of each key newest first. LastModified only has a resolution of one
+        // second, so break ties with IsLatest and then with the listing order, which S3 already
+        // gives newest first (the sort is stable).
+        entries.sort_by(|entry1, entry2| {
+            entry1
+                .key()
+                .cmp(entry2.key())
+                .then(entry2.last_modified().cmp(&entry1.last_modified()))
+                .then(entry2.is_latest().cmp(&entry1.is_latest()))
+        });
+
+        let mut picked = Vec::new();
+        for entry in entries {
+            if entry.last_modified() > self.as_of || self.picked_key.as_deref() == Some(entry.key()) {
+                continue;
+            }
+            self.picked_key = Some(entry.key().to_owned());
+            if let ObjectVersionEntry::Version(version) = entry {
+                picked.push(version);
+            }
+        }
+        picked
+    }
+}
+
+/// Find the version of the object with the given key as of the given time, if it existed then
+pub(super) async fn object_version<OC: ObjectClient>(
+    client: &OC,
+    bucket: &str,
+    key: &str,
+    as_of: OffsetDateTime,
+) -> Result<Option<ObjectVersionInfo>, InodeError> {
+    let mut picker = VersionPicker::new(as_of);
+    let mut key_marker = None;
+    let mut version_id_marker = None;
+    loop {
+        let ListObjectVersionsResult {
+            versions,
+            next_key_marker,
+            next_version_id_marker,
+            ..
+        } = client
+            .list_object_versions(
+                bucket,
+                key_marker.as_deref(),
+                version_id_marker.as_deref(),
+                "",
+                LOOKUP_PAGE_SIZE,
+                key,
+            )
+            .await
+            .map_err(|e| InodeError::client_error(e, "ListObjectVersions failed", bucket, key))?;
+
+        // The versions of `key` come before those of any longer key that has it as a prefix
+        let past_key = versions.iter().any(|entry| entry.key() != key);
+        if let Some(version) = picker.pick(versions).into_iter().find(|v| v.key == key) {
+            trace!(key, version_id = version.version_id, "picked version");
+            return Ok(Some(version));
+        }
+        if picker.picked_key.as_deref() == Some(key) || past_key || next_key_marker.as_deref() != Some(key) {
+            trace!(key, "no version as of the timestamp");
+            return Ok(None);
+        }
+        key_marker = next_key_marker;
+        version_id_marker = next_version_id_marker;
+    }
+}
+
+/// Whether there is a directory with the given prefix, which must end in `/`
+pub(super) async fn directory_exists<OC: ObjectClient>(
+    client: &OC,
+    bucket: &str,
+    prefix: &str,
+) -> Result<bool, InodeError> {
+    let result = client
+        .list_object_versions(bucket, None, None, "/", 1, prefix)
+        .await
+        .map_err(|e| InodeError::client_error(e, "ListObjectVersions failed", bucket, prefix))?;
+    Ok(!result.versions.is_empty() || !result.common_prefixes.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use mountpoint_s3_client::types::DeleteMarkerInfo;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn version(key: &str, version_id: &str, last_modified: OffsetDateTime) -> ObjectVersionEntry {
+        ObjectVersionEntry::Version(ObjectVersionInfo {
+            key: key.to_owned(),
+            version_id: version_id.to_owned(),
+            is_latest: false,
+            size: 0,
+            last_modified,
+            storage_class: None,
+            etag: "\"etag\"".to_owned(),
+        })
+    }
+
+    fn delete_marker(key: &str, last_modified: OffsetDateTime) -> ObjectVersionEntry {
+        ObjectVersionEntry::DeleteMarker(DeleteMarkerInfo {
+            key: key.to_owned(),
+            version_id: "marker".to_owned(),
+            is_latest: false,
+            last_modified,
+        })
+    }
+
+    fn latest(mut entry: ObjectVersionEntry) -> ObjectVersionEntry {
+        match &mut entry {
+            ObjectVersionEntry::Version(version) => version.is_latest = true,
+            ObjectVersionEntry::DeleteMarker(marker) => marker.is_latest = true,
+        }
+        entry
+    }
+
+    #[test]
+    fn test_pick_versions() {
+        let mut picker = VersionPicker::new(datetime!(2024-06-01 00:00 UTC));
+
+        // `b` was deleted before the timestamp, and `d` after it
+        let entries = vec![
+            latest(version("a", "a2", datetime!(2024-07-01 00:00 UTC))),
+            version("a", "a1", datetime!(2024-05-01 00:00 UTC)),
+            latest(delete_marker("b", datetime!(2024-02-01 00:00 UTC))),
+            version("b", "b1", datetime!(2024-01-01 00:00 UTC)),
+            latest(version("c", "c1", datetime!(2024-07-01 00:00 UTC))),
+            latest(delete_marker("d", datetime!(2024-08-01 00:00 UTC))),
+            version("d", "d2", datetime!(2024-03-01 00:00 UTC)),
+        ];
+        let picked = picker.pick(entries);
+        let picked: Vec<_> = picked.iter().map(|v| v.version_id.as_str()).collect();
+        assert_eq!(picked, ["a1", "d2"]);
+
+        // Older versions of a picked key on the next page are skipped
+        let picked = picker.pick(vec![
+            version("d", "d1", datetime!(2024-02-01 00:00 UTC)),
+            latest(version("e", "e1", datetime!(2024-02-01 00:00 UTC))),
+        ]);
+        let picked: Vec<_> = picked.iter().map(|v| v.version_id.as_str()).collect();
+        assert_eq!(picked, ["e1"]);
+    }
+
+    #[test]
+    fn test_pick_versions_with_equal_timestamps() {
+        let time = datetime!(2024-06-01 00:00 UTC);
+        let mut picker = VersionPicker::new(time);
+
+        let entries = vec![
+            // `a` was created and then deleted in the same second, and recreated after the timestamp
+            latest(version("a", "a2", datetime!(2024-07-01 00:00 UTC))),
+            delete_marker("a", time),
+            version("a", "a1", time),
+            // `b` was overwritten in the same second
+            latest(version("b", "b2", time)),
+            version("b", "b1", time),
+            // `c` was deleted and then recreated in the same second
+            latest(version("c", "c2", time)),
+            delete_marker("c", time),
+            version("c", "c1", datetime!(2024-05-01 00:00 UTC)),
+            // `d` was created and then deleted in the same second
+            latest(delete_marker("d", time)),
+            version("d", "d1", time),
+        ];
+        let picked = picker.pick(entries);
+        let picked: Vec<_> = picked.iter().map(|v| v.version_id.as_str()).collect();
+        assert_eq!(picked, ["b2", "c2"]);
+
+        // The latest version wins a tie even if it isn't listed first
+        let mut picker = VersionPicker::new(time);
+        let picked = picker.pick(vec![version("e", "e1", time), latest(version("e", "e2", time))]);
+        let picked: Vec<_> = picked.iter().map(|v| v.version_id.as_str()).collect();
+        assert_eq!(picked, ["e2"]);
+    }
+}
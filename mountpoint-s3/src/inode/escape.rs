@@ -0,0 +1,89 @@
+//! Reversible escaping of the components of object keys that are not valid file names.
+//!
+//! When [SuperblockConfig::escape_names](super::SuperblockConfig::escape_names) is set, each
+//! component of a key (the text between two `/` delimiters) is shown as a file name with `%`
+//! escapes, rather than being hidden when it isn't a valid name:
+//!
+//! * `%` is escaped as `%25` and NUL as `%00`, wherever they appear;
+//! * the components `.` and `..` are escaped as `%2E` and `%2E%2E`;
+//! * an empty component, like the one between the slashes of `a//b`, is shown as `%`.
+//!
+//! Every component has exactly one escaped name, and names that are not the escaped form of any
+//! component (like `%41` or `100%`) don't refer to any key, so the mapping is reversible.
+
+use std::fmt::Write;
+
+/// The name of the empty component
+const EMPTY_NAME: &str = "%";
+
+/// The file name that shows a component of an object key
+pub(super) fn escape_name(component: &str) -> String {
+    match component {
+        "" => EMPTY_NAME.to_owned(),
+        "." => "%2E".to_owned(),
+        ".." => "%2E%2E".to_owned(),
+        _ => {
+            let mut name = String::with_capacity(component.len());
+            for c in component.chars() {
+                match c {
+                    '%' | '\0' => write!(name, "%{:02X}", c as u32).unwrap(),
+                    c => name.push(c),
+                }
+            }
+            name
+        }
+    }
+}
+
+/// The component of an object key shown by a file name, if the name is the escaped form of one
+pub(super) fn unescape_name(name: &str) -> Option<String> {
+    if name == EMPTY_NAME {
+        return Some(String::new());
+    }
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    let component = String::from_utf8(bytes).ok()?;
+    // Only accept the one escaped form of each component, so that no two names share a key
+    (!component.contains('/') && escape_name(&component) == name).then_some(component)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("file.txt", "file.txt"; "valid name")]
+    #[test_case("", "%"; "empty")]
+    #[test_case(".", "%2E"; "dot")]
+    #[test_case("..", "%2E%2E"; "dot dot")]
+    #[test_case("...", "..."; "three dots")]
+    #[test_case("100%", "100%25"; "percent")]
+    #[test_case("a\0b", "a%00b"; "nul")]
+    #[test_case("%2E", "%252E"; "escaped dot")]
+    fn test_escape_name(component: &str, name: &str) {
+        assert_eq!(escape_name(component), name);
+        assert_eq!(unescape_name(name).as_deref(), Some(component));
+    }
+
+    #[test_case("100%"; "trailing percent")]
+    #[test_case("%4"; "short escape")]
+    #[test_case("%zz"; "not hex")]
+    #[test_case("%41"; "unnecessary escape")]
+    #[test_case("%2e"; "lowercase escape")]
+    #[test_case("%2F"; "slash")]
+    #[test_case("a%2E"; "dot inside a name")]
+    fn test_unescape_invalid_name(name: &str) {
+        assert_eq!(unescape_name(name), None);
+    }
+}
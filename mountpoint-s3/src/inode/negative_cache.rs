@@ -2,7 +2,7 @@ use std::time::{Duration, Instant};
 
 use linked_hash_map::LinkedHashMap;
 
-use super::{expiry::Expiry, InodeNo};
+use super::{expiry::Expiry, InodeNo, NameComparison};
 
 use crate::sync::RwLock;
 
@@ -16,6 +16,8 @@ pub struct NegativeCache {
     max_size: usize,
     /// TTL of a key at insertion.
     ttl: Duration,
+    /// How child names are compared. An entry covers every name equal to its own.
+    name_comparison: NameComparison,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq)]
@@ -25,21 +27,26 @@ struct Key {
 }
 
 impl NegativeCache {
-    pub fn new(max_size: usize, ttl: Duration) -> Self {
+    pub fn new(max_size: usize, ttl: Duration, name_comparison: NameComparison) -> Self {
         Self {
             map: RwLock::new(Default::default()),
             max_size,
             ttl,
+            name_comparison,
+        }
+    }
+
+    fn key(&self, parent_ino: InodeNo, child_name: &str) -> Key {
+        Key {
+            parent_ino,
+            child_name: self.name_comparison.fold(child_name).into_owned(),
         }
     }
 
     /// Check whether the cache contains a **current** entry for the given
     /// (`parent_ino`, `child_name`) pair.
     pub fn contains(&self, parent_ino: InodeNo, child_name: &str) -> bool {
-        let key = Key {
-            parent_ino,
-            child_name: child_name.to_owned(),
-        };
+        let key = self.key(parent_ino, child_name);
         let start = Instant::now();
         let contains_current = self
             .map
@@ -58,10 +65,7 @@ impl NegativeCache {
 
     /// Remove an entry from the cache. If the entry was not present, this is a no-op.
     pub fn remove(&self, parent_ino: InodeNo, child_name: &str) {
-        let key = Key {
-            parent_ino,
-            child_name: child_name.to_owned(),
-        };
+        let key = self.key(parent_ino, child_name);
         let start = Instant::now();
         let mut map = self.map.write().unwrap();
         if map.remove(&key).is_some() {
@@ -80,10 +84,7 @@ impl NegativeCache {
     /// that have already expired.
     pub fn insert(&self, parent_ino: InodeNo, child_name: &str) {
         let expiry = Expiry::from_now(self.ttl);
-        let key = Key {
-            parent_ino,
-            child_name: child_name.to_owned(),
-        };
+        let key = self.key(parent_ino, child_name);
         let start = Instant::now();
         let mut map = self.map.write().unwrap();
         if map.insert(key, expiry).is_none() {
@@ -117,11 +118,11 @@ mod tests {
     use std::thread::sleep;
     use std::time::{Duration, Instant};
 
-    use super::NegativeCache;
+    use super::{NameComparison, NegativeCache};
 
     #[test]
     fn test_contains() {
-        let cache = NegativeCache::new(100, Duration::from_secs(60));
+        let cache = NegativeCache::new(100, Duration::from_secs(60), Default::default());
 
         cache.insert(1, "child1");
         assert!(cache.contains(1, "child1"));
@@ -131,7 +132,7 @@ mod tests {
 
     #[test]
     fn test_insert() {
-        let cache = NegativeCache::new(100, Duration::from_secs(60));
+        let cache = NegativeCache::new(100, Duration::from_secs(60), Default::default());
 
         cache.insert(1, "child1");
         assert!(cache.contains(1, "child1"));
@@ -148,7 +149,7 @@ mod tests {
 
     #[test]
     fn test_remove() {
-        let cache = NegativeCache::new(100, Duration::from_secs(60));
+        let cache = NegativeCache::new(100, Duration::from_secs(60), Default::default());
 
         cache.insert(1, "child1");
         cache.insert(1, "child2");
@@ -163,9 +164,25 @@ mod tests {
         assert!(cache.contains(2, "child1"));
     }
 
+    #[test]
+    fn test_case_insensitive() {
+        let name_comparison = NameComparison {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let cache = NegativeCache::new(100, Duration::from_secs(60), name_comparison);
+
+        cache.insert(1, "Child1");
+        assert!(cache.contains(1, "child1"));
+        assert!(cache.contains(1, "CHILD1"));
+
+        cache.remove(1, "cHILD1");
+        assert!(!cache.contains(1, "Child1"));
+    }
+
     #[test]
     fn test_max_size() {
-        let cache = NegativeCache::new(2, Duration::from_secs(60));
+        let cache = NegativeCache::new(2, Duration::from_secs(60), Default::default());
 
         cache.insert(1, "child1");
         assert!(cache.contains(1, "child1"));
@@ -182,7 +199,7 @@ mod tests {
 
     #[test]
     fn test_expiration() {
-        let cache = NegativeCache::new(100, Duration::from_millis(1));
+        let cache = NegativeCache::new(100, Duration::from_millis(1), Default::default());
 
         cache.insert(1, "child1");
         sleep(Duration::from_millis(2));
@@ -191,7 +208,7 @@ mod tests {
 
     #[test]
     fn test_insert_after_expiry() {
-        let cache = NegativeCache::new(100, Duration::from_millis(50));
+        let cache = NegativeCache::new(100, Duration::from_millis(50), Default::default());
 
         cache.insert(1, "child1");
         sleep(Duration::from_millis(100));
@@ -204,7 +221,7 @@ mod tests {
     #[test]
     fn test_insert_resets_ttl() {
         let ttl = Duration::from_millis(100);
-        let cache = NegativeCache::new(100, ttl);
+        let cache = NegativeCache::new(100, ttl, Default::default());
 
         cache.insert(1, "child1");
         let inserted_time = Instant::now();
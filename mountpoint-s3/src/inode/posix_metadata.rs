@@ -0,0 +1,111 @@
+//! POSIX attributes of files stored in object metadata.
+//!
+//! The names and formats match those used by s3fs, so that permissions and owners round-trip
+//! between the two: `mode` holds the decimal `st_mode` (including the file type bits), `uid` and
+//! `gid` the decimal owner IDs, and `mtime` the modification time in seconds since the epoch.
+
+use std::collections::HashMap;
+
+use time::OffsetDateTime;
+
+use super::InodeStat;
+
+const MODE_METADATA: &str = "mode";
+const UID_METADATA: &str = "uid";
+const GID_METADATA: &str = "gid";
+const MTIME_METADATA: &str = "mtime";
+
+/// Update a stat with the POSIX attributes in the user-defined metadata of its object. Missing or
+/// malformed entries are ignored.
+pub fn apply_posix_metadata(stat: &mut InodeStat, user_metadata: &HashMap<String, String>) {
+    let get = |name: &str| user_metadata.get(name).map(|value| value.trim());
+    if let Some(mode) = get(MODE_METADATA).and_then(|v| v.parse::<u32>().ok()) {
+        stat.mode = Some((mode & 0o7777) as u16);
+    }
+    if let Some(uid) = get(UID_METADATA).and_then(|v| v.parse().ok()) {
+        stat.uid = Some(uid);
+    }
+    if let Some(gid) = get(GID_METADATA).and_then(|v| v.parse().ok()) {
+        stat.gid = Some(gid);
+    }
+    if let Some(mtime) = get(MTIME_METADATA).and_then(parse_mtime) {
+        stat.mtime = mtime;
+    }
+}
+
+/// Build the user-defined metadata holding the POSIX attributes of a regular file
+pub fn posix_metadata(perm: u16, uid: u32, gid: u32, mtime: OffsetDateTime) -> HashMap<String, String> {
+    let mode = u32::from(libc::S_IFREG) | u32::from(perm);
+    HashMap::from([
+        (MODE_METADATA.to_owned(), mode.to_string()),
+        (UID_METADATA.to_owned(), uid.to_string()),
+        (GID_METADATA.to_owned(), gid.to_string()),
+        (MTIME_METADATA.to_owned(), mtime.unix_timestamp().to_string()),
+    ])
+}
+
+/// Parse a modification time in seconds, which may have a fractional part
+fn parse_mtime(value: &str) -> Option<OffsetDateTime> {
+    let (secs, nanos) = match value.split_once('.') {
+        Some((secs, fraction)) => {
+            if fraction.is_empty() || fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let nanos: i128 = format!("{fraction:0<9}").parse().ok()?;
+            (secs, nanos)
+        }
+        None => (value, 0),
+    };
+    let secs: i128 = secs.parse().ok()?;
+    OffsetDateTime::from_unix_timestamp_nanos(secs * 1_000_000_000 + nanos).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mtime = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let metadata = posix_metadata(0o640, 1000, 100, mtime);
+        assert_eq!(metadata[MODE_METADATA], "33184");
+
+        let mut stat = InodeStat::for_file(0, OffsetDateTime::UNIX_EPOCH, None, None, None, Duration::ZERO);
+        apply_posix_metadata(&mut stat, &metadata);
+        assert_eq!(stat.mode, Some(0o640));
+        assert_eq!(stat.uid, Some(1000));
+        assert_eq!(stat.gid, Some(100));
+        assert_eq!(stat.mtime, mtime);
+    }
+
+    #[test]
+    fn test_malformed_metadata() {
+        let metadata = HashMap::from([
+            (MODE_METADATA.to_owned(), "rwxr-xr-x".to_owned()),
+            (UID_METADATA.to_owned(), "-1".to_owned()),
+            (GID_METADATA.to_owned(), "".to_owned()),
+            (MTIME_METADATA.to_owned(), "yesterday".to_owned()),
+        ]);
+        let mut stat = InodeStat::for_file(0, OffsetDateTime::UNIX_EPOCH, None, None, None, Duration::ZERO);
+        apply_posix_metadata(&mut stat, &metadata);
+        assert_eq!(stat.mode, None);
+        assert_eq!(stat.uid, None);
+        assert_eq!(stat.gid, None);
+        assert_eq!(stat.mtime, OffsetDateTime::UNIX_EPOCH);
+    }
+
+    #[test_case("1700000000", Some(1_700_000_000_000_000_000); "seconds")]
+    #[test_case("1700000000.5", Some(1_700_000_000_500_000_000); "fraction")]
+    #[test_case("1700000000.000000001", Some(1_700_000_000_000_000_001); "nanoseconds")]
+    #[test_case("1700000000.", None; "empty fraction")]
+    #[test_case("1700000000.0000000001", None; "too precise")]
+    #[test_case("1700000000.-5", None; "negative fraction")]
+    fn test_parse_mtime(value: &str, expected_nanos: Option<i128>) {
+        let expected = expected_nanos.map(|nanos| OffsetDateTime::from_unix_timestamp_nanos(nanos).unwrap());
+        assert_eq!(parse_mtime(value), expected);
+    }
+}
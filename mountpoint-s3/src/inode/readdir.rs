@@ -36,26 +36,37 @@
 //!   snapshot in time of the directory.
 
 use std::cmp::Ordering;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
 
 use mountpoint_s3_client::types::ObjectInfo;
 use mountpoint_s3_client::ObjectClient;
+use time::OffsetDateTime;
 use tracing::{error, trace, warn};
 
 use crate::sync::{Arc, AsyncMutex, Mutex};
+use crate::unicode::UnicodeNormalization;
 
 use super::{
-    valid_inode_name, InodeError, InodeKind, InodeKindData, InodeNo, InodeStat, LookedUp, RemoteLookup, SuperblockInner,
+    as_of, escape, valid_inode_name, versions, InodeError, InodeKind, InodeKindData, InodeNo, InodeStat, LookedUp,
+    RemoteLookup, SuperblockInner,
 };
 
+/// Number of entries to ask for in each ListObjectsV2 request made to find an equal name
+const FIND_NAME_PAGE_SIZE: usize = 1000;
+
 /// Handle for an inflight directory listing
 #[derive(Debug)]
 pub struct ReaddirHandle {
     inner: Arc<SuperblockInner>,
     dir_ino: InodeNo,
     parent_ino: InodeNo,
+    full_path: String,
     iter: AsyncMutex<ReaddirIter>,
     readded: Mutex<Option<LookedUp>>,
+    /// Normalized names of the non-ASCII entries returned so far, to skip entries whose names are
+    /// equal to them once normalized
+    normalized_names: Mutex<HashSet<String>>,
 }
 
 impl ReaddirHandle {
@@ -66,11 +77,14 @@ impl ReaddirHandle {
         full_path: String,
         page_size: usize,
     ) -> Result<Self, InodeError> {
-        let local_entries = {
+        let (local_entries, versions_of) = {
             let inode = inner.get(dir_ino)?;
             let kind_data = &inode.get_inode_state()?.kind_data;
+            let versions_of = kind_data.versions_of().map(str::to_owned);
             let local_files = match kind_data {
-                InodeKindData::File { .. } => return Err(InodeError::NotADirectory(inode.err())),
+                InodeKindData::File { .. } | InodeKindData::Symlink { .. } => {
+                    return Err(InodeError::NotADirectory(inode.err()))
+                }
                 InodeKindData::Directory { writing_children, .. } => writing_children.iter().map(|ino| {
                     let inode = inner.get(*ino)?;
                     let stat = inode.get_inode_state()?.stat.clone();
@@ -83,7 +97,7 @@ impl ReaddirHandle {
             match local_files.collect::<Result<Vec<_>, _>>() {
                 Ok(mut new_results) => {
                     new_results.sort();
-                    new_results
+                    (new_results, versions_of)
                 }
                 Err(e) => {
                     error!(error=?e, "readdir failed listing local files");
@@ -92,18 +106,36 @@ impl ReaddirHandle {
             }
         };
 
-        let iter = if inner.config.s3_personality.is_list_ordered() {
-            ReaddirIter::ordered(&inner.bucket, &full_path, page_size, local_entries.into())
+        let iter = if let Some(key) = versions_of {
+            ReaddirIter::Versions(versions::ReaddirIter::new(&inner.bucket, &key))
+        } else if inner.config.s3_personality.is_list_ordered() {
+            ReaddirIter::ordered(
+                &inner.bucket,
+                &full_path,
+                page_size,
+                local_entries.into(),
+                inner.config.as_of,
+                inner.config.escape_names,
+            )
         } else {
-            ReaddirIter::unordered(&inner.bucket, &full_path, page_size, local_entries.into())
+            ReaddirIter::unordered(
+                &inner.bucket,
+                &full_path,
+                page_size,
+                local_entries.into(),
+                inner.config.as_of,
+                inner.config.escape_names,
+            )
         };
 
         Ok(Self {
             inner,
             dir_ino,
             parent_ino,
+            full_path,
             iter: AsyncMutex::new(iter),
             readded: Default::default(),
+            normalized_names: Default::default(),
         })
     }
 
@@ -116,7 +148,7 @@ impl ReaddirHandle {
         }
 
         // Loop because the next entry from the [ReaddirIter] may be hidden from the file system,
-        // if it has an invalid name.
+        // if it has an invalid name or is filtered out.
         loop {
             let next = {
                 let mut iter = self.iter.lock().await;
@@ -126,7 +158,17 @@ impl ReaddirHandle {
             if let Some(next) = next {
                 // Short-circuit the update if we know it'll fail because the name is invalid
                 if !valid_inode_name(next.name()) {
-                    warn!("{} has an invalid name and will be unavailable", next.description());
+                    warn!(
+                        "{} has an invalid name and will be unavailable unless mounted with --escape-invalid-names",
+                        next.description()
+                    );
+                } else if !self.is_shown(&next) {
+                    trace!("{} is filtered out", next.description());
+                } else if self.is_shadowed_by_normalized_name(&next) {
+                    warn!(
+                        "{} is omitted because another entry has the same normalized name",
+                        next.description()
+                    );
                 } else {
                     let lookup = self.instantiate_remote_inode(next)?;
                     return Ok(Some(lookup));
@@ -137,6 +179,31 @@ impl ReaddirHandle {
         }
     }
 
+    /// Whether the entry passes the superblock's key filter. Local entries were checked when they
+    /// were created.
+    fn is_shown(&self, entry: &ReaddirEntry) -> bool {
+        match entry {
+            ReaddirEntry::RemotePrefix { name } => self
+                .inner
+                .child_key(&self.full_path, name)
+                .is_some_and(|key| self.inner.is_shown(&key, InodeKind::Directory)),
+            ReaddirEntry::RemoteObject { object_info, .. } => self.inner.is_shown(&object_info.key, InodeKind::File),
+            ReaddirEntry::LocalInode { .. } => true,
+        }
+    }
+
+    /// Whether the entry's name is equal to the name of an entry returned earlier once normalized
+    /// following [SuperblockConfig::normalize_unicode](super::SuperblockConfig::normalize_unicode).
+    /// Normalization leaves ASCII names alone, so only other names need to be checked.
+    fn is_shadowed_by_normalized_name(&self, entry: &ReaddirEntry) -> bool {
+        let normalization = self.inner.config.normalize_unicode;
+        if normalization == UnicodeNormalization::None || entry.name().is_ascii() {
+            return false;
+        }
+        let normalized = normalization.normalize(entry.name()).into_owned();
+        !self.normalized_names.lock().unwrap().insert(normalized)
+    }
+
     /// Re-add an entry to the front of the queue if the consumer wasn't able to use it
     pub fn readd(&self, entry: LookedUp) {
         let old = self.readded.lock().unwrap().replace(entry);
@@ -166,21 +233,27 @@ impl ReaddirHandle {
                 Some(RemoteLookup {
                     stat,
                     kind: InodeKind::Directory,
+                    maybe_symlink: false,
+                    versions_of: None,
+                    version_id: None,
                 })
             }
-            ReaddirEntry::RemoteObject { object_info, .. } => {
-                let stat = InodeStat::for_file(
-                    object_info.size as usize,
-                    object_info.last_modified,
-                    Some(object_info.etag.clone()),
-                    object_info.storage_class.clone(),
-                    object_info.restore_status,
-                    self.inner.config.cache_config.file_ttl,
-                );
-                Some(RemoteLookup {
-                    stat,
-                    kind: InodeKind::File,
-                })
+            ReaddirEntry::RemoteObject {
+                object_info,
+                version_id,
+                ..
+            } => {
+                // ListObjectsV2 doesn't return user-defined metadata, so the attributes of the
+                // object may be incomplete
+                let mut lookup = self.inner.object_lookup(object_info, None);
+                lookup.version_id.clone_from(version_id);
+                if version_id.is_none() && self.inner.needs_user_metadata(object_info.size) {
+                    // Rather than doing a HeadObject for every object, have the kernel look it up
+                    // (which gets its metadata) before use
+                    lookup.maybe_symlink = self.inner.may_be_symlink(object_info.size);
+                    lookup.stat.update_validity(Duration::ZERO);
+                }
+                Some(lookup)
             }
         };
         self.inner.update_from_remote(self.dir_ino, entry.name(), remote_lookup)
@@ -199,10 +272,19 @@ impl ReaddirHandle {
 /// A single entry in a readdir stream. Remote entries have not yet been converted to inodes -- that
 /// should be done lazily by the consumer of the entry.
 #[derive(Debug, Clone)]
-enum ReaddirEntry {
-    RemotePrefix { name: String },
-    RemoteObject { name: String, object_info: ObjectInfo },
-    LocalInode { lookup: LookedUp },
+pub(super) enum ReaddirEntry {
+    RemotePrefix {
+        name: String,
+    },
+    RemoteObject {
+        name: String,
+        object_info: ObjectInfo,
+        /// Version of the object to pin reads to, if not the current one
+        version_id: Option<String>,
+    },
+    LocalInode {
+        lookup: LookedUp,
+    },
 }
 
 // This looks a little silly but makes the [Ord] implementation for [ReaddirEntry] a bunch clearer
@@ -236,13 +318,14 @@ impl ReaddirEntry {
             Self::RemotePrefix { name } => {
                 format!("directory '{name}'")
             }
-            Self::RemoteObject { name, object_info } => {
+            Self::RemoteObject { name, object_info, .. } => {
                 format!("file '{}' (full key {:?})", name, object_info.key)
             }
             Self::LocalInode { lookup } => {
                 let kind = match lookup.inode.kind() {
                     InodeKind::Directory => "directory",
                     InodeKind::File => "file",
+                    InodeKind::Symlink => "symlink",
                 };
                 format!("local {} '{}'", kind, lookup.inode.name())
             }
@@ -279,29 +362,99 @@ impl Ord for ReaddirEntry {
 enum ReaddirIter {
     Ordered(ordered::ReaddirIter),
     Unordered(unordered::ReaddirIter),
+    Versions(versions::ReaddirIter),
 }
 
 impl ReaddirIter {
-    fn ordered(bucket: &str, full_path: &str, page_size: usize, local_entries: VecDeque<ReaddirEntry>) -> Self {
-        Self::Ordered(ordered::ReaddirIter::new(bucket, full_path, page_size, local_entries))
+    fn ordered(
+        bucket: &str,
+        full_path: &str,
+        page_size: usize,
+        local_entries: VecDeque<ReaddirEntry>,
+        as_of: Option<OffsetDateTime>,
+        escape_names: bool,
+    ) -> Self {
+        Self::Ordered(ordered::ReaddirIter::new(
+            bucket,
+            full_path,
+            page_size,
+            local_entries,
+            as_of,
+            escape_names,
+        ))
     }
 
-    fn unordered(bucket: &str, full_path: &str, page_size: usize, local_entries: VecDeque<ReaddirEntry>) -> Self {
-        Self::Unordered(unordered::ReaddirIter::new(bucket, full_path, page_size, local_entries))
+    fn unordered(
+        bucket: &str,
+        full_path: &str,
+        page_size: usize,
+        local_entries: VecDeque<ReaddirEntry>,
+        as_of: Option<OffsetDateTime>,
+        escape_names: bool,
+    ) -> Self {
+        Self::Unordered(unordered::ReaddirIter::new(
+            bucket,
+            full_path,
+            page_size,
+            local_entries,
+            as_of,
+            escape_names,
+        ))
     }
 
     async fn next(&mut self, client: &impl ObjectClient) -> Result<Option<ReaddirEntry>, InodeError> {
         match self {
             Self::Ordered(iter) => iter.next(client).await,
             Self::Unordered(iter) => iter.next(client).await,
+            Self::Versions(iter) => iter.next(client).await,
+        }
+    }
+}
+
+/// Find the name of an entry of the remote directory with the given prefix that is equal to `name`
+/// as compared by the superblock's [NameComparison](super::NameComparison), by listing the directory
+pub(super) async fn find_equal_name(
+    inner: &SuperblockInner,
+    client: &impl ObjectClient,
+    full_path: &str,
+    name: &str,
+) -> Result<Option<String>, InodeError> {
+    let comparison = inner.name_comparison;
+    let folded = comparison.fold(name);
+    let mut iter = RemoteIter::new(
+        &inner.bucket,
+        full_path,
+        FIND_NAME_PAGE_SIZE,
+        inner.config.s3_personality.is_list_ordered(),
+        inner.config.as_of,
+        inner.config.escape_names,
+    );
+    while let Some(entry) = iter.next(client).await? {
+        if comparison.fold(entry.name()) == folded {
+            return Ok(Some(entry.name().to_owned()));
         }
     }
+    Ok(None)
+}
+
+/// The name of the entry for a component of a key
+fn entry_name(component: &str, escape_names: bool) -> String {
+    if escape_names {
+        escape::escape_name(component)
+    } else {
+        component.to_owned()
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum RemoteIterState {
     /// Next ListObjects call should use this continuation token
     InProgress(Option<String>),
+    /// Next ListObjectVersions call should start after this key and version
+    InProgressVersions {
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+    },
     /// No more ListObjects calls to make
     Finished,
 }
@@ -310,6 +463,9 @@ enum RemoteIterState {
 /// handles combining directories (common prefixes) and files (objects) into a single stream,
 /// and re-sorting that stream to account for common prefixes not being in lexicographic order (see
 /// the module comment).
+///
+/// For point-in-time views, the entries are listed with ListObjectVersions instead, and each file
+/// is the version of its object picked by an [as_of::VersionPicker].
 #[derive(Debug)]
 struct RemoteIter {
     entries: VecDeque<ReaddirEntry>,
@@ -318,61 +474,72 @@ struct RemoteIter {
     page_size: usize,
     state: RemoteIterState,
     ordered: bool,
+    picker: Option<as_of::VersionPicker>,
+    /// Escape the names of entries, for [SuperblockConfig::escape_names](super::SuperblockConfig::escape_names)
+    escape_names: bool,
 }
 
 impl RemoteIter {
-    fn new(bucket: &str, full_path: &str, page_size: usize, ordered: bool) -> Self {
+    fn new(
+        bucket: &str,
+        full_path: &str,
+        page_size: usize,
+        ordered: bool,
+        as_of: Option<OffsetDateTime>,
+        escape_names: bool,
+    ) -> Self {
+        let state = match as_of {
+            Some(_) => RemoteIterState::InProgressVersions {
+                key_marker: None,
+                version_id_marker: None,
+            },
+            None => RemoteIterState::InProgress(None),
+        };
         Self {
             entries: VecDeque::new(),
             bucket: bucket.to_owned(),
             full_path: full_path.to_owned(),
             page_size,
-            state: RemoteIterState::InProgress(None),
+            state,
             ordered,
+            picker: as_of.map(as_of::VersionPicker::new),
+            escape_names,
         }
     }
 
     async fn next(&mut self, client: &impl ObjectClient) -> Result<Option<ReaddirEntry>, InodeError> {
         if self.entries.is_empty() {
-            let continuation_token = match &mut self.state {
+            let (common_prefixes, objects) = match &mut self.state {
                 RemoteIterState::Finished => {
                     trace!(self=?self as *const _, prefix=?self.full_path, "remote iter finished");
                     return Ok(None);
                 }
-                RemoteIterState::InProgress(token) => token.take(),
-            };
-
-            trace!(self=?self as *const _, prefix=?self.full_path, ?continuation_token, "continuing remote iter");
-
-            let result = client
-                .list_objects(
-                    &self.bucket,
-                    continuation_token.as_deref(),
-                    "/",
-                    self.page_size,
-                    self.full_path.as_str(),
-                )
-                .await
-                .map_err(|e| InodeError::client_error(e, "ListObjectsV2 failed", &self.bucket, &self.full_path))?;
-
-            self.state = match result.next_continuation_token {
-                Some(token) => RemoteIterState::InProgress(Some(token)),
-                None => RemoteIterState::Finished,
+                RemoteIterState::InProgress(token) => {
+                    let continuation_token = token.take();
+                    self.list_objects(client, continuation_token).await?
+                }
+                RemoteIterState::InProgressVersions {
+                    key_marker,
+                    version_id_marker,
+                } => {
+                    let (key_marker, version_id_marker) = (key_marker.take(), version_id_marker.take());
+                    self.list_object_versions(client, key_marker, version_id_marker).await?
+                }
             };
 
-            let prefixes = result
-                .common_prefixes
-                .into_iter()
-                .map(|prefix| ReaddirEntry::RemotePrefix {
-                    name: prefix[self.full_path.len()..prefix.len() - 1].to_owned(),
-                });
+            let prefixes = common_prefixes.into_iter().map(|prefix| ReaddirEntry::RemotePrefix {
+                name: entry_name(&prefix[self.full_path.len()..prefix.len() - 1], self.escape_names),
+            });
 
-            let objects = result
-                .objects
+            // The directory's own marker object, like the `dir/` keys the S3 Console creates, is not
+            // an entry of the directory
+            let objects = objects
                 .into_iter()
-                .map(|object_info| ReaddirEntry::RemoteObject {
-                    name: object_info.key[self.full_path.len()..].to_owned(),
+                .filter(|(object_info, _)| object_info.key != self.full_path)
+                .map(|(object_info, version_id)| ReaddirEntry::RemoteObject {
+                    name: entry_name(&object_info.key[self.full_path.len()..], self.escape_names),
                     object_info,
+                    version_id,
                 });
 
             if self.ordered {
@@ -391,6 +558,79 @@ impl RemoteIter {
 
         Ok(self.entries.pop_front())
     }
+
+    /// List the next page of common prefixes and objects with ListObjectsV2
+    async fn list_objects(
+        &mut self,
+        client: &impl ObjectClient,
+        continuation_token: Option<String>,
+    ) -> Result<(Vec<String>, Vec<(ObjectInfo, Option<String>)>), InodeError> {
+        trace!(self=?self as *const _, prefix=?self.full_path, ?continuation_token, "continuing remote iter");
+
+        let result = client
+            .list_objects(
+                &self.bucket,
+                continuation_token.as_deref(),
+                "/",
+                self.page_size,
+                self.full_path.as_str(),
+            )
+            .await
+            .map_err(|e| InodeError::client_error(e, "ListObjectsV2 failed", &self.bucket, &self.full_path))?;
+
+        self.state = match result.next_continuation_token {
+            Some(token) => RemoteIterState::InProgress(Some(token)),
+            None => RemoteIterState::Finished,
+        };
+
+        let objects = result
+            .objects
+            .into_iter()
+            .map(|object_info| (object_info, None))
+            .collect();
+        Ok((result.common_prefixes, objects))
+    }
+
+    /// List the next page of common prefixes and picked object versions with ListObjectVersions
+    async fn list_object_versions(
+        &mut self,
+        client: &impl ObjectClient,
+        key_marker: Option<String>,
+        version_id_marker: Option<String>,
+    ) -> Result<(Vec<String>, Vec<(ObjectInfo, Option<String>)>), InodeError> {
+        trace!(self=?self as *const _, prefix=?self.full_path, ?key_marker, ?version_id_marker, "continuing remote versions iter");
+
+        let result = client
+            .list_object_versions(
+                &self.bucket,
+                key_marker.as_deref(),
+                version_id_marker.as_deref(),
+                "/",
+                self.page_size,
+                self.full_path.as_str(),
+            )
+            .await
+            .map_err(|e| InodeError::client_error(e, "ListObjectVersions failed", &self.bucket, &self.full_path))?;
+
+        self.state = match result.next_key_marker {
+            Some(key_marker) => RemoteIterState::InProgressVersions {
+                key_marker: Some(key_marker),
+                version_id_marker: result.next_version_id_marker,
+            },
+            None => RemoteIterState::Finished,
+        };
+
+        let picker = self.picker.as_mut().expect("versions are only listed with a picker");
+        let objects = picker
+            .pick(result.versions)
+            .into_iter()
+            .map(|version| {
+                let version_id = version.version_id.clone();
+                (versions::version_object_info(version), Some(version_id))
+            })
+            .collect();
+        Ok((result.common_prefixes, objects))
+    }
 }
 
 /// Iterator implementation for S3 implementations that provide lexicographically ordered LIST.
@@ -415,9 +655,11 @@ mod ordered {
             full_path: &str,
             page_size: usize,
             local_entries: VecDeque<ReaddirEntry>,
+            as_of: Option<OffsetDateTime>,
+            escape_names: bool,
         ) -> Self {
             Self {
-                remote: RemoteIter::new(bucket, full_path, page_size, true),
+                remote: RemoteIter::new(bucket, full_path, page_size, true, as_of, escape_names),
                 local: LocalIter::new(local_entries),
                 next_remote: None,
                 next_local: None,
@@ -517,6 +759,8 @@ mod unordered {
             full_path: &str,
             page_size: usize,
             local_entries: VecDeque<ReaddirEntry>,
+            as_of: Option<OffsetDateTime>,
+            escape_names: bool,
         ) -> Self {
             let local_map = local_entries
                 .into_iter()
@@ -529,7 +773,7 @@ mod unordered {
                 .collect::<HashMap<_, _>>();
 
             Self {
-                remote: RemoteIter::new(bucket, full_path, page_size, false),
+                remote: RemoteIter::new(bucket, full_path, page_size, false, as_of, escape_names),
                 local: local_map,
                 local_iter: VecDeque::new(),
             }
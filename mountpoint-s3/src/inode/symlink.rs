@@ -0,0 +1,77 @@
+//! Representation of symbolic links as S3 objects.
+//!
+//! Symlinks are stored the same way s3fs stores them, so that links round-trip between the two:
+//! the content of the object is the link target, and its `mode` user-defined metadata entry (the
+//! `x-amz-meta-mode` header) holds the decimal `st_mode`, with the file type bits of a symlink.
+//! ListObjectsV2 doesn't return metadata, so an object is only known to be a symlink once it is
+//! looked up, and its target is only read when the link is followed.
+
+use std::collections::HashMap;
+
+/// Name of the user-defined metadata entry that holds the mode of an object
+const MODE_METADATA: &str = "mode";
+
+/// Longest link target, in bytes. Larger objects are never symlinks.
+const MAX_TARGET_LEN: u64 = libc::PATH_MAX as u64 - 1;
+
+/// Build the user-defined metadata for a symlink object
+pub fn symlink_metadata() -> HashMap<String, String> {
+    let mode = u32::from(libc::S_IFLNK) | 0o777;
+    HashMap::from([(MODE_METADATA.to_owned(), mode.to_string())])
+}
+
+/// Whether an object of this size could hold the target of a symlink
+pub fn is_target_size(size: u64) -> bool {
+    size > 0 && size <= MAX_TARGET_LEN
+}
+
+/// Whether the user-defined metadata of an object marks it as a symlink
+pub fn is_symlink(user_metadata: &HashMap<String, String>) -> bool {
+    let Some(mode) = user_metadata
+        .get(MODE_METADATA)
+        .and_then(|value| value.trim().parse::<u32>().ok())
+    else {
+        return false;
+    };
+    mode & u32::from(libc::S_IFMT) == u32::from(libc::S_IFLNK)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = symlink_metadata();
+        // The mode s3fs writes for symlinks, 0o120777
+        assert_eq!(metadata[MODE_METADATA], "41471");
+        assert!(is_symlink(&metadata));
+    }
+
+    #[test_case("41471", true; "symlink")]
+    #[test_case(" 41380 ", true; "symlink with other permissions")]
+    #[test_case("33188", false; "regular file")]
+    #[test_case("16877", false; "directory")]
+    #[test_case("0o120777", false; "octal")]
+    #[test_case("", false; "empty")]
+    fn test_is_symlink(mode: &str, expected: bool) {
+        let metadata = HashMap::from([(MODE_METADATA.to_owned(), mode.to_owned())]);
+        assert_eq!(is_symlink(&metadata), expected);
+    }
+
+    #[test]
+    fn test_not_a_symlink() {
+        let metadata = HashMap::from([("other".to_owned(), "value".to_owned())]);
+        assert!(!is_symlink(&metadata));
+    }
+
+    #[test_case(0, false; "empty")]
+    #[test_case(1, true; "one byte")]
+    #[test_case(MAX_TARGET_LEN, true; "longest target")]
+    #[test_case(MAX_TARGET_LEN + 1, false; "too long")]
+    fn test_is_target_size(size: u64, expected: bool) {
+        assert_eq!(is_target_size(size), expected);
+    }
+}
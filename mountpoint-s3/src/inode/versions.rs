@@ -0,0 +1,167 @@
+//! A read-only view of the noncurrent versions of objects.
+//!
+//! When enabled, looking up `<name>.versions` in a directory finds a virtual directory with one
+//! file for each noncurrent version of the object `<name>`, if it has any. The files are named by
+//! the time the version was created and its version ID, like `20240102T030405Z_<version id>`, so
+//! they sort from oldest to newest. Reads of these files are pinned to their version.
+//!
+//! Versions directories don't appear in `readdir` of their parent, and nothing in them can be
+//! modified.
+
+use std::collections::VecDeque;
+
+use mountpoint_s3_client::types::{ObjectInfo, ObjectVersionEntry, ObjectVersionInfo};
+use mountpoint_s3_client::ObjectClient;
+use time::UtcOffset;
+use tracing::trace;
+
+use super::readdir::ReaddirEntry;
+use super::InodeError;
+
+/// Suffix of the name of the versions directory of an object
+pub(super) const VERSIONS_SUFFIX: &str = ".versions";
+
+/// Number of versions to ask for in each ListObjectVersions request
+const LIST_PAGE_SIZE: usize = 1000;
+
+/// The name of the file for a version in its versions directory
+pub(super) fn version_file_name(version: &ObjectVersionInfo) -> String {
+    let time = version.last_modified.to_offset(UtcOffset::UTC);
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z_{}",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second(),
+        version.version_id,
+    )
+}
+
+/// The version ID of the file with the given name in a versions directory, if the name is valid
+pub(super) fn version_id_from_name(name: &str) -> Option<&str> {
+    name.split_once('_')
+        .map(|(_, version_id)| version_id)
+        .filter(|version_id| !version_id.is_empty())
+}
+
+/// The object metadata of a version, as if it was the current version of its key
+pub(super) fn version_object_info(version: ObjectVersionInfo) -> ObjectInfo {
+    ObjectInfo {
+        key: version.key,
+        size: version.size,
+        last_modified: version.last_modified,
+        storage_class: version.storage_class,
+        restore_status: None,
+        etag: version.etag,
+    }
+}
+
+/// List the noncurrent versions of the object with the given key, newest first. Delete markers are
+/// skipped.
+pub(super) async fn list_noncurrent_versions<OC: ObjectClient>(
+    client: &OC,
+    bucket: &str,
+    key: &str,
+) -> Result<Vec<ObjectVersionInfo>, InodeError> {
+    let mut versions = Vec::new();
+    let mut key_marker = None;
+    let mut version_id_marker = None;
+    loop {
+        let result = client
+            .list_object_versions(
+                bucket,
+                key_marker.as_deref(),
+                version_id_marker.as_deref(),
+                "",
+                LIST_PAGE_SIZE,
+                key,
+            )
+            .await
+            .map_err(|e| InodeError::client_error(e, "ListObjectVersions failed", bucket, key))?;
+
+        // The versions of `key` come before those of any longer key that has it as a prefix
+        let mut past_key = false;
+        for entry in result.versions {
+            if entry.key() != key {
+                past_key = true;
+                break;
+            }
+            match entry {
+                ObjectVersionEntry::Version(version) if !version.is_latest => versions.push(version),
+                _ => {}
+            }
+        }
+
+        if past_key || result.next_key_marker.as_deref() != Some(key) {
+            break;
+        }
+        key_marker = result.next_key_marker;
+        version_id_marker = result.next_version_id_marker;
+    }
+    trace!(key, count = versions.len(), "listed noncurrent versions");
+    Ok(versions)
+}
+
+/// An iterator over the files of a versions directory. All versions are listed by the first call
+/// to [ReaddirIter::next], since most objects only have a few.
+#[derive(Debug)]
+pub(super) struct ReaddirIter {
+    bucket: String,
+    key: String,
+    entries: Option<VecDeque<ReaddirEntry>>,
+}
+
+impl ReaddirIter {
+    pub(super) fn new(bucket: &str, key: &str) -> Self {
+        Self {
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            entries: None,
+        }
+    }
+
+    pub(super) async fn next(&mut self, client: &impl ObjectClient) -> Result<Option<ReaddirEntry>, InodeError> {
+        if self.entries.is_none() {
+            let versions = list_noncurrent_versions(client, &self.bucket, &self.key).await?;
+            let mut entries: Vec<_> = versions
+                .into_iter()
+                .map(|version| ReaddirEntry::RemoteObject {
+                    name: version_file_name(&version),
+                    version_id: Some(version.version_id.clone()),
+                    object_info: version_object_info(version),
+                })
+                .collect();
+            entries.sort();
+            self.entries = Some(entries.into());
+        }
+
+        Ok(self.entries.as_mut().and_then(|entries| entries.pop_front()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn test_version_file_name() {
+        let version = ObjectVersionInfo {
+            key: "dir/file".to_owned(),
+            version_id: "3HL4kqtJlcpXroDTDmJ.rmSpXd3dIbrHY".to_owned(),
+            is_latest: false,
+            size: 10,
+            last_modified: datetime!(2024-01-02 03:04:05.678 +01:00),
+            storage_class: None,
+            etag: "\"etag\"".to_owned(),
+        };
+        let name = version_file_name(&version);
+        assert_eq!(name, "20240102T020405Z_3HL4kqtJlcpXroDTDmJ.rmSpXd3dIbrHY");
+        assert_eq!(version_id_from_name(&name), Some("3HL4kqtJlcpXroDTDmJ.rmSpXd3dIbrHY"));
+        assert_eq!(version_id_from_name("20240102T020405Z_"), None);
+        assert_eq!(version_id_from_name("not-a-version"), None);
+    }
+}
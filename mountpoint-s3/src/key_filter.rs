@@ -0,0 +1,137 @@
+//! Filters that hide some of the objects of a bucket from the mount.
+//!
+//! Patterns are matched against object keys with the mounted prefix removed. For a single bucket
+//! that's the path of the file relative to the mount point, but with `--add-bucket` it's the key
+//! under each bucket's prefix, which doesn't include the directory named after the bucket.
+
+use std::str::FromStr;
+
+use regex::Regex;
+
+/// A glob pattern for the paths of files and directories, relative to the mounted prefix.
+///
+/// `*` matches any sequence of characters other than `/`, `**` matches any sequence of characters,
+/// and `?` matches any single character other than `/`. Patterns without a `/` are matched against
+/// the last component of a path, so `*.crc` matches `*.crc` files in any directory, while patterns
+/// with a `/` are matched against the whole path.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    regex: Regex,
+    matches_name: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        let trimmed = pattern.trim_start_matches('/');
+        let mut regex = String::from("^");
+        let mut chars = trimmed.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        regex.push('$');
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            regex: Regex::new(&regex)?,
+            matches_name: !trimmed.contains('/'),
+        })
+    }
+
+    /// Whether the pattern matches a path, which must not start or end with `/`
+    pub fn matches(&self, path: &str) -> bool {
+        if self.matches_name {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            self.regex.is_match(name)
+        } else {
+            self.regex.is_match(path)
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl FromStr for Glob {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, regex::Error> {
+        Glob::new(s)
+    }
+}
+
+/// Filters on the paths of the files and directories that a mount shows. Paths that are filtered
+/// out behave as if their objects did not exist, and can't be created.
+#[derive(Debug, Clone, Default)]
+pub struct KeyFilter {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+impl KeyFilter {
+    /// Show only the files that match one of `include`, unless it is empty, and hide all files and
+    /// directories that match one of `exclude`
+    pub fn new(include: Vec<Glob>, exclude: Vec<Glob>) -> Self {
+        Self { include, exclude }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether the file with the given path is shown
+    pub fn includes_file(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob.matches(path))) && self.includes_directory(path)
+    }
+
+    /// Whether the directory with the given path is shown. Include patterns only select files, so
+    /// that the directories that contain them are still reachable.
+    pub fn includes_directory(&self, path: &str) -> bool {
+        !self.exclude.iter().any(|glob| glob.matches(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    #[test_case("*.crc", "a.crc", true; "extension")]
+    #[test_case("*.crc", "dir/sub/a.crc", true; "extension in a directory")]
+    #[test_case("*.crc", "a.crc.txt", false; "different extension")]
+    #[test_case("_SUCCESS", "dir/_SUCCESS", true; "exact name")]
+    #[test_case("_SUCCESS", "dir/_SUCCESS2", false; "longer name")]
+    #[test_case("part-?", "part-1", true; "single character")]
+    #[test_case("part-?", "part-10", false; "two characters")]
+    #[test_case("logs/*.txt", "logs/a.txt", true; "path")]
+    #[test_case("logs/*.txt", "logs/2024/a.txt", false; "star does not match slash")]
+    #[test_case("logs/**.txt", "logs/2024/a.txt", true; "double star matches slash")]
+    #[test_case("/logs/*", "logs/a", true; "leading slash")]
+    #[test_case("a.b", "axb", false; "dot is literal")]
+    fn test_glob(pattern: &str, path: &str, matches: bool) {
+        let glob = Glob::new(pattern).unwrap();
+        assert_eq!(glob.matches(path), matches);
+    }
+
+    #[test]
+    fn test_key_filter() {
+        let filter = KeyFilter::new(
+            vec![Glob::new("*.parquet").unwrap()],
+            vec![Glob::new("_temporary").unwrap()],
+        );
+        assert!(filter.includes_file("dir/a.parquet"));
+        assert!(!filter.includes_file("dir/a.crc"));
+        assert!(filter.includes_directory("dir"));
+        assert!(!filter.includes_directory("dir/_temporary"));
+        assert!(KeyFilter::default().includes_file("anything"));
+    }
+}
@@ -6,13 +6,15 @@ pub mod data_cache;
 pub mod fs;
 pub mod fuse;
 mod inode;
+pub mod key_filter;
 pub mod logging;
 pub mod metrics;
-mod object;
+pub mod object;
 pub mod prefetch;
 pub mod prefix;
 pub mod s3;
 mod sync;
+pub mod unicode;
 mod upload;
 
 pub use fs::{S3Filesystem, S3FilesystemConfig, ServerSideEncryption};
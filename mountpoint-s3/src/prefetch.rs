@@ -7,6 +7,7 @@
 //! we increase the size of the GetObject requests up to some maximum. If the reader ever makes a
 //! non-sequential read, we abandon the prefetching and start again with the minimum request size.
 
+mod cache_admission;
 mod caching_stream;
 mod part;
 mod part_queue;
@@ -22,7 +23,6 @@ use async_trait::async_trait;
 use futures::task::Spawn;
 use metrics::{counter, histogram};
 use mountpoint_s3_client::error::{GetObjectError, ObjectClientError};
-use mountpoint_s3_client::types::ETag;
 use mountpoint_s3_client::ObjectClient;
 use thiserror::Error;
 use tracing::trace;
@@ -31,11 +31,13 @@ use crate::checksums::{ChecksummedBytes, IntegrityError};
 use crate::data_cache::DataCache;
 use crate::object::ObjectId;
 use crate::prefetch::caching_stream::CachingPartStream;
-use crate::prefetch::part_stream::{ClientPartStream, ObjectPartStream, RequestRange};
+use crate::prefetch::part_stream::{ClientPartStream, ObjectPartStream, ReadPosition, RequestRange};
 use crate::prefetch::seek_window::SeekWindow;
 use crate::prefetch::task::RequestTask;
 use crate::sync::Arc;
 
+pub use crate::prefetch::cache_admission::CacheAdmissionConfig;
+
 /// Generic interface to handle reading data from an object.
 pub trait Prefetch {
     type PrefetchResult<Client: ObjectClient + Send + Sync + 'static>: PrefetchResult<Client>;
@@ -45,9 +47,8 @@ pub trait Prefetch {
         &self,
         client: Arc<Client>,
         bucket: &str,
-        key: &str,
+        object_id: ObjectId,
         size: u64,
-        etag: ETag,
     ) -> Self::PrefetchResult<Client>
     where
         Client: ObjectClient + Send + Sync + 'static;
@@ -99,12 +100,13 @@ pub fn caching_prefetch<Cache, Runtime>(
     cache: Cache,
     runtime: Runtime,
     prefetcher_config: PrefetcherConfig,
+    admission_config: CacheAdmissionConfig,
 ) -> CachingPrefetcher<Cache, Runtime>
 where
     Cache: DataCache + Send + Sync + 'static,
     Runtime: Spawn + Send + Sync + 'static,
 {
-    let part_stream = CachingPartStream::new(runtime, cache);
+    let part_stream = CachingPartStream::new_with_admission(runtime, cache, admission_config);
     Prefetcher::new(part_stream, prefetcher_config)
 }
 
@@ -180,9 +182,8 @@ where
         &self,
         client: Arc<Client>,
         bucket: &str,
-        key: &str,
+        object_id: ObjectId,
         size: u64,
-        etag: ETag,
     ) -> Self::PrefetchResult<Client>
     where
         Client: ObjectClient + Send + Sync + 'static,
@@ -192,9 +193,8 @@ where
             self.part_stream.clone(),
             self.config,
             bucket,
-            key,
+            object_id,
             size,
-            etag,
         )
     }
 }
@@ -218,8 +218,11 @@ pub struct PrefetchGetObject<Stream: ObjectPartStream, Client: ObjectClient> {
     object_id: ObjectId,
     // preferred part size in the prefetcher's part queue, not the object part
     preferred_part_size: usize,
-    /// Start offset for sequential read, used for calculating contiguous read metric
+    /// Start offset for sequential read, used for calculating contiguous read metric and to tell
+    /// the part stream how long the sequential read is
     sequential_read_start_offset: u64,
+    /// Whether any request was made yet, so the part stream can tell new readers from seeks
+    has_spawned_request: bool,
     next_sequential_read_offset: u64,
     next_request_size: usize,
     next_request_offset: u64,
@@ -346,9 +349,8 @@ where
         part_stream: Arc<Stream>,
         config: PrefetcherConfig,
         bucket: &str,
-        key: &str,
+        object_id: ObjectId,
         size: u64,
-        etag: ETag,
     ) -> Self {
         PrefetchGetObject {
             client,
@@ -359,11 +361,12 @@ where
             backward_seek_window: SeekWindow::new(config.max_backward_seek_distance as usize),
             preferred_part_size: 128 * 1024,
             sequential_read_start_offset: 0,
+            has_spawned_request: false,
             next_sequential_read_offset: 0,
             next_request_size: config.first_request_size,
             next_request_offset: 0,
             bucket: bucket.to_owned(),
-            object_id: ObjectId::new(key.to_owned(), etag),
+            object_id,
             size,
         }
     }
@@ -402,14 +405,19 @@ where
         }
 
         let range = RequestRange::new(self.size as usize, start, self.next_request_size);
+        let position = ReadPosition {
+            is_first_request: !self.has_spawned_request,
+            sequential_offset: start - self.sequential_read_start_offset,
+        };
         let task = self.part_stream.spawn_get_object_request(
             &self.client,
             &self.bucket,
-            self.object_id.key(),
-            self.object_id.etag().clone(),
+            self.object_id.clone(),
             range,
             self.preferred_part_size,
+            position,
         );
+        self.has_spawned_request = true;
 
         // [read] will reset these if the reader stops making sequential requests
         self.next_request_offset += task.total_size() as u64;
@@ -563,7 +571,10 @@ mod tests {
     use futures::executor::{block_on, ThreadPool};
     use mountpoint_s3_client::error::{GetObjectError, ObjectClientError};
     use mountpoint_s3_client::failure_client::{countdown_failure_client, RequestFailureMap};
-    use mountpoint_s3_client::mock_client::{ramp_bytes, MockClient, MockClientConfig, MockClientError, MockObject};
+    use mountpoint_s3_client::mock_client::{
+        ramp_bytes, MockClient, MockClientConfig, MockClientError, MockObject, Operation,
+    };
+    use mountpoint_s3_client::types::ETag;
     use proptest::proptest;
     use proptest::strategy::{Just, Strategy};
     use proptest_derive::Arbitrary;
@@ -626,7 +637,7 @@ mod tests {
         };
 
         let prefetcher = Prefetcher::new(part_stream, prefetcher_config);
-        let mut request = prefetcher.prefetch(client, "test-bucket", "hello", size, etag);
+        let mut request = prefetcher.prefetch(client, "test-bucket", ObjectId::new("hello".to_owned(), etag), size);
 
         let mut next_offset = 0;
         loop {
@@ -722,7 +733,12 @@ mod tests {
         };
 
         let prefetcher = Prefetcher::new(part_stream, prefetcher_config);
-        let mut request = prefetcher.prefetch(Arc::new(client), "test-bucket", "hello", size, etag);
+        let mut request = prefetcher.prefetch(
+            Arc::new(client),
+            "test-bucket",
+            ObjectId::new("hello".to_owned(), etag),
+            size,
+        );
 
         let mut next_offset = 0;
         loop {
@@ -849,7 +865,12 @@ mod tests {
         };
 
         let prefetcher = Prefetcher::new(part_stream, prefetcher_config);
-        let mut request = prefetcher.prefetch(client, "test-bucket", "hello", object_size, etag);
+        let mut request = prefetcher.prefetch(
+            client,
+            "test-bucket",
+            ObjectId::new("hello".to_owned(), etag),
+            object_size,
+        );
 
         for (offset, length) in reads {
             assert!(offset < object_size);
@@ -995,8 +1016,12 @@ mod tests {
 
         // Try every possible seek from first_read_size
         for offset in first_read_size + 1..OBJECT_SIZE {
-            let mut request =
-                prefetcher.prefetch(client.clone(), "test-bucket", "hello", OBJECT_SIZE as u64, etag.clone());
+            let mut request = prefetcher.prefetch(
+                client.clone(),
+                "test-bucket",
+                ObjectId::new("hello".to_owned(), etag.clone()),
+                OBJECT_SIZE as u64,
+            );
             if first_read_size > 0 {
                 let _first_read = block_on(request.read(0, first_read_size)).unwrap();
             }
@@ -1033,8 +1058,12 @@ mod tests {
 
         // Try every possible seek from first_read_size
         for offset in 0..first_read_size {
-            let mut request =
-                prefetcher.prefetch(client.clone(), "test-bucket", "hello", OBJECT_SIZE as u64, etag.clone());
+            let mut request = prefetcher.prefetch(
+                client.clone(),
+                "test-bucket",
+                ObjectId::new("hello".to_owned(), etag.clone()),
+                OBJECT_SIZE as u64,
+            );
             if first_read_size > 0 {
                 let _first_read = block_on(request.read(0, first_read_size)).unwrap();
             }
@@ -1045,6 +1074,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cache_admission_counts_readers() {
+        const OBJECT_SIZE: usize = 16 * MB;
+
+        let config = MockClientConfig {
+            bucket: "test-bucket".to_string(),
+            part_size: 1 * MB,
+            ..Default::default()
+        };
+        let client = Arc::new(MockClient::new(config));
+        let object = MockObject::ramp(0xaa, OBJECT_SIZE, ETag::for_tests());
+        let object_id = ObjectId::new("hello".to_owned(), object.etag());
+        client.add_object("hello", object);
+
+        let runtime = ThreadPool::builder().pool_size(1).create().unwrap();
+        let admission_config = CacheAdmissionConfig {
+            min_reads: 2,
+            ..Default::default()
+        };
+        let part_stream =
+            CachingPartStream::new_with_admission(runtime, InMemoryDataCache::new(MB as u64), admission_config);
+        // Make every seek restart the prefetcher
+        let prefetcher_config = PrefetcherConfig {
+            max_forward_seek_wait_distance: 1,
+            max_backward_seek_distance: 1,
+            ..Default::default()
+        };
+        let prefetcher = Prefetcher::new(part_stream, prefetcher_config);
+
+        // Read a byte at each offset with a new file handle, and return the number of GetObject requests
+        let read_with_new_handle = |offsets: &[usize]| {
+            let get_object_counter = client.new_counter(Operation::GetObject);
+            let mut request = prefetcher.prefetch(client.clone(), "test-bucket", object_id.clone(), OBJECT_SIZE as u64);
+            for &offset in offsets {
+                let byte = block_on(request.read(offset as u64, 1)).unwrap();
+                assert_eq!(byte.into_bytes().unwrap()[..], ramp_bytes(0xaa + offset, 1)[..]);
+            }
+            get_object_counter.count()
+        };
+
+        // A reader that seeks back and forth only counts as one read
+        read_with_new_handle(&[0, 8 * MB, 0, 8 * MB]);
+        assert!(
+            read_with_new_handle(&[0]) > 0,
+            "the first block should not be cached yet"
+        );
+        assert_eq!(
+            read_with_new_handle(&[0]),
+            0,
+            "the first block should be cached by the second reader"
+        );
+    }
+
     #[cfg(feature = "shuttle")]
     mod shuttle_tests {
         use super::*;
@@ -1092,7 +1174,12 @@ mod tests {
             };
 
             let prefetcher = Prefetcher::new(ClientPartStream::new(ShuttleRuntime), prefetcher_config);
-            let mut request = prefetcher.prefetch(client, "test-bucket", "hello", object_size, file_etag);
+            let mut request = prefetcher.prefetch(
+                client,
+                "test-bucket",
+                ObjectId::new("hello".to_owned(), file_etag),
+                object_size,
+            );
 
             let mut next_offset = 0;
             loop {
@@ -1149,7 +1236,12 @@ mod tests {
             };
 
             let prefetcher = Prefetcher::new(ClientPartStream::new(ShuttleRuntime), prefetcher_config);
-            let mut request = prefetcher.prefetch(client, "test-bucket", "hello", object_size, file_etag);
+            let mut request = prefetcher.prefetch(
+                client,
+                "test-bucket",
+                ObjectId::new("hello".to_owned(), file_etag),
+                object_size,
+            );
 
             let num_reads = rng.gen_range(10usize..50);
             for _ in 0..num_reads {
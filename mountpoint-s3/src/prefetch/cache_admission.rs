@@ -0,0 +1,185 @@
+//! Decides which object content read from S3 is written to the data cache.
+//!
+//! Without a filter, every block read from S3 is cached, so a single large read of data that is
+//! never read again, like a bulk copy of a large object, can evict all of the content that other
+//! readers use repeatedly. The filter can require objects to be read several times before their
+//! content is cached, skip large objects, and stop caching long sequential reads.
+
+use linked_hash_map::LinkedHashMap;
+
+use crate::object::ObjectId;
+use crate::prefetch::part_stream::{ReadPosition, RequestRange};
+use crate::sync::Mutex;
+
+/// The number of objects whose reads are counted for [CacheAdmissionConfig::min_reads]. Objects
+/// that were read least recently are forgotten first.
+const MAX_COUNTED_OBJECTS: usize = 64 * 1024;
+
+/// Configuration for the filter in front of the data cache.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheAdmissionConfig {
+    /// The number of times an object must be read before its content is cached. Each file handle
+    /// that reads the object counts as one read, however it seeks.
+    pub min_reads: u32,
+    /// The size of the largest object whose content is cached
+    pub max_object_size: Option<u64>,
+    /// How much of an object a run of sequential reads can read before the rest of the run is no
+    /// longer cached
+    pub max_sequential_read: Option<u64>,
+}
+
+impl Default for CacheAdmissionConfig {
+    fn default() -> Self {
+        Self {
+            min_reads: 1,
+            max_object_size: None,
+            max_sequential_read: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct CacheAdmission {
+    config: CacheAdmissionConfig,
+    read_counts: Mutex<LinkedHashMap<ObjectId, u32>>,
+}
+
+impl CacheAdmission {
+    pub(super) fn new(config: CacheAdmissionConfig) -> Self {
+        Self {
+            config,
+            read_counts: Mutex::new(LinkedHashMap::new()),
+        }
+    }
+
+    /// Admit the blocks of a request to the cache. The first request of each reader counts as a new
+    /// read of the object.
+    ///
+    /// Returns the offset in the object before which the blocks read by the request start, for them
+    /// to be cached.
+    pub(super) fn admit(&self, object_id: &ObjectId, range: &RequestRange, position: ReadPosition) -> u64 {
+        if !self.is_read_often_enough(object_id, position.is_first_request) {
+            return 0;
+        }
+        if self
+            .config
+            .max_object_size
+            .is_some_and(|max_object_size| range.object_size() as u64 > max_object_size)
+        {
+            return 0;
+        }
+        match self.config.max_sequential_read {
+            Some(max_sequential_read) => {
+                let run_start = range.start().saturating_sub(position.sequential_offset);
+                run_start.saturating_add(max_sequential_read)
+            }
+            None => u64::MAX,
+        }
+    }
+
+    /// Whether an object has been read often enough to be cached, counting a new read first if
+    /// `new_read` is set. Objects that were forgotten since their last read start again.
+    fn is_read_often_enough(&self, object_id: &ObjectId, new_read: bool) -> bool {
+        if self.config.min_reads <= 1 {
+            return true;
+        }
+        let mut read_counts = self.read_counts.lock().unwrap();
+        let mut count = read_counts.get_refresh(object_id).copied().unwrap_or(0);
+        if new_read {
+            count = (count + 1).min(self.config.min_reads);
+            read_counts.insert(object_id.clone(), count);
+            if read_counts.len() > MAX_COUNTED_OBJECTS {
+                read_counts.pop_front();
+            }
+        }
+        count >= self.config.min_reads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mountpoint_s3_client::types::ETag;
+    use test_case::test_case;
+
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn object_id(key: &str) -> ObjectId {
+        ObjectId::new(key.to_owned(), ETag::for_tests())
+    }
+
+    /// The position of a request of a new reader
+    fn first_request() -> ReadPosition {
+        ReadPosition {
+            is_first_request: true,
+            sequential_offset: 0,
+        }
+    }
+
+    /// The position of a later request of a reader, which has read `sequential_offset` bytes
+    /// sequentially since it last seeked
+    fn later_request(sequential_offset: u64) -> ReadPosition {
+        ReadPosition {
+            is_first_request: false,
+            sequential_offset,
+        }
+    }
+
+    #[test]
+    fn test_admit_everything_by_default() {
+        let admission = CacheAdmission::new(Default::default());
+        let range = RequestRange::new(100 * MB as usize, 10 * MB, MB as usize);
+        assert_eq!(admission.admit(&object_id("a"), &range, first_request()), u64::MAX);
+        assert_eq!(
+            admission.admit(&object_id("a"), &range, later_request(50 * MB)),
+            u64::MAX
+        );
+    }
+
+    #[test]
+    fn test_min_reads() {
+        let admission = CacheAdmission::new(CacheAdmissionConfig {
+            min_reads: 3,
+            ..Default::default()
+        });
+        let range = RequestRange::new(10 * MB as usize, 0, MB as usize);
+        let id = object_id("a");
+
+        assert_eq!(admission.admit(&id, &range, first_request()), 0);
+        // Later requests of the same reader don't count as reads, even after it seeks
+        assert_eq!(admission.admit(&id, &range, later_request(MB)), 0);
+        assert_eq!(admission.admit(&id, &range, later_request(0)), 0);
+        assert_eq!(admission.admit(&id, &range, first_request()), 0);
+        assert_eq!(admission.admit(&object_id("b"), &range, first_request()), 0);
+        assert_eq!(admission.admit(&id, &range, first_request()), u64::MAX);
+        assert_eq!(admission.admit(&id, &range, later_request(MB)), u64::MAX);
+        assert_eq!(admission.admit(&id, &range, first_request()), u64::MAX);
+    }
+
+    #[test_case(10 * MB, true; "small object")]
+    #[test_case(10 * MB + 1, false; "large object")]
+    fn test_max_object_size(object_size: u64, admitted: bool) {
+        let admission = CacheAdmission::new(CacheAdmissionConfig {
+            max_object_size: Some(10 * MB),
+            ..Default::default()
+        });
+        let range = RequestRange::new(object_size as usize, 0, MB as usize);
+        assert_eq!(admission.admit(&object_id("a"), &range, first_request()) > 0, admitted);
+    }
+
+    #[test]
+    fn test_max_sequential_read() {
+        let admission = CacheAdmission::new(CacheAdmissionConfig {
+            max_sequential_read: Some(8 * MB),
+            ..Default::default()
+        });
+        let id = object_id("a");
+
+        // A run of sequential reads that started at 4 MiB, and has read 2 MiB so far
+        let range = RequestRange::new(100 * MB as usize, 6 * MB, 16 * MB as usize);
+        assert_eq!(admission.admit(&id, &range, later_request(2 * MB)), 12 * MB);
+        let range = RequestRange::new(100 * MB as usize, 22 * MB, 64 * MB as usize);
+        assert_eq!(admission.admit(&id, &range, later_request(18 * MB)), 12 * MB);
+    }
+}
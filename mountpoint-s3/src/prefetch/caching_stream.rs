@@ -4,15 +4,17 @@ use std::{ops::Range, sync::Arc};
 use bytes::Bytes;
 use futures::task::{Spawn, SpawnExt};
 use futures::{pin_mut, StreamExt};
-use mountpoint_s3_client::{types::ETag, ObjectClient};
+use mountpoint_s3_client::types::GetObjectParams;
+use mountpoint_s3_client::ObjectClient;
 use tracing::{debug_span, trace, warn, Instrument};
 
 use crate::checksums::ChecksummedBytes;
 use crate::data_cache::{BlockIndex, DataCache};
 use crate::object::ObjectId;
+use crate::prefetch::cache_admission::{CacheAdmission, CacheAdmissionConfig};
 use crate::prefetch::part::Part;
 use crate::prefetch::part_queue::{unbounded_part_queue, PartQueueProducer};
-use crate::prefetch::part_stream::{ObjectPartStream, RequestRange};
+use crate::prefetch::part_stream::{ObjectPartStream, ReadPosition, RequestRange};
 use crate::prefetch::task::RequestTask;
 use crate::prefetch::PrefetchReadError;
 
@@ -21,13 +23,21 @@ use crate::prefetch::PrefetchReadError;
 #[derive(Debug)]
 pub struct CachingPartStream<Cache, Runtime> {
     cache: Arc<Cache>,
+    admission: CacheAdmission,
     runtime: Runtime,
 }
 
 impl<Cache, Runtime> CachingPartStream<Cache, Runtime> {
     pub fn new(runtime: Runtime, cache: Cache) -> Self {
+        Self::new_with_admission(runtime, cache, Default::default())
+    }
+
+    /// Create a new [CachingPartStream] that only writes the blocks admitted by the given
+    /// configuration to the cache.
+    pub fn new_with_admission(runtime: Runtime, cache: Cache, admission_config: CacheAdmissionConfig) -> Self {
         Self {
             cache: Arc::new(cache),
+            admission: CacheAdmission::new(admission_config),
             runtime,
         }
     }
@@ -42,14 +52,15 @@ where
         &self,
         client: &Client,
         bucket: &str,
-        key: &str,
-        if_match: ETag,
+        object_id: ObjectId,
         range: RequestRange,
         _preferred_part_size: usize,
+        position: ReadPosition,
     ) -> RequestTask<<Client as ObjectClient>::ClientError>
     where
         Client: ObjectClient + Clone + Send + Sync + 'static,
     {
+        let cache_until = self.admission.admit(&object_id, &range, position);
         let range = range.align(self.cache.block_size(), false);
 
         let start = range.start();
@@ -63,8 +74,8 @@ where
                 client.clone(),
                 self.cache.clone(),
                 bucket.to_owned(),
-                key.to_owned(),
-                if_match,
+                object_id,
+                cache_until,
                 part_queue_producer,
             );
             let span = debug_span!("prefetch", ?range);
@@ -83,6 +94,8 @@ struct CachingRequest<Client: ObjectClient, Cache> {
     cache: Arc<Cache>,
     bucket: String,
     cache_key: ObjectId,
+    /// Only blocks that start before this offset are written to the cache
+    cache_until: u64,
     part_queue_producer: PartQueueProducer<Client::ClientError>,
 }
 
@@ -95,16 +108,16 @@ where
         client: Client,
         cache: Arc<Cache>,
         bucket: String,
-        key: String,
-        etag: ETag,
+        cache_key: ObjectId,
+        cache_until: u64,
         part_queue_producer: PartQueueProducer<Client::ClientError>,
     ) -> Self {
-        let cache_key = ObjectId::new(key, etag);
         Self {
             client,
             cache,
             bucket,
             cache_key,
+            cache_until,
             part_queue_producer,
         }
     }
@@ -169,8 +182,10 @@ where
             .get_object(
                 &self.bucket,
                 key,
-                Some(block_aligned_byte_range),
-                Some(self.cache_key.etag().clone()),
+                &GetObjectParams::new()
+                    .range(Some(block_aligned_byte_range))
+                    .if_match(Some(self.cache_key.etag().clone()))
+                    .version_id(self.cache_key.version_id().map(str::to_owned)),
             )
             .await
         {
@@ -259,6 +274,11 @@ where
     }
 
     fn update_cache(&self, block_index: u64, block_offset: u64, block: &ChecksummedBytes) {
+        if block_offset >= self.cache_until {
+            trace!(key=?self.cache_key.key(), block_index, "block not admitted to cache");
+            metrics::counter!("prefetch.blocks_not_admitted_to_cache").increment(1);
+            return;
+        }
         // TODO: consider updating the cache asynchronously
         let start = Instant::now();
         match self
@@ -321,6 +341,7 @@ mod tests {
 
     use futures::executor::{block_on, ThreadPool};
     use mountpoint_s3_client::mock_client::{MockClient, MockClientConfig, MockObject, Operation};
+    use mountpoint_s3_client::types::ETag;
     use test_case::test_case;
 
     use crate::data_cache::InMemoryDataCache;
@@ -347,7 +368,6 @@ mod tests {
         let key = "object";
         let seed = 0xaa;
         let object = MockObject::ramp(seed, object_size, ETag::for_tests());
-        let etag = object.etag();
         let id = ObjectId::new(key.to_owned(), object.etag());
 
         let cache = InMemoryDataCache::new(block_size as u64);
@@ -367,7 +387,8 @@ mod tests {
         let first_read_count = {
             // First request (from client)
             let get_object_counter = mock_client.new_counter(Operation::GetObject);
-            let request_task = stream.spawn_get_object_request(&mock_client, bucket, key, etag.clone(), range, 0);
+            let request_task =
+                stream.spawn_get_object_request(&mock_client, bucket, id.clone(), range, 0, first_request());
             compare_read(&id, &object, request_task);
             get_object_counter.count()
         };
@@ -376,7 +397,8 @@ mod tests {
         let second_read_count = {
             // Second request (from cache)
             let get_object_counter = mock_client.new_counter(Operation::GetObject);
-            let request_task = stream.spawn_get_object_request(&mock_client, bucket, key, etag.clone(), range, 0);
+            let request_task =
+                stream.spawn_get_object_request(&mock_client, bucket, id.clone(), range, 0, first_request());
             compare_read(&id, &object, request_task);
             get_object_counter.count()
         };
@@ -392,7 +414,6 @@ mod tests {
         let object_size = 16 * MB;
         let seed = 0xaa;
         let object = MockObject::ramp(seed, object_size, ETag::for_tests());
-        let etag = object.etag();
         let id = ObjectId::new(key.to_owned(), object.etag());
 
         let cache = InMemoryDataCache::new(block_size as u64);
@@ -411,12 +432,72 @@ mod tests {
         for offset in [0, 512 * KB, 1 * MB, 4 * MB, 9 * MB] {
             for preferred_size in [1 * KB, 512 * KB, 4 * MB, 12 * MB, 16 * MB] {
                 let range = RequestRange::new(object_size, offset as u64, preferred_size);
-                let request_task = stream.spawn_get_object_request(&mock_client, bucket, key, etag.clone(), range, 0);
+                let request_task =
+                    stream.spawn_get_object_request(&mock_client, bucket, id.clone(), range, 0, first_request());
                 compare_read(&id, &object, request_task);
             }
         }
     }
 
+    #[test_case(2, None, None, 0; "second read")]
+    #[test_case(1, Some(4 * MB), None, 16; "large object")]
+    #[test_case(1, None, Some(6 * MB), 10; "long sequential read")]
+    fn test_cache_admission(
+        min_reads: u32,
+        max_object_size: Option<usize>,
+        max_sequential_read: Option<usize>,
+        uncached_blocks: u64,
+    ) {
+        let admission_config = CacheAdmissionConfig {
+            min_reads,
+            max_object_size: max_object_size.map(|size| size as u64),
+            max_sequential_read: max_sequential_read.map(|size| size as u64),
+        };
+        let key = "object";
+        let block_size = 1 * MB;
+        let object_size = 16 * MB;
+        let object = MockObject::ramp(0xaa, object_size, ETag::for_tests());
+        let id = ObjectId::new(key.to_owned(), object.etag());
+
+        let cache = InMemoryDataCache::new(block_size as u64);
+        let bucket = "test-bucket";
+        let config = MockClientConfig {
+            bucket: bucket.to_string(),
+            part_size: 8 * MB,
+            ..Default::default()
+        };
+        let mock_client = Arc::new(MockClient::new(config));
+        mock_client.add_object(key, object.clone());
+
+        let runtime = ThreadPool::builder().pool_size(1).create().unwrap();
+        let stream = CachingPartStream::new_with_admission(runtime, cache, admission_config);
+        let range = RequestRange::new(object_size, 0, object_size);
+
+        for _ in 0..2 {
+            let request_task =
+                stream.spawn_get_object_request(&mock_client, bucket, id.clone(), range, 0, first_request());
+            compare_read(&id, &object, request_task);
+        }
+
+        let uncached = (0..(object_size / block_size) as u64)
+            .filter(|&block_idx| {
+                stream
+                    .cache
+                    .get_block(&id, block_idx, block_idx * block_size as u64)
+                    .unwrap()
+                    .is_none()
+            })
+            .count() as u64;
+        assert_eq!(uncached, uncached_blocks);
+    }
+
+    fn first_request() -> ReadPosition {
+        ReadPosition {
+            is_first_request: true,
+            sequential_offset: 0,
+        }
+    }
+
     fn compare_read<E: std::error::Error + Send + Sync>(
         id: &ObjectId,
         object: &MockObject,
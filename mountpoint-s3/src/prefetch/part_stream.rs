@@ -3,7 +3,8 @@ use std::{fmt::Debug, ops::Range};
 use bytes::Bytes;
 use futures::task::SpawnExt;
 use futures::{pin_mut, task::Spawn, StreamExt};
-use mountpoint_s3_client::{types::ETag, ObjectClient};
+use mountpoint_s3_client::types::GetObjectParams;
+use mountpoint_s3_client::ObjectClient;
 use tracing::{debug_span, error, trace, Instrument};
 
 use crate::checksums::ChecksummedBytes;
@@ -17,20 +18,31 @@ use crate::prefetch::PrefetchReadError;
 pub trait ObjectPartStream {
     /// Spawns a request to get the content of an object. The object data will be retrieved in fixed size
     /// parts and can then be consumed using [RequestTask::read]. Callers need to specify a preferred
-    /// size for the parts, but implementations are allowed to ignore it.
+    /// size for the parts, but implementations are allowed to ignore it. `position` tells where the
+    /// reader of the object was when it made the request.
     fn spawn_get_object_request<Client>(
         &self,
         client: &Client,
         bucket: &str,
-        key: &str,
-        if_match: ETag,
+        object_id: ObjectId,
         range: RequestRange,
         preferred_part_size: usize,
+        position: ReadPosition,
     ) -> RequestTask<Client::ClientError>
     where
         Client: ObjectClient + Clone + Send + Sync + 'static;
 }
 
+/// Where the reader of an object, like a file handle, was when it made a
+/// [ObjectPartStream::spawn_get_object_request] request.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReadPosition {
+    /// Whether this is the first request of the reader. Requests made after the reader seeks are not.
+    pub is_first_request: bool,
+    /// How much of the object the reader had read sequentially before the start of the range
+    pub sequential_offset: u64,
+}
+
 /// The range of a [ObjectPartStream::spawn_get_object_request] request.
 /// Includes the total size of the object.
 #[derive(Clone, Copy)]
@@ -167,10 +179,10 @@ where
         &self,
         client: &Client,
         bucket: &str,
-        key: &str,
-        if_match: ETag,
+        object_id: ObjectId,
         range: RequestRange,
         preferred_part_size: usize,
+        _position: ReadPosition,
     ) -> RequestTask<Client::ClientError>
     where
         Client: ObjectClient + Clone + Send + Sync + 'static,
@@ -186,17 +198,23 @@ where
         let request_task = {
             let client = client.clone();
             let bucket = bucket.to_owned();
-            let id = ObjectId::new(key.to_owned(), if_match);
             let span = debug_span!("prefetch", range=?request_range);
 
             async move {
                 let get_object_result = match client
-                    .get_object(&bucket, id.key(), Some(request_range.into()), Some(id.etag().clone()))
+                    .get_object(
+                        &bucket,
+                        object_id.key(),
+                        &GetObjectParams::new()
+                            .range(Some(request_range.into()))
+                            .if_match(Some(object_id.etag().clone()))
+                            .version_id(object_id.version_id().map(str::to_owned)),
+                    )
                     .await
                 {
                     Ok(get_object_result) => get_object_result,
                     Err(e) => {
-                        error!(key=id.key(), error=?e, "GetObject request failed");
+                        error!(key=object_id.key(), error=?e, "GetObject request failed");
                         part_queue_producer.push(Err(PrefetchReadError::GetRequestFailed(e)));
                         return;
                     }
@@ -221,13 +239,13 @@ where
                                 // S3 doesn't provide checksum for us if the request range is not aligned to
                                 // object part boundaries, so we're computing our own checksum here.
                                 let checksum_bytes = ChecksummedBytes::new(chunk);
-                                let part = Part::new(id.clone(), curr_offset, checksum_bytes);
+                                let part = Part::new(object_id.clone(), curr_offset, checksum_bytes);
                                 curr_offset += part.len() as u64;
                                 part_queue_producer.push(Ok(part));
                             }
                         }
                         Some(Err(e)) => {
-                            error!(key=id.key(), error=?e, "GetObject body part failed");
+                            error!(key=object_id.key(), error=?e, "GetObject body part failed");
                             part_queue_producer.push(Err(PrefetchReadError::GetRequestFailed(e)));
                             break;
                         }
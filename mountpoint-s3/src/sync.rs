@@ -11,6 +11,7 @@ mod std {
     pub use std::thread;
 
     pub use async_lock::Mutex as AsyncMutex;
+    pub use async_lock::MutexGuard as AsyncMutexGuard;
     pub use async_lock::RwLock as AsyncRwLock;
 
     pub use async_channel;
@@ -27,6 +28,7 @@ mod shuttle {
     // TODO these might need a richer Shuttle mock
     pub use async_channel;
     pub use async_lock::Mutex as AsyncMutex;
+    pub use async_lock::MutexGuard as AsyncMutexGuard;
     pub use async_lock::RwLock as AsyncRwLock;
 }
 
@@ -0,0 +1,51 @@
+use std::borrow::Cow;
+
+use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization as _};
+
+/// The Unicode normalization form that file names are compared in. Names that are equal once
+/// normalized refer to the same file, whichever form the object's key uses. For example, macOS
+/// clients usually upload keys in NFD, where `é` is an `e` followed by a combining accent, while
+/// Linux clients usually type names in NFC, where it is a single character.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnicodeNormalization {
+    /// Compare names byte by byte
+    #[default]
+    None,
+    /// Compare names in Normalization Form C (canonical composition)
+    Nfc,
+    /// Compare names in Normalization Form D (canonical decomposition)
+    Nfd,
+}
+
+impl UnicodeNormalization {
+    /// The normalized form of a name
+    pub fn normalize<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::None => Cow::Borrowed(name),
+            Self::Nfc if is_nfc(name) => Cow::Borrowed(name),
+            Self::Nfc => Cow::Owned(name.nfc().collect()),
+            Self::Nfd if is_nfd(name) => Cow::Borrowed(name),
+            Self::Nfd => Cow::Owned(name.nfd().collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use test_case::test_case;
+
+    use super::*;
+
+    const NFC: &str = "caf\u{e9}";
+    const NFD: &str = "cafe\u{301}";
+
+    #[test_case(UnicodeNormalization::None, NFC, NFC)]
+    #[test_case(UnicodeNormalization::None, NFD, NFD)]
+    #[test_case(UnicodeNormalization::Nfc, NFC, NFC)]
+    #[test_case(UnicodeNormalization::Nfc, NFD, NFC)]
+    #[test_case(UnicodeNormalization::Nfd, NFC, NFD)]
+    #[test_case(UnicodeNormalization::Nfd, NFD, NFD)]
+    fn test_normalize(normalization: UnicodeNormalization, name: &str, expected: &str) {
+        assert_eq!(normalization.normalize(name), expected);
+    }
+}
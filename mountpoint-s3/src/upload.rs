@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
 use std::{fmt::Debug, sync::Arc};
 
 use mountpoint_s3_client::checksums::crc32c_from_base64;
@@ -12,10 +15,19 @@ use tracing::error;
 use crate::checksums::combine_checksums;
 use crate::fs::{ServerSideEncryption, SseCorruptedError};
 
+mod append;
+pub use append::{AppendUploadError, AppendUploadRequest};
+
+mod staged;
+pub use staged::{BaseObject, StagedUploadError, StagedUploadRequest};
+
 type PutRequestError<Client> = ObjectClientError<PutObjectError, <Client as ObjectClient>::ClientError>;
 
 const MAX_S3_MULTIPART_UPLOAD_PARTS: usize = 10000;
 
+/// Maximum size of a part of a multipart upload
+const MAX_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
 /// An [Uploader] creates and manages streaming PutObject requests.
 #[derive(Debug)]
 pub struct Uploader<Client> {
@@ -30,6 +42,31 @@ struct UploaderInner<Client> {
     use_additional_checksums: bool,
 }
 
+impl<Client> UploaderInner<Client> {
+    /// Parameters for a new object with the given user-defined metadata
+    fn put_object_params(&self, user_metadata: HashMap<String, String>) -> Result<PutObjectParams, SseCorruptedError> {
+        let mut params = PutObjectParams::new().user_metadata(user_metadata);
+
+        if self.use_additional_checksums {
+            params = params.trailing_checksums(PutObjectTrailingChecksums::Enabled);
+        } else {
+            params = params.trailing_checksums(PutObjectTrailingChecksums::ReviewOnly);
+        }
+
+        if let Some(storage_class) = &self.storage_class {
+            params = params.storage_class(storage_class.clone());
+        }
+        // If we have detected corruption of SSE settings, we return an error, which will currently be reported as
+        // `libc::EIO` on `open()`. MP won't be able to open files for write from this point, but this is a relatively
+        // low-risk error as data can not be uploaded with wrong SSE settings yet. Thus there is no strong reason for
+        // MP to crash and it may continue serving read's.
+        let (sse_type, key_id) = self.server_side_encryption.clone().into_inner()?;
+        params = params.server_side_encryption(sse_type);
+        params = params.ssekms_key_id(key_id);
+        Ok(params)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum UploadPutError<S, C> {
     #[error("put request creation failed")]
@@ -55,13 +92,52 @@ impl<Client: ObjectClient> Uploader<Client> {
         Self { inner: Arc::new(inner) }
     }
 
-    /// Start a new put request to the specified object.
+    /// Start a new put request to the specified object, with the given user-defined metadata.
     pub async fn put(
         &self,
         bucket: &str,
         key: &str,
+        user_metadata: HashMap<String, String>,
     ) -> Result<UploadRequest<Client>, UploadPutError<PutObjectError, Client::ClientError>> {
-        UploadRequest::new(Arc::clone(&self.inner), bucket, key).await
+        let params = self.inner.put_object_params(user_metadata)?;
+        UploadRequest::new(Arc::clone(&self.inner), bucket, key, params).await
+    }
+
+    /// Start a new upload to the specified object that stages its content in a file in
+    /// `staging_dir`. Nothing is sent to S3 until the request is completed. If `base` is given,
+    /// the upload starts from the content of that existing object instead of an empty one.
+    pub fn stage(
+        &self,
+        staging_dir: &Path,
+        bucket: &str,
+        key: &str,
+        user_metadata: HashMap<String, String>,
+        base: Option<BaseObject>,
+    ) -> std::io::Result<StagedUploadRequest<Client>> {
+        StagedUploadRequest::new(Arc::clone(&self.inner), staging_dir, bucket, key, user_metadata, base)
+    }
+
+    /// Start a new upload that appends to the end of an existing object. If `use_write_offset` is
+    /// set, data is appended to the object directly, which only directory buckets support.
+    /// Otherwise, the object is replaced by one that copies its current content, and the existing
+    /// object's `user_metadata` should be given to keep it.
+    pub async fn append(
+        &self,
+        bucket: &str,
+        key: &str,
+        base: BaseObject,
+        user_metadata: HashMap<String, String>,
+        use_write_offset: bool,
+    ) -> Result<AppendUploadRequest<Client>, AppendUploadError<Client::ClientError>> {
+        AppendUploadRequest::new(
+            Arc::clone(&self.inner),
+            bucket,
+            key,
+            base,
+            user_metadata,
+            use_write_offset,
+        )
+        .await
     }
 
     #[cfg(test)]
@@ -103,26 +179,8 @@ impl<Client: ObjectClient> UploadRequest<Client> {
         inner: Arc<UploaderInner<Client>>,
         bucket: &str,
         key: &str,
+        params: PutObjectParams,
     ) -> Result<UploadRequest<Client>, UploadPutError<PutObjectError, Client::ClientError>> {
-        let mut params = PutObjectParams::new();
-
-        if inner.use_additional_checksums {
-            params = params.trailing_checksums(PutObjectTrailingChecksums::Enabled);
-        } else {
-            params = params.trailing_checksums(PutObjectTrailingChecksums::ReviewOnly);
-        }
-
-        if let Some(storage_class) = &inner.storage_class {
-            params = params.storage_class(storage_class.clone());
-        }
-        // If we have detected corruption of SSE settings, we return an error, which will currently be reported as
-        // `libc::EIO` on `open()`. MP won't be able to open files for write from this point, but this is a relatively
-        // low-risk error as data can not be uploaded with wrong SSE settings yet. Thus there is no strong reason for
-        // MP to crash and it may continue serving read's.
-        let (sse_type, key_id) = inner.server_side_encryption.clone().into_inner()?;
-        params = params.server_side_encryption(sse_type);
-        params = params.ssekms_key_id(key_id);
-
         let request = inner.client.put_object(bucket, key, &params).await?;
         let maximum_upload_size = inner
             .client
@@ -175,18 +233,7 @@ impl<Client: ObjectClient> UploadRequest<Client> {
             .request
             .review_and_complete(move |review| verify_checksums(review, size, checksum))
             .await?;
-        if let Err(err) = self
-            .sse
-            .verify_response(result.sse_type.as_deref(), result.sse_kms_key_id.as_deref())
-        {
-            error!(key=?self.key, error=?err, "SSE settings were corrupted after the upload completion");
-            // Reaching this point is very unlikely and means that SSE settings were corrupted in transit or on S3 side, this may be a sign of a bug
-            // in CRT code or S3. Thus, we terminate Mountpoint to send the most noticeable signal to customer about the issue. We prefer exiting
-            // instead of returning an error because:
-            // 1. this error would only be reported on `flush` which many applications ignore and
-            // 2. the reported error is severe as the object was already uploaded to S3.
-            std::process::exit(1);
-        }
+        verify_sse_response(&self.sse, &self.key, &result);
         Ok(result)
     }
 }
@@ -202,6 +249,25 @@ impl<Client: ObjectClient> Debug for UploadRequest<Client> {
     }
 }
 
+/// Split `range` into `count` contiguous pieces of (almost) equal length
+fn split_evenly(range: Range<u64>, count: u64) -> impl Iterator<Item = Range<u64>> {
+    let len = range.end - range.start;
+    (0..count).map(move |i| range.start + len * i / count..range.start + len * (i + 1) / count)
+}
+
+/// Check that the object was uploaded with the expected SSE settings, or exit
+fn verify_sse_response(sse: &ServerSideEncryption, key: &str, result: &PutObjectResult) {
+    if let Err(err) = sse.verify_response(result.sse_type.as_deref(), result.sse_kms_key_id.as_deref()) {
+        error!(?key, error=?err, "SSE settings were corrupted after the upload completion");
+        // Reaching this point is very unlikely and means that SSE settings were corrupted in transit or on S3 side, this may be a sign of a bug
+        // in CRT code or S3. Thus, we terminate Mountpoint to send the most noticeable signal to customer about the issue. We prefer exiting
+        // instead of returning an error because:
+        // 1. this error would only be reported on `flush` which many applications ignore and
+        // 2. the reported error is severe as the object was already uploaded to S3.
+        std::process::exit(1);
+    }
+}
+
 fn verify_checksums(review: UploadReview, expected_size: u64, expected_checksum: Crc32c) -> bool {
     let mut uploaded_size = 0u64;
     let mut uploaded_checksum = Crc32c::new(0);
@@ -245,8 +311,6 @@ fn verify_checksums(review: UploadReview, expected_size: u64, expected_checksum:
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
     use super::*;
     use mountpoint_s3_client::{
         failure_client::countdown_failure_client,
@@ -266,7 +330,7 @@ mod tests {
             ..Default::default()
         }));
         let uploader = Uploader::new(client.clone(), None, ServerSideEncryption::default(), true);
-        let request = uploader.put(bucket, key).await.unwrap();
+        let request = uploader.put(bucket, key, Default::default()).await.unwrap();
 
         assert!(!client.contains_key(key));
         assert!(client.is_upload_in_progress(key));
@@ -277,6 +341,24 @@ mod tests {
         assert!(!client.is_upload_in_progress(key));
     }
 
+    #[tokio::test]
+    async fn user_metadata_test() {
+        let bucket = "bucket";
+        let key = "hello";
+
+        let client = Arc::new(MockClient::new(MockClientConfig {
+            bucket: bucket.to_owned(),
+            part_size: 32,
+            ..Default::default()
+        }));
+        let uploader = Uploader::new(client.clone(), None, ServerSideEncryption::default(), true);
+        let user_metadata = HashMap::from([("owner".to_owned(), "data-team".to_owned())]);
+        let request = uploader.put(bucket, key, user_metadata.clone()).await.unwrap();
+        request.complete().await.unwrap();
+
+        assert_eq!(client.get_object_user_metadata(key).unwrap(), user_metadata);
+    }
+
     #[tokio::test]
     async fn write_order_test() {
         let bucket = "bucket";
@@ -296,7 +378,7 @@ mod tests {
             true,
         );
 
-        let mut request = uploader.put(bucket, key).await.unwrap();
+        let mut request = uploader.put(bucket, key, Default::default()).await.unwrap();
 
         let data = b"foo";
         let mut offset = 0;
@@ -347,7 +429,7 @@ mod tests {
 
         // First request fails on first write.
         {
-            let mut request = uploader.put(bucket, key).await.unwrap();
+            let mut request = uploader.put(bucket, key, Default::default()).await.unwrap();
 
             let data = b"foo";
             request.write(0, data).await.expect_err("first write should fail");
@@ -357,7 +439,7 @@ mod tests {
 
         // Second request fails on complete (after one write).
         {
-            let mut request = uploader.put(bucket, key).await.unwrap();
+            let mut request = uploader.put(bucket, key, Default::default()).await.unwrap();
 
             let data = b"foo";
             _ = request.write(0, data).await.unwrap();
@@ -385,7 +467,7 @@ mod tests {
             ..Default::default()
         }));
         let uploader = Uploader::new(client.clone(), None, ServerSideEncryption::default(), true);
-        let mut request = uploader.put(bucket, key).await.unwrap();
+        let mut request = uploader.put(bucket, key, Default::default()).await.unwrap();
 
         let successful_writes = PART_SIZE * MAX_S3_MULTIPART_UPLOAD_PARTS / write_size;
         let data = vec![0xaa; write_size];
@@ -424,7 +506,7 @@ mod tests {
             .server_side_encryption
             .corrupt_data(sse_type_corrupted.map(String::from), key_id_corrupted.map(String::from));
         let err = uploader
-            .put("bucket", "hello")
+            .put("bucket", "hello", Default::default())
             .await
             .expect_err("sse checksum must be checked");
         assert!(matches!(
@@ -450,6 +532,9 @@ mod tests {
             ServerSideEncryption::new(Some("aws:kms".to_string()), Some("some_key".to_string())),
             true,
         );
-        uploader.put(bucket, key).await.expect("put with sse should succeed");
+        uploader
+            .put(bucket, key, Default::default())
+            .await
+            .expect("put with sse should succeed");
     }
 }
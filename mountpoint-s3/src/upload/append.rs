@@ -0,0 +1,433 @@
+//! Uploads that append to the end of an existing object.
+//!
+//! Directory buckets (S3 Express One Zone) support appending to an object directly, with each
+//! request giving the offset it expects the object to end at. Other buckets don't, so instead we
+//! replace the object with a multipart upload whose first parts are copied from the existing object
+//! with UploadPartCopy, followed by the appended data. Small objects can't be copied as a part, so
+//! they are downloaded and uploaded again along with the first appended data.
+//!
+//! In both cases, the upload fails if the existing object changed since it was opened: appends give
+//! the offset they expect, and copies, downloads, and completing the multipart upload require the
+//! object to still have the same ETag. Otherwise, a concurrent overwrite could be silently replaced.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use futures::{pin_mut, StreamExt};
+use mountpoint_s3_client::error::{GetObjectError, ObjectClientError, PutObjectError};
+use mountpoint_s3_client::types::{ETag, GetObjectParams, GetObjectRequest, MIN_MULTIPART_PART_SIZE};
+use mountpoint_s3_client::{MultipartUpload, ObjectClient};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::fs::SseCorruptedError;
+
+use super::{
+    split_evenly, verify_sse_response, BaseObject, UploaderInner, MAX_PART_SIZE, MAX_S3_MULTIPART_UPLOAD_PARTS,
+};
+
+#[derive(Debug, Error)]
+pub enum AppendUploadError<E: std::error::Error> {
+    #[error("put request failed")]
+    PutRequestFailed(#[source] ObjectClientError<PutObjectError, E>),
+
+    #[error("get request for the existing object failed")]
+    GetRequestFailed(#[source] ObjectClientError<GetObjectError, E>),
+
+    #[error("out of order write is NOT supported by Mountpoint, aborting the upload; expected offset {expected_offset:?} but got {write_offset:?}")]
+    OutOfOrderWrite { write_offset: u64, expected_offset: u64 },
+
+    #[error("object exceeded maximum upload size of {maximum_size} bytes")]
+    ObjectTooBig { maximum_size: usize },
+
+    #[error("SSE settings corrupted")]
+    SseCorruptedError(#[from] SseCorruptedError),
+}
+
+/// Manages an upload that appends to an existing object.
+///
+/// Like [super::UploadRequest], it only accepts sequential writes, starting at the end of the
+/// existing object. Written data is buffered until there is enough of it for a part.
+pub struct AppendUploadRequest<Client: ObjectClient> {
+    inner: Arc<UploaderInner<Client>>,
+    bucket: String,
+    key: String,
+    /// Size of the existing object
+    base_size: u64,
+    /// Size of the object, including the data that is still buffered
+    next_offset: u64,
+    /// Size of the object that was already sent to S3
+    uploaded: u64,
+    buffer: Vec<u8>,
+    part_size: usize,
+    maximum_upload_size: Option<usize>,
+    state: AppendState<Client>,
+}
+
+enum AppendState<Client: ObjectClient> {
+    /// Data is appended to the object directly, at the offset the object is expected to end at
+    WriteOffset,
+    /// Data is uploaded as parts of a multipart upload that replaces the object, as long as it
+    /// still has the ETag it was opened with
+    Multipart {
+        upload: Client::MultipartUpload,
+        base_etag: ETag,
+    },
+}
+
+impl<Client: ObjectClient> AppendUploadRequest<Client> {
+    pub(super) async fn new(
+        inner: Arc<UploaderInner<Client>>,
+        bucket: &str,
+        key: &str,
+        base: BaseObject,
+        user_metadata: HashMap<String, String>,
+        use_write_offset: bool,
+    ) -> Result<Self, AppendUploadError<Client::ClientError>> {
+        let part_size = inner
+            .client
+            .part_size()
+            .unwrap_or_default()
+            .max(MIN_MULTIPART_PART_SIZE as usize);
+        let mut request = Self {
+            inner,
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            base_size: base.size,
+            next_offset: base.size,
+            uploaded: base.size,
+            buffer: Vec::new(),
+            part_size,
+            maximum_upload_size: None,
+            state: AppendState::WriteOffset,
+        };
+        if use_write_offset {
+            return Ok(request);
+        }
+
+        // Objects too small to be copied as a part are uploaded again from the buffer
+        if base.size < MIN_MULTIPART_PART_SIZE {
+            request.buffer = request.download(&base).await?;
+            request.uploaded = 0;
+        }
+
+        let params = request.inner.put_object_params(user_metadata)?;
+        let mut upload = request
+            .inner
+            .client
+            .create_multipart_upload(bucket, key, &params)
+            .await
+            .map_err(AppendUploadError::PutRequestFailed)?;
+
+        let mut copied_parts = 0;
+        if request.uploaded > 0 {
+            let count = base.size.div_ceil(MAX_PART_SIZE);
+            for range in split_evenly(0..base.size, count) {
+                if let Err(e) = upload.upload_part_copy(key, range, Some(base.etag.clone())).await {
+                    abort_upload(upload, key).await;
+                    return Err(AppendUploadError::PutRequestFailed(e));
+                }
+            }
+            copied_parts = count as usize;
+        }
+        request.maximum_upload_size = Some(
+            (base.size as usize).saturating_add(
+                MAX_S3_MULTIPART_UPLOAD_PARTS
+                    .saturating_sub(copied_parts)
+                    .saturating_mul(part_size),
+            ),
+        );
+        request.state = AppendState::Multipart {
+            upload,
+            base_etag: base.etag,
+        };
+        Ok(request)
+    }
+
+    /// Size of the object, including the data appended so far
+    pub fn size(&self) -> u64 {
+        self.next_offset
+    }
+
+    pub async fn write(&mut self, offset: i64, data: &[u8]) -> Result<usize, AppendUploadError<Client::ClientError>> {
+        if offset != self.next_offset as i64 {
+            return Err(AppendUploadError::OutOfOrderWrite {
+                write_offset: offset as u64,
+                expected_offset: self.next_offset,
+            });
+        }
+        if let Some(maximum_size) = self.maximum_upload_size {
+            if self.next_offset + data.len() as u64 > maximum_size as u64 {
+                return Err(AppendUploadError::ObjectTooBig { maximum_size });
+            }
+        }
+
+        self.buffer.extend_from_slice(data);
+        self.next_offset += data.len() as u64;
+        while self.buffer.len() >= self.part_size {
+            let rest = self.buffer.split_off(self.part_size);
+            let part = std::mem::replace(&mut self.buffer, rest);
+            self.upload(&part).await?;
+        }
+        Ok(data.len())
+    }
+
+    /// Upload the remaining data and complete the upload. Nothing is uploaded if no data was
+    /// appended.
+    pub async fn complete(mut self) -> Result<(), AppendUploadError<Client::ClientError>> {
+        if self.next_offset == self.base_size {
+            if let AppendState::Multipart { upload, .. } = self.state {
+                abort_upload(upload, &self.key).await;
+            }
+            return Ok(());
+        }
+
+        if !self.buffer.is_empty() {
+            let part = std::mem::take(&mut self.buffer);
+            self.upload(&part).await?;
+        }
+        if let AppendState::Multipart { upload, base_etag } = self.state {
+            let result = upload
+                .complete(Some(base_etag))
+                .await
+                .map_err(AppendUploadError::PutRequestFailed)?;
+            verify_sse_response(&self.inner.server_side_encryption, &self.key, &result);
+        }
+        Ok(())
+    }
+
+    /// Give up on the upload, leaving the existing object unchanged
+    pub async fn abort(self) {
+        if let AppendState::Multipart { upload, .. } = self.state {
+            abort_upload(upload, &self.key).await;
+        }
+    }
+
+    async fn upload(&mut self, data: &[u8]) -> Result<(), AppendUploadError<Client::ClientError>> {
+        match &mut self.state {
+            AppendState::WriteOffset => {
+                let result = self
+                    .inner
+                    .client
+                    .append_object(&self.bucket, &self.key, self.uploaded, data)
+                    .await
+                    .map_err(AppendUploadError::PutRequestFailed)?;
+                verify_sse_response(&self.inner.server_side_encryption, &self.key, &result);
+            }
+            AppendState::Multipart { upload, .. } => {
+                upload
+                    .upload_part(data)
+                    .await
+                    .map_err(AppendUploadError::PutRequestFailed)?;
+            }
+        }
+        self.uploaded += data.len() as u64;
+        Ok(())
+    }
+
+    /// Download the whole existing object, as long as it still has the expected ETag
+    async fn download(&self, base: &BaseObject) -> Result<Vec<u8>, AppendUploadError<Client::ClientError>> {
+        let request = self
+            .inner
+            .client
+            .get_object(
+                &self.bucket,
+                &self.key,
+                &GetObjectParams::new().if_match(Some(base.etag.clone())),
+            )
+            .await
+            .map_err(AppendUploadError::GetRequestFailed)?;
+        pin_mut!(request);
+        request.as_mut().increment_read_window(base.size as usize);
+
+        let mut body = vec![0u8; base.size as usize];
+        while let Some(part) = request.next().await {
+            let (offset, data) = part.map_err(AppendUploadError::GetRequestFailed)?;
+            let offset = offset as usize;
+            body[offset..offset + data.len()].copy_from_slice(&data);
+        }
+        Ok(body)
+    }
+}
+
+impl<Client: ObjectClient> Debug for AppendUploadRequest<Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match &self.state {
+            AppendState::WriteOffset => "WriteOffset",
+            AppendState::Multipart { .. } => "Multipart",
+        };
+        f.debug_struct("AppendUploadRequest")
+            .field("bucket", &self.bucket)
+            .field("key", &self.key)
+            .field("next_offset", &self.next_offset)
+            .field("state", &state)
+            .finish()
+    }
+}
+
+async fn abort_upload(upload: impl MultipartUpload, key: &str) {
+    if let Err(error) = upload.abort().await {
+        warn!(?key, ?error, "failed to abort multipart upload");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mountpoint_s3_client::mock_client::{ramp_bytes, MockClient, MockClientConfig, MockObject, Operation};
+    use mountpoint_s3_client::types::ETag;
+    use test_case::test_case;
+
+    use crate::fs::ServerSideEncryption;
+    use crate::upload::Uploader;
+
+    use super::*;
+
+    const MIB: usize = 1024 * 1024;
+
+    fn new_uploader() -> (Arc<MockClient>, Uploader<MockClient>) {
+        let client = Arc::new(MockClient::new(MockClientConfig {
+            bucket: "bucket".to_owned(),
+            part_size: MIB,
+            ..Default::default()
+        }));
+        let uploader = Uploader::new(client.clone(), None, ServerSideEncryption::default(), true);
+        (client, uploader)
+    }
+
+    fn add_base_object(client: &MockClient, key: &str, size: usize) -> BaseObject {
+        let mut object = MockObject::ramp(0xaa, size, ETag::for_tests());
+        object.set_user_metadata(HashMap::from([("owner".to_owned(), "data-team".to_owned())]));
+        client.add_object(key, object);
+        BaseObject {
+            etag: ETag::for_tests(),
+            size: size as u64,
+        }
+    }
+
+    async fn get_object_bytes(client: &MockClient, key: &str) -> Vec<u8> {
+        let get = client.get_object("bucket", key, &GetObjectParams::new()).await.unwrap();
+        get.collect().await.unwrap().to_vec()
+    }
+
+    #[test_case(10, false; "small object")]
+    #[test_case(6 * MIB, false; "large object")]
+    #[test_case(10, true; "write offset")]
+    #[tokio::test]
+    async fn append_test(base_size: usize, use_write_offset: bool) {
+        let (client, uploader) = new_uploader();
+        let key = "hello";
+        let base = add_base_object(&client, key, base_size);
+        let user_metadata = client.get_object_user_metadata(key).unwrap();
+
+        let copy_counter = client.new_counter(Operation::UploadPartCopy);
+        let append_counter = client.new_counter(Operation::AppendObject);
+        let mut request = uploader
+            .append("bucket", key, base, user_metadata.clone(), use_write_offset)
+            .await
+            .unwrap();
+        let appended = ramp_bytes(0x11, 6 * MIB);
+        let mut offset = base_size;
+        for chunk in appended.chunks(MIB) {
+            request.write(offset as i64, chunk).await.unwrap();
+            offset += chunk.len();
+        }
+        request.write(0, b"hello").await.expect_err("writes must be sequential");
+        request.complete().await.unwrap();
+
+        let mut expected = ramp_bytes(0xaa, base_size);
+        expected.extend_from_slice(&appended);
+        assert_eq!(get_object_bytes(&client, key).await, expected);
+        assert_eq!(client.get_object_user_metadata(key).unwrap(), user_metadata);
+        assert_eq!(copy_counter.count(), (base_size >= 5 * MIB && !use_write_offset) as u64);
+        assert_eq!(append_counter.count(), if use_write_offset { 2 } else { 0 });
+        assert!(!client.is_upload_in_progress(key));
+    }
+
+    #[test_case(10, false; "small object")]
+    #[test_case(6 * MIB, false; "large object")]
+    #[test_case(10, true; "write offset")]
+    #[tokio::test]
+    async fn append_nothing_test(base_size: usize, use_write_offset: bool) {
+        let (client, uploader) = new_uploader();
+        let key = "hello";
+        let base = add_base_object(&client, key, base_size);
+
+        let request = uploader
+            .append("bucket", key, base, Default::default(), use_write_offset)
+            .await
+            .unwrap();
+        request.complete().await.unwrap();
+
+        // The existing object is left as it was
+        assert_eq!(get_object_bytes(&client, key).await, ramp_bytes(0xaa, base_size));
+        assert!(!client.get_object_user_metadata(key).unwrap().is_empty());
+        assert!(!client.is_upload_in_progress(key));
+    }
+
+    #[test_case(10, false; "small object")]
+    #[test_case(6 * MIB, false; "large object")]
+    #[test_case(10, true; "write offset")]
+    #[tokio::test]
+    async fn append_changed_object_test(base_size: usize, use_write_offset: bool) {
+        let (client, uploader) = new_uploader();
+        let key = "hello";
+        let base = add_base_object(&client, key, base_size);
+
+        // The object changes after it was opened
+        client.add_object(
+            key,
+            MockObject::constant(0, base_size + 1, "\"other\"".parse().unwrap()),
+        );
+        let result = match uploader
+            .append("bucket", key, base, Default::default(), use_write_offset)
+            .await
+        {
+            Ok(mut request) => {
+                request.write(base_size as i64, b"hello").await.unwrap();
+                request.complete().await
+            }
+            Err(e) => Err(e),
+        };
+        assert!(matches!(
+            result,
+            Err(AppendUploadError::PutRequestFailed(ObjectClientError::ServiceError(
+                PutObjectError::PreconditionFailed | PutObjectError::InvalidWriteOffset
+            ))) | Err(AppendUploadError::GetRequestFailed(ObjectClientError::ServiceError(
+                GetObjectError::PreconditionFailed
+            )))
+        ));
+        assert_eq!(get_object_bytes(&client, key).await, vec![0; base_size + 1]);
+        assert!(!client.is_upload_in_progress(key));
+    }
+
+    #[test_case(10; "small object")]
+    #[test_case(6 * MIB; "large object")]
+    #[tokio::test]
+    async fn append_overwritten_before_complete_test(base_size: usize) {
+        let (client, uploader) = new_uploader();
+        let key = "hello";
+        let base = add_base_object(&client, key, base_size);
+
+        let mut request = uploader
+            .append("bucket", key, base, Default::default(), false)
+            .await
+            .unwrap();
+        request.write(base_size as i64, b"hello").await.unwrap();
+
+        // The object is overwritten while the upload is in progress
+        client.add_object(
+            key,
+            MockObject::constant(0, base_size + 1, "\"other\"".parse().unwrap()),
+        );
+        let result = request.complete().await;
+        assert!(matches!(
+            result,
+            Err(AppendUploadError::PutRequestFailed(ObjectClientError::ServiceError(
+                PutObjectError::PreconditionFailed
+            )))
+        ));
+        assert_eq!(get_object_bytes(&client, key).await, vec![0; base_size + 1]);
+        assert!(!client.is_upload_in_progress(key));
+    }
+}
@@ -0,0 +1,799 @@
+//! Uploads that stage the content of an object in a local file.
+//!
+//! Streaming uploads require data to be written sequentially, since it is sent to S3 as it arrives.
+//! A staged upload instead writes to a local file, which allows writes at any offset as well as
+//! reads of the data written so far, and only uploads the object when it is completed.
+//!
+//! A staged upload can also start from an existing object. Ranges of that object are only
+//! downloaded into the staging file when they are read, and ranges large enough to be parts of a
+//! multipart upload are copied on the server side (with UploadPartCopy) when the upload completes
+//! rather than being downloaded and uploaded again.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::ops::Range;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::{pin_mut, StreamExt};
+use mountpoint_s3_client::error::{GetObjectError, ObjectClientError, PutObjectError};
+use mountpoint_s3_client::types::{ETag, GetObjectParams, GetObjectRequest, PutObjectResult, MIN_MULTIPART_PART_SIZE};
+use mountpoint_s3_client::{MultipartUpload, ObjectClient};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::fs::SseCorruptedError;
+
+use super::{
+    split_evenly, verify_sse_response, UploadPutError, UploadRequest, UploadWriteError, UploaderInner, MAX_PART_SIZE,
+    MAX_S3_MULTIPART_UPLOAD_PARTS,
+};
+
+/// Size of the chunks read from the staging file when uploading it, if the client has no part size
+const DEFAULT_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum StagedUploadError<E: std::error::Error> {
+    #[error("staging file access failed")]
+    StagingFile(#[from] io::Error),
+
+    #[error("object exceeded maximum upload size of {maximum_size} bytes")]
+    ObjectTooBig { maximum_size: usize },
+
+    #[error("put request failed")]
+    PutRequestFailed(#[source] ObjectClientError<PutObjectError, E>),
+
+    #[error("get request for the existing object failed")]
+    GetRequestFailed(#[source] ObjectClientError<GetObjectError, E>),
+
+    #[error("SSE settings corrupted")]
+    SseCorruptedError(#[from] SseCorruptedError),
+
+    #[error("the ETag of the uploaded object is unknown")]
+    MissingETag,
+}
+
+/// An existing object that a staged upload starts from
+#[derive(Debug, Clone)]
+pub struct BaseObject {
+    pub etag: ETag,
+    pub size: u64,
+}
+
+/// Manages the upload of an object whose content is staged in a local file.
+///
+/// The staging file is unlinked as soon as it is created, so it is removed when the request is
+/// dropped, or if Mountpoint exits before completing it.
+pub struct StagedUploadRequest<Client: ObjectClient> {
+    inner: Arc<UploaderInner<Client>>,
+    bucket: String,
+    key: String,
+    user_metadata: HashMap<String, String>,
+    file: File,
+    size: u64,
+    maximum_upload_size: Option<usize>,
+    /// The existing object this upload starts from, if any
+    base: Option<BaseObject>,
+    /// Length of the prefix of the existing object that is still part of this object. Everything
+    /// past it was either written or is zeroes from extending the object.
+    base_len: u64,
+    /// Ranges written since the upload started
+    written: RangeSet,
+    /// Ranges of the staging file that hold valid data, either written or fetched from the existing object
+    present: RangeSet,
+    /// Whether the object differs from the existing object, so must be uploaded on completion
+    modified: bool,
+}
+
+impl<Client: ObjectClient> StagedUploadRequest<Client> {
+    pub(super) fn new(
+        inner: Arc<UploaderInner<Client>>,
+        staging_dir: &Path,
+        bucket: &str,
+        key: &str,
+        user_metadata: HashMap<String, String>,
+        base: Option<BaseObject>,
+    ) -> io::Result<Self> {
+        let file = create_staging_file(staging_dir)?;
+        let size = base.as_ref().map_or(0, |base| base.size);
+        file.set_len(size)?;
+        let maximum_upload_size = inner
+            .client
+            .part_size()
+            .map(|ps| ps.saturating_mul(MAX_S3_MULTIPART_UPLOAD_PARTS));
+        Ok(Self {
+            inner,
+            bucket: bucket.to_owned(),
+            key: key.to_owned(),
+            user_metadata,
+            file,
+            size,
+            maximum_upload_size,
+            modified: base.is_none(),
+            base,
+            base_len: size,
+            written: Default::default(),
+            present: Default::default(),
+        })
+    }
+
+    /// Size of the object, including any gaps left between writes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Whether the object differs from the existing object, so will be uploaded on completion
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Replace the user-defined metadata the object will be uploaded with. The object only counts
+    /// as modified if the metadata changed.
+    pub fn set_user_metadata(&mut self, user_metadata: HashMap<String, String>) {
+        if user_metadata != self.user_metadata {
+            self.user_metadata = user_metadata;
+            self.modified = true;
+        }
+    }
+
+    pub fn write(&mut self, offset: i64, data: &[u8]) -> Result<usize, StagedUploadError<Client::ClientError>> {
+        let offset = u64::try_from(offset).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let end = offset + data.len() as u64;
+        self.check_size(end)?;
+
+        self.file.write_all_at(data, offset)?;
+        self.written.insert(offset..end);
+        self.present.insert(offset..end);
+        self.size = self.size.max(end);
+        self.modified = true;
+        Ok(data.len())
+    }
+
+    /// Truncate or extend the object to `size`. Extending it fills the new range with zeroes.
+    pub fn set_size(&mut self, size: u64) -> Result<(), StagedUploadError<Client::ClientError>> {
+        self.check_size(size)?;
+        if size == self.size {
+            return Ok(());
+        }
+
+        self.file.set_len(size)?;
+        self.base_len = self.base_len.min(size);
+        self.written.truncate(size);
+        self.present.truncate(size);
+        self.size = size;
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Read back the content of the object. Gaps between writes read as zeroes, and ranges of the
+    /// existing object are downloaded the first time they are read.
+    pub async fn read(&mut self, offset: u64, size: usize) -> Result<Bytes, StagedUploadError<Client::ClientError>> {
+        let end = self.size.min(offset.saturating_add(size as u64));
+        if offset >= end {
+            return Ok(Bytes::new());
+        }
+        self.fetch(offset..end).await?;
+        let mut buffer = vec![0u8; (end - offset) as usize];
+        self.file.read_exact_at(&mut buffer, offset)?;
+        Ok(buffer.into())
+    }
+
+    /// Upload the content of the staging file to S3. Does nothing if the request started from an
+    /// existing object and neither its content nor its metadata were changed.
+    pub async fn complete(mut self) -> Result<(), StagedUploadError<Client::ClientError>> {
+        if !self.modified {
+            return Ok(());
+        }
+        self.upload().await?;
+        Ok(())
+    }
+
+    /// Upload the current content of the staging file to S3, like [Self::complete], but keep the
+    /// request usable. The uploaded object becomes the existing object that later changes are
+    /// uploaded against.
+    pub async fn sync(&mut self) -> Result<(), StagedUploadError<Client::ClientError>> {
+        if !self.modified {
+            return Ok(());
+        }
+        let etag = self.upload().await?.etag.ok_or(StagedUploadError::MissingETag)?;
+        if self.base.is_none() {
+            // Without an existing object, the staging file already holds all of the content
+            self.present.insert(0..self.size);
+        }
+        self.base = Some(BaseObject { etag, size: self.size });
+        self.base_len = self.size;
+        self.written = Default::default();
+        self.modified = false;
+        Ok(())
+    }
+
+    async fn upload(&mut self) -> Result<PutObjectResult, StagedUploadError<Client::ClientError>> {
+        let part_size = (self.inner.client.part_size().unwrap_or(DEFAULT_CHUNK_SIZE) as u64)
+            .clamp(MIN_MULTIPART_PART_SIZE, MAX_PART_SIZE / 2);
+        let parts = plan_parts(self.size, &self.written.gaps(0..self.base_len), part_size);
+        let has_copies = parts.iter().any(|part| matches!(part, PlannedPart::Copy(_)));
+        let copy_source_etag = self
+            .base
+            .as_ref()
+            .filter(|_| has_copies && parts.len() <= MAX_S3_MULTIPART_UPLOAD_PARTS)
+            .map(|base| base.etag.clone());
+        match copy_source_etag {
+            Some(etag) => self.upload_multipart(parts, etag).await,
+            None => self.upload_streaming().await,
+        }
+    }
+
+    /// Upload the whole content of the staging file, downloading any part of the existing object
+    /// that has not been fetched yet. If there is an existing object, it must still have the ETag
+    /// it had when it was opened.
+    async fn upload_streaming(&mut self) -> Result<PutObjectResult, StagedUploadError<Client::ClientError>> {
+        self.fetch(0..self.size).await?;
+
+        let if_match = self.base.as_ref().map(|base| base.etag.clone());
+        let params = self
+            .inner
+            .put_object_params(self.user_metadata.clone())?
+            .if_match(if_match);
+        let mut request = UploadRequest::new(self.inner.clone(), &self.bucket, &self.key, params)
+            .await
+            .map_err(|e| match e {
+                UploadPutError::ClientError(e) => StagedUploadError::PutRequestFailed(e),
+                UploadPutError::SseCorruptedError(e) => StagedUploadError::SseCorruptedError(e),
+            })?;
+
+        let chunk_size = self.inner.client.part_size().unwrap_or(DEFAULT_CHUNK_SIZE);
+        let mut buffer = vec![0u8; chunk_size.min(self.size as usize)];
+        let mut offset = 0;
+        while offset < self.size {
+            let len = buffer.len().min((self.size - offset) as usize);
+            self.file.read_exact_at(&mut buffer[..len], offset)?;
+            request
+                .write(offset as i64, &buffer[..len])
+                .await
+                .map_err(|e| match e {
+                    UploadWriteError::PutRequestFailed(e) => StagedUploadError::PutRequestFailed(e),
+                    UploadWriteError::ObjectTooBig { maximum_size } => StagedUploadError::ObjectTooBig { maximum_size },
+                    UploadWriteError::OutOfOrderWrite { .. } => unreachable!("staged data is uploaded in order"),
+                })?;
+            offset += len as u64;
+        }
+
+        request.complete().await.map_err(StagedUploadError::PutRequestFailed)
+    }
+
+    /// Upload the object as a multipart upload that copies unchanged ranges from the existing
+    /// object, which must still have the given ETag
+    async fn upload_multipart(
+        &mut self,
+        parts: Vec<PlannedPart>,
+        etag: ETag,
+    ) -> Result<PutObjectResult, StagedUploadError<Client::ClientError>> {
+        let params = self.inner.put_object_params(self.user_metadata.clone())?;
+        let mut upload = self
+            .inner
+            .client
+            .create_multipart_upload(&self.bucket, &self.key, &params)
+            .await
+            .map_err(StagedUploadError::PutRequestFailed)?;
+
+        for part in parts {
+            if let Err(err) = self.upload_planned_part(&mut upload, part, &etag).await {
+                if let Err(abort_err) = upload.abort().await {
+                    warn!(key=?self.key, error=?abort_err, "failed to abort multipart upload");
+                }
+                return Err(err);
+            }
+        }
+
+        let result = upload
+            .complete(Some(etag))
+            .await
+            .map_err(StagedUploadError::PutRequestFailed)?;
+        verify_sse_response(&self.inner.server_side_encryption, &self.key, &result);
+        Ok(result)
+    }
+
+    async fn upload_planned_part(
+        &mut self,
+        upload: &mut Client::MultipartUpload,
+        part: PlannedPart,
+        etag: &ETag,
+    ) -> Result<(), StagedUploadError<Client::ClientError>> {
+        match part {
+            PlannedPart::Copy(range) => upload.upload_part_copy(&self.key, range, Some(etag.clone())).await,
+            PlannedPart::Local(range) => {
+                self.fetch(range.clone()).await?;
+                let mut buffer = vec![0u8; (range.end - range.start) as usize];
+                self.file.read_exact_at(&mut buffer, range.start)?;
+                upload.upload_part(&buffer).await
+            }
+        }
+        .map_err(StagedUploadError::PutRequestFailed)
+    }
+
+    /// Download the ranges of the existing object within `range` that are not in the staging file yet
+    async fn fetch(&mut self, range: Range<u64>) -> Result<(), StagedUploadError<Client::ClientError>> {
+        let Some(base) = &self.base else {
+            return Ok(());
+        };
+        let range = range.start..range.end.min(self.base_len);
+        for missing in self.present.gaps(range) {
+            let request = self
+                .inner
+                .client
+                .get_object(
+                    &self.bucket,
+                    &self.key,
+                    &GetObjectParams::new()
+                        .range(Some(missing.clone()))
+                        .if_match(Some(base.etag.clone())),
+                )
+                .await
+                .map_err(StagedUploadError::GetRequestFailed)?;
+            pin_mut!(request);
+            request
+                .as_mut()
+                .increment_read_window((missing.end - missing.start) as usize);
+            while let Some(part) = request.next().await {
+                let (offset, body) = part.map_err(StagedUploadError::GetRequestFailed)?;
+                self.file.write_all_at(&body, offset)?;
+            }
+            self.present.insert(missing);
+        }
+        Ok(())
+    }
+
+    fn check_size(&self, size: u64) -> Result<(), StagedUploadError<Client::ClientError>> {
+        if let Some(maximum_size) = self.maximum_upload_size {
+            if size > maximum_size as u64 {
+                return Err(StagedUploadError::ObjectTooBig { maximum_size });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Client: ObjectClient> Debug for StagedUploadRequest<Client> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StagedUploadRequest")
+            .field("bucket", &self.bucket)
+            .field("key", &self.key)
+            .field("size", &self.size)
+            .field("base", &self.base)
+            .finish()
+    }
+}
+
+/// Create an anonymous file in the staging directory
+fn create_staging_file(staging_dir: &Path) -> io::Result<File> {
+    static NEXT_FILE_ID: AtomicU64 = AtomicU64::new(0);
+
+    let file_id = NEXT_FILE_ID.fetch_add(1, Ordering::SeqCst);
+    let path = staging_dir.join(format!(".mountpoint-staging-{}-{}", std::process::id(), file_id));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?;
+    fs::remove_file(&path)?;
+    Ok(file)
+}
+
+/// A set of offsets, stored as sorted ranges that neither overlap nor touch
+#[derive(Debug, Default)]
+struct RangeSet {
+    ranges: Vec<Range<u64>>,
+}
+
+impl RangeSet {
+    fn insert(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        let mut merged = range;
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 1);
+        let mut inserted = false;
+        for r in self.ranges.drain(..) {
+            if r.end < merged.start {
+                ranges.push(r);
+            } else if r.start > merged.end {
+                if !inserted {
+                    ranges.push(merged.clone());
+                    inserted = true;
+                }
+                ranges.push(r);
+            } else {
+                merged = merged.start.min(r.start)..merged.end.max(r.end);
+            }
+        }
+        if !inserted {
+            ranges.push(merged);
+        }
+        self.ranges = ranges;
+    }
+
+    /// Remove all offsets at or after `len`
+    fn truncate(&mut self, len: u64) {
+        self.ranges.retain_mut(|r| {
+            r.end = r.end.min(len);
+            r.start < r.end
+        });
+    }
+
+    /// The subranges of `range` that are not in the set
+    fn gaps(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        let mut gaps = Vec::new();
+        let mut start = range.start;
+        for r in &self.ranges {
+            if r.end <= start {
+                continue;
+            }
+            if r.start >= range.end {
+                break;
+            }
+            if r.start > start {
+                gaps.push(start..r.start);
+            }
+            start = r.end;
+        }
+        if start < range.end {
+            gaps.push(start..range.end);
+        }
+        gaps
+    }
+}
+
+/// A part of a multipart upload that modifies an existing object
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PlannedPart {
+    /// Upload this range from the staging file
+    Local(Range<u64>),
+    /// Copy this range from the existing object
+    Copy(Range<u64>),
+}
+
+/// Split an object of `size` bytes into parts, copying as much as possible of the `unchanged`
+/// ranges from the existing object.
+///
+/// Every part but the last must be at least [MIN_MULTIPART_PART_SIZE], so unchanged ranges that
+/// are too small to be copied are uploaded along with their neighbours, and local parts that would
+/// be too small grow into the following unchanged range.
+fn plan_parts(size: u64, unchanged: &[Range<u64>], part_size: u64) -> Vec<PlannedPart> {
+    let mut planned = Vec::new();
+    let mut local_start = 0;
+    for copy in unchanged {
+        let mut copy = copy.clone();
+        if copy.end - copy.start < MIN_MULTIPART_PART_SIZE {
+            continue;
+        }
+        let local_len = copy.start - local_start;
+        if local_len > 0 && local_len < MIN_MULTIPART_PART_SIZE {
+            copy.start += MIN_MULTIPART_PART_SIZE - local_len;
+            if copy.end - copy.start < MIN_MULTIPART_PART_SIZE {
+                continue;
+            }
+        }
+        if copy.start > local_start {
+            planned.push(PlannedPart::Local(local_start..copy.start));
+        }
+        local_start = copy.end;
+        planned.push(PlannedPart::Copy(copy));
+    }
+    if local_start < size {
+        planned.push(PlannedPart::Local(local_start..size));
+    }
+
+    // Split parts that are too big, keeping all the pieces above the minimum part size
+    let mut parts = Vec::with_capacity(planned.len());
+    for part in planned {
+        match part {
+            PlannedPart::Local(range) => {
+                let count = ((range.end - range.start) / part_size).max(1);
+                parts.extend(split_evenly(range, count).map(PlannedPart::Local));
+            }
+            PlannedPart::Copy(range) => {
+                let count = (range.end - range.start).div_ceil(MAX_PART_SIZE);
+                parts.extend(split_evenly(range, count).map(PlannedPart::Copy));
+            }
+        }
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use mountpoint_s3_client::mock_client::{ramp_bytes, MockClient, MockClientConfig, MockObject, Operation};
+    use test_case::test_case;
+
+    use crate::fs::ServerSideEncryption;
+    use crate::upload::Uploader;
+
+    use super::*;
+
+    fn new_uploader(part_size: usize) -> (Arc<MockClient>, Uploader<MockClient>) {
+        let client = Arc::new(MockClient::new(MockClientConfig {
+            bucket: "bucket".to_owned(),
+            part_size,
+            ..Default::default()
+        }));
+        let uploader = Uploader::new(client.clone(), None, ServerSideEncryption::default(), true);
+        (client, uploader)
+    }
+
+    #[tokio::test]
+    async fn random_writes_test() {
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (client, uploader) = new_uploader(32);
+        let key = "hello";
+
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", key, Default::default(), None)
+            .unwrap();
+        request.write(10, b"world").unwrap();
+        request.write(0, b"hello").unwrap();
+        request.write(3, b"p").unwrap();
+        assert_eq!(request.size(), 15);
+        assert_eq!(request.read(0, 5).await.unwrap(), &b"helpo"[..]);
+        assert_eq!(request.read(5, 5).await.unwrap(), &[0u8; 5][..]);
+        assert_eq!(request.read(12, 100).await.unwrap(), &b"rld"[..]);
+        assert!(request.read(20, 10).await.unwrap().is_empty());
+
+        // The staging file is anonymous and nothing is sent to S3 before completion
+        assert_eq!(fs::read_dir(staging_dir.path()).unwrap().count(), 0);
+        assert!(!client.is_upload_in_progress(key));
+        assert!(!client.contains_key(key));
+
+        request.complete().await.unwrap();
+        let get = client.get_object("bucket", key, &GetObjectParams::new()).await.unwrap();
+        let body = get.collect().await.unwrap();
+        assert_eq!(&body[..], b"helpo\0\0\0\0\0world");
+    }
+
+    #[tokio::test]
+    async fn user_metadata_test() {
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (client, uploader) = new_uploader(32);
+        let key = "hello";
+
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", key, Default::default(), None)
+            .unwrap();
+        let user_metadata = HashMap::from([("owner".to_owned(), "data-team".to_owned())]);
+        request.set_user_metadata(user_metadata.clone());
+        request.complete().await.unwrap();
+
+        assert_eq!(client.get_object_user_metadata(key).unwrap(), user_metadata);
+    }
+
+    #[tokio::test]
+    async fn maximum_size_test() {
+        const PART_SIZE: usize = 32;
+
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (_client, uploader) = new_uploader(PART_SIZE);
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", "hello", Default::default(), None)
+            .unwrap();
+
+        let maximum_size = PART_SIZE * MAX_S3_MULTIPART_UPLOAD_PARTS;
+        request.write(maximum_size as i64 - 1, b"a").expect("object should fit");
+        let err = request
+            .write(maximum_size as i64, b"a")
+            .expect_err("object should be too big");
+        assert!(matches!(err, StagedUploadError::ObjectTooBig { .. }));
+        assert_eq!(request.size(), maximum_size as u64);
+    }
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn new_base_object(client: &MockClient, key: &str, size: usize) -> BaseObject {
+        let object = MockObject::ramp(0xaa, size, ETag::for_tests());
+        let base = BaseObject {
+            etag: object.etag(),
+            size: size as u64,
+        };
+        client.add_object(key, object);
+        base
+    }
+
+    async fn get_object_bytes(client: &MockClient, key: &str) -> Vec<u8> {
+        let get = client.get_object("bucket", key, &GetObjectParams::new()).await.unwrap();
+        get.collect().await.unwrap().to_vec()
+    }
+
+    #[tokio::test]
+    async fn modify_test() {
+        const SIZE: usize = 16 * MIB as usize;
+
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (client, uploader) = new_uploader(8 * MIB as usize);
+        let key = "hello";
+        let base = new_base_object(&client, key, SIZE);
+
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", key, Default::default(), Some(base))
+            .unwrap();
+        assert_eq!(request.size(), SIZE as u64);
+        request.write(1, b"hello").unwrap();
+        request.write(SIZE as i64 - 2, b"world").unwrap();
+
+        // Only the ranges that were read and not written are downloaded
+        let get_counter = client.new_counter(Operation::GetObject);
+        let expected = ramp_bytes(0xaa, SIZE);
+        assert_eq!(
+            request.read(0, 10).await.unwrap()[..],
+            [&expected[..1], &b"hello"[..], &expected[6..10]].concat()
+        );
+        assert_eq!(request.read(4096, 10).await.unwrap()[..], expected[4096..4106]);
+        assert_eq!(get_counter.count(), 3);
+
+        let copy_counter = client.new_counter(Operation::UploadPartCopy);
+        request.complete().await.unwrap();
+        assert!(copy_counter.count() > 0, "unchanged ranges should be copied");
+        assert!(!client.is_upload_in_progress(key));
+
+        let mut expected = expected;
+        expected[1..6].copy_from_slice(b"hello");
+        expected.truncate(SIZE - 2);
+        expected.extend_from_slice(b"world");
+        assert_eq!(get_object_bytes(&client, key).await, expected);
+    }
+
+    #[tokio::test]
+    async fn modify_unchanged_test() {
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (client, uploader) = new_uploader(32);
+        let key = "hello";
+        let base = new_base_object(&client, key, 1024);
+
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", key, Default::default(), Some(base))
+            .unwrap();
+        assert_eq!(request.read(0, 10).await.unwrap()[..], ramp_bytes(0xaa, 10));
+
+        let put_counter = client.new_counter(Operation::PutObject);
+        let create_counter = client.new_counter(Operation::CreateMultipartUpload);
+        request.complete().await.unwrap();
+        assert_eq!(put_counter.count(), 0);
+        assert_eq!(create_counter.count(), 0);
+    }
+
+    #[test_case(100, 200; "truncate and extend")]
+    #[test_case(20 * MIB, 20 * MIB; "extend past large base")]
+    #[test_case(10 * MIB, 12 * MIB; "truncate large base")]
+    #[tokio::test]
+    async fn modify_set_size_test(first_size: u64, second_size: u64) {
+        const SIZE: usize = 16 * MIB as usize;
+
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (client, uploader) = new_uploader(8 * MIB as usize);
+        let key = "hello";
+        let base = new_base_object(&client, key, SIZE);
+
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", key, Default::default(), Some(base))
+            .unwrap();
+        request.set_size(first_size).unwrap();
+        request.set_size(second_size).unwrap();
+        assert_eq!(request.size(), second_size);
+
+        let kept = first_size.min(SIZE as u64) as usize;
+        let mut expected = ramp_bytes(0xaa, kept);
+        expected.resize(second_size as usize, 0);
+        let offset = kept.saturating_sub(5);
+        assert_eq!(
+            request.read(offset as u64, 10).await.unwrap()[..],
+            expected[offset..(offset + 10).min(expected.len())]
+        );
+
+        request.complete().await.unwrap();
+        assert_eq!(get_object_bytes(&client, key).await, expected);
+    }
+
+    #[tokio::test]
+    async fn modify_replaced_object_test() {
+        const SIZE: usize = 16 * MIB as usize;
+
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (client, uploader) = new_uploader(8 * MIB as usize);
+        let key = "hello";
+        let base = new_base_object(&client, key, SIZE);
+
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", key, Default::default(), Some(base))
+            .unwrap();
+        request.write(0, b"hello").unwrap();
+
+        // The object is replaced while it is being modified
+        client.add_object(key, MockObject::constant(0, 10, "\"other\"".parse().unwrap()));
+        let abort_counter = client.new_counter(Operation::AbortMultipartUpload);
+        let err = request.complete().await.expect_err("upload should fail");
+        assert_eq!(abort_counter.count(), 1, "the multipart upload should be aborted once");
+        assert!(matches!(
+            err,
+            StagedUploadError::GetRequestFailed(ObjectClientError::ServiceError(GetObjectError::PreconditionFailed))
+                | StagedUploadError::PutRequestFailed(ObjectClientError::ServiceError(
+                    PutObjectError::PreconditionFailed
+                ))
+        ));
+        assert!(!client.is_upload_in_progress(key));
+        assert_eq!(get_object_bytes(&client, key).await, vec![0u8; 10]);
+    }
+
+    #[tokio::test]
+    async fn modify_replaced_small_object_test() {
+        let staging_dir = tempfile::tempdir().unwrap();
+        let (client, uploader) = new_uploader(32);
+        let key = "hello";
+        let base = new_base_object(&client, key, 1024);
+
+        // Overwrite the whole object, so that nothing is downloaded and it is uploaded with PutObject
+        let mut request = uploader
+            .stage(staging_dir.path(), "bucket", key, Default::default(), Some(base))
+            .unwrap();
+        request.write(0, &[1u8; 1024]).unwrap();
+
+        // The object is replaced while it is being modified
+        client.add_object(key, MockObject::constant(0, 10, "\"other\"".parse().unwrap()));
+        let put_counter = client.new_counter(Operation::PutObject);
+        let err = request.complete().await.expect_err("upload should fail");
+        assert!(matches!(
+            err,
+            StagedUploadError::PutRequestFailed(ObjectClientError::ServiceError(PutObjectError::PreconditionFailed))
+        ));
+        assert_eq!(put_counter.count(), 1);
+        assert_eq!(get_object_bytes(&client, key).await, vec![0u8; 10]);
+    }
+
+    #[test]
+    fn range_set_test() {
+        let mut set = RangeSet::default();
+        set.insert(10..20);
+        set.insert(30..40);
+        set.insert(20..25);
+        set.insert(0..0);
+        assert_eq!(set.ranges, vec![10..25, 30..40]);
+        assert_eq!(set.gaps(0..50), vec![0..10, 25..30, 40..50]);
+        assert_eq!(set.gaps(12..35), vec![25..30]);
+        assert!(set.gaps(31..39).is_empty());
+
+        set.insert(5..35);
+        assert_eq!(set.ranges, vec![5..40]);
+        set.truncate(20);
+        assert_eq!(set.ranges, vec![5..20]);
+        set.truncate(5);
+        assert!(set.ranges.is_empty());
+    }
+
+    #[test_case(10 * MIB, &[], &[PlannedPart::Local(0..10 * MIB)]; "no unchanged ranges")]
+    #[test_case(20 * MIB, &[0..20 * MIB], &[PlannedPart::Copy(0..20 * MIB)]; "unchanged object")]
+    #[test_case(20 * MIB, &[0..MIB, 2 * MIB..20 * MIB], &[PlannedPart::Local(0..5 * MIB), PlannedPart::Copy(5 * MIB..20 * MIB)]; "small local part grows")]
+    #[test_case(20 * MIB, &[0..4 * MIB, 10 * MIB..14 * MIB], &[PlannedPart::Local(0..10 * MIB), PlannedPart::Local(10 * MIB..20 * MIB)]; "small unchanged ranges")]
+    #[test_case(20 * MIB, &[MIB..9 * MIB], &[PlannedPart::Local(0..10 * MIB), PlannedPart::Local(10 * MIB..20 * MIB)]; "unchanged range too small after growing")]
+    #[test_case(20 * MIB, &[5 * MIB..20 * MIB], &[PlannedPart::Local(0..5 * MIB), PlannedPart::Copy(5 * MIB..20 * MIB)]; "local part at minimum size")]
+    #[test_case(30 * MIB, &[10 * MIB..20 * MIB], &[PlannedPart::Local(0..10 * MIB), PlannedPart::Copy(10 * MIB..20 * MIB), PlannedPart::Local(20 * MIB..30 * MIB)]; "copy in the middle")]
+    #[test_case(24 * MIB, &[], &[PlannedPart::Local(0..8 * MIB), PlannedPart::Local(8 * MIB..16 * MIB), PlannedPart::Local(16 * MIB..24 * MIB)]; "local parts split")]
+    fn plan_parts_test(size: u64, unchanged: &[Range<u64>], expected: &[PlannedPart]) {
+        assert_eq!(plan_parts(size, unchanged, 8 * MIB), expected);
+    }
+
+    #[test]
+    fn plan_large_copy_test() {
+        let size = 2 * MAX_PART_SIZE + 1;
+        let parts = plan_parts(size, &[0..size], 8 * MIB);
+        assert_eq!(parts.len(), 3);
+        for part in &parts {
+            let PlannedPart::Copy(range) = part else {
+                panic!("unexpected part {part:?}");
+            };
+            let len = range.end - range.start;
+            assert!((MIN_MULTIPART_PART_SIZE..=MAX_PART_SIZE).contains(&len));
+        }
+    }
+}
@@ -229,6 +229,22 @@ fn max_ttl_exceeded() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test_case("--reported-capacity")]
+#[test_case("--reported-free-space")]
+fn reported_size_overflows(flag: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = assert_fs::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("mount-s3")?;
+
+    cmd.arg("test-bucket")
+        .arg(dir.path())
+        .arg(flag)
+        .arg(format!("{}", (u64::MAX >> 20) + 1));
+    let error_message = format!("invalid value '{}' for '{flag} <MiB>'", (u64::MAX >> 20) + 1);
+    cmd.assert().failure().stderr(predicate::str::contains(error_message));
+
+    Ok(())
+}
+
 #[test_case("20000000000000000000")]
 #[test_case("infinite")]
 fn invalid_ttl(invalid_ttl: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -166,7 +166,7 @@ pub mod mock_session {
             };
             let client = Arc::new(MockClient::new(client_config));
             let runtime = ThreadPool::builder().pool_size(1).create().unwrap();
-            let prefetcher = caching_prefetch(cache, runtime, test_config.prefetcher_config);
+            let prefetcher = caching_prefetch(cache, runtime, test_config.prefetcher_config, Default::default());
             let session = create_fuse_session(
                 client.clone(),
                 prefetcher,
@@ -319,7 +319,7 @@ pub mod s3_session {
                 .endpoint_config(EndpointConfig::new(&region));
             let client = S3CrtClient::new(client_config).unwrap();
             let runtime = client.event_loop_group();
-            let prefetcher = caching_prefetch(cache, runtime, test_config.prefetcher_config);
+            let prefetcher = caching_prefetch(cache, runtime, test_config.prefetcher_config, Default::default());
             let session = create_fuse_session(
                 client,
                 prefetcher,
@@ -11,7 +11,7 @@ pub mod s3;
 use aws_sdk_s3::config::Credentials;
 use fuser::{FileAttr, FileType};
 use futures::executor::ThreadPool;
-use mountpoint_s3::fs::{DirectoryEntry, DirectoryReplier};
+use mountpoint_s3::fs::{BucketMount, DirectoryEntry, DirectoryReplier};
 use mountpoint_s3::prefetch::{default_prefetch, DefaultPrefetcher};
 use mountpoint_s3::prefix::Prefix;
 use mountpoint_s3::{S3Filesystem, S3FilesystemConfig};
@@ -57,6 +57,19 @@ where
     S3Filesystem::new(client, prefetcher, bucket, prefix, config)
 }
 
+pub fn make_test_multi_bucket_filesystem<Client>(
+    client: Client,
+    buckets: Vec<BucketMount>,
+    config: S3FilesystemConfig,
+) -> TestS3Filesystem<Client>
+where
+    Client: ObjectClient + Send + Sync + 'static,
+{
+    let runtime = ThreadPool::builder().pool_size(1).create().unwrap();
+    let prefetcher = default_prefetch(runtime, Default::default());
+    S3Filesystem::new_multi_bucket(client, prefetcher, buckets, config)
+}
+
 #[track_caller]
 pub fn assert_attr(attr: FileAttr, ftype: FileType, size: u64, uid: u32, gid: u32, perm: u16) {
     assert_eq!(attr.kind, ftype);
@@ -6,16 +6,18 @@ use libc::S_IFREG;
 use mountpoint_s3::fs::error_metadata::MOUNTPOINT_ERROR_LOOKUP_NONEXISTENT;
 #[cfg(all(feature = "s3_tests", not(feature = "s3express_tests")))]
 use mountpoint_s3::fs::error_metadata::{ErrorMetadata, MOUNTPOINT_ERROR_CLIENT};
-use mountpoint_s3::fs::{CacheConfig, ToErrno, FUSE_ROOT_INODE};
+use mountpoint_s3::fs::{BucketMount, CacheConfig, ToErrno, FUSE_ROOT_INODE};
+use mountpoint_s3::key_filter::{Glob, KeyFilter};
 use mountpoint_s3::prefix::Prefix;
 use mountpoint_s3::s3::S3Personality;
+use mountpoint_s3::unicode::UnicodeNormalization;
 use mountpoint_s3::S3FilesystemConfig;
 use mountpoint_s3_client::config::{EndpointConfig, S3ClientConfig};
 #[cfg(all(feature = "s3_tests", not(feature = "s3express_tests")))]
 use mountpoint_s3_client::error_metadata::ClientErrorMetadata;
 use mountpoint_s3_client::failure_client::countdown_failure_client;
 use mountpoint_s3_client::mock_client::{MockClient, MockClientConfig, MockClientError, MockObject, Operation};
-use mountpoint_s3_client::types::{ETag, RestoreStatus};
+use mountpoint_s3_client::types::{ETag, GetObjectParams, RestoreStatus};
 #[cfg(all(feature = "s3_tests", not(feature = "s3express_tests")))]
 use mountpoint_s3_client::PutObjectRequest;
 use mountpoint_s3_client::{ObjectClient, S3CrtClient};
@@ -26,12 +28,16 @@ use std::collections::HashMap;
 use std::ffi::OsString;
 use std::ops::Add;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use std::time::{Duration, SystemTime};
 use test_case::test_case;
+use time::macros::datetime;
 
 mod common;
-use common::{assert_attr, make_test_filesystem, make_test_filesystem_with_client, DirectoryReply, TestS3Filesystem};
+use common::{
+    assert_attr, make_test_filesystem, make_test_filesystem_with_client, make_test_multi_bucket_filesystem,
+    DirectoryReply, TestS3Filesystem,
+};
 #[cfg(all(feature = "s3_tests", not(feature = "s3express_tests")))]
 use common::{
     get_crt_client_auth_config,
@@ -425,6 +431,354 @@ async fn test_unlink_cached() {
     assert_eq!(list_counter.count(), 2);
 }
 
+#[tokio::test]
+async fn test_rename_cached() {
+    let fs_config = S3FilesystemConfig {
+        cache_config: CacheConfig {
+            serve_lookup_from_cache: true,
+            dir_ttl: Duration::from_secs(600),
+            file_ttl: Duration::from_secs(600),
+            ..Default::default()
+        },
+        allow_delete: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem("test_rename_cached", &Default::default(), fs_config);
+
+    client.add_object("file1.txt", MockObject::constant(0xa1, 15, ETag::for_tests()));
+
+    let parent_ino = FUSE_ROOT_INODE;
+    let entry = fs
+        .lookup(parent_ino, "file1.txt".as_ref())
+        .await
+        .expect("should find file as object exists");
+    let _err = fs
+        .lookup(parent_ino, "file2.txt".as_ref())
+        .await
+        .expect_err("destination should not exist yet");
+
+    let copy_counter = client.new_counter(Operation::CopyObject);
+    let delete_counter = client.new_counter(Operation::DeleteObject);
+    fs.rename(parent_ino, "file1.txt".as_ref(), parent_ino, "file2.txt".as_ref(), 0)
+        .await
+        .expect("rename should succeed");
+    assert_eq!(copy_counter.count(), 1);
+    assert_eq!(delete_counter.count(), 1);
+
+    let head_counter = client.new_counter(Operation::HeadObject);
+    let list_counter = client.new_counter(Operation::ListObjectsV2);
+
+    // Both names should be served from the cache, without going to the client
+    let renamed = fs
+        .lookup(parent_ino, "file2.txt".as_ref())
+        .await
+        .expect("renamed file should be visible");
+    assert_eq!(renamed.attr.ino, entry.attr.ino);
+    assert_eq!(renamed.attr.size, 15);
+    let err = fs
+        .lookup(parent_ino, "file1.txt".as_ref())
+        .await
+        .expect_err("source should be gone");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+    assert_eq!(head_counter.count(), 0);
+    assert_eq!(list_counter.count(), 0);
+}
+
+#[test_case(false, 0, libc::EPERM; "deletes disabled")]
+#[test_case(true, libc::RENAME_EXCHANGE, libc::EINVAL; "exchange")]
+#[tokio::test]
+async fn test_rename_not_permitted(allow_delete: bool, flags: u32, expected_errno: i32) {
+    let fs_config = S3FilesystemConfig {
+        allow_delete,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem("test_rename_not_permitted", &Default::default(), fs_config);
+
+    client.add_object("file1.txt", MockObject::constant(0xa1, 15, ETag::for_tests()));
+
+    let err = fs
+        .rename(
+            FUSE_ROOT_INODE,
+            "file1.txt".as_ref(),
+            FUSE_ROOT_INODE,
+            "file2.txt".as_ref(),
+            flags,
+        )
+        .await
+        .expect_err("rename should fail");
+    assert_eq!(err.to_errno(), expected_errno);
+    assert!(client.contains_key("file1.txt"));
+    assert!(!client.contains_key("file2.txt"));
+}
+
+#[tokio::test]
+async fn test_xattrs() {
+    let (client, fs) = make_test_filesystem("test_xattrs", &Default::default(), Default::default());
+
+    let mut object = MockObject::constant(0xa1, 15, ETag::for_tests());
+    object.set_content_type(Some("text/plain".to_owned()));
+    object.set_user_metadata(HashMap::from([("owner".to_owned(), "data-team".to_owned())]));
+    client.add_object("dir/file.txt", object);
+
+    let dir = fs
+        .lookup(FUSE_ROOT_INODE, "dir".as_ref())
+        .await
+        .expect("should find dir as object exists");
+    let entry = fs
+        .lookup(dir.attr.ino, "file.txt".as_ref())
+        .await
+        .expect("should find file as object exists");
+    let ino = entry.attr.ino;
+
+    let names = fs.listxattr(ino).await.expect("listxattr should succeed");
+    assert_eq!(
+        names,
+        vec![
+            "user.s3.etag",
+            "user.s3.storage_class",
+            "user.s3.last_modified",
+            "user.s3.content_type",
+            "user.s3.meta.owner",
+        ]
+    );
+
+    let getxattr = |name: &'static str| fs.getxattr(ino, name.as_ref());
+    let etag = getxattr("user.s3.etag").await.expect("getxattr should succeed");
+    assert_eq!(etag, Some(ETag::for_tests().as_str().as_bytes().to_vec()));
+    let storage_class = getxattr("user.s3.storage_class").await.unwrap();
+    assert_eq!(storage_class.as_deref(), Some(&b"STANDARD"[..]));
+    let content_type = getxattr("user.s3.content_type").await.unwrap();
+    assert_eq!(content_type.as_deref(), Some(&b"text/plain"[..]));
+    let owner = getxattr("user.s3.meta.owner").await.unwrap();
+    assert_eq!(owner.as_deref(), Some(&b"data-team"[..]));
+
+    let head_counter = client.new_counter(Operation::HeadObject);
+    let missing = getxattr("user.s3.meta.missing").await.unwrap();
+    assert_eq!(missing, None);
+    assert_eq!(head_counter.count(), 1);
+    let other_namespace = getxattr("security.selinux").await.unwrap();
+    assert_eq!(other_namespace, None);
+    assert_eq!(
+        head_counter.count(),
+        1,
+        "attributes outside user.s3 should not need a request"
+    );
+
+    // Directories have no extended attributes
+    let names = fs.listxattr(dir.attr.ino).await.expect("listxattr should succeed");
+    assert!(names.is_empty());
+}
+
+#[tokio::test]
+async fn test_setxattr_user_metadata() {
+    let (client, fs) = make_test_filesystem("test_setxattr_user_metadata", &Default::default(), Default::default());
+
+    let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
+    let dentry = fs
+        .mknod(FUSE_ROOT_INODE, "file.bin".as_ref(), mode, 0, 0)
+        .await
+        .unwrap();
+    let file_ino = dentry.attr.ino;
+
+    // Metadata can be set before the file is opened...
+    fs.setxattr(file_ino, "user.s3.meta.Owner".as_ref(), b"data-team", 0)
+        .await
+        .expect("setxattr should succeed before open");
+
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+
+    // ...or after it is opened, as long as no data has been written
+    fs.setxattr(file_ino, "user.s3.meta.source".as_ref(), b"pipeline", 0)
+        .await
+        .expect("setxattr should succeed before writing");
+    let err = fs
+        .setxattr(file_ino, "user.s3.meta.source".as_ref(), b"other", libc::XATTR_CREATE)
+        .await
+        .expect_err("XATTR_CREATE should fail if the attribute exists");
+    assert_eq!(err.to_errno(), libc::EEXIST);
+    let owner = fs.getxattr(file_ino, "user.s3.meta.owner".as_ref()).await.unwrap();
+    assert_eq!(owner.as_deref(), Some(&b"data-team"[..]));
+
+    fs.write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None).await.unwrap();
+    let err = fs
+        .setxattr(file_ino, "user.s3.meta.late".as_ref(), b"value", 0)
+        .await
+        .expect_err("setxattr should fail after writing");
+    assert_eq!(err.to_errno(), libc::EPERM);
+
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let expected = HashMap::from([
+        ("owner".to_owned(), "data-team".to_owned()),
+        ("source".to_owned(), "pipeline".to_owned()),
+    ]);
+    assert_eq!(client.get_object_user_metadata("file.bin").unwrap(), expected);
+
+    let err = fs
+        .setxattr(file_ino, "user.s3.meta.owner".as_ref(), b"other", 0)
+        .await
+        .expect_err("setxattr should fail on remote files");
+    assert_eq!(err.to_errno(), libc::EPERM);
+    let err = fs
+        .setxattr(file_ino, "user.s3.etag".as_ref(), b"etag", 0)
+        .await
+        .expect_err("object attributes are read-only");
+    assert_eq!(err.to_errno(), libc::EPERM);
+    let err = fs
+        .setxattr(file_ino, "security.selinux".as_ref(), b"label", 0)
+        .await
+        .expect_err("other namespaces are not supported");
+    assert_eq!(err.to_errno(), libc::ENOTSUP);
+}
+
+#[test_case(false; "symlinks disabled")]
+#[test_case(true; "symlinks enabled")]
+#[tokio::test]
+async fn test_symlink(allow_symlinks: bool) {
+    let fs_config = S3FilesystemConfig {
+        allow_symlinks,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem("test_symlink", &Default::default(), fs_config);
+
+    let target = "../some dir/target.txt";
+    let result = fs.symlink(FUSE_ROOT_INODE, "link".as_ref(), target.as_ref()).await;
+    if !allow_symlinks {
+        assert_eq!(result.expect_err("symlinks are disabled").to_errno(), libc::EPERM);
+        assert!(!client.contains_key("link"));
+        return;
+    }
+
+    let dentry = result.expect("symlink should succeed");
+    assert_eq!(dentry.attr.kind, FileType::Symlink);
+    assert_eq!(dentry.attr.size, target.len() as u64);
+    assert_eq!(dentry.attr.perm, 0o777);
+    let link_ino = dentry.attr.ino;
+
+    // Stored like s3fs does: the target is the content, and the mode marks the object as a symlink
+    let get = client
+        .get_object("test_symlink", "link", &GetObjectParams::new())
+        .await
+        .expect("get_object should succeed");
+    assert_eq!(&get.collect().await.unwrap()[..], target.as_bytes());
+    let expected = HashMap::from([("mode".to_owned(), "41471".to_owned())]);
+    assert_eq!(client.get_object_user_metadata("link").unwrap(), expected);
+
+    assert_eq!(fs.readlink(link_ino).await.unwrap(), target.as_bytes());
+    let err = fs
+        .open(link_ino, libc::O_RDONLY, 0)
+        .await
+        .expect_err("symlinks can't be opened");
+    assert_eq!(err.to_errno(), libc::ELOOP);
+
+    let err = fs
+        .symlink(FUSE_ROOT_INODE, "link".as_ref(), "other".as_ref())
+        .await
+        .expect_err("link already exists");
+    assert_eq!(err.to_errno(), libc::EEXIST);
+
+    // A fresh file system finds the symlink from the object in S3
+    let fs = make_test_filesystem_with_client(
+        client,
+        "test_symlink",
+        &Default::default(),
+        S3FilesystemConfig {
+            allow_symlinks,
+            ..Default::default()
+        },
+    );
+    let dentry = fs.lookup(FUSE_ROOT_INODE, "link".as_ref()).await.unwrap();
+    assert_eq!(dentry.attr.kind, FileType::Symlink);
+    assert_eq!(dentry.attr.size, target.len() as u64);
+    assert_eq!(fs.readlink(dentry.attr.ino).await.unwrap(), target.as_bytes());
+}
+
+#[test_case(false; "posix metadata disabled")]
+#[test_case(true; "posix metadata enabled")]
+#[tokio::test]
+async fn test_posix_metadata(posix_metadata: bool) {
+    let fs_config = S3FilesystemConfig {
+        posix_metadata,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem("test_posix_metadata", &Default::default(), fs_config);
+    let (uid, gid) = (getuid().into(), getgid().into());
+
+    let mut object = MockObject::from_bytes(b"hello", ETag::for_tests());
+    object.set_user_metadata(HashMap::from([
+        ("mode".to_owned(), "33216".to_owned()), // regular file + 0700 permissions
+        ("uid".to_owned(), "1234".to_owned()),
+        ("gid".to_owned(), "5678".to_owned()),
+        ("mtime".to_owned(), "1700000000".to_owned()),
+    ]));
+    client.add_object("remote.txt", object);
+
+    // Listing doesn't read the metadata of each object, so the attributes aren't cached until the
+    // object is looked up
+    let head_counter = client.new_counter(Operation::HeadObject);
+    let dir_handle = fs.opendir(FUSE_ROOT_INODE, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs
+        .readdirplus(FUSE_ROOT_INODE, dir_handle, 0, &mut reply)
+        .await
+        .unwrap();
+    let entry = reply.entries.iter().find(|entry| entry.name == "remote.txt").unwrap();
+    assert_eq!(entry.ttl.is_zero(), posix_metadata);
+    assert_eq!(head_counter.count(), 0);
+    fs.releasedir(FUSE_ROOT_INODE, dir_handle, 0).await.unwrap();
+
+    let dentry = fs.lookup(FUSE_ROOT_INODE, "remote.txt".as_ref()).await.unwrap();
+    if posix_metadata {
+        assert_attr(dentry.attr, FileType::RegularFile, 5, 1234, 5678, 0o700);
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(dentry.attr.mtime, mtime);
+    } else {
+        assert_attr(dentry.attr, FileType::RegularFile, 5, uid, gid, 0o644);
+    }
+
+    let mode = libc::S_IFREG | 0o600;
+    let dentry = fs.mknod(FUSE_ROOT_INODE, "new.txt".as_ref(), mode, 0, 0).await.unwrap();
+    let file_ino = dentry.attr.ino;
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+
+    // The mode can be changed until data is written
+    let attr = fs
+        .setattr(file_ino, Some(0o640), None, None, None, None, None, None)
+        .await
+        .unwrap();
+    fs.write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None).await.unwrap();
+    if posix_metadata {
+        assert_eq!(attr.attr.perm, 0o640);
+        let err = fs
+            .setattr(file_ino, Some(0o600), None, None, None, None, None, None)
+            .await
+            .expect_err("mode can't change after data is written");
+        assert_eq!(err.to_errno(), libc::EPERM);
+    } else {
+        assert_eq!(attr.attr.perm, 0o644);
+    }
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let user_metadata = client.get_object_user_metadata("new.txt").unwrap();
+    if posix_metadata {
+        let regular_file_640 = (libc::S_IFREG | 0o640).to_string();
+        assert_eq!(user_metadata.get("mode"), Some(&regular_file_640));
+        assert_eq!(user_metadata.get("uid"), Some(&uid.to_string()));
+        assert_eq!(user_metadata.get("gid"), Some(&gid.to_string()));
+        assert!(user_metadata.contains_key("mtime"));
+    } else {
+        assert!(user_metadata.is_empty());
+    }
+}
+
 #[tokio::test]
 async fn test_mknod_cached() {
     const BUCKET_NAME: &str = "test_mknod_cached";
@@ -603,7 +957,7 @@ async fn test_sequential_write(write_size: usize) {
 
     // Check that the object made it to S3 as we expected
     let get = client
-        .get_object(BUCKET_NAME, "dir1/file2.bin", None, None)
+        .get_object(BUCKET_NAME, "dir1/file2.bin", &GetObjectParams::new())
         .await
         .unwrap();
     let actual = get.collect().await.unwrap();
@@ -699,112 +1053,564 @@ async fn test_unordered_write_fails(offset: i64) {
 }
 
 #[tokio::test]
-async fn test_duplicate_write_fails() {
-    const BUCKET_NAME: &str = "test_duplicate_write_fails";
+async fn test_staged_random_write() {
+    const BUCKET_NAME: &str = "test_staged_random_write";
 
-    let (_client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), Default::default());
+    let staging_dir = tempfile::tempdir().unwrap();
+    let fs_config = S3FilesystemConfig {
+        write_staging_dir: Some(staging_dir.path().to_owned()),
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
 
     let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
     let dentry = fs
-        .mknod(FUSE_ROOT_INODE, "file2.bin".as_ref(), mode, 0, 0)
+        .mknod(FUSE_ROOT_INODE, "file.bin".as_ref(), mode, 0, 0)
         .await
         .unwrap();
-    assert_eq!(dentry.attr.size, 0);
     let file_ino = dentry.attr.ino;
 
-    let _opened = fs
-        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_RDWR, 0)
         .await
-        .unwrap();
+        .unwrap()
+        .fh;
 
-    let err = fs
-        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+    // Write the end of the file first, then go back to fill in the start
+    fs.write(file_ino, fh, 100, &[0xbb; 28], 0, 0, None).await.unwrap();
+    fs.write(file_ino, fh, 0, &[0xaa; 50], 0, 0, None).await.unwrap();
+    fs.write(file_ino, fh, 20, &[0xcc; 10], 0, 0, None).await.unwrap();
+    let attr = fs.getattr(file_ino).await.unwrap().attr;
+    assert_eq!(attr.size, 128);
+
+    // Data can be read back before the file is closed, with gaps reading as zeroes
+    let read = fs.read(file_ino, fh, 15, 40, 0, None).await.unwrap();
+    let mut expected = vec![0xaa; 5];
+    expected.extend([0xcc; 10]);
+    expected.extend([0xaa; 20]);
+    expected.extend([0; 5]);
+    assert_eq!(&read[..], &expected[..]);
+    let read = fs.read(file_ino, fh, 120, 100, 0, None).await.unwrap();
+    assert_eq!(&read[..], &[0xbb; 8]);
+
+    assert!(!client.contains_key("file.bin"));
+    fs.flush(file_ino, fh, 0, 0).await.unwrap();
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let mut expected = vec![0xaa; 20];
+    expected.extend([0xcc; 10]);
+    expected.extend([0xaa; 20]);
+    expected.extend([0; 50]);
+    expected.extend([0xbb; 28]);
+    let get = client
+        .get_object(BUCKET_NAME, "file.bin", &GetObjectParams::new())
         .await
-        .expect_err("should not be able to write twice")
-        .to_errno();
-    assert_eq!(err, libc::EPERM);
+        .unwrap();
+    let actual = get.collect().await.unwrap();
+    assert_eq!(&actual[..], &expected[..]);
 }
 
+#[test_case(0; "new file")]
+#[test_case(16 * 1024 * 1024; "existing object")]
 #[tokio::test]
-async fn test_upload_aborted_on_write_failure() {
-    const BUCKET_NAME: &str = "test_upload_aborted_on_write_failure";
-    const FILE_NAME: &str = "foo.bin";
+async fn test_staged_write_after_fsync(existing_size: usize) {
+    const BUCKET_NAME: &str = "test_staged_write_after_fsync";
 
-    let client_config = MockClientConfig {
-        bucket: BUCKET_NAME.to_string(),
-        part_size: 1024 * 1024,
+    let staging_dir = tempfile::tempdir().unwrap();
+    let fs_config = S3FilesystemConfig {
+        write_staging_dir: Some(staging_dir.path().to_owned()),
+        allow_overwrite: true,
         ..Default::default()
     };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
 
-    let client = Arc::new(MockClient::new(client_config));
-    let mut put_failures = HashMap::new();
-    put_failures.insert(1, Ok((2, MockClientError("error".to_owned().into()))));
+    let file_ino = if existing_size > 0 {
+        client.add_object("file.bin", MockObject::constant(0xaa, existing_size, ETag::for_tests()));
+        fs.lookup(FUSE_ROOT_INODE, "file.bin".as_ref()).await.unwrap().attr.ino
+    } else {
+        let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
+        let dentry = fs
+            .mknod(FUSE_ROOT_INODE, "file.bin".as_ref(), mode, 0, 0)
+            .await
+            .unwrap();
+        dentry.attr.ino
+    };
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_RDWR, 0)
+        .await
+        .unwrap()
+        .fh;
 
-    let failure_client = countdown_failure_client(
-        client.clone(),
-        Default::default(),
-        Default::default(),
-        Default::default(),
-        put_failures,
-    );
-    let fs = make_test_filesystem_with_client(
-        Arc::new(failure_client),
-        BUCKET_NAME,
-        &Default::default(),
-        Default::default(),
-    );
+    async fn get_object_bytes(client: &MockClient) -> Vec<u8> {
+        let get = client
+            .get_object(BUCKET_NAME, "file.bin", &GetObjectParams::new())
+            .await
+            .unwrap();
+        get.collect().await.unwrap().to_vec()
+    }
 
-    let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
-    let dentry = fs.mknod(FUSE_ROOT_INODE, FILE_NAME.as_ref(), mode, 0, 0).await.unwrap();
-    assert_eq!(dentry.attr.size, 0);
-    let file_ino = dentry.attr.ino;
+    // Each fsync uploads the file as it is, and the handle can still be written afterwards
+    let mut expected = vec![0xaa; existing_size];
+    expected.resize(existing_size.max(100), 0);
+    fs.write(file_ino, fh, 50, &[0xbb; 50], 0, 0, None).await.unwrap();
+    expected[50..100].fill(0xbb);
+    fs.fsync(file_ino, fh, false).await.unwrap();
+    assert_eq!(get_object_bytes(&client).await, expected);
+
+    fs.write(file_ino, fh, 0, &[0xcc; 10], 0, 0, None).await.unwrap();
+    expected[0..10].fill(0xcc);
+    let read = fs.read(file_ino, fh, 0, 100, 0, None).await.unwrap();
+    assert_eq!(&read[..], &expected[..100]);
+    fs.fsync(file_ino, fh, false).await.unwrap();
+    assert_eq!(get_object_bytes(&client).await, expected);
+
+    fs.write(file_ino, fh, 95, &[0xdd; 10], 0, 0, None).await.unwrap();
+    expected.resize(expected.len().max(105), 0);
+    expected[95..105].fill(0xdd);
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+    assert_eq!(get_object_bytes(&client).await, expected);
+}
+
+#[tokio::test]
+async fn test_staged_posix_mtime() {
+    const BUCKET_NAME: &str = "test_staged_posix_mtime";
+
+    let staging_dir = tempfile::tempdir().unwrap();
+    let fs_config = S3FilesystemConfig {
+        write_staging_dir: Some(staging_dir.path().to_owned()),
+        posix_metadata: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
 
+    let mode = libc::S_IFREG | 0o600;
+    let dentry = fs
+        .mknod(FUSE_ROOT_INODE, "file.bin".as_ref(), mode, 0, 0)
+        .await
+        .unwrap();
+    let file_ino = dentry.attr.ino;
     let fh = fs
         .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
         .await
         .unwrap()
         .fh;
+    fs.write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None).await.unwrap();
 
-    let written = fs
-        .write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None)
+    // The modification time is uploaded as it is when the file is closed, not when it was opened
+    let mtime = datetime!(2023-11-14 22:13:20 UTC);
+    fs.setattr(file_ino, None, None, None, None, Some(mtime), None, None)
         .await
-        .expect("first write should succeed");
+        .unwrap();
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
 
-    assert!(client.is_upload_in_progress(FILE_NAME));
+    let user_metadata = client.get_object_user_metadata("file.bin").unwrap();
+    assert_eq!(user_metadata.get("mtime"), Some(&"1700000000".to_owned()));
+}
 
-    let write_error = fs
-        .write(file_ino, fh, written as i64, &[0xaa; 27], 0, 0, None)
-        .await
-        .expect_err("second write should fail")
-        .to_errno();
-    assert_eq!(write_error, libc::EIO);
+#[test_case(libc::O_RDWR; "read-write")]
+#[test_case(libc::O_WRONLY; "write-only")]
+#[tokio::test]
+async fn test_staged_modify(flags: i32) {
+    const BUCKET_NAME: &str = "test_staged_modify";
+    const SIZE: usize = 16 * 1024 * 1024;
 
-    let err = fs
-        .write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None)
-        .await
-        .expect_err("subsequent writes should fail")
-        .to_errno();
+    let staging_dir = tempfile::tempdir().unwrap();
+    let fs_config = S3FilesystemConfig {
+        write_staging_dir: Some(staging_dir.path().to_owned()),
+        allow_overwrite: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
 
-    assert_eq!(err, libc::EIO);
+    let mut object = MockObject::constant(0xaa, SIZE, ETag::for_tests());
+    let user_metadata = HashMap::from([("owner".to_owned(), "data-team".to_owned())]);
+    object.set_user_metadata(user_metadata.clone());
+    client.add_object("file.bin", object);
+    let file_ino = fs.lookup(FUSE_ROOT_INODE, "file.bin".as_ref()).await.unwrap().attr.ino;
+
+    let fh = fs.open(file_ino, libc::S_IFREG as i32 | flags, 0).await.unwrap().fh;
+    fs.write(file_ino, fh, 10, &[0xbb; 5], 0, 0, None).await.unwrap();
+    let attr = fs.getattr(file_ino).await.unwrap().attr;
+    assert_eq!(attr.size, SIZE as u64);
+    if flags == libc::O_RDWR {
+        let read = fs.read(file_ino, fh, 8, 10, 0, None).await.unwrap();
+        assert_eq!(&read[..], &[0xaa, 0xaa, 0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xaa, 0xaa, 0xaa]);
+    }
 
-    assert!(!client.is_upload_in_progress(FILE_NAME));
-    assert!(!client.contains_key(FILE_NAME));
+    fs.flush(file_ino, fh, 0, 0).await.unwrap();
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let mut expected = vec![0xaa; SIZE];
+    expected[10..15].fill(0xbb);
+    let get = client
+        .get_object(BUCKET_NAME, "file.bin", &GetObjectParams::new())
+        .await
+        .unwrap();
+    let actual = get.collect().await.unwrap();
+    assert_eq!(&actual[..], &expected[..]);
+    assert_eq!(client.get_object_user_metadata("file.bin").unwrap(), user_metadata);
+}
+
+#[test_case(false; "posix metadata disabled")]
+#[test_case(true; "posix metadata enabled")]
+#[tokio::test]
+async fn test_staged_modify_unchanged(posix_metadata: bool) {
+    const BUCKET_NAME: &str = "test_staged_modify_unchanged";
+
+    let staging_dir = tempfile::tempdir().unwrap();
+    let fs_config = S3FilesystemConfig {
+        write_staging_dir: Some(staging_dir.path().to_owned()),
+        allow_overwrite: true,
+        posix_metadata,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
+
+    client.add_object("file.bin", MockObject::constant(0xaa, 100, ETag::for_tests()));
+    let file_ino = fs.lookup(FUSE_ROOT_INODE, "file.bin".as_ref()).await.unwrap().attr.ino;
+
+    // Opening a file for writes without changing it does not upload it again, even when its
+    // handle is synced and flushed
+    let put_counter = client.new_counter(Operation::PutObject);
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_RDWR, 0)
+        .await
+        .unwrap()
+        .fh;
+    let read = fs.read(file_ino, fh, 0, 10, 0, None).await.unwrap();
+    assert_eq!(&read[..], &[0xaa; 10]);
+    fs.fsync(file_ino, fh, false).await.unwrap();
+    fs.flush(file_ino, fh, 0, 0).await.unwrap();
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+    assert_eq!(put_counter.count(), 0);
+
+    // The file can be opened for reads again once the handle is released
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+}
+
+#[test_case(40; "truncate")]
+#[test_case(150; "extend")]
+#[tokio::test]
+async fn test_staged_set_size(size: u64) {
+    const BUCKET_NAME: &str = "test_staged_set_size";
+
+    let staging_dir = tempfile::tempdir().unwrap();
+    let fs_config = S3FilesystemConfig {
+        write_staging_dir: Some(staging_dir.path().to_owned()),
+        allow_overwrite: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
+
+    client.add_object("file.bin", MockObject::constant(0xaa, 100, ETag::for_tests()));
+    let file_ino = fs.lookup(FUSE_ROOT_INODE, "file.bin".as_ref()).await.unwrap().attr.ino;
+
+    // Resizing a file that is not open modifies its object right away
+    let attr = fs
+        .setattr(file_ino, None, None, None, None, None, Some(size), None)
+        .await
+        .expect("setattr should resize the file");
+    assert_eq!(attr.attr.size, size);
+
+    let mut expected = vec![0xaa; 100];
+    expected.resize(size as usize, 0);
+    let get = client
+        .get_object(BUCKET_NAME, "file.bin", &GetObjectParams::new())
+        .await
+        .unwrap();
+    let actual = get.collect().await.unwrap();
+    assert_eq!(&actual[..], &expected[..]);
+
+    // Resizing an open file applies to its staged upload
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    fs.write(file_ino, fh, 0, &[0xbb; 10], 0, 0, None).await.unwrap();
+    let attr = fs
+        .setattr(file_ino, None, None, None, None, None, Some(20), None)
+        .await
+        .expect("setattr should resize the file");
+    assert_eq!(attr.attr.size, 20);
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let mut expected = vec![0xbb; 10];
+    expected.extend([0xaa; 10]);
+    let get = client
+        .get_object(BUCKET_NAME, "file.bin", &GetObjectParams::new())
+        .await
+        .unwrap();
+    let actual = get.collect().await.unwrap();
+    assert_eq!(&actual[..], &expected[..]);
+}
+
+#[tokio::test]
+async fn test_staged_modify_requires_overwrite() {
+    const BUCKET_NAME: &str = "test_staged_modify_requires_overwrite";
+
+    let staging_dir = tempfile::tempdir().unwrap();
+    let fs_config = S3FilesystemConfig {
+        write_staging_dir: Some(staging_dir.path().to_owned()),
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
+
+    client.add_object("file.bin", MockObject::constant(0xaa, 100, ETag::for_tests()));
+    let file_ino = fs.lookup(FUSE_ROOT_INODE, "file.bin".as_ref()).await.unwrap().attr.ino;
 
     let err = fs
-        .fsync(file_ino, fh, true)
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
         .await
-        .expect_err("subsequent fsync should fail")
-        .to_errno();
-    assert_eq!(err, libc::EIO);
+        .expect_err("existing files can only be modified with overwrites allowed");
+    assert_eq!(err.to_errno(), libc::EPERM);
+    let err = fs
+        .setattr(file_ino, None, None, None, None, None, Some(40), None)
+        .await
+        .expect_err("existing files can only be resized with overwrites allowed");
+    assert_eq!(err.to_errno(), libc::EPERM);
+}
 
-    fs.release(file_ino, fh, 0, None, true)
+#[test_case(S3Personality::Standard; "general purpose bucket")]
+#[test_case(S3Personality::ExpressOneZone; "directory bucket")]
+#[tokio::test]
+async fn test_append(s3_personality: S3Personality) {
+    const BUCKET_NAME: &str = "test_append";
+
+    let fs_config = S3FilesystemConfig {
+        allow_overwrite: true,
+        s3_personality,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
+
+    let mut object = MockObject::constant(0xaa, 100, ETag::for_tests());
+    let user_metadata = HashMap::from([("owner".to_owned(), "data-team".to_owned())]);
+    object.set_user_metadata(user_metadata.clone());
+    client.add_object("file.bin", object);
+    let file_ino = fs.lookup(FUSE_ROOT_INODE, "file.bin".as_ref()).await.unwrap().attr.ino;
+
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY | libc::O_APPEND, 0)
         .await
-        .expect("release succeeds (no op)");
+        .unwrap()
+        .fh;
+    let err = fs
+        .write(file_ino, fh, 0, &[0xbb; 5], 0, 0, None)
+        .await
+        .expect_err("writes must start at the end of the file");
+    assert_eq!(err.to_errno(), libc::EINVAL);
+    let err = fs
+        .write(file_ino, fh, 100, &[0xbb; 5], 0, 0, None)
+        .await
+        .expect_err("failed appends can't be continued");
+    assert_eq!(err.to_errno(), libc::EINVAL);
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    // The failed append left the object unchanged
+    let get = client
+        .get_object(BUCKET_NAME, "file.bin", &GetObjectParams::new())
+        .await
+        .unwrap();
+    assert_eq!(&get.collect().await.unwrap()[..], &[0xaa; 100][..]);
+
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY | libc::O_APPEND, 0)
+        .await
+        .unwrap()
+        .fh;
+    fs.write(file_ino, fh, 100, &[0xbb; 5], 0, 0, None).await.unwrap();
+    fs.write(file_ino, fh, 105, &[0xcc; 5], 0, 0, None).await.unwrap();
+    let attr = fs.getattr(file_ino).await.unwrap().attr;
+    assert_eq!(attr.size, 110);
+    fs.flush(file_ino, fh, 0, 0).await.unwrap();
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let mut expected = vec![0xaa; 100];
+    expected.extend_from_slice(&[0xbb; 5]);
+    expected.extend_from_slice(&[0xcc; 5]);
+    let get = client
+        .get_object(BUCKET_NAME, "file.bin", &GetObjectParams::new())
+        .await
+        .unwrap();
+    assert_eq!(&get.collect().await.unwrap()[..], &expected[..]);
+    assert_eq!(client.get_object_user_metadata("file.bin").unwrap(), user_metadata);
+    let attr = fs.getattr(file_ino).await.unwrap().attr;
+    assert_eq!(attr.size, 110);
 }
 
 #[tokio::test]
-async fn test_upload_aborted_on_fsync_failure() {
-    const BUCKET_NAME: &str = "test_upload_aborted_on_fsync_failure";
+async fn test_append_requires_overwrite() {
+    const BUCKET_NAME: &str = "test_append_requires_overwrite";
+
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), Default::default());
+
+    client.add_object("file.bin", MockObject::constant(0xaa, 100, ETag::for_tests()));
+    let file_ino = fs.lookup(FUSE_ROOT_INODE, "file.bin".as_ref()).await.unwrap().attr.ino;
+
+    let err = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY | libc::O_APPEND, 0)
+        .await
+        .expect_err("existing files can only be appended to with overwrites allowed");
+    assert_eq!(err.to_errno(), libc::EINVAL);
+}
+
+#[test_case(10; "small object")]
+#[test_case(5 * 1024 * 1024 * 1024; "object larger than a single reply")]
+#[tokio::test]
+async fn test_copy_file_range(size: usize) {
+    const BUCKET_NAME: &str = "test_copy_file_range";
+
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), Default::default());
+
+    let mut src = MockObject::constant(0xaa, size, ETag::for_tests());
+    src.set_user_metadata(HashMap::from([("origin".to_owned(), "source".to_owned())]));
+    client.add_object("src.bin", src);
+    let src_ino = fs.lookup(FUSE_ROOT_INODE, "src.bin".as_ref()).await.unwrap().attr.ino;
+    let src_fh = fs
+        .open(src_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+
+    let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
+    let dst_ino = fs
+        .mknod(FUSE_ROOT_INODE, "dst.bin".as_ref(), mode, 0, 0)
+        .await
+        .unwrap()
+        .attr
+        .ino;
+    let dst_fh = fs
+        .open(dst_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    fs.setxattr(dst_ino, "user.s3.meta.owner".as_ref(), b"copier", 0)
+        .await
+        .unwrap();
+
+    let copy_counter = client.new_counter(Operation::CopyObject);
+    let mut offset = 0;
+    loop {
+        let copied = fs
+            .copy_file_range(src_ino, src_fh, offset, dst_ino, dst_fh, offset, u64::MAX, 0)
+            .await
+            .unwrap();
+        if copied == 0 {
+            break;
+        }
+        offset += copied as i64;
+    }
+    assert_eq!(offset as usize, size);
+    assert_eq!(copy_counter.count(), 1);
+
+    // The copy completes the new file
+    let err = fs
+        .write(dst_ino, dst_fh, offset, &[0xbb; 5], 0, 0, None)
+        .await
+        .expect_err("can't write after a copy");
+    assert_eq!(err.to_errno(), libc::EIO);
+    fs.release(dst_ino, dst_fh, 0, None, true).await.unwrap();
+    fs.release(src_ino, src_fh, 0, None, true).await.unwrap();
+
+    let attr = fs.getattr(dst_ino).await.unwrap().attr;
+    assert_eq!(attr.size, size as u64);
+    assert!(!client.is_upload_in_progress("dst.bin"));
+    let head = client.head_object(BUCKET_NAME, "dst.bin").await.unwrap();
+    assert_eq!(head.object.size, size as u64);
+    // The new object has the metadata set on the new file, not the source's
+    let expected = HashMap::from([("owner".to_owned(), "copier".to_owned())]);
+    assert_eq!(head.user_metadata, expected);
+}
+
+#[tokio::test]
+async fn test_copy_file_range_unsupported() {
+    const BUCKET_NAME: &str = "test_copy_file_range_unsupported";
+
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), Default::default());
+
+    client.add_object("src.bin", MockObject::constant(0xaa, 10, ETag::for_tests()));
+    let src_ino = fs.lookup(FUSE_ROOT_INODE, "src.bin".as_ref()).await.unwrap().attr.ino;
+    let src_fh = fs
+        .open(src_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+
+    let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
+    let dst_ino = fs
+        .mknod(FUSE_ROOT_INODE, "dst.bin".as_ref(), mode, 0, 0)
+        .await
+        .unwrap()
+        .attr
+        .ino;
+    let dst_fh = fs
+        .open(dst_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+
+    let copy_counter = client.new_counter(Operation::CopyObject);
+    for (offset_in, offset_out, len) in [(0, 0, 5), (1, 0, 9), (0, 1, 10)] {
+        let err = fs
+            .copy_file_range(src_ino, src_fh, offset_in, dst_ino, dst_fh, offset_out, len, 0)
+            .await
+            .expect_err("only whole files can be copied");
+        assert_eq!(err.to_errno(), libc::EOPNOTSUPP);
+    }
+
+    // Copies into a file that was already written to fall back too
+    fs.write(dst_ino, dst_fh, 0, &[0xbb; 5], 0, 0, None).await.unwrap();
+    let err = fs
+        .copy_file_range(src_ino, src_fh, 0, dst_ino, dst_fh, 0, 10, 0)
+        .await
+        .expect_err("only new files can be copied into");
+    assert_eq!(err.to_errno(), libc::EOPNOTSUPP);
+    assert_eq!(copy_counter.count(), 0);
+
+    fs.release(dst_ino, dst_fh, 0, None, true).await.unwrap();
+    let get = client
+        .get_object(BUCKET_NAME, "dst.bin", &GetObjectParams::new())
+        .await
+        .unwrap();
+    assert_eq!(&get.collect().await.unwrap()[..], &[0xbb; 5][..]);
+}
+
+#[tokio::test]
+async fn test_duplicate_write_fails() {
+    const BUCKET_NAME: &str = "test_duplicate_write_fails";
+
+    let (_client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), Default::default());
+
+    let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
+    let dentry = fs
+        .mknod(FUSE_ROOT_INODE, "file2.bin".as_ref(), mode, 0, 0)
+        .await
+        .unwrap();
+    assert_eq!(dentry.attr.size, 0);
+    let file_ino = dentry.attr.ino;
+
+    let _opened = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap();
+
+    let err = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .expect_err("should not be able to write twice")
+        .to_errno();
+    assert_eq!(err, libc::EPERM);
+}
+
+#[tokio::test]
+async fn test_upload_aborted_on_write_failure() {
+    const BUCKET_NAME: &str = "test_upload_aborted_on_write_failure";
     const FILE_NAME: &str = "foo.bin";
 
     let client_config = MockClientConfig {
@@ -842,30 +1648,45 @@ async fn test_upload_aborted_on_fsync_failure() {
         .unwrap()
         .fh;
 
-    _ = fs
+    let written = fs
         .write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None)
         .await
         .expect("first write should succeed");
 
     assert!(client.is_upload_in_progress(FILE_NAME));
 
+    let write_error = fs
+        .write(file_ino, fh, written as i64, &[0xaa; 27], 0, 0, None)
+        .await
+        .expect_err("second write should fail")
+        .to_errno();
+    assert_eq!(write_error, libc::EIO);
+
     let err = fs
-        .fsync(file_ino, fh, true)
+        .write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None)
         .await
-        .expect_err("subsequent fsync should fail")
+        .expect_err("subsequent writes should fail")
         .to_errno();
+
     assert_eq!(err, libc::EIO);
 
     assert!(!client.is_upload_in_progress(FILE_NAME));
     assert!(!client.contains_key(FILE_NAME));
 
+    let err = fs
+        .fsync(file_ino, fh, true)
+        .await
+        .expect_err("subsequent fsync should fail")
+        .to_errno();
+    assert_eq!(err, libc::EIO);
+
     fs.release(file_ino, fh, 0, None, true)
         .await
         .expect("release succeeds (no op)");
 }
 
 #[tokio::test]
-async fn test_upload_aborted_on_release_failure() {
+async fn test_upload_aborted_on_fsync_failure() {
     const BUCKET_NAME: &str = "test_upload_aborted_on_fsync_failure";
     const FILE_NAME: &str = "foo.bin";
 
@@ -912,36 +1733,98 @@ async fn test_upload_aborted_on_release_failure() {
     assert!(client.is_upload_in_progress(FILE_NAME));
 
     let err = fs
-        .release(file_ino, fh, 0, None, true)
+        .fsync(file_ino, fh, true)
         .await
-        .expect_err("subsequent release should fail")
+        .expect_err("subsequent fsync should fail")
         .to_errno();
     assert_eq!(err, libc::EIO);
 
     assert!(!client.is_upload_in_progress(FILE_NAME));
     assert!(!client.contains_key(FILE_NAME));
+
+    fs.release(file_ino, fh, 0, None, true)
+        .await
+        .expect("release succeeds (no op)");
 }
 
 #[tokio::test]
-async fn test_stat_block_size() {
-    let (client, fs) = make_test_filesystem("test_stat_block_size", &Default::default(), Default::default());
-
-    client.add_object(
-        "file0.txt",
-        MockObject::constant(0xa1, 0, ETag::from_str("test_etag_1").unwrap()),
-    );
-    client.add_object(
-        "file1.txt",
-        MockObject::constant(0xa2, 1, ETag::from_str("test_etag_2").unwrap()),
-    );
-    client.add_object(
-        "file4096.txt",
-        MockObject::constant(0xa3, 4096, ETag::from_str("test_etag_3").unwrap()),
-    );
-    client.add_object(
-        "file4097.txt",
-        MockObject::constant(0xa3, 4097, ETag::from_str("test_etag_4").unwrap()),
-    );
+async fn test_upload_aborted_on_release_failure() {
+    const BUCKET_NAME: &str = "test_upload_aborted_on_fsync_failure";
+    const FILE_NAME: &str = "foo.bin";
+
+    let client_config = MockClientConfig {
+        bucket: BUCKET_NAME.to_string(),
+        part_size: 1024 * 1024,
+        ..Default::default()
+    };
+
+    let client = Arc::new(MockClient::new(client_config));
+    let mut put_failures = HashMap::new();
+    put_failures.insert(1, Ok((2, MockClientError("error".to_owned().into()))));
+
+    let failure_client = countdown_failure_client(
+        client.clone(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        put_failures,
+    );
+    let fs = make_test_filesystem_with_client(
+        Arc::new(failure_client),
+        BUCKET_NAME,
+        &Default::default(),
+        Default::default(),
+    );
+
+    let mode = libc::S_IFREG | libc::S_IRWXU; // regular file + 0700 permissions
+    let dentry = fs.mknod(FUSE_ROOT_INODE, FILE_NAME.as_ref(), mode, 0, 0).await.unwrap();
+    assert_eq!(dentry.attr.size, 0);
+    let file_ino = dentry.attr.ino;
+
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+
+    _ = fs
+        .write(file_ino, fh, 0, &[0xaa; 27], 0, 0, None)
+        .await
+        .expect("first write should succeed");
+
+    assert!(client.is_upload_in_progress(FILE_NAME));
+
+    let err = fs
+        .release(file_ino, fh, 0, None, true)
+        .await
+        .expect_err("subsequent release should fail")
+        .to_errno();
+    assert_eq!(err, libc::EIO);
+
+    assert!(!client.is_upload_in_progress(FILE_NAME));
+    assert!(!client.contains_key(FILE_NAME));
+}
+
+#[tokio::test]
+async fn test_stat_block_size() {
+    let (client, fs) = make_test_filesystem("test_stat_block_size", &Default::default(), Default::default());
+
+    client.add_object(
+        "file0.txt",
+        MockObject::constant(0xa1, 0, ETag::from_str("test_etag_1").unwrap()),
+    );
+    client.add_object(
+        "file1.txt",
+        MockObject::constant(0xa2, 1, ETag::from_str("test_etag_2").unwrap()),
+    );
+    client.add_object(
+        "file4096.txt",
+        MockObject::constant(0xa3, 4096, ETag::from_str("test_etag_3").unwrap()),
+    );
+    client.add_object(
+        "file4097.txt",
+        MockObject::constant(0xa3, 4097, ETag::from_str("test_etag_4").unwrap()),
+    );
 
     let lookup = fs.lookup(FUSE_ROOT_INODE, "file0.txt".as_ref()).await.unwrap();
     assert_eq!(lookup.attr.blocks, 0);
@@ -1038,6 +1921,691 @@ async fn test_local_dir(prefix: &str) {
     assert!(matches!(lookup, Err(e) if e.to_errno() == libc::ENOENT));
 }
 
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_directory_markers(prefix: &str) {
+    const BUCKET_NAME: &str = "test_directory_markers";
+
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let fs_config = || S3FilesystemConfig {
+        directory_markers: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, fs_config());
+
+    fs.mkdir(FUSE_ROOT_INODE, "dir".as_ref(), libc::S_IFDIR, 0)
+        .await
+        .unwrap();
+    assert!(client.contains_key(&format!("{prefix}dir/")));
+
+    // The empty directory persists in a new file system, and its marker is not an entry in it
+    let fs = make_test_filesystem_with_client(client.clone(), BUCKET_NAME, &prefix, fs_config());
+    let dir_entry = fs.lookup(FUSE_ROOT_INODE, "dir".as_ref()).await.unwrap();
+    assert_eq!(dir_entry.attr.kind, FileType::Directory);
+    let dir_ino = dir_entry.attr.ino;
+    let dir_handle = fs.opendir(dir_ino, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs.readdirplus(dir_ino, dir_handle, 0, &mut reply).await.unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, [".", ".."]);
+    fs.releasedir(dir_ino, dir_handle, 0).await.unwrap();
+
+    // Directories with other content can't be removed
+    client.add_object(&format!("{prefix}dir/file.bin"), b"hello".into());
+    let err = fs
+        .rmdir(FUSE_ROOT_INODE, "dir".as_ref())
+        .await
+        .expect_err("directory is not empty");
+    assert_eq!(err.to_errno(), libc::ENOTEMPTY);
+    client.remove_object(&format!("{prefix}dir/file.bin"));
+
+    fs.rmdir(FUSE_ROOT_INODE, "dir".as_ref()).await.unwrap();
+    assert!(!client.contains_key(&format!("{prefix}dir/")));
+    let err = fs
+        .lookup(FUSE_ROOT_INODE, "dir".as_ref())
+        .await
+        .expect_err("directory was removed");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+}
+
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_show_versions(prefix: &str) {
+    const BUCKET_NAME: &str = "test_show_versions";
+
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let fs_config = S3FilesystemConfig {
+        show_versions: true,
+        allow_delete: true,
+        allow_overwrite: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, fs_config);
+
+    let key = format!("{prefix}dir/file.txt");
+    let mut old_version = MockObject::from(b"first");
+    old_version.set_last_modified(datetime!(2024-01-02 03:04:05 UTC));
+    client.add_object_version(&key, "v1", old_version);
+    let mut old_version = MockObject::from(b"second");
+    old_version.set_last_modified(datetime!(2024-02-03 04:05:06 UTC));
+    client.add_object_version(&key, "v2", old_version);
+    client.add_object(&key, b"current".into());
+    client.add_object(&format!("{prefix}dir/other.txt"), b"other".into());
+
+    let dir_ino = fs.lookup(FUSE_ROOT_INODE, "dir".as_ref()).await.unwrap().attr.ino;
+
+    // Versions directories are hidden from their parent
+    let dir_handle = fs.opendir(dir_ino, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs.readdirplus(dir_ino, dir_handle, 0, &mut reply).await.unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "file.txt", "other.txt"]);
+    fs.releasedir(dir_ino, dir_handle, 0).await.unwrap();
+
+    // Objects without noncurrent versions have no versions directory
+    let err = fs
+        .lookup(dir_ino, "other.txt.versions".as_ref())
+        .await
+        .expect_err("object has no noncurrent versions");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+
+    let versions_entry = fs.lookup(dir_ino, "file.txt.versions".as_ref()).await.unwrap();
+    assert_eq!(versions_entry.attr.kind, FileType::Directory);
+    let versions_ino = versions_entry.attr.ino;
+
+    let versions_handle = fs.opendir(versions_ino, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs
+        .readdirplus(versions_ino, versions_handle, 0, &mut reply)
+        .await
+        .unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "20240102T030405Z_v1", "20240203T040506Z_v2"]);
+    fs.releasedir(versions_ino, versions_handle, 0).await.unwrap();
+
+    // Reads are pinned to the version of the file
+    let version_entry = fs.lookup(versions_ino, "20240102T030405Z_v1".as_ref()).await.unwrap();
+    assert_eq!(version_entry.attr.size, 5);
+    let version_ino = version_entry.attr.ino;
+    let fh = fs
+        .open(version_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    let bytes_read = fs.read(version_ino, fh, 0, 1024, 0, None).await.unwrap();
+    assert_eq!(&bytes_read[..], b"first");
+    fs.release(version_ino, fh, 0, None, true).await.unwrap();
+
+    // Names that don't match a version are not found
+    let err = fs
+        .lookup(versions_ino, "20240203T040506Z_v1".as_ref())
+        .await
+        .expect_err("version was created at a different time");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+
+    // Nothing in a versions directory can be modified
+    let err = fs
+        .open(version_ino, libc::S_IFREG as i32 | libc::O_WRONLY | libc::O_TRUNC, 0)
+        .await
+        .expect_err("versions are read-only");
+    assert_eq!(err.to_errno(), libc::EROFS);
+    let err = fs
+        .unlink(versions_ino, "20240102T030405Z_v1".as_ref())
+        .await
+        .expect_err("versions are read-only");
+    assert_eq!(err.to_errno(), libc::EROFS);
+    let err = fs
+        .mknod(versions_ino, "new.txt".as_ref(), libc::S_IFREG | libc::S_IRWXU, 0, 0)
+        .await
+        .expect_err("versions are read-only");
+    assert_eq!(err.to_errno(), libc::EROFS);
+    let err = fs
+        .rmdir(dir_ino, "file.txt.versions".as_ref())
+        .await
+        .expect_err("versions are read-only");
+    assert_eq!(err.to_errno(), libc::EROFS);
+
+    // The current version is unaffected
+    let file_ino = fs.lookup(dir_ino, "file.txt".as_ref()).await.unwrap().attr.ino;
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    let bytes_read = fs.read(file_ino, fh, 0, 1024, 0, None).await.unwrap();
+    assert_eq!(&bytes_read[..], b"current");
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_versions_not_shown_by_default() {
+    let (client, fs) = make_test_filesystem(
+        "test_versions_not_shown_by_default",
+        &Default::default(),
+        Default::default(),
+    );
+    client.add_object_version("file.txt", "v1", b"old".into());
+    client.add_object("file.txt", b"new".into());
+
+    let err = fs
+        .lookup(FUSE_ROOT_INODE, "file.txt.versions".as_ref())
+        .await
+        .expect_err("versions are not shown");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+}
+
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_as_of(prefix: &str) {
+    const BUCKET_NAME: &str = "test_as_of";
+
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let fs_config = S3FilesystemConfig {
+        as_of: Some(datetime!(2024-06-01 00:00 UTC)),
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, fs_config);
+
+    // Overwritten after the timestamp
+    let mut old_version = MockObject::from(b"old");
+    old_version.set_last_modified(datetime!(2024-01-01 00:00 UTC));
+    client.add_object_version(&format!("{prefix}dir/overwritten.txt"), "v1", old_version);
+    let mut new_version = MockObject::from(b"new");
+    new_version.set_last_modified(datetime!(2024-09-01 00:00 UTC));
+    client.add_object(&format!("{prefix}dir/overwritten.txt"), new_version);
+    // Deleted before the timestamp
+    let mut deleted = MockObject::from(b"deleted");
+    deleted.set_last_modified(datetime!(2024-01-01 00:00 UTC));
+    client.add_object_version(&format!("{prefix}dir/deleted.txt"), "v1", deleted);
+    client.add_delete_marker(
+        &format!("{prefix}dir/deleted.txt"),
+        "m1",
+        datetime!(2024-03-01 00:00 UTC),
+    );
+    // Created after the timestamp
+    let mut created = MockObject::from(b"created");
+    created.set_last_modified(datetime!(2024-09-01 00:00 UTC));
+    client.add_object(&format!("{prefix}dir/created.txt"), created);
+    // Unchanged since before the timestamp
+    let mut unchanged = MockObject::from(b"unchanged");
+    unchanged.set_last_modified(datetime!(2024-02-01 00:00 UTC));
+    client.add_object(&format!("{prefix}unchanged.txt"), unchanged);
+
+    let root_handle = fs.opendir(FUSE_ROOT_INODE, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs
+        .readdirplus(FUSE_ROOT_INODE, root_handle, 0, &mut reply)
+        .await
+        .unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "dir", "unchanged.txt"]);
+    fs.releasedir(FUSE_ROOT_INODE, root_handle, 0).await.unwrap();
+
+    let dir_ino = fs.lookup(FUSE_ROOT_INODE, "dir".as_ref()).await.unwrap().attr.ino;
+    let dir_handle = fs.opendir(dir_ino, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs.readdirplus(dir_ino, dir_handle, 0, &mut reply).await.unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "overwritten.txt"]);
+    fs.releasedir(dir_ino, dir_handle, 0).await.unwrap();
+
+    for name in ["deleted.txt", "created.txt"] {
+        let err = fs
+            .lookup(dir_ino, name.as_ref())
+            .await
+            .expect_err("object did not exist at the timestamp");
+        assert_eq!(err.to_errno(), libc::ENOENT);
+    }
+
+    // Reads are pinned to the version as of the timestamp
+    for (parent, name, contents) in [
+        (dir_ino, "overwritten.txt", &b"old"[..]),
+        (FUSE_ROOT_INODE, "unchanged.txt", &b"unchanged"[..]),
+    ] {
+        let entry = fs.lookup(parent, name.as_ref()).await.unwrap();
+        assert_eq!(entry.attr.size, contents.len() as u64);
+        let ino = entry.attr.ino;
+        let fh = fs.open(ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0).await.unwrap().fh;
+        let bytes_read = fs.read(ino, fh, 0, 1024, 0, None).await.unwrap();
+        assert_eq!(&bytes_read[..], contents);
+        fs.release(ino, fh, 0, None, true).await.unwrap();
+    }
+
+    // Nothing can be modified
+    let file_ino = fs.lookup(dir_ino, "overwritten.txt".as_ref()).await.unwrap().attr.ino;
+    let err = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_WRONLY | libc::O_TRUNC, 0)
+        .await
+        .expect_err("point-in-time mounts are read-only");
+    assert_eq!(err.to_errno(), libc::EROFS);
+    let err = fs
+        .mknod(dir_ino, "new.txt".as_ref(), libc::S_IFREG | libc::S_IRWXU, 0, 0)
+        .await
+        .expect_err("point-in-time mounts are read-only");
+    assert_eq!(err.to_errno(), libc::EROFS);
+}
+
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_key_filter(prefix: &str) {
+    const BUCKET_NAME: &str = "test_key_filter";
+
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let fs_config = S3FilesystemConfig {
+        key_filter: KeyFilter::new(
+            vec![Glob::new("*.parquet").unwrap()],
+            vec![Glob::new("_temporary").unwrap()],
+        ),
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, fs_config);
+
+    for key in [
+        "dir/part-0.parquet",
+        "dir/part-0.parquet.crc",
+        "dir/_SUCCESS",
+        "dir/_temporary/part-1.parquet",
+        "top.parquet",
+    ] {
+        client.add_object(&format!("{prefix}{key}"), b"data".into());
+    }
+
+    let dir_ino = fs.lookup(FUSE_ROOT_INODE, "dir".as_ref()).await.unwrap().attr.ino;
+    let dir_handle = fs.opendir(dir_ino, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs.readdirplus(dir_ino, dir_handle, 0, &mut reply).await.unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "part-0.parquet"]);
+    fs.releasedir(dir_ino, dir_handle, 0).await.unwrap();
+
+    for name in ["part-0.parquet.crc", "_SUCCESS", "_temporary"] {
+        let err = fs
+            .lookup(dir_ino, name.as_ref())
+            .await
+            .expect_err("filtered out keys should not be found");
+        assert_eq!(err.to_errno(), libc::ENOENT);
+    }
+    fs.lookup(FUSE_ROOT_INODE, "top.parquet".as_ref()).await.unwrap();
+
+    // Filtered out names can't be created
+    let mode = libc::S_IFREG | libc::S_IRWXU;
+    let err = fs
+        .mknod(dir_ino, "new.crc".as_ref(), mode, 0, 0)
+        .await
+        .expect_err("filtered out names can't be created");
+    assert_eq!(err.to_errno(), libc::EPERM);
+    let err = fs
+        .mkdir(FUSE_ROOT_INODE, "_temporary".as_ref(), libc::S_IFDIR, 0)
+        .await
+        .expect_err("excluded directories can't be created");
+    assert_eq!(err.to_errno(), libc::EPERM);
+    fs.mknod(dir_ino, "new.parquet".as_ref(), mode, 0, 0).await.unwrap();
+}
+
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_escape_invalid_names(prefix: &str) {
+    const BUCKET_NAME: &str = "test_escape_invalid_names";
+
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let fs_config = S3FilesystemConfig {
+        escape_names: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, fs_config);
+
+    for key in ["a//b.txt", "dir/./c.txt", "dir/../d.txt", "100%.txt", "normal.txt"] {
+        client.add_object(&format!("{prefix}{key}"), key.as_bytes().into());
+    }
+
+    async fn readdir_names(fs: &TestS3Filesystem<Arc<MockClient>>, ino: u64) -> Vec<OsString> {
+        let handle = fs.opendir(ino, 0).await.unwrap().fh;
+        let mut reply = Default::default();
+        let _reply = fs.readdirplus(ino, handle, 0, &mut reply).await.unwrap();
+        fs.releasedir(ino, handle, 0).await.unwrap();
+        reply.entries.iter().map(|e| e.name.clone()).collect()
+    }
+
+    assert_eq!(
+        readdir_names(&fs, FUSE_ROOT_INODE).await,
+        [".", "..", "100%25.txt", "a", "dir", "normal.txt"]
+    );
+    let dir_ino = fs.lookup(FUSE_ROOT_INODE, "dir".as_ref()).await.unwrap().attr.ino;
+    assert_eq!(readdir_names(&fs, dir_ino).await, [".", "..", "%2E", "%2E%2E"]);
+
+    // Escaped names can be looked up and read
+    let a_ino = fs.lookup(FUSE_ROOT_INODE, "a".as_ref()).await.unwrap().attr.ino;
+    let empty_ino = fs.lookup(a_ino, "%".as_ref()).await.unwrap().attr.ino;
+    let dot_dot_ino = fs.lookup(dir_ino, "%2E%2E".as_ref()).await.unwrap().attr.ino;
+    for (parent, name, key) in [
+        (empty_ino, "b.txt", "a//b.txt"),
+        (dot_dot_ino, "d.txt", "dir/../d.txt"),
+        (FUSE_ROOT_INODE, "100%25.txt", "100%.txt"),
+    ] {
+        let ino = fs.lookup(parent, name.as_ref()).await.unwrap().attr.ino;
+        let fh = fs.open(ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0).await.unwrap().fh;
+        let bytes_read = fs.read(ino, fh, 0, 1024, 0, None).await.unwrap();
+        assert_eq!(&bytes_read[..], key.as_bytes());
+        fs.release(ino, fh, 0, None, true).await.unwrap();
+    }
+
+    // Names that aren't the escaped form of any key don't exist and can't be created
+    let err = fs
+        .lookup(FUSE_ROOT_INODE, "100%.txt".as_ref())
+        .await
+        .expect_err("not an escaped name");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+    let err = fs
+        .mknod(FUSE_ROOT_INODE, "50%.txt".as_ref(), libc::S_IFREG | libc::S_IRWXU, 0, 0)
+        .await
+        .expect_err("not an escaped name");
+    assert_eq!(err.to_errno(), libc::EINVAL);
+
+    // New files are uploaded to the unescaped key
+    new_local_file(&fs, "50%25.txt").await;
+    assert!(client.contains_key(&format!("{prefix}50%.txt")));
+}
+
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_case_insensitive(prefix: &str) {
+    const BUCKET_NAME: &str = "test_case_insensitive";
+
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let fs_config = S3FilesystemConfig {
+        case_insensitive: true,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, fs_config);
+
+    client.add_object(&format!("{prefix}Dir/Report.TXT"), b"report".into());
+    client.add_object(&format!("{prefix}other.txt"), b"other".into());
+
+    // Names that only differ in case find the same inodes
+    let dir_ino = fs.lookup(FUSE_ROOT_INODE, "Dir".as_ref()).await.unwrap().attr.ino;
+    let entry = fs.lookup(FUSE_ROOT_INODE, "dIR".as_ref()).await.unwrap();
+    assert_eq!(entry.attr.ino, dir_ino);
+    let file_ino = fs.lookup(dir_ino, "report.txt".as_ref()).await.unwrap().attr.ino;
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    let bytes_read = fs.read(file_ino, fh, 0, 1024, 0, None).await.unwrap();
+    assert_eq!(&bytes_read[..], b"report");
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let err = fs
+        .lookup(FUSE_ROOT_INODE, "missing.txt".as_ref())
+        .await
+        .expect_err("no name matches ignoring case");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+
+    // Names that only differ in case from existing ones can't be created, whether the existing
+    // file is remote or local
+    let mode = libc::S_IFREG | libc::S_IRWXU;
+    let err = fs
+        .mknod(FUSE_ROOT_INODE, "OTHER.TXT".as_ref(), mode, 0, 0)
+        .await
+        .expect_err("name differs only in case");
+    assert_eq!(err.to_errno(), libc::EEXIST);
+    fs.mknod(dir_ino, "new.txt".as_ref(), mode, 0, 0).await.unwrap();
+    let err = fs
+        .mknod(dir_ino, "New.txt".as_ref(), mode, 0, 0)
+        .await
+        .expect_err("name differs only in case");
+    assert_eq!(err.to_errno(), libc::EEXIST);
+}
+
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_normalize_unicode(prefix: &str) {
+    const BUCKET_NAME: &str = "test_normalize_unicode";
+
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let fs_config = S3FilesystemConfig {
+        normalize_unicode: UnicodeNormalization::Nfc,
+        ..Default::default()
+    };
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, fs_config);
+
+    // Keys uploaded in NFD, as from macOS, and a name that exists in both forms
+    client.add_object(&format!("{prefix}cafe\u{301}/menu.txt"), b"menu".into());
+    client.add_object(&format!("{prefix}re\u{301}sume\u{301}.txt"), b"resume".into());
+    client.add_object(&format!("{prefix}nai\u{308}ve.txt"), b"nfd".into());
+    client.add_object(&format!("{prefix}na\u{ef}ve.txt"), b"nfc".into());
+
+    // Listings show the original names, omitting names equal to an earlier one
+    let root_handle = fs.opendir(FUSE_ROOT_INODE, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs
+        .readdirplus(FUSE_ROOT_INODE, root_handle, 0, &mut reply)
+        .await
+        .unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [".", "..", "cafe\u{301}", "nai\u{308}ve.txt", "re\u{301}sume\u{301}.txt"]
+    );
+    fs.releasedir(FUSE_ROOT_INODE, root_handle, 0).await.unwrap();
+
+    // Names typed in NFC find the NFD keys
+    let dir_ino = fs.lookup(FUSE_ROOT_INODE, "caf\u{e9}".as_ref()).await.unwrap().attr.ino;
+    fs.lookup(dir_ino, "menu.txt".as_ref()).await.unwrap();
+    let file_ino = fs
+        .lookup(FUSE_ROOT_INODE, "r\u{e9}sum\u{e9}.txt".as_ref())
+        .await
+        .unwrap()
+        .attr
+        .ino;
+    let fh = fs
+        .open(file_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    let bytes_read = fs.read(file_ino, fh, 0, 1024, 0, None).await.unwrap();
+    assert_eq!(&bytes_read[..], b"resume");
+    fs.release(file_ino, fh, 0, None, true).await.unwrap();
+
+    let err = fs
+        .mknod(
+            FUSE_ROOT_INODE,
+            "r\u{e9}sum\u{e9}.txt".as_ref(),
+            libc::S_IFREG | libc::S_IRWXU,
+            0,
+            0,
+        )
+        .await
+        .expect_err("an equal name exists");
+    assert_eq!(err.to_errno(), libc::EEXIST);
+}
+
+#[tokio::test]
+async fn test_statfs() {
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), Default::default());
+    client.add_object("dir/file.txt", b"hello".into());
+
+    let statfs = fs.statfs(FUSE_ROOT_INODE).await.unwrap();
+    assert_eq!(statfs.block_size, 1024 * 1024, "block size is the part size");
+    assert_eq!(statfs.fragment_size, statfs.block_size);
+    assert_eq!(statfs.max_name_length, 1024);
+    assert!(statfs.total_blocks * statfs.block_size as u64 >= 1 << 50);
+    assert_eq!(statfs.free_blocks, statfs.total_blocks);
+    assert_eq!(statfs.available_blocks, statfs.total_blocks);
+
+    // Looking up files uses inodes
+    let dir_ino = fs.lookup(FUSE_ROOT_INODE, "dir".as_ref()).await.unwrap().attr.ino;
+    fs.lookup(dir_ino, "file.txt".as_ref()).await.unwrap();
+    let after_lookup = fs.statfs(FUSE_ROOT_INODE).await.unwrap();
+    assert_eq!(after_lookup.total_inodes, statfs.total_inodes);
+    assert_eq!(after_lookup.free_inodes, statfs.free_inodes - 2);
+
+    let fs_config = S3FilesystemConfig {
+        statfs_capacity: 100 * 1024 * 1024,
+        statfs_free_space: 30 * 1024 * 1024 + 1,
+        ..Default::default()
+    };
+    let (_client, fs) = make_test_filesystem(BUCKET_NAME, &Default::default(), fs_config);
+    let statfs = fs.statfs(FUSE_ROOT_INODE).await.unwrap();
+    assert_eq!(statfs.total_blocks, 100);
+    assert_eq!(statfs.free_blocks, 30);
+    assert_eq!(statfs.available_blocks, 30);
+}
+
+#[test_case(""; "unprefixed")]
+#[test_case("test_prefix/"; "prefixed")]
+#[tokio::test]
+async fn test_file_locks(prefix: &str) {
+    let prefix = Prefix::new(prefix).expect("valid prefix");
+    let (client, fs) = make_test_filesystem(BUCKET_NAME, &prefix, Default::default());
+    client.add_object(&format!("{prefix}file.db"), b"data".into());
+
+    let ino = fs.lookup(FUSE_ROOT_INODE, "file.db".as_ref()).await.unwrap().attr.ino;
+    let fh = fs.open(ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0).await.unwrap().fh;
+    let pid = std::process::id();
+
+    // A POSIX lock on a range excludes other owners from that range only
+    fs.setlk(ino, fh, 1, 0, 99, libc::F_WRLCK, pid).await.unwrap();
+    let err = fs
+        .setlk(ino, fh, 2, 50, 149, libc::F_RDLCK, pid)
+        .await
+        .expect_err("range is locked");
+    assert_eq!(err.to_errno(), libc::EAGAIN);
+    let lock = fs.getlk(ino, fh, 2, 50, 149, libc::F_RDLCK, pid).await.unwrap();
+    assert_eq!((lock.start, lock.end, lock.typ, lock.pid), (0, 99, libc::F_WRLCK, pid));
+    fs.setlk(ino, fh, 2, 100, 149, libc::F_RDLCK, pid).await.unwrap();
+
+    // Closing a file descriptor releases the POSIX locks of its owner
+    fs.flush(ino, fh, 1, pid).await.unwrap();
+    let lock = fs.getlk(ino, fh, 3, 0, 99, libc::F_WRLCK, pid).await.unwrap();
+    assert_eq!(lock.typ, libc::F_UNLCK);
+
+    // A waiting request gets its reply once the conflicting lock is released
+    fs.setlk(ino, fh, 2, 0, u64::MAX, libc::F_UNLCK, pid).await.unwrap();
+    fs.setlk(ino, fh, 4, 0, u64::MAX, libc::F_WRLCK, pid).await.unwrap();
+    let (sender, receiver) = mpsc::channel();
+    fs.setlkw(ino, fh, 5, 0, u64::MAX, libc::F_RDLCK, pid, move |result| {
+        sender.send(result.map_err(|e| e.to_errno())).unwrap()
+    });
+    assert!(receiver.try_recv().is_err(), "request should be waiting");
+    fs.setlk(ino, fh, 4, 0, u64::MAX, libc::F_UNLCK, pid).await.unwrap();
+    assert_eq!(receiver.try_recv().unwrap(), Ok(()));
+
+    // A waiting request fails with EINTR when its owner closes the file, for example when the
+    // process is killed while it waits
+    let (sender, receiver) = mpsc::channel();
+    fs.setlkw(ino, fh, 6, 0, u64::MAX, libc::F_WRLCK, pid, move |result| {
+        sender.send(result.map_err(|e| e.to_errno())).unwrap()
+    });
+    assert!(receiver.try_recv().is_err(), "request should be waiting");
+    fs.flush(ino, fh, 6, pid).await.unwrap();
+    assert_eq!(receiver.try_recv().unwrap(), Err(libc::EINTR));
+
+    fs.release(ino, fh, 0, None, false).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_multi_bucket() {
+    const BUCKET_NAME: &str = "test_multi_bucket";
+
+    // The mock client only has one bucket, so mount two prefixes of it
+    let client = Arc::new(MockClient::new(MockClientConfig {
+        bucket: BUCKET_NAME.to_owned(),
+        part_size: 1024 * 1024,
+        ..Default::default()
+    }));
+    let buckets = ["first", "second"]
+        .into_iter()
+        .map(|name| BucketMount {
+            name: name.to_owned(),
+            bucket: BUCKET_NAME.to_owned(),
+            prefix: Prefix::new(&format!("{name}/")).unwrap(),
+        })
+        .collect();
+    let fs_config = S3FilesystemConfig {
+        allow_delete: true,
+        ..Default::default()
+    };
+    let fs = make_test_multi_bucket_filesystem(client.clone(), buckets, fs_config);
+
+    client.add_object("first/file.txt", b"first".into());
+    client.add_object("second/file.txt", b"second".into());
+    client.add_object("second/dir/other.txt", b"other".into());
+
+    // The root directory lists the buckets
+    let root_attr = fs.getattr(FUSE_ROOT_INODE).await.unwrap().attr;
+    assert_eq!(root_attr.kind, FileType::Directory);
+    let root_handle = fs.opendir(FUSE_ROOT_INODE, 0).await.unwrap().fh;
+    let mut reply = Default::default();
+    let _reply = fs
+        .readdirplus(FUSE_ROOT_INODE, root_handle, 0, &mut reply)
+        .await
+        .unwrap();
+    let names = reply.entries.iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, [".", "..", "first", "second"]);
+    fs.releasedir(FUSE_ROOT_INODE, root_handle, 0).await.unwrap();
+
+    let err = fs
+        .lookup(FUSE_ROOT_INODE, "third".as_ref())
+        .await
+        .expect_err("no such bucket");
+    assert_eq!(err.to_errno(), libc::ENOENT);
+    let err = fs
+        .mkdir(FUSE_ROOT_INODE, "third".as_ref(), libc::S_IFDIR, 0)
+        .await
+        .expect_err("the root directory is read-only");
+    assert_eq!(err.to_errno(), libc::EPERM);
+
+    // Each bucket has its own inodes, even for files with the same name
+    let mut file_inos = Vec::new();
+    for (name, contents) in [("first", &b"first"[..]), ("second", &b"second"[..])] {
+        let dir_ino = fs.lookup(FUSE_ROOT_INODE, name.as_ref()).await.unwrap().attr.ino;
+        let file_ino = fs.lookup(dir_ino, "file.txt".as_ref()).await.unwrap().attr.ino;
+        let fh = fs
+            .open(file_ino, libc::S_IFREG as i32 | libc::O_RDONLY, 0)
+            .await
+            .unwrap()
+            .fh;
+        let bytes_read = fs.read(file_ino, fh, 0, 1024, 0, None).await.unwrap();
+        assert_eq!(&bytes_read[..], contents);
+        fs.release(file_ino, fh, 0, None, true).await.unwrap();
+        file_inos.push((dir_ino, file_ino));
+    }
+    assert_ne!(file_inos[0].1, file_inos[1].1);
+
+    // Writes go to the right bucket and prefix
+    let (first_ino, _) = file_inos[0];
+    let (second_ino, _) = file_inos[1];
+    let new_ino = fs
+        .mknod(first_ino, "new.txt".as_ref(), libc::S_IFREG | libc::S_IRWXU, 0, 0)
+        .await
+        .unwrap()
+        .attr
+        .ino;
+    let fh = fs
+        .open(new_ino, libc::S_IFREG as i32 | libc::O_WRONLY, 0)
+        .await
+        .unwrap()
+        .fh;
+    fs.write(new_ino, fh, 0, b"new", 0, 0, None).await.unwrap();
+    fs.release(new_ino, fh, 0, None, true).await.unwrap();
+    assert!(client.contains_key("first/new.txt"));
+
+    // Files can't be renamed between buckets
+    let err = fs
+        .rename(first_ino, "new.txt".as_ref(), second_ino, "new.txt".as_ref(), 0)
+        .await
+        .expect_err("renames between buckets are not supported");
+    assert_eq!(err.to_errno(), libc::EXDEV);
+}
+
 #[tokio::test]
 async fn test_directory_shadowing_lookup() {
     let (client, fs) = make_test_filesystem(
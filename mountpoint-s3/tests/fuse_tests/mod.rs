@@ -6,8 +6,10 @@ mod perm_test;
 mod prefetch_test;
 mod read_test;
 mod readdir_test;
+mod rename_test;
 mod rmdir_test;
 mod semantics_doc_test;
 mod setattr_test;
+mod symlink_test;
 mod unlink_test;
 mod write_test;
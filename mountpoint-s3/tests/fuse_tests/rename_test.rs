@@ -0,0 +1,168 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read, Write};
+
+use fuser::BackgroundSession;
+use mountpoint_s3::S3FilesystemConfig;
+use tempfile::TempDir;
+use test_case::test_case;
+
+use crate::common::fuse::{self, read_dir_to_entry_names, TestClientBox, TestSessionConfig};
+
+/// Write a temporary file and then move it into place, like many tools do to publish files atomically.
+fn rename_after_write_test<F>(creator_fn: F, prefix: &str)
+where
+    F: FnOnce(&str, TestSessionConfig) -> (TempDir, BackgroundSession, TestClientBox),
+{
+    let test_session_config = TestSessionConfig {
+        filesystem_config: S3FilesystemConfig {
+            allow_delete: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mount_point, _session, mut test_client) = creator_fn(prefix, test_session_config);
+
+    // Persist implicit directories for test
+    test_client.put_object("dir/other.txt", b"other").unwrap();
+    test_client.put_object("out/other.txt", b"other").unwrap();
+
+    let tmp_path = mount_point.path().join("dir/part.tmp");
+    let final_path = mount_point.path().join("out/part");
+
+    let mut f = File::create(&tmp_path).expect("create should succeed");
+    f.write_all(b"hello world").expect("write should succeed");
+    drop(f);
+
+    fs::rename(&tmp_path, &final_path).expect("rename should succeed");
+
+    assert!(!test_client.contains_key("dir/part.tmp").unwrap());
+    assert!(test_client.contains_key("out/part").unwrap());
+
+    let err = fs::metadata(&tmp_path).expect_err("source should be gone");
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let mut contents = String::new();
+    File::open(&final_path)
+        .expect("open should succeed")
+        .read_to_string(&mut contents)
+        .expect("read should succeed");
+    assert_eq!(contents, "hello world");
+
+    let read_dir_iter = fs::read_dir(mount_point.path().join("dir")).unwrap();
+    assert_eq!(read_dir_to_entry_names(read_dir_iter), vec!["other.txt"]);
+    let read_dir_iter = fs::read_dir(mount_point.path().join("out")).unwrap();
+    assert_eq!(read_dir_to_entry_names(read_dir_iter), vec!["other.txt", "part"]);
+}
+
+#[cfg(feature = "s3_tests")]
+#[test]
+fn rename_after_write_test_s3() {
+    rename_after_write_test(fuse::s3_session::new, "rename_after_write_test");
+}
+
+#[test_case(""; "no prefix")]
+#[test_case("rename_after_write_test"; "prefix")]
+fn rename_after_write_test_mock(prefix: &str) {
+    rename_after_write_test(fuse::mock_session::new, prefix);
+}
+
+fn rename_overwrite_test<F>(creator_fn: F, allow_overwrite: bool)
+where
+    F: FnOnce(&str, TestSessionConfig) -> (TempDir, BackgroundSession, TestClientBox),
+{
+    let test_session_config = TestSessionConfig {
+        filesystem_config: S3FilesystemConfig {
+            allow_delete: true,
+            allow_overwrite,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mount_point, _session, mut test_client) = creator_fn("rename_overwrite_test", test_session_config);
+
+    test_client.put_object("src.txt", b"new").unwrap();
+    test_client.put_object("dst.txt", b"old").unwrap();
+
+    let src_path = mount_point.path().join("src.txt");
+    let dst_path = mount_point.path().join("dst.txt");
+
+    let result = fs::rename(&src_path, &dst_path);
+    let expected = if allow_overwrite {
+        result.expect("rename should replace the destination");
+        assert!(!test_client.contains_key("src.txt").unwrap());
+        "new"
+    } else {
+        let err = result.expect_err("rename should not replace the destination");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+        assert!(test_client.contains_key("src.txt").unwrap());
+        "old"
+    };
+
+    let contents = fs::read_to_string(&dst_path).expect("read should succeed");
+    assert_eq!(contents, expected);
+}
+
+#[cfg(feature = "s3_tests")]
+#[test_case(false; "overwrite disallowed")]
+#[test_case(true; "overwrite allowed")]
+fn rename_overwrite_test_s3(allow_overwrite: bool) {
+    rename_overwrite_test(fuse::s3_session::new, allow_overwrite);
+}
+
+#[test_case(false; "overwrite disallowed")]
+#[test_case(true; "overwrite allowed")]
+fn rename_overwrite_test_mock(allow_overwrite: bool) {
+    rename_overwrite_test(fuse::mock_session::new, allow_overwrite);
+}
+
+fn rename_directory_test<F>(creator_fn: F, prefix: &str)
+where
+    F: FnOnce(&str, TestSessionConfig) -> (TempDir, BackgroundSession, TestClientBox),
+{
+    let test_session_config = TestSessionConfig {
+        filesystem_config: S3FilesystemConfig {
+            allow_delete: true,
+            allow_directory_rename: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mount_point, _session, mut test_client) = creator_fn(prefix, test_session_config);
+
+    test_client.put_object("dir/a.txt", b"a").unwrap();
+    test_client.put_object("dir/sub/b.txt", b"b").unwrap();
+
+    let src_path = mount_point.path().join("dir");
+    let dst_path = mount_point.path().join("renamed");
+
+    // Read through the directory first so the kernel knows about the children
+    let read_dir_iter = fs::read_dir(src_path.join("sub")).unwrap();
+    assert_eq!(read_dir_to_entry_names(read_dir_iter), vec!["b.txt"]);
+
+    fs::rename(&src_path, &dst_path).expect("rename should succeed");
+
+    assert!(!test_client.contains_key("dir/a.txt").unwrap());
+    assert!(!test_client.contains_key("dir/sub/b.txt").unwrap());
+    assert!(test_client.contains_key("renamed/a.txt").unwrap());
+    assert!(test_client.contains_key("renamed/sub/b.txt").unwrap());
+
+    let read_dir_iter = fs::read_dir(mount_point.path()).unwrap();
+    assert_eq!(read_dir_to_entry_names(read_dir_iter), vec!["renamed"]);
+    let read_dir_iter = fs::read_dir(&dst_path).unwrap();
+    assert_eq!(read_dir_to_entry_names(read_dir_iter), vec!["a.txt", "sub"]);
+
+    let contents = fs::read_to_string(dst_path.join("sub/b.txt")).expect("read should succeed");
+    assert_eq!(contents, "b");
+}
+
+#[cfg(feature = "s3_tests")]
+#[test]
+fn rename_directory_test_s3() {
+    rename_directory_test(fuse::s3_session::new, "rename_directory_test");
+}
+
+#[test_case(""; "no prefix")]
+#[test_case("rename_directory_test"; "prefix")]
+fn rename_directory_test_mock(prefix: &str) {
+    rename_directory_test(fuse::mock_session::new, prefix);
+}
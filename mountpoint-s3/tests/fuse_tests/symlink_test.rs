@@ -0,0 +1,89 @@
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::symlink;
+
+use fuser::BackgroundSession;
+use mountpoint_s3::S3FilesystemConfig;
+use tempfile::TempDir;
+use test_case::test_case;
+
+use crate::common::fuse::{self, read_dir_to_entry_names, TestClientBox, TestSessionConfig};
+
+fn symlink_test<F>(creator_fn: F, prefix: &str, allow_symlinks: bool)
+where
+    F: FnOnce(&str, TestSessionConfig) -> (TempDir, BackgroundSession, TestClientBox),
+{
+    let test_session_config = TestSessionConfig {
+        filesystem_config: S3FilesystemConfig {
+            allow_symlinks,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (mount_point, _session, mut test_client) = creator_fn(prefix, test_session_config);
+
+    test_client.put_object("dir/target.txt", b"hello world").unwrap();
+
+    let link_path = mount_point.path().join("dir/link");
+    let result = symlink("target.txt", &link_path);
+    if !allow_symlinks {
+        let err = result.expect_err("symlinks are disabled");
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+        assert!(!test_client.contains_key("dir/link").unwrap());
+        return;
+    }
+    result.expect("symlink should succeed");
+    assert!(test_client.contains_key("dir/link").unwrap());
+
+    let metadata = fs::symlink_metadata(&link_path).unwrap();
+    assert!(metadata.file_type().is_symlink());
+    assert_eq!(metadata.len(), "target.txt".len() as u64);
+    assert_eq!(fs::read_link(&link_path).unwrap().to_str(), Some("target.txt"));
+
+    // Opening the link follows it to the target
+    let mut contents = String::new();
+    File::open(&link_path)
+        .expect("open should succeed")
+        .read_to_string(&mut contents)
+        .expect("read should succeed");
+    assert_eq!(contents, "hello world");
+
+    let read_dir_iter = fs::read_dir(mount_point.path().join("dir")).unwrap();
+    let entries = read_dir_iter.map(|entry| entry.unwrap()).collect::<Vec<_>>();
+    let link_entry = entries
+        .iter()
+        .find(|entry| entry.file_name() == "link")
+        .expect("link should be listed");
+    assert!(link_entry.file_type().unwrap().is_symlink());
+
+    // A dangling link can still be read, but not followed
+    let dangling_path = mount_point.path().join("dir/dangling");
+    symlink("missing.txt", &dangling_path).expect("symlink should succeed");
+    assert_eq!(fs::read_link(&dangling_path).unwrap().to_str(), Some("missing.txt"));
+    let err = File::open(&dangling_path).expect_err("target doesn't exist");
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+
+    let read_dir_iter = fs::read_dir(mount_point.path().join("dir")).unwrap();
+    assert_eq!(
+        read_dir_to_entry_names(read_dir_iter),
+        vec!["dangling", "link", "target.txt"]
+    );
+}
+
+#[cfg(feature = "s3_tests")]
+#[test_case(true; "symlinks enabled")]
+#[test_case(false; "symlinks disabled")]
+fn symlink_test_s3(allow_symlinks: bool) {
+    symlink_test(fuse::s3_session::new, "symlink_test", allow_symlinks);
+}
+
+#[test_case(""; "no prefix")]
+#[test_case("symlink_test"; "prefix")]
+fn symlink_test_mock(prefix: &str) {
+    symlink_test(fuse::mock_session::new, prefix, true);
+}
+
+#[test]
+fn symlink_disabled_test_mock() {
+    symlink_test(fuse::mock_session::new, "", false);
+}